@@ -12,8 +12,39 @@
 extern crate finitediff;
 extern crate test;
 
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
 const MASSIVENESS: usize = 256;
 
+/// Counts the allocations of the current thread
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Number of allocations made by `f`
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.with(|a| a.get());
+    drop(test::black_box(f()));
+    ALLOCATIONS.with(|a| a.get()) - before
+}
+
 fn cost_vec_f64(x: &Vec<f64>) -> f64 {
     x.iter().fold(0.0, |a, acc| a + acc)
 }
@@ -102,6 +133,22 @@ mod tests {
         });
     }
 
+    #[bench]
+    fn forward_diff_trivial_vec_f64(b: &mut Bencher) {
+        let x = vec![1.0f64; MASSIVENESS];
+        b.iter(|| {
+            black_box(x.forward_diff(&|x: &Vec<f64>| x[0]));
+        });
+    }
+
+    #[bench]
+    fn forward_diff_generic_trivial_vec_f64(b: &mut Bencher) {
+        let x = vec![1.0f64; MASSIVENESS];
+        b.iter(|| {
+            black_box(forward_diff_generic(&x, |x: &Vec<f64>| x[0]));
+        });
+    }
+
     #[cfg(feature = "ndarray")]
     #[bench]
     fn forward_diff_ndarray_f64(b: &mut Bencher) {
@@ -145,6 +192,62 @@ mod tests {
         });
     }
 
+    #[bench]
+    fn forward_jacobian_into_vec_f64(b: &mut Bencher) {
+        let x = vec![1.0f64; MASSIVENESS];
+        let mut out = vec![vec![0.0; MASSIVENESS]; MASSIVENESS];
+        b.iter(|| {
+            x.forward_jacobian_into(&cost_multi_vec_f64, &mut out);
+            black_box(&out);
+        });
+    }
+
+    #[bench]
+    fn forward_jacobian_context_vec_f64(b: &mut Bencher) {
+        let x = vec![1.0f64; MASSIVENESS];
+        let mut ctx = DiffContext::new(MASSIVENESS, MASSIVENESS);
+        // without a context, the Jacobian, its rows and the perturbed parameters are allocated
+        // in every call; with a context, only the `n+1` values returned by the function remain
+        let before = allocations(|| x.forward_jacobian(&cost_multi_vec_f64));
+        let after = allocations(|| {
+            ctx.forward_jacobian(&x, &cost_multi_vec_f64).unwrap();
+        });
+        assert_eq!(before, 2 * MASSIVENESS + 3);
+        assert_eq!(after, MASSIVENESS + 1);
+        b.iter(|| {
+            black_box(ctx.forward_jacobian(&x, &cost_multi_vec_f64).unwrap());
+        });
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[bench]
+    fn forward_jacobian_into_ndarray_f64(b: &mut Bencher) {
+        let x = ndarray::Array1::from(vec![1.0f64; MASSIVENESS]);
+        let mut out = ndarray::Array2::zeros((MASSIVENESS, MASSIVENESS));
+        b.iter(|| {
+            x.forward_jacobian_into(&cost_multi_ndarray_f64, &mut out);
+            black_box(&out);
+        });
+    }
+
+    #[cfg(feature = "rayon")]
+    #[bench]
+    fn forward_jacobian_par_vec_f64(b: &mut Bencher) {
+        let x = vec![1.0f64; MASSIVENESS];
+        b.iter(|| {
+            black_box(x.forward_jacobian_par(&cost_multi_vec_f64));
+        });
+    }
+
+    #[cfg(all(feature = "rayon", feature = "ndarray"))]
+    #[bench]
+    fn forward_jacobian_par_ndarray_f64(b: &mut Bencher) {
+        let x = ndarray::Array1::from(vec![1.0f64; MASSIVENESS]);
+        b.iter(|| {
+            black_box(x.forward_jacobian_par(&cost_multi_ndarray_f64));
+        });
+    }
+
     #[bench]
     fn central_jacobian_vec_f64(b: &mut Bencher) {
         let x = vec![1.0f64; MASSIVENESS];
@@ -162,6 +265,27 @@ mod tests {
         });
     }
 
+    #[bench]
+    fn central_jacobian_into_vec_f64(b: &mut Bencher) {
+        let x = vec![1.0f64; MASSIVENESS];
+        let mut out = vec![vec![0.0; MASSIVENESS]; MASSIVENESS];
+        b.iter(|| {
+            x.central_jacobian_into(&cost_multi_vec_f64, &mut out);
+            black_box(&out);
+        });
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[bench]
+    fn central_jacobian_into_ndarray_f64(b: &mut Bencher) {
+        let x = ndarray::Array1::from(vec![1.0f64; MASSIVENESS]);
+        let mut out = ndarray::Array2::zeros((MASSIVENESS, MASSIVENESS));
+        b.iter(|| {
+            x.central_jacobian_into(&cost_multi_ndarray_f64, &mut out);
+            black_box(&out);
+        });
+    }
+
     #[bench]
     fn forward_jacobian_vec_prod_vec_f64(b: &mut Bencher) {
         let x = vec![1.0f64; MASSIVENESS];
@@ -0,0 +1,439 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::utils::*;
+use crate::FloatEps;
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+/// Maximum number of steps which are tried for each parameter
+pub const ADAPTIVE_MAX_STEPS: usize = 10;
+
+/// Number of components of the gradient which are probed with central differences by `auto_diff`
+pub const AUTO_DIFF_PROBES: usize = 2;
+
+/// Initial step for a parameter. It is rather large because the truncation error is removed by
+/// the extrapolation.
+#[inline(always)]
+fn initial_step<F: FloatEps>(xi: F) -> F {
+    F::from(0.1).unwrap() * step_magnitude(xi)
+}
+
+/// Selects the derivative along a single parameter from forward differences at shrinking steps.
+///
+/// `diff(h)` returns the forward difference at step `h`. The step is halved successively and the
+/// forward differences are extrapolated in a Neville tableau (Ridders' method). Each entry comes
+/// with an estimate of its error, and the entry with the smallest estimate is returned. As soon as
+/// the most extrapolated entries start to diverge, rounding errors dominate and no smaller steps
+/// are tried.
+fn adaptive_component<F: FloatEps>(h0: F, mut diff: impl FnMut(F) -> F) -> F {
+    let two = F::from(2.0).unwrap();
+    let mut prev = vec![diff(h0)];
+    let mut best = prev[0];
+    let mut err = F::infinity();
+    let mut h = h0;
+    for _ in 1..ADAPTIVE_MAX_STEPS {
+        h /= two;
+        let mut row = Vec::with_capacity(prev.len() + 1);
+        row.push(diff(h));
+        let mut factor = F::one();
+        for m in 1..=prev.len() {
+            // the error of forward differences contains all powers of `h`, therefore column `m`
+            // removes `h^m`
+            factor *= two;
+            let t = (factor * row[m - 1] - prev[m - 1]) / (factor - F::one());
+            let e = (t - row[m - 1]).abs().max((t - prev[m - 1]).abs());
+            if e <= err {
+                err = e;
+                best = t;
+            }
+            row.push(t);
+        }
+        if (row[row.len() - 1] - prev[prev.len() - 1]).abs() >= two * err {
+            break;
+        }
+        prev = row;
+    }
+    best
+}
+
+pub fn adaptive_diff_vec<F: FloatEps>(x: &Vec<F>, f: &dyn Fn(&Vec<F>) -> F) -> Vec<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            adaptive_component(initial_step(x[i]), |h| {
+                (mod_and_calc_vec(&mut xt, f, i, h) - fx) / h
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "ndarray")]
+pub fn adaptive_diff_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+) -> ndarray::Array1<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            adaptive_component(initial_step(x[i]), |h| {
+                (mod_and_calc_ndarray(&mut xt, f, i, h) - fx) / h
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "nalgebra")]
+pub fn adaptive_diff_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> nalgebra::DVector<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            adaptive_component(initial_step(x[i]), |h| {
+                (mod_and_calc_nalgebra(&mut xt, f, i, h) - fx) / h
+            })
+        }),
+    )
+}
+
+/// Selects between forward and central differences for all components of the gradient.
+///
+/// `diff(i, central)` returns the forward or central difference of the `i`th component. Forward
+/// differences are computed for all components, central differences only for `AUTO_DIFF_PROBES`
+/// evenly spaced components. If the forward difference of any probed component deviates from the
+/// central difference by more than `target_rel_err` relative to the latter, the central
+/// differences of the remaining components are computed as well and returned instead.
+fn auto_components<F: FloatEps>(
+    n: usize,
+    target_rel_err: F,
+    mut diff: impl FnMut(usize, bool) -> F,
+) -> Vec<F> {
+    let forward: Vec<F> = (0..n).map(|i| diff(i, false)).collect();
+    let k = AUTO_DIFF_PROBES.min(n);
+    let probes: Vec<(usize, F)> = (0..k)
+        .map(|p| {
+            let i = p * n / k;
+            (i, diff(i, true))
+        })
+        .collect();
+    if probes
+        .iter()
+        .all(|&(i, c)| (forward[i] - c).abs() <= target_rel_err * c.abs())
+    {
+        return forward;
+    }
+    let mut probes = probes.into_iter().peekable();
+    (0..n)
+        .map(|i| match probes.peek() {
+            Some(&(j, c)) if j == i => {
+                probes.next();
+                c
+            }
+            _ => diff(i, true),
+        })
+        .collect()
+}
+
+pub fn auto_diff_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    target_rel_err: F,
+) -> Vec<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    auto_components(x.len(), target_rel_err, |i, central| {
+        if central {
+            let h = central_step(x[i]);
+            (mod_and_calc_vec(&mut xt, f, i, h) - mod_and_calc_vec(&mut xt, f, i, -h))
+                / (F::from(2.0).unwrap() * h)
+        } else {
+            let h = step_for(x[i], F::EPS);
+            (mod_and_calc_vec(&mut xt, f, i, h) - fx) / h
+        }
+    })
+}
+
+#[cfg(feature = "ndarray")]
+pub fn auto_diff_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+    target_rel_err: F,
+) -> ndarray::Array1<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    ndarray::Array1::from(auto_components(x.len(), target_rel_err, |i, central| {
+        if central {
+            let h = central_step(x[i]);
+            (mod_and_calc_ndarray(&mut xt, f, i, h) - mod_and_calc_ndarray(&mut xt, f, i, -h))
+                / (F::from(2.0).unwrap() * h)
+        } else {
+            let h = step_for(x[i], F::EPS);
+            (mod_and_calc_ndarray(&mut xt, f, i, h) - fx) / h
+        }
+    }))
+}
+
+#[cfg(feature = "nalgebra")]
+pub fn auto_diff_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    target_rel_err: F,
+) -> nalgebra::DVector<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    nalgebra::DVector::from_vec(auto_components(x.len(), target_rel_err, |i, central| {
+        if central {
+            let h = central_step(x[i]);
+            (mod_and_calc_nalgebra(&mut xt, f, i, h) - mod_and_calc_nalgebra(&mut xt, f, i, -h))
+                / (F::from(2.0).unwrap() * h)
+        } else {
+            let h = step_for(x[i], F::EPS);
+            (mod_and_calc_nalgebra(&mut xt, f, i, h) - fx) / h
+        }
+    }))
+}
+
+/// Sample standard deviation of the forward differences of all components over `repeats`
+/// repetitions.
+///
+/// `eval(None)` returns `f(x)` and `eval(Some(i))` returns `f(x + h * e_i)`. In every repetition,
+/// `f(x)` is evaluated once and shared by all components.
+fn noise_components<F: FloatEps>(
+    n: usize,
+    repeats: usize,
+    h: F,
+    mut eval: impl FnMut(Option<usize>) -> F,
+) -> Vec<F> {
+    assert!(repeats >= 2, "at least two repetitions are required");
+    let mut samples: Vec<Vec<F>> = vec![Vec::with_capacity(repeats); n];
+    for _ in 0..repeats {
+        let fx = eval(None);
+        for (i, s) in samples.iter_mut().enumerate() {
+            s.push((eval(Some(i)) - fx) / h);
+        }
+    }
+    let r = F::from(repeats).unwrap();
+    samples
+        .iter()
+        .map(|s| {
+            let mean = s.iter().fold(F::zero(), |acc, &v| acc + v) / r;
+            let var = s
+                .iter()
+                .fold(F::zero(), |acc, &v| acc + (v - mean) * (v - mean))
+                / (r - F::one());
+            var.sqrt()
+        })
+        .collect()
+}
+
+pub fn estimate_diff_noise_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &mut dyn FnMut(&Vec<F>) -> F,
+    repeats: usize,
+) -> Vec<F> {
+    let h = F::EPS.sqrt();
+    let mut xt = x.clone();
+    noise_components(x.len(), repeats, h, |i| match i {
+        None => (f)(x),
+        Some(i) => {
+            xt[i] = x[i] + h;
+            let v = (f)(&xt);
+            xt[i] = x[i];
+            v
+        }
+    })
+}
+
+#[cfg(feature = "ndarray")]
+pub fn estimate_diff_noise_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &mut dyn FnMut(&ndarray::Array1<F>) -> F,
+    repeats: usize,
+) -> ndarray::Array1<F> {
+    let h = F::EPS.sqrt();
+    let mut xt = x.clone();
+    ndarray::Array1::from(noise_components(x.len(), repeats, h, |i| match i {
+        None => (f)(x),
+        Some(i) => {
+            xt[i] = x[i] + h;
+            let v = (f)(&xt);
+            xt[i] = x[i];
+            v
+        }
+    }))
+}
+
+#[cfg(feature = "nalgebra")]
+pub fn estimate_diff_noise_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &mut dyn FnMut(&nalgebra::DVector<F>) -> F,
+    repeats: usize,
+) -> nalgebra::DVector<F> {
+    let h = F::EPS.sqrt();
+    let mut xt = x.clone();
+    nalgebra::DVector::from_vec(noise_components(x.len(), repeats, h, |i| match i {
+        None => (f)(x),
+        Some(i) => {
+            xt[i] = x[i] + h;
+            let v = (f)(&xt);
+            xt[i] = x[i];
+            v
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CountingFn;
+
+    #[test]
+    fn test_adaptive_diff_vec_f64() {
+        let f = |x: &Vec<f64>| x[0].sin();
+        let grad = adaptive_diff_vec(&vec![1.0f64], &f);
+        assert!((grad[0] - 1.0f64.cos()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_adaptive_diff_vec_f64_scales() {
+        let f = |x: &Vec<f64>| x[0].exp() + 1e3 * x[1].powi(3) + x[2].ln();
+        let p = vec![2.0f64, 0.5, 1e4];
+        let grad = adaptive_diff_vec(&p, &f);
+        let res = [2.0f64.exp(), 750.0, 1e-4];
+
+        for i in 0..3 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_adaptive_diff_vec_budget() {
+        let f = CountingFn::new(|x: &Vec<f64>| x[0].sin() + x[1].cos());
+        adaptive_diff_vec(&vec![1.0f64, 1.0], &|x| f.call(x));
+        assert!(f.count() <= 1 + 2 * ADAPTIVE_MAX_STEPS);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_adaptive_diff_ndarray_f64() {
+        let f = |x: &ndarray::Array1<f64>| x[0].sin();
+        let grad = adaptive_diff_ndarray(&ndarray::Array1::from(vec![1.0f64]), &f);
+        assert!((grad[0] - 1.0f64.cos()).abs() < 1e-10);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_adaptive_diff_nalgebra_f64() {
+        let f = |x: &nalgebra::DVector<f64>| x[0].sin();
+        let grad = adaptive_diff_nalgebra(&nalgebra::DVector::from_vec(vec![1.0f64]), &f);
+        assert!((grad[0] - 1.0f64.cos()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_auto_diff_vec_keeps_forward() {
+        let f = CountingFn::new(|x: &Vec<f64>| x[0] + 2.0 * x[1] + 3.0 * x[2]);
+        let p = vec![1.0f64, 1.0, 1.0];
+        let grad = auto_diff_vec(&p, &|x| f.call(x), 1e-6);
+        // n+1 forward and 2*AUTO_DIFF_PROBES central evaluations
+        assert_eq!(f.count(), 4 + 2 * AUTO_DIFF_PROBES);
+        assert_eq!(grad, crate::diff::forward_diff_vec(&p, &|x| f.call(x)));
+    }
+
+    #[test]
+    fn test_auto_diff_vec_switches_to_central() {
+        // strongly curved in x[1], where the truncation error of forward differences dominates
+        let f = CountingFn::new(|x: &Vec<f64>| x[0] + 1e4 * x[1].powi(2) + x[2]);
+        let p = vec![1.0f64, 1e-4, 1.0];
+        let grad = auto_diff_vec(&p, &|x| f.call(x), 1e-6);
+        // n+1 forward and 2*n central evaluations, the probes are not repeated
+        assert_eq!(f.count(), 4 + 6);
+        assert_eq!(grad, crate::diff::central_diff_vec(&p, &|x| f.call(x)));
+
+        let forward = crate::diff::forward_diff_vec(&p, &|x| f.call(x));
+        assert!((forward[1] - 2.0).abs() > 1e-6 * 2.0);
+        assert!((grad[1] - 2.0).abs() < 1e-6 * 2.0);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_auto_diff_ndarray_switches_to_central() {
+        let f = |x: &ndarray::Array1<f64>| x[0] + 1e4 * x[1].powi(2);
+        let p = ndarray::Array1::from(vec![1.0f64, 1e-4]);
+        let grad = auto_diff_ndarray(&p, &f, 1e-6);
+        assert_eq!(grad, crate::diff_ndarray::central_diff_ndarray(&p, &f));
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_auto_diff_nalgebra_switches_to_central() {
+        let f = |x: &nalgebra::DVector<f64>| x[0] + 1e4 * x[1].powi(2);
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 1e-4]);
+        let grad = auto_diff_nalgebra(&p, &f, 1e-6);
+        assert_eq!(grad, crate::diff_nalgebra::central_diff_nalgebra(&p, &f));
+    }
+
+    #[test]
+    fn test_estimate_diff_noise_vec() {
+        let x = vec![1.0f64, 2.0];
+        let mut f = |x: &Vec<f64>| x[0].sin() + x[1].powi(2);
+        let noise = estimate_diff_noise_vec(&x, &mut f, 5);
+        assert_eq!(noise, vec![0.0, 0.0]);
+
+        let mut k = 0u32;
+        let mut noisy = |x: &Vec<f64>| {
+            k += 1;
+            x[0] + 1e-6 * (k as f64).sin()
+        };
+        let noise = estimate_diff_noise_vec(&x, &mut noisy, 10);
+        assert_eq!(k, 30);
+        assert!(noise.iter().all(|&s| s > 1.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_estimate_diff_noise_vec_repeats() {
+        estimate_diff_noise_vec(&vec![1.0f64], &mut |x| x[0], 1);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_estimate_diff_noise_ndarray() {
+        let x = ndarray::Array1::from(vec![1.0f64, 2.0]);
+        let mut k = 0u32;
+        let noise = estimate_diff_noise_ndarray(
+            &x,
+            &mut |x| {
+                k += 1;
+                x[0] + 1e-6 * (k as f64).sin()
+            },
+            10,
+        );
+        assert!(noise.iter().all(|&s| s > 1.0));
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_estimate_diff_noise_nalgebra() {
+        let x = nalgebra::DVector::from_vec(vec![1.0f64, 2.0]);
+        let mut k = 0u32;
+        let noise = estimate_diff_noise_nalgebra(
+            &x,
+            &mut |x| {
+                k += 1;
+                x[0] + 1e-6 * (k as f64).sin()
+            },
+            10,
+        );
+        assert!(noise.iter().all(|&s| s > 1.0));
+    }
+}
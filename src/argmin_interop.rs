@@ -0,0 +1,114 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::FiniteDiff;
+use argmin::core::{CostFunction, Error, Gradient, Hessian};
+
+/// Wraps a cost function such that it can be passed to argmin solvers which require gradients or
+/// Hessians.
+///
+/// The gradient is computed with `forward_diff` and the Hessian with `forward_hessian_nograd`,
+/// therefore only the cost function itself needs to be provided.
+///
+/// ```
+/// use argmin::core::{CostFunction, Gradient};
+/// use finitediff::FiniteDiffProblem;
+///
+/// let problem = FiniteDiffProblem::new(|x: &Vec<f64>| x[0].powi(2) + 3.0 * x[1]);
+/// let x = vec![1.0f64, 2.0];
+///
+/// assert_eq!(problem.cost(&x).unwrap(), 7.0);
+/// let grad = problem.gradient(&x).unwrap();
+/// assert!((grad[0] - 2.0).abs() < 1e-6);
+/// assert!((grad[1] - 3.0).abs() < 1e-6);
+/// ```
+pub struct FiniteDiffProblem<F> {
+    f: F,
+}
+
+impl<F: Fn(&Vec<f64>) -> f64> FiniteDiffProblem<F> {
+    /// Wrap the cost function `f`
+    pub fn new(f: F) -> Self {
+        FiniteDiffProblem { f }
+    }
+}
+
+impl<F: Fn(&Vec<f64>) -> f64> CostFunction for FiniteDiffProblem<F> {
+    type Param = Vec<f64>;
+    type Output = f64;
+
+    fn cost(&self, param: &Self::Param) -> Result<Self::Output, Error> {
+        Ok((self.f)(param))
+    }
+}
+
+impl<F: Fn(&Vec<f64>) -> f64> Gradient for FiniteDiffProblem<F> {
+    type Param = Vec<f64>;
+    type Gradient = Vec<f64>;
+
+    fn gradient(&self, param: &Self::Param) -> Result<Self::Gradient, Error> {
+        Ok(param.forward_diff(&self.f))
+    }
+}
+
+impl<F: Fn(&Vec<f64>) -> f64> Hessian for FiniteDiffProblem<F> {
+    type Param = Vec<f64>;
+    type Hessian = Vec<Vec<f64>>;
+
+    fn hessian(&self, param: &Self::Param) -> Result<Self::Hessian, Error> {
+        Ok(param.forward_hessian_nograd(&self.f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMP_ACC: f64 = 1e-4;
+
+    fn rosenbrock(x: &Vec<f64>) -> f64 {
+        (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0].powi(2)).powi(2)
+    }
+
+    #[test]
+    fn test_finite_diff_problem_gradient() {
+        let problem = FiniteDiffProblem::new(rosenbrock);
+        let x = vec![0.5f64, 1.0];
+        let grad = problem.gradient(&x).unwrap();
+        let res = [-2.0 * 0.5 - 400.0 * 0.5 * 0.75, 200.0 * 0.75];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC * res[i].abs());
+        }
+    }
+
+    #[test]
+    fn test_finite_diff_problem_hessian() {
+        let problem =
+            FiniteDiffProblem::new(|x: &Vec<f64>| x[0] + x[1].powi(2) + x[2] * x[3].powi(2));
+        let x = vec![1.0f64, 1.0, 1.0, 1.0];
+        let hessian = problem.hessian(&x).unwrap();
+        let res = [
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 2.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 2.0],
+            [0.0, 0.0, 2.0, 2.0],
+        ];
+
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_finite_diff_problem_cost() {
+        let problem = FiniteDiffProblem::new(rosenbrock);
+        assert_eq!(problem.cost(&vec![1.0f64, 1.0]).unwrap(), 0.0);
+    }
+}
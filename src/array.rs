@@ -0,0 +1,232 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Finite differences for stack-allocated parameter vectors of type `[f64; N]`.
+//!
+//! Gradients, Jacobians and the Hessian without gradient are computed directly on the array.
+//! All other methods of `FiniteDiff` convert to `Vec<f64>` and use the corresponding `_vec`
+//! implementation, which allocates.
+
+use crate::hessian::nograd_diagonal;
+use crate::utils::*;
+use crate::EPS_F64;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+// the test harness links `std`, whose inherent float methods take precedence over `Float`
+#[cfg(all(not(feature = "std"), not(test)))]
+use num_traits::Float;
+
+/// I wish this wasn't necessary!
+#[inline(always)]
+fn eps_nograd() -> f64 {
+    EPS_F64 * 2.0
+}
+
+pub fn forward_diff_array<const N: usize>(x: &[f64; N], f: &dyn Fn(&[f64; N]) -> f64) -> [f64; N] {
+    let fx = (f)(x);
+    let mut xt = *x;
+    let mut out = [0.0; N];
+    for i in 0..N {
+        let h = step_for(x[i], EPS_F64);
+        let fx1 = mod_and_calc_array_f64(&mut xt, f, i, h);
+        out[i] = (fx1 - fx) / h;
+    }
+    out
+}
+
+pub fn central_diff_array<const N: usize>(x: &[f64; N], f: &dyn Fn(&[f64; N]) -> f64) -> [f64; N] {
+    let mut xt = *x;
+    let mut out = [0.0; N];
+    for i in 0..N {
+        let h = central_step(x[i]);
+        let fx1 = mod_and_calc_array_f64(&mut xt, f, i, h);
+        let fx2 = mod_and_calc_array_f64(&mut xt, f, i, -h);
+        out[i] = (fx1 - fx2) / (2.0 * h);
+    }
+    out
+}
+
+pub fn forward_jacobian_array<const N: usize>(
+    x: &[f64; N],
+    fs: &dyn Fn(&[f64; N]) -> [f64; N],
+) -> [[f64; N]; N] {
+    let mut out = [[0.0; N]; N];
+    forward_jacobian_into_array(x, fs, &mut out);
+    out
+}
+
+pub fn forward_jacobian_into_array<const N: usize>(
+    x: &[f64; N],
+    fs: &dyn Fn(&[f64; N]) -> [f64; N],
+    out: &mut [[f64; N]; N],
+) {
+    let fx = (fs)(x);
+    let mut xt = *x;
+    for i in 0..N {
+        let h = step_for(x[i], EPS_F64);
+        let fx1 = mod_and_calc_array_f64(&mut xt, fs, i, h);
+        for j in 0..N {
+            out[i][j] = (fx1[j] - fx[j]) / h;
+        }
+    }
+}
+
+pub fn central_jacobian_array<const N: usize>(
+    x: &[f64; N],
+    fs: &dyn Fn(&[f64; N]) -> [f64; N],
+) -> [[f64; N]; N] {
+    let mut out = [[0.0; N]; N];
+    central_jacobian_into_array(x, fs, &mut out);
+    out
+}
+
+pub fn central_jacobian_into_array<const N: usize>(
+    x: &[f64; N],
+    fs: &dyn Fn(&[f64; N]) -> [f64; N],
+    out: &mut [[f64; N]; N],
+) {
+    let mut xt = *x;
+    for i in 0..N {
+        let h = step_for(x[i], EPS_F64);
+        let fx1 = mod_and_calc_array_f64(&mut xt, fs, i, h);
+        let fx2 = mod_and_calc_array_f64(&mut xt, fs, i, -h);
+        for j in 0..N {
+            out[i][j] = (fx1[j] - fx2[j]) / (2.0 * h);
+        }
+    }
+}
+
+pub fn forward_hessian_nograd_array<const N: usize>(
+    x: &[f64; N],
+    f: &dyn Fn(&[f64; N]) -> f64,
+) -> [[f64; N]; N] {
+    let fx = (f)(x);
+    let mut xt = *x;
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let mut fxei = [0.0; N];
+    for i in 0..N {
+        fxei[i] = mod_and_calc_array_f64(&mut xt, f, i, eps_nograd().sqrt());
+    }
+
+    let mut out = [[0.0; N]; N];
+    for i in 0..N {
+        for j in 0..=i {
+            let t = if i == j {
+                nograd_diagonal(fx, |h| mod_and_calc_array_f64(&mut xt, f, i, h))
+            } else {
+                let xti = xt[i];
+                let xtj = xt[j];
+                xt[i] += eps_nograd().sqrt();
+                xt[j] += eps_nograd().sqrt();
+                let fxij = (f)(&xt);
+                xt[i] = xti;
+                xt[j] = xtj;
+                (fxij - fxei[i] - fxei[j] + fx) / eps_nograd()
+            };
+            out[i][j] = t;
+            out[j][i] = t;
+        }
+    }
+    out
+}
+
+/// Converts a `Vec<f64>` returned by one of the `_vec` implementations back into an array.
+#[inline(always)]
+pub fn vec_to_array<const N: usize>(v: &[f64]) -> [f64; N] {
+    let mut out = [0.0; N];
+    out.copy_from_slice(v);
+    out
+}
+
+/// Converts a matrix returned by one of the `_vec` implementations back into a nested array.
+#[inline(always)]
+pub fn mat_to_array<const N: usize>(m: &[Vec<f64>]) -> [[f64; N]; N] {
+    let mut out = [[0.0; N]; N];
+    for (o, row) in out.iter_mut().zip(m.iter()) {
+        o.copy_from_slice(row);
+    }
+    out
+}
+
+#[inline(always)]
+pub fn array_to_mat<const N: usize>(m: &[[f64; N]; N]) -> Vec<Vec<f64>> {
+    m.iter().map(|row| row.to_vec()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMP_ACC: f64 = 1e-6;
+
+    fn f(x: &[f64; 3]) -> f64 {
+        x[0] + x[1].powi(2) + x[0] * x[2].powi(2)
+    }
+
+    fn fs(x: &[f64; 3]) -> [f64; 3] {
+        [x[0] * x[1], x[1].powi(2) + x[2], x[0].sin()]
+    }
+
+    #[test]
+    fn test_forward_diff_array() {
+        let grad = forward_diff_array(&[1.0, 2.0, 1.0], &f);
+        let res = [2.0, 4.0, 2.0];
+        for i in 0..3 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_central_diff_array() {
+        let grad = central_diff_array(&[1.0, 2.0, 1.0], &f);
+        let res = [2.0, 4.0, 2.0];
+        for i in 0..3 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_array() {
+        let jacobian = forward_jacobian_array(&[1.0, 2.0, 1.0], &fs);
+        let res = [[2.0, 0.0, 1.0f64.cos()], [1.0, 4.0, 0.0], [0.0, 1.0, 0.0]];
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_array() {
+        let jacobian = central_jacobian_array(&[1.0, 2.0, 1.0], &fs);
+        let res = [[2.0, 0.0, 1.0f64.cos()], [1.0, 4.0, 0.0], [0.0, 1.0, 0.0]];
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_array() {
+        let hessian = forward_hessian_nograd_array(&[1.0, 1.0, 1.0], &f);
+        let res = [[0.0, 0.0, 2.0], [0.0, 2.0, 0.0], [2.0, 0.0, 2.0]];
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_array_conversions() {
+        let m = [[1.0, 2.0], [3.0, 4.0]];
+        assert_eq!(mat_to_array::<2>(&array_to_mat(&m)), m);
+        assert_eq!(vec_to_array::<2>(&[5.0, 6.0]), [5.0, 6.0]);
+    }
+}
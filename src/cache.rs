@@ -0,0 +1,229 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::cell::{Cell, RefCell};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+/// Parameter vectors which can identify an entry of the cache of `MemoizedFn`.
+///
+/// The key consists of the raw bits of each coordinate, because floating point numbers implement
+/// neither `Hash` nor `Ord`. Two points are therefore only considered equal if they are equal bit
+/// for bit (which also distinguishes `0.0` from `-0.0`).
+pub trait CacheKey {
+    /// Raw bits of all coordinates
+    fn cache_key(&self) -> Vec<u64>;
+}
+
+/// Scalars which can be part of a `CacheKey`
+pub trait KeyBits: Copy {
+    /// Raw bits of the value
+    fn key_bits(self) -> u64;
+}
+
+impl KeyBits for f64 {
+    fn key_bits(self) -> u64 {
+        self.to_bits()
+    }
+}
+
+impl KeyBits for f32 {
+    fn key_bits(self) -> u64 {
+        u64::from(self.to_bits())
+    }
+}
+
+impl<F: KeyBits> CacheKey for [F] {
+    fn cache_key(&self) -> Vec<u64> {
+        self.iter().map(|x| x.key_bits()).collect()
+    }
+}
+
+impl<F: KeyBits> CacheKey for Vec<F> {
+    fn cache_key(&self) -> Vec<u64> {
+        self.as_slice().cache_key()
+    }
+}
+
+impl<F: KeyBits, const N: usize> CacheKey for [F; N] {
+    fn cache_key(&self) -> Vec<u64> {
+        self[..].cache_key()
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<F: KeyBits> CacheKey for ndarray::Array1<F> {
+    fn cache_key(&self) -> Vec<u64> {
+        self.iter().map(|x| x.key_bits()).collect()
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<F: KeyBits> CacheKey for ndarray::Array2<F> {
+    fn cache_key(&self) -> Vec<u64> {
+        // the shape is part of the key, otherwise a 2x3 and a 3x2 matrix could collide
+        let (rows, cols) = self.dim();
+        let mut key = Vec::with_capacity(self.len() + 2);
+        key.push(rows as u64);
+        key.push(cols as u64);
+        key.extend(self.iter().map(|x| x.key_bits()));
+        key
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<F: KeyBits + nalgebra::Scalar> CacheKey for nalgebra::DVector<F> {
+    fn cache_key(&self) -> Vec<u64> {
+        self.iter().map(|x| x.key_bits()).collect()
+    }
+}
+
+/// Wrapper around a function which caches its results.
+///
+/// Every evaluation at a point which was already visited is answered from the cache instead of
+/// calling the function again. This pays off for expensive functions whenever the same points are
+/// visited more than once, for instance the unperturbed position by the diagonal entries of
+/// `central_hessian_nograd`, or all points if a derivative is computed repeatedly at the same
+/// parameters. Points are compared bit for bit (see `CacheKey`), and the cache grows until
+/// `clear` is called.
+///
+/// ```
+/// use finitediff::{FiniteDiff, MemoizedFn};
+///
+/// let f = MemoizedFn::new(|x: &Vec<f64>| x[0].powi(2) * x[1]);
+///
+/// let x = vec![1.0f64, 2.0];
+/// let grad1 = x.forward_diff(&|x| f.call(x));
+/// let grad2 = x.forward_diff(&|x| f.call(x));
+///
+/// assert_eq!(grad1, grad2);
+/// // the second gradient did not evaluate the function at all
+/// assert_eq!(f.hits(), 3);
+/// ```
+pub struct MemoizedFn<F, T> {
+    f: F,
+    cache: RefCell<BTreeMap<Vec<u64>, T>>,
+    hits: Cell<usize>,
+}
+
+impl<F, T: Clone> MemoizedFn<F, T> {
+    /// Wrap the function `f`
+    pub fn new(f: F) -> Self {
+        MemoizedFn {
+            f,
+            cache: RefCell::new(BTreeMap::new()),
+            hits: Cell::new(0),
+        }
+    }
+
+    /// Evaluate the wrapped function, or return the cached value if `x` was already visited
+    pub fn call<P: CacheKey + ?Sized>(&self, x: &P) -> T
+    where
+        F: Fn(&P) -> T,
+    {
+        let key = x.cache_key();
+        if let Some(v) = self.cache.borrow().get(&key) {
+            self.hits.set(self.hits.get() + 1);
+            return v.clone();
+        }
+        // the borrow is released while `f` runs, such that `f` may itself use the cache
+        let v = (self.f)(x);
+        self.cache.borrow_mut().insert(key, v.clone());
+        v
+    }
+
+    /// Number of evaluations which were answered from the cache
+    pub fn hits(&self) -> usize {
+        self.hits.get()
+    }
+
+    /// Number of cached points
+    pub fn len(&self) -> usize {
+        self.cache.borrow().len()
+    }
+
+    /// Returns `true` if nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.cache.borrow().is_empty()
+    }
+
+    /// Remove all cached values and set the number of hits to zero
+    pub fn clear(&self) {
+        self.cache.borrow_mut().clear();
+        self.hits.set(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CountingFn, FiniteDiff};
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    fn f(x: &Vec<f64>) -> f64 {
+        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
+    }
+
+    fn x() -> Vec<f64> {
+        vec![1.0f64, 1.0, 1.0, 1.0]
+    }
+
+    #[test]
+    fn test_memoized_fn_central_hessian_nograd() {
+        let cf = CountingFn::new(f);
+        let mf = MemoizedFn::new(|x: &Vec<f64>| cf.call(x));
+        let hessian = x().central_hessian_nograd(&|x| mf.call(x));
+        let res = x().central_hessian_nograd(&f);
+        assert_eq!(hessian, res);
+        // 4 evaluations for each of the 10 entries with j <= i
+        assert_eq!(cf.count() + mf.hits(), 40);
+        assert_eq!(cf.count(), mf.len());
+        // each diagonal entry visits f(x) twice, which is only evaluated once overall
+        assert_eq!(cf.count(), 40 - 2 * 4 + 1);
+    }
+
+    #[test]
+    fn test_memoized_fn_repeated() {
+        let cf = CountingFn::new(f);
+        let mf = MemoizedFn::new(|x: &Vec<f64>| cf.call(x));
+        x().forward_hessian_nograd(&|x| mf.call(x));
+        let count = cf.count();
+        // a gradient at the same position reuses f(x)
+        x().forward_diff(&|x| mf.call(x));
+        assert_eq!(cf.count(), count + 4);
+        // nothing new is evaluated the second time
+        x().forward_hessian_nograd(&|x| mf.call(x));
+        assert_eq!(cf.count(), count + 4);
+
+        mf.clear();
+        assert!(mf.is_empty());
+        assert_eq!(mf.hits(), 0);
+        x().forward_diff(&|x| mf.call(x));
+        assert_eq!(cf.count(), count + 9);
+    }
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!(
+            vec![1.0f64, -0.0].cache_key(),
+            vec![1.0f64.to_bits(), (-0.0f64).to_bits()]
+        );
+        assert_ne!(vec![0.0f64].cache_key(), vec![-0.0f64].cache_key());
+        assert_eq!([1.0f32].cache_key(), vec![u64::from(1.0f32.to_bits())]);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_cache_key_ndarray2() {
+        let a = ndarray::Array2::<f64>::zeros((2, 3));
+        let b = ndarray::Array2::<f64>::zeros((3, 2));
+        assert_ne!(a.cache_key(), b.cache_key());
+    }
+}
@@ -0,0 +1,263 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::fmt;
+use num_traits::Float;
+
+/// A single entry of an analytic gradient or Jacobian which does not agree with its finite
+/// difference approximation
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradCheckFailure<F> {
+    /// Position of the entry: `[i]` for gradients and `[i, j]` for Jacobians
+    pub index: Vec<usize>,
+    /// Value provided by the user
+    pub analytic: F,
+    /// Value computed via central differences
+    pub numeric: F,
+    /// `|analytic - numeric|`
+    pub abs_err: F,
+    /// `|analytic - numeric| / max(|analytic|, |numeric|)`
+    pub rel_err: F,
+}
+
+/// List of all entries which failed a gradient or Jacobian check
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradCheckReport<F> {
+    pub failures: Vec<GradCheckFailure<F>>,
+}
+
+impl<F: Float + fmt::Display> fmt::Display for GradCheckReport<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} entries failed the check:", self.failures.len())?;
+        for fail in self.failures.iter() {
+            writeln!(
+                f,
+                "  {:?}: analytic = {}, numeric = {}, abs. error = {}, rel. error = {}",
+                fail.index, fail.analytic, fail.numeric, fail.abs_err, fail.rel_err
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: Float + fmt::Debug + fmt::Display> std::error::Error for GradCheckReport<F> {}
+
+/// Compares pairs of analytic and numeric values. An entry passes if either the absolute or the
+/// relative error is not larger than `tol`.
+pub fn check_entries<F: Float>(
+    entries: impl Iterator<Item = (Vec<usize>, F, F)>,
+    tol: F,
+) -> Result<(), GradCheckReport<F>> {
+    let failures: Vec<GradCheckFailure<F>> = entries
+        .filter_map(|(index, analytic, numeric)| {
+            let abs_err = (analytic - numeric).abs();
+            let scale = analytic.abs().max(numeric.abs());
+            let rel_err = if scale > F::zero() {
+                abs_err / scale
+            } else {
+                F::zero()
+            };
+            // written such that NaN values fail the check
+            if abs_err <= tol || rel_err <= tol {
+                None
+            } else {
+                Some(GradCheckFailure {
+                    index,
+                    analytic,
+                    numeric,
+                    abs_err,
+                    rel_err,
+                })
+            }
+        })
+        .collect();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(GradCheckReport { failures })
+    }
+}
+
+pub fn check_gradient_vec<F: Float>(
+    analytic: &Vec<F>,
+    numeric: &Vec<F>,
+    tol: F,
+) -> Result<(), GradCheckReport<F>> {
+    assert_eq!(
+        analytic.len(),
+        numeric.len(),
+        "analytic gradient has wrong length"
+    );
+    check_entries(
+        analytic
+            .iter()
+            .zip(numeric.iter())
+            .enumerate()
+            .map(|(i, (&a, &n))| (vec![i], a, n)),
+        tol,
+    )
+}
+
+pub fn check_jacobian_vec<F: Float>(
+    analytic: &Vec<Vec<F>>,
+    numeric: &Vec<Vec<F>>,
+    tol: F,
+) -> Result<(), GradCheckReport<F>> {
+    assert_eq!(
+        analytic.len(),
+        numeric.len(),
+        "analytic Jacobian has wrong dimensions"
+    );
+    check_entries(
+        analytic
+            .iter()
+            .zip(numeric.iter())
+            .enumerate()
+            .flat_map(|(i, (ar, nr))| {
+                assert_eq!(ar.len(), nr.len(), "analytic Jacobian has wrong dimensions");
+                ar.iter()
+                    .zip(nr.iter())
+                    .enumerate()
+                    .map(move |(j, (&a, &n))| (vec![i, j], a, n))
+            }),
+        tol,
+    )
+}
+
+#[cfg(feature = "ndarray")]
+pub fn check_gradient_ndarray<F: Float>(
+    analytic: &ndarray::Array1<F>,
+    numeric: &ndarray::Array1<F>,
+    tol: F,
+) -> Result<(), GradCheckReport<F>> {
+    assert_eq!(
+        analytic.len(),
+        numeric.len(),
+        "analytic gradient has wrong length"
+    );
+    check_entries(
+        analytic
+            .iter()
+            .zip(numeric.iter())
+            .enumerate()
+            .map(|(i, (&a, &n))| (vec![i], a, n)),
+        tol,
+    )
+}
+
+#[cfg(feature = "ndarray")]
+pub fn check_jacobian_ndarray<F: Float>(
+    analytic: &ndarray::Array2<F>,
+    numeric: &ndarray::Array2<F>,
+    tol: F,
+) -> Result<(), GradCheckReport<F>> {
+    assert_eq!(
+        analytic.dim(),
+        numeric.dim(),
+        "analytic Jacobian has wrong dimensions"
+    );
+    check_entries(
+        analytic
+            .indexed_iter()
+            .zip(numeric.iter())
+            .map(|(((i, j), &a), &n)| (vec![i, j], a, n)),
+        tol,
+    )
+}
+
+#[cfg(feature = "nalgebra")]
+pub fn check_gradient_nalgebra<F: Float + nalgebra::Scalar>(
+    analytic: &nalgebra::DVector<F>,
+    numeric: &nalgebra::DVector<F>,
+    tol: F,
+) -> Result<(), GradCheckReport<F>> {
+    assert_eq!(
+        analytic.len(),
+        numeric.len(),
+        "analytic gradient has wrong length"
+    );
+    check_entries(
+        analytic
+            .iter()
+            .zip(numeric.iter())
+            .enumerate()
+            .map(|(i, (&a, &n))| (vec![i], a, n)),
+        tol,
+    )
+}
+
+#[cfg(feature = "nalgebra")]
+pub fn check_jacobian_nalgebra<F: Float + nalgebra::Scalar>(
+    analytic: &nalgebra::DMatrix<F>,
+    numeric: &nalgebra::DMatrix<F>,
+    tol: F,
+) -> Result<(), GradCheckReport<F>> {
+    assert_eq!(
+        analytic.shape(),
+        numeric.shape(),
+        "analytic Jacobian has wrong dimensions"
+    );
+    let (n, m) = analytic.shape();
+    check_entries(
+        (0..n).flat_map(|i| (0..m).map(move |j| (vec![i, j], analytic[(i, j)], numeric[(i, j)]))),
+        tol,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_gradient_vec_f64() {
+        let analytic = vec![1.0f64, 2.0, 0.0];
+        let numeric = vec![1.0f64 + 1e-9, 2.5, 1e-12];
+        let report = check_gradient_vec(&analytic, &numeric, 1e-6).unwrap_err();
+
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].index, vec![1]);
+        assert!((report.failures[0].abs_err - 0.5).abs() < 1e-15);
+        assert!((report.failures[0].rel_err - 0.2).abs() < 1e-15);
+        assert!(check_gradient_vec(&analytic, &numeric, 0.3).is_ok());
+    }
+
+    #[test]
+    fn test_check_gradient_vec_f64_nan() {
+        let analytic = vec![1.0f64, f64::NAN];
+        let numeric = vec![1.0f64, 2.0];
+        let report = check_gradient_vec(&analytic, &numeric, 1e-6).unwrap_err();
+
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].index, vec![1]);
+    }
+
+    #[test]
+    fn test_check_jacobian_vec_f64() {
+        let analytic = vec![vec![1.0f64, 2.0], vec![3.0, 4.0]];
+        let numeric = vec![vec![1.0f64, 2.0], vec![3.0, 5.0]];
+        let report = check_jacobian_vec(&analytic, &numeric, 1e-6).unwrap_err();
+
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].index, vec![1, 1]);
+        assert_eq!(report.failures[0].analytic, 4.0);
+        assert_eq!(report.failures[0].numeric, 5.0);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_check_jacobian_ndarray_f64() {
+        let analytic = ndarray::array![[1.0f64, 2.0], [3.0, 4.0]];
+        let numeric = ndarray::array![[1.0f64, 2.5], [3.0, 4.0]];
+        let report = check_jacobian_ndarray(&analytic, &numeric, 1e-6).unwrap_err();
+
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].index, vec![0, 1]);
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Absolute difference of two entries, infinite if one of them does not exist
+#[inline(always)]
+fn entry_diff(a: Option<&f64>, b: Option<&f64>) -> f64 {
+    match (a, b) {
+        (Some(a), Some(b)) => (a - b).abs(),
+        _ => f64::INFINITY,
+    }
+}
+
+/// Returns the entry `(i, j)` if it deviates by more than `tol`.
+#[inline(always)]
+fn check_entry(i: usize, j: usize, diff: f64, tol: f64) -> Result<(), (usize, usize, f64)> {
+    if diff > tol || diff.is_nan() {
+        Err((i, j, diff))
+    } else {
+        Ok(())
+    }
+}
+
+/// Compares two matrices, for instance a finite difference Jacobian with an analytic reference,
+/// entry by entry.
+///
+/// Returns the row, the column and the absolute difference of the first entry in row-major order
+/// which differs by more than `tol`. `NaN` entries are never considered equal. If the shapes
+/// differ, the first entry which only exists in one of the matrices is returned with an infinite
+/// difference, where a missing row is reported at column 0. The rows may have different lengths.
+///
+/// ```
+/// use finitediff::{approx_eq_matrix, FiniteDiff};
+///
+/// let x = vec![1.0f64, 2.0];
+/// let jacobian = x.forward_jacobian(&|x| vec![x[0] * x[1], x[1].powi(2)]);
+///
+/// assert_eq!(approx_eq_matrix(&jacobian, &vec![vec![2.0, 0.0], vec![1.0, 4.0]], 1e-6), Ok(()));
+///
+/// let (row, col, _diff) = approx_eq_matrix(&jacobian, &vec![vec![2.0, 0.0], vec![1.0, 5.0]], 1e-6)
+///     .unwrap_err();
+/// assert_eq!((row, col), (1, 1));
+/// ```
+pub fn approx_eq_matrix(
+    a: &Vec<Vec<f64>>,
+    b: &Vec<Vec<f64>>,
+    tol: f64,
+) -> Result<(), (usize, usize, f64)> {
+    for i in 0..a.len().max(b.len()) {
+        match (a.get(i), b.get(i)) {
+            (Some(ai), Some(bi)) => {
+                for j in 0..ai.len().max(bi.len()) {
+                    check_entry(i, j, entry_diff(ai.get(j), bi.get(j)), tol)?;
+                }
+            }
+            _ => return Err((i, 0, f64::INFINITY)),
+        }
+    }
+    Ok(())
+}
+
+/// Same as `approx_eq_matrix`, but for `ndarray::Array2<f64>`.
+#[cfg(feature = "ndarray")]
+pub fn approx_eq_matrix_ndarray(
+    a: &ndarray::Array2<f64>,
+    b: &ndarray::Array2<f64>,
+    tol: f64,
+) -> Result<(), (usize, usize, f64)> {
+    let (rows, cols) = (a.nrows().max(b.nrows()), a.ncols().max(b.ncols()));
+    for i in 0..rows {
+        for j in 0..cols {
+            check_entry(i, j, entry_diff(a.get((i, j)), b.get((i, j))), tol)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    #[test]
+    fn test_approx_eq_matrix() {
+        let a = vec![vec![1.0, 2.0], vec![3.0, 4.0, 5.0]];
+        assert_eq!(approx_eq_matrix(&a, &a, 0.0), Ok(()));
+        let b = vec![vec![1.0, 2.1], vec![3.0, 4.0, 5.5]];
+        assert_eq!(approx_eq_matrix(&a, &b, 0.6), Ok(()));
+        let (i, j, diff) = approx_eq_matrix(&a, &b, 0.2).unwrap_err();
+        assert_eq!((i, j), (1, 2));
+        assert!((diff - 0.5).abs() < 1e-12);
+        let (i, j, _) = approx_eq_matrix(&a, &b, 0.01).unwrap_err();
+        assert_eq!((i, j), (0, 1));
+    }
+
+    #[test]
+    fn test_approx_eq_matrix_shape_and_nan() {
+        let a = vec![vec![1.0, 2.0], vec![3.0]];
+        assert_eq!(
+            approx_eq_matrix(&a, &vec![vec![1.0, 2.0], vec![3.0, 4.0]], 1.0),
+            Err((1, 1, f64::INFINITY))
+        );
+        assert_eq!(
+            approx_eq_matrix(&a, &vec![vec![1.0, 2.0]], 1.0),
+            Err((1, 0, f64::INFINITY))
+        );
+        assert_eq!(
+            approx_eq_matrix(&vec![vec![]], &vec![vec![], vec![]], 1.0),
+            Err((1, 0, f64::INFINITY))
+        );
+        let (i, j, diff) =
+            approx_eq_matrix(&a, &vec![vec![1.0, f64::NAN], vec![3.0]], 1.0).unwrap_err();
+        assert_eq!((i, j), (0, 1));
+        assert!(diff.is_nan());
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_approx_eq_matrix_ndarray() {
+        let a = ndarray::array![[1.0, 2.0], [3.0, 4.0]];
+        let b = ndarray::array![[1.0, 2.0], [3.0, 4.5]];
+        assert_eq!(approx_eq_matrix_ndarray(&a, &b, 0.6), Ok(()));
+        assert_eq!(approx_eq_matrix_ndarray(&a, &b, 0.1), Err((1, 1, 0.5)));
+        let c = ndarray::array![[1.0, 2.0, 0.0], [3.0, 4.0, 0.0]];
+        assert_eq!(
+            approx_eq_matrix_ndarray(&a, &c, 1.0),
+            Err((0, 2, f64::INFINITY))
+        );
+    }
+}
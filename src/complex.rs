@@ -0,0 +1,305 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::utils::*;
+use crate::{FiniteDiffError, FloatEps};
+use num_complex::Complex;
+
+/// Size of the imaginary perturbation. Since there is no subtraction involved, this can be
+/// (almost) arbitrarily small.
+#[inline(always)]
+fn complex_step<F: FloatEps>() -> F {
+    F::from(1e-30).unwrap()
+}
+
+/// Compares the complex-step derivative `cs` with the real central difference `cd` of parameter
+/// `i`, relative to the magnitude of `cd` (but at least 1).
+#[inline(always)]
+fn check_holomorphic<F: FloatEps>(i: usize, cs: F, cd: F, tol: f64) -> Result<F, FiniteDiffError> {
+    let tol = F::from(tol).unwrap();
+    if (cs - cd).abs() <= tol * cd.abs().max(F::one()) {
+        Ok(cs)
+    } else {
+        Err(FiniteDiffError::NonHolomorphic { index: i })
+    }
+}
+
+/// Complex-step differentiation
+///
+/// Requires the cost function to be holomorphic, i.e. it must not use operations such as `abs`,
+/// `re` or `conj` on its complex input.
+pub trait ComplexStep
+where
+    Self: Sized,
+{
+    /// Complex counterpart of the parameter vector
+    type ComplexParam;
+    /// Complex counterpart of the scalar type
+    type ComplexScalar;
+
+    /// Gradient of `f` calculated via the complex step
+    ///
+    /// `df/dx_i (x) \approx Im(f(x + i * h * e_i))/h  \forall i`
+    ///
+    /// where `f` is a holomorphic extension of the cost function, `e_i` is the `i`th unit vector
+    /// and `h = 1e-30`. Since no differences are taken, the result is accurate to machine
+    /// precision.
+    /// For a parameter vector of length `n`, this requires `n` evaluations of `f`.
+    fn complex_step_diff(&self, f: &dyn Fn(&Self::ComplexParam) -> Self::ComplexScalar) -> Self;
+
+    /// Same as `complex_step_diff`, but additionally checks every entry against a real central
+    /// difference
+    ///
+    /// `f` is also evaluated at `x +/- sqrt(EPS) * e_i`, i.e. perturbed along the real axis, and
+    /// the central difference `Re(f(x + sqrt(EPS) * e_i) - f(x - sqrt(EPS) * e_i))/(2 * sqrt(EPS))`
+    /// is compared to the complex-step derivative. If they differ by more than
+    /// `tol * max(1, |central difference|)`, `FiniteDiffError::NonHolomorphic` is returned for
+    /// the first such parameter. This catches cost functions which use `abs`, `re` or `conj` on
+    /// their input. Since the central difference is only accurate to about `EPS^(2/3)`, `tol`
+    /// should not be chosen much smaller than `1e-6` for `f64`.
+    ///
+    /// The check costs one real central difference per parameter, so for a parameter vector of
+    /// length `n` this requires `3 * n` evaluations of `f` instead of `n`.
+    ///
+    /// ```
+    /// use finitediff::{ComplexStep, FiniteDiffError};
+    /// use num_complex::Complex;
+    ///
+    /// let x = vec![1.0f64, -2.0];
+    ///
+    /// let f = |x: &Vec<Complex<f64>>| x[0] * x[1].exp();
+    /// assert!(x.complex_step_diff_checked(&f, 1e-6).is_ok());
+    ///
+    /// // `norm` is not holomorphic, the complex step yields 0 for the second parameter
+    /// let f = |x: &Vec<Complex<f64>>| x[0] * x[1].norm();
+    /// assert_eq!(
+    ///     x.complex_step_diff_checked(&f, 1e-6),
+    ///     Err(FiniteDiffError::NonHolomorphic { index: 1 })
+    /// );
+    /// ```
+    fn complex_step_diff_checked(
+        &self,
+        f: &dyn Fn(&Self::ComplexParam) -> Self::ComplexScalar,
+        tol: f64,
+    ) -> Result<Self, FiniteDiffError>;
+}
+
+impl<F> ComplexStep for Vec<F>
+where
+    F: FloatEps,
+{
+    type ComplexParam = Vec<Complex<F>>;
+    type ComplexScalar = Complex<F>;
+
+    fn complex_step_diff(&self, f: &dyn Fn(&Self::ComplexParam) -> Self::ComplexScalar) -> Self {
+        complex_step_diff_vec(self, f)
+    }
+
+    fn complex_step_diff_checked(
+        &self,
+        f: &dyn Fn(&Self::ComplexParam) -> Self::ComplexScalar,
+        tol: f64,
+    ) -> Result<Self, FiniteDiffError> {
+        complex_step_diff_checked_vec(self, f, tol)
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<F> ComplexStep for ndarray::Array1<F>
+where
+    F: FloatEps,
+{
+    type ComplexParam = ndarray::Array1<Complex<F>>;
+    type ComplexScalar = Complex<F>;
+
+    fn complex_step_diff(&self, f: &dyn Fn(&Self::ComplexParam) -> Self::ComplexScalar) -> Self {
+        complex_step_diff_ndarray(self, f)
+    }
+
+    fn complex_step_diff_checked(
+        &self,
+        f: &dyn Fn(&Self::ComplexParam) -> Self::ComplexScalar,
+        tol: f64,
+    ) -> Result<Self, FiniteDiffError> {
+        complex_step_diff_checked_ndarray(self, f, tol)
+    }
+}
+
+pub fn complex_step_diff_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<Complex<F>>) -> Complex<F>,
+) -> Vec<F> {
+    let h = complex_step::<F>();
+    let mut xt: Vec<Complex<F>> = x.iter().map(|&xi| Complex::new(xi, F::zero())).collect();
+    (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, Complex::new(F::zero(), h));
+            fx1.im / h
+        })
+        .collect()
+}
+
+pub fn complex_step_diff_checked_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<Complex<F>>) -> Complex<F>,
+    tol: f64,
+) -> Result<Vec<F>, FiniteDiffError> {
+    let h = complex_step::<F>();
+    let hr = F::EPS.sqrt();
+    let mut xt: Vec<Complex<F>> = x.iter().map(|&xi| Complex::new(xi, F::zero())).collect();
+    (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, Complex::new(F::zero(), h));
+            let fx2 = mod_and_calc_vec(&mut xt, f, i, Complex::new(hr, F::zero()));
+            let fx3 = mod_and_calc_vec(&mut xt, f, i, Complex::new(-hr, F::zero()));
+            let cd = (fx2.re - fx3.re) / (F::from(2.0).unwrap() * hr);
+            check_holomorphic(i, fx1.im / h, cd, tol)
+        })
+        .collect()
+}
+
+#[cfg(feature = "ndarray")]
+pub fn complex_step_diff_ndarray<F: FloatEps>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<Complex<F>>) -> Complex<F>,
+) -> ndarray::Array1<F> {
+    let h = complex_step::<F>();
+    let mut xt = x.mapv(|xi| Complex::new(xi, F::zero()));
+    (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_ndarray(&mut xt, f, i, Complex::new(F::zero(), h));
+            fx1.im / h
+        })
+        .collect()
+}
+
+#[cfg(feature = "ndarray")]
+pub fn complex_step_diff_checked_ndarray<F: FloatEps>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<Complex<F>>) -> Complex<F>,
+    tol: f64,
+) -> Result<ndarray::Array1<F>, FiniteDiffError> {
+    let h = complex_step::<F>();
+    let hr = F::EPS.sqrt();
+    let mut xt = x.mapv(|xi| Complex::new(xi, F::zero()));
+    (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_ndarray(&mut xt, f, i, Complex::new(F::zero(), h));
+            let fx2 = mod_and_calc_ndarray(&mut xt, f, i, Complex::new(hr, F::zero()));
+            let fx3 = mod_and_calc_ndarray(&mut xt, f, i, Complex::new(-hr, F::zero()));
+            let cd = (fx2.re - fx3.re) / (F::from(2.0).unwrap() * hr);
+            check_holomorphic(i, fx1.im / h, cd, tol)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMP_ACC: f64 = 1e-15;
+
+    fn f(x: &Vec<Complex<f64>>) -> Complex<f64> {
+        x[0] + x[1].powi(2) + x[0].exp() * x[1].sin()
+    }
+
+    fn res(x: &Vec<f64>) -> Vec<f64> {
+        vec![
+            1.0 + x[0].exp() * x[1].sin(),
+            2.0 * x[1] + x[0].exp() * x[1].cos(),
+        ]
+    }
+
+    #[test]
+    fn test_complex_step_diff_vec_f64() {
+        let x = vec![1.0f64, 2.0];
+        let grad = complex_step_diff_vec(&x, &f);
+        let res = res(&x);
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC * res[i].abs())
+        }
+    }
+
+    #[test]
+    fn test_complex_step_diff_vec_f64_trait() {
+        let x = vec![0.5f64, -1.5];
+        let grad = x.complex_step_diff(&f);
+        let res = res(&x);
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC * res[i].abs())
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_complex_step_diff_ndarray_f64() {
+        use ndarray::{array, Array1};
+
+        let f = |x: &Array1<Complex<f64>>| -> Complex<f64> {
+            x[0] + x[1].powi(2) + x[0].exp() * x[1].sin()
+        };
+        let x = array![1.0f64, 2.0];
+        let grad = x.complex_step_diff(&f);
+        let res = res(&x.to_vec());
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC * res[i].abs())
+        }
+    }
+
+    #[test]
+    fn test_complex_step_diff_checked_vec_f64() {
+        let x = vec![1.0f64, 2.0];
+        let grad = complex_step_diff_checked_vec(&x, &f, 1e-6).unwrap();
+        assert_eq!(grad, complex_step_diff_vec(&x, &f));
+
+        let g = |x: &Vec<Complex<f64>>| x[0].powi(2) + Complex::new(x[1].re.powi(2), 0.0);
+        assert_eq!(
+            complex_step_diff_checked_vec(&x, &g, 1e-6),
+            Err(FiniteDiffError::NonHolomorphic { index: 1 })
+        );
+        let g = |x: &Vec<Complex<f64>>| x[0].conj() * x[1];
+        assert_eq!(
+            x.complex_step_diff_checked(&g, 1e-6),
+            Err(FiniteDiffError::NonHolomorphic { index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_complex_step_diff_checked_evaluations() {
+        let counter = core::cell::Cell::new(0);
+        let g = |x: &Vec<Complex<f64>>| {
+            counter.set(counter.get() + 1);
+            f(x)
+        };
+        let x = vec![1.0f64, 2.0];
+        x.complex_step_diff_checked(&g, 1e-6).unwrap();
+        assert_eq!(counter.get(), 3 * x.len());
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_complex_step_diff_checked_ndarray_f64() {
+        use ndarray::{array, Array1};
+
+        let f = |x: &Array1<Complex<f64>>| -> Complex<f64> {
+            x[0] + x[1].powi(2) + x[0].exp() * x[1].sin()
+        };
+        let x = array![1.0f64, 2.0];
+        assert_eq!(
+            x.complex_step_diff_checked(&f, 1e-6).unwrap(),
+            x.complex_step_diff(&f)
+        );
+        let g = |x: &Array1<Complex<f64>>| -> Complex<f64> { x[0] * x[1].norm() };
+        assert_eq!(
+            x.complex_step_diff_checked(&g, 1e-6),
+            Err(FiniteDiffError::NonHolomorphic { index: 1 })
+        );
+    }
+}
@@ -0,0 +1,526 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Configurable finite differences.
+//!
+//! Instead of choosing one of the many methods of `FiniteDiff`, the scheme, the step and the
+//! order of the stencil are set once in a `FiniteDiffConfig`, which can then be reused for
+//! gradients and Jacobians of all supported parameter vectors.
+
+use crate::array::*;
+use crate::diff::*;
+#[cfg(feature = "nalgebra")]
+use crate::diff_nalgebra::*;
+#[cfg(feature = "ndarray")]
+use crate::diff_ndarray::*;
+use crate::error::{check_dim, FiniteDiffError};
+use crate::jacobian::*;
+#[cfg(feature = "nalgebra")]
+use crate::jacobian_nalgebra::*;
+#[cfg(feature = "ndarray")]
+use crate::jacobian_ndarray::*;
+use crate::utils::step_magnitude;
+use crate::FiniteDiff;
+use crate::FloatEps;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Finite difference scheme
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// `(f(x + h * e_i) - f(x)) / h`
+    Forward,
+    /// `(f(x) - f(x - h * e_i)) / h`
+    Backward,
+    /// Symmetric stencils around `x`
+    Central,
+}
+
+/// Strategy for choosing the step of each parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepStrategy<F> {
+    /// The same absolute step for all parameters
+    Absolute(F),
+    /// `factor * max(|x_i|, 1)`, which keeps the step significant for large parameters
+    Relative(F),
+    /// Individual absolute steps; the length must match the parameter vector
+    PerComponent(Vec<F>),
+}
+
+impl<F: FloatEps> Default for StepStrategy<F> {
+    /// `Absolute(sqrt(EPS))`
+    fn default() -> Self {
+        StepStrategy::Absolute(F::EPS.sqrt())
+    }
+}
+
+impl<F: FloatEps> StepStrategy<F> {
+    /// Step for the parameter `xi` at index `i`.
+    ///
+    /// Panics if `i` is out of bounds for `PerComponent`.
+    #[inline(always)]
+    pub fn step(&self, i: usize, xi: F) -> F {
+        match self {
+            StepStrategy::Absolute(h) => *h,
+            StepStrategy::Relative(factor) => *factor * step_magnitude(xi),
+            StepStrategy::PerComponent(steps) => steps[i],
+        }
+    }
+
+    /// Checks whether the strategy can be applied to a parameter vector of length `n`.
+    fn check(&self, n: usize) -> Result<(), FiniteDiffError> {
+        match self {
+            StepStrategy::PerComponent(steps) => check_dim(n, steps.len()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Stencils which are available for the combinations of `Method` and order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stencil {
+    Forward,
+    Backward,
+    Central,
+    Central5,
+}
+
+/// Configuration of a finite difference approximation.
+///
+/// `order` is the order of accuracy of the stencil. `Method::Forward` and `Method::Backward`
+/// support order 1, `Method::Central` supports order 2 (three-point stencil) and order 4
+/// (five-point stencil). If no order is set, the lowest order of the method is used.
+/// Unsupported combinations are reported as `FiniteDiffError::UnsupportedOrder`.
+///
+/// ```
+/// use finitediff::{FiniteDiffConfig, Method, StepStrategy};
+///
+/// let f = |x: &Vec<f64>| x[0].sin() + x[1].powi(3);
+///
+/// let config = FiniteDiffConfig::new()
+///     .method(Method::Central)
+///     .order(4)
+///     .step(StepStrategy::Absolute(1e-3));
+///
+/// let grad = config.gradient(&vec![1.0f64, 2.0], &f).unwrap();
+///
+/// assert!((grad[0] - 1.0f64.cos()).abs() < 1e-10);
+/// assert!((grad[1] - 12.0).abs() < 1e-10);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FiniteDiffConfig<F> {
+    method: Method,
+    step: StepStrategy<F>,
+    order: Option<usize>,
+}
+
+impl<F: FloatEps> Default for FiniteDiffConfig<F> {
+    fn default() -> Self {
+        FiniteDiffConfig {
+            method: Method::Forward,
+            step: StepStrategy::default(),
+            order: None,
+        }
+    }
+}
+
+impl<F: FloatEps> FiniteDiffConfig<F> {
+    /// Forward differences of order 1 with the default step
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the finite difference scheme
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Set the step strategy
+    pub fn step(mut self, step: StepStrategy<F>) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Set the order of accuracy of the stencil
+    pub fn order(mut self, order: usize) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    /// Gradient of `f` at `x`
+    pub fn gradient<T: ConfigInput<Scalar = F>>(
+        &self,
+        x: &T,
+        f: &dyn Fn(&T) -> F,
+    ) -> Result<T, FiniteDiffError> {
+        x.config_gradient(self, f)
+    }
+
+    /// Jacobian of `fs` at `x`, in the same layout as the Jacobian methods of `FiniteDiff`
+    pub fn jacobian<T: ConfigInput<Scalar = F>>(
+        &self,
+        x: &T,
+        fs: &dyn Fn(&T) -> T::OperatorOutput,
+    ) -> Result<T::Jacobian, FiniteDiffError> {
+        x.config_jacobian(self, fs)
+    }
+
+    /// Validates the configuration for a parameter vector of length `n` and selects the stencil.
+    fn stencil(&self, n: usize) -> Result<Stencil, FiniteDiffError> {
+        self.step.check(n)?;
+        match (self.method, self.order) {
+            (Method::Forward, None) | (Method::Forward, Some(1)) => Ok(Stencil::Forward),
+            (Method::Backward, None) | (Method::Backward, Some(1)) => Ok(Stencil::Backward),
+            (Method::Central, None) | (Method::Central, Some(2)) => Ok(Stencil::Central),
+            (Method::Central, Some(4)) => Ok(Stencil::Central5),
+            (_, Some(order)) => Err(FiniteDiffError::UnsupportedOrder { order }),
+        }
+    }
+
+    /// Steps for all parameters. Backward differences are forward differences with negative steps.
+    fn steps<'a>(&'a self, x: impl Iterator<Item = &'a F> + 'a) -> impl Iterator<Item = F> + 'a {
+        let sign = if self.method == Method::Backward {
+            -F::one()
+        } else {
+            F::one()
+        };
+        x.enumerate()
+            .map(move |(i, &xi)| sign * self.step.step(i, xi))
+    }
+}
+
+/// Parameter vectors which can be used with `FiniteDiffConfig`
+pub trait ConfigInput: FiniteDiff {
+    /// Gradient of `f` as configured in `config`
+    fn config_gradient(
+        &self,
+        config: &FiniteDiffConfig<Self::Scalar>,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+    ) -> Result<Self, FiniteDiffError>;
+
+    /// Jacobian of `fs` as configured in `config`
+    fn config_jacobian(
+        &self,
+        config: &FiniteDiffConfig<Self::Scalar>,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Result<Self::Jacobian, FiniteDiffError>;
+}
+
+impl<F: FloatEps> ConfigInput for Vec<F> {
+    fn config_gradient(
+        &self,
+        config: &FiniteDiffConfig<F>,
+        f: &dyn Fn(&Self) -> F,
+    ) -> Result<Self, FiniteDiffError> {
+        let stencil = config.stencil(self.len())?;
+        let steps = config.steps(self.iter()).collect();
+        Ok(match stencil {
+            Stencil::Forward | Stencil::Backward => forward_diff_scaled_vec(self, f, &steps),
+            Stencil::Central => central_diff_scaled_vec(self, f, &steps),
+            Stencil::Central5 => central_diff_5_scaled_vec(self, f, &steps),
+        })
+    }
+
+    fn config_jacobian(
+        &self,
+        config: &FiniteDiffConfig<F>,
+        fs: &dyn Fn(&Self) -> Self,
+    ) -> Result<Vec<Vec<F>>, FiniteDiffError> {
+        let stencil = config.stencil(self.len())?;
+        let steps = config.steps(self.iter()).collect();
+        Ok(match stencil {
+            Stencil::Forward | Stencil::Backward => forward_jacobian_scaled_vec(self, fs, &steps),
+            Stencil::Central => central_jacobian_scaled_vec(self, fs, &steps),
+            Stencil::Central5 => central_jacobian_5_scaled_vec(self, fs, &steps),
+        })
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<F: FloatEps + ndarray::ScalarOperand> ConfigInput for ndarray::Array1<F> {
+    fn config_gradient(
+        &self,
+        config: &FiniteDiffConfig<F>,
+        f: &dyn Fn(&Self) -> F,
+    ) -> Result<Self, FiniteDiffError> {
+        let stencil = config.stencil(self.len())?;
+        let steps = config.steps(self.iter()).collect();
+        Ok(match stencil {
+            Stencil::Forward | Stencil::Backward => forward_diff_scaled_ndarray(self, f, &steps),
+            Stencil::Central => central_diff_scaled_ndarray(self, f, &steps),
+            Stencil::Central5 => central_diff_5_scaled_ndarray(self, f, &steps),
+        })
+    }
+
+    fn config_jacobian(
+        &self,
+        config: &FiniteDiffConfig<F>,
+        fs: &dyn Fn(&Self) -> Self,
+    ) -> Result<ndarray::Array2<F>, FiniteDiffError> {
+        let stencil = config.stencil(self.len())?;
+        let steps = config.steps(self.iter()).collect();
+        Ok(match stencil {
+            Stencil::Forward | Stencil::Backward => {
+                forward_jacobian_scaled_ndarray(self, fs, &steps)
+            }
+            Stencil::Central => central_jacobian_scaled_ndarray(self, fs, &steps),
+            Stencil::Central5 => central_jacobian_5_scaled_ndarray(self, fs, &steps),
+        })
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<F: FloatEps + nalgebra::Scalar> ConfigInput for nalgebra::DVector<F> {
+    fn config_gradient(
+        &self,
+        config: &FiniteDiffConfig<F>,
+        f: &dyn Fn(&Self) -> F,
+    ) -> Result<Self, FiniteDiffError> {
+        let stencil = config.stencil(self.len())?;
+        let steps = nalgebra::DVector::from_iterator(self.len(), config.steps(self.iter()));
+        Ok(match stencil {
+            Stencil::Forward | Stencil::Backward => forward_diff_scaled_nalgebra(self, f, &steps),
+            Stencil::Central => central_diff_scaled_nalgebra(self, f, &steps),
+            Stencil::Central5 => central_diff_5_scaled_nalgebra(self, f, &steps),
+        })
+    }
+
+    fn config_jacobian(
+        &self,
+        config: &FiniteDiffConfig<F>,
+        fs: &dyn Fn(&Self) -> Self,
+    ) -> Result<nalgebra::DMatrix<F>, FiniteDiffError> {
+        let stencil = config.stencil(self.len())?;
+        let steps = nalgebra::DVector::from_iterator(self.len(), config.steps(self.iter()));
+        Ok(match stencil {
+            Stencil::Forward | Stencil::Backward => {
+                forward_jacobian_scaled_nalgebra(self, fs, &steps)
+            }
+            Stencil::Central => central_jacobian_scaled_nalgebra(self, fs, &steps),
+            Stencil::Central5 => central_jacobian_5_scaled_nalgebra(self, fs, &steps),
+        })
+    }
+}
+
+impl<const N: usize> ConfigInput for [f64; N] {
+    fn config_gradient(
+        &self,
+        config: &FiniteDiffConfig<f64>,
+        f: &dyn Fn(&Self) -> f64,
+    ) -> Result<Self, FiniteDiffError> {
+        let f = |x: &Vec<f64>| (f)(&vec_to_array(x));
+        config
+            .gradient(&self.to_vec(), &f)
+            .map(|g| vec_to_array(&g))
+    }
+
+    fn config_jacobian(
+        &self,
+        config: &FiniteDiffConfig<f64>,
+        fs: &dyn Fn(&Self) -> Self,
+    ) -> Result<[[f64; N]; N], FiniteDiffError> {
+        let fs = |x: &Vec<f64>| (fs)(&vec_to_array(x)).to_vec();
+        config
+            .jacobian(&self.to_vec(), &fs)
+            .map(|j| mat_to_array(&j))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    const COMP_ACC: f64 = 1e-6;
+
+    fn f(x: &Vec<f64>) -> f64 {
+        x[0].sin() + x[1].powi(3)
+    }
+
+    fn fs(x: &Vec<f64>) -> Vec<f64> {
+        vec![x[0] * x[1], x[1].exp()]
+    }
+
+    const GRAD: [f64; 2] = [0.5403023058681398, 12.0];
+
+    fn jac() -> [[f64; 2]; 2] {
+        [[2.0, 0.0], [1.0, 2.0f64.exp()]]
+    }
+
+    #[test]
+    fn test_config_gradient_vec() {
+        let x = vec![1.0f64, 2.0];
+        for &(method, order) in [
+            (Method::Forward, 1),
+            (Method::Backward, 1),
+            (Method::Central, 2),
+            (Method::Central, 4),
+        ]
+        .iter()
+        {
+            let config = FiniteDiffConfig::new().method(method).order(order);
+            let grad = config.gradient(&x, &f).unwrap();
+            for i in 0..2 {
+                assert!((GRAD[i] - grad[i]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_config_jacobian_vec() {
+        let x = vec![1.0f64, 2.0];
+        for &method in [Method::Forward, Method::Backward, Method::Central].iter() {
+            let config = FiniteDiffConfig::new().method(method);
+            let jacobian = config.jacobian(&x, &fs).unwrap();
+            for i in 0..2 {
+                for j in 0..2 {
+                    assert!((jac()[i][j] - jacobian[i][j]).abs() < COMP_ACC);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_config_order_accuracy() {
+        // with a large step, only the fourth order stencil is still accurate
+        let x = vec![1.0f64, 2.0];
+        let config = FiniteDiffConfig::new()
+            .method(Method::Central)
+            .step(StepStrategy::Absolute(1e-2));
+        let grad2 = config.gradient(&x, &f).unwrap();
+        let grad4 = config.clone().order(4).gradient(&x, &f).unwrap();
+        assert!((GRAD[0] - grad2[0]).abs() > 1e-6);
+        assert!((GRAD[0] - grad4[0]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_config_backward() {
+        // |x| has a kink at 0: forward and backward differences see different slopes
+        let f = |x: &Vec<f64>| x[0].abs();
+        let x = vec![0.0f64];
+        let fwd = FiniteDiffConfig::new().gradient(&x, &f).unwrap();
+        let bwd = FiniteDiffConfig::new()
+            .method(Method::Backward)
+            .gradient(&x, &f)
+            .unwrap();
+        assert!((fwd[0] - 1.0).abs() < COMP_ACC);
+        assert!((bwd[0] + 1.0).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_config_unsupported_order() {
+        let x = vec![1.0f64, 2.0];
+        let config = FiniteDiffConfig::new().method(Method::Forward).order(2);
+        assert_eq!(
+            config.gradient(&x, &f),
+            Err(FiniteDiffError::UnsupportedOrder { order: 2 })
+        );
+        let config = FiniteDiffConfig::new().method(Method::Central).order(3);
+        assert_eq!(
+            config.jacobian(&x, &fs),
+            Err(FiniteDiffError::UnsupportedOrder { order: 3 })
+        );
+    }
+
+    #[test]
+    fn test_config_relative_step() {
+        // the absolute default step is lost in the rounding of 1e8
+        let f = |x: &Vec<f64>| x[0].ln() + x[1].powi(2);
+        let x = vec![1e8f64, 1.0];
+        let absolute = FiniteDiffConfig::new().gradient(&x, &f).unwrap();
+        let relative = FiniteDiffConfig::new()
+            .step(StepStrategy::Relative(f64::EPSILON.sqrt()))
+            .gradient(&x, &f)
+            .unwrap();
+        assert_eq!(absolute[0], 0.0);
+        assert!(((relative[0] - 1e-8) / 1e-8).abs() < 1e-3);
+        assert!((relative[1] - 2.0).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_config_per_component_step() {
+        let x = vec![1.0f64, 2.0];
+        let config = FiniteDiffConfig::new()
+            .method(Method::Central)
+            .step(StepStrategy::PerComponent(vec![1e-5, 1e-4]));
+        let grad = config.gradient(&x, &f).unwrap();
+        let jacobian = config.jacobian(&x, &fs).unwrap();
+        for i in 0..2 {
+            assert!((GRAD[i] - grad[i]).abs() < COMP_ACC);
+            for j in 0..2 {
+                assert!((jac()[i][j] - jacobian[i][j]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_config_per_component_step_dim() {
+        let config = FiniteDiffConfig::new().step(StepStrategy::PerComponent(vec![1e-6]));
+        assert_eq!(
+            config.gradient(&vec![1.0f64, 2.0], &f),
+            Err(FiniteDiffError::DimensionMismatch {
+                expected: 2,
+                got: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_step_strategy() {
+        assert_eq!(StepStrategy::Absolute(0.1).step(0, 100.0), 0.1);
+        assert_eq!(StepStrategy::Relative(0.1).step(0, -100.0), 10.0);
+        assert_eq!(StepStrategy::Relative(0.1).step(0, 0.5), 0.1);
+        assert_eq!(StepStrategy::PerComponent(vec![0.1, 0.2]).step(1, 3.0), 0.2);
+    }
+
+    #[test]
+    fn test_config_array() {
+        let f = |x: &[f64; 2]| x[0].sin() + x[1].powi(3);
+        let config = FiniteDiffConfig::new().method(Method::Central).order(4);
+        let grad = config.gradient(&[1.0, 2.0], &f).unwrap();
+        for i in 0..2 {
+            assert!((GRAD[i] - grad[i]).abs() < COMP_ACC);
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_config_ndarray() {
+        let x = ndarray::Array1::from(vec![1.0f64, 2.0]);
+        let f = |x: &ndarray::Array1<f64>| x[0].sin() + x[1].powi(3);
+        let fs = |x: &ndarray::Array1<f64>| ndarray::Array1::from(vec![x[0] * x[1], x[1].exp()]);
+        let config = FiniteDiffConfig::new().method(Method::Central).order(4);
+        let grad = config.gradient(&x, &f).unwrap();
+        let jacobian = config.jacobian(&x, &fs).unwrap();
+        for i in 0..2 {
+            assert!((GRAD[i] - grad[i]).abs() < COMP_ACC);
+            for j in 0..2 {
+                assert!((jac()[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_config_nalgebra() {
+        let x = nalgebra::DVector::from_vec(vec![1.0f64, 2.0]);
+        let f = |x: &nalgebra::DVector<f64>| x[0].sin() + x[1].powi(3);
+        let fs =
+            |x: &nalgebra::DVector<f64>| nalgebra::DVector::from_vec(vec![x[0] * x[1], x[1].exp()]);
+        let config = FiniteDiffConfig::new().method(Method::Backward);
+        let grad = config.gradient(&x, &f).unwrap();
+        let jacobian = config.jacobian(&x, &fs).unwrap();
+        for i in 0..2 {
+            assert!((GRAD[i] - grad[i]).abs() < COMP_ACC);
+            for j in 0..2 {
+                assert!((jac()[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::error::check_dim;
+use crate::utils::*;
+use crate::{FiniteDiffError, FloatEps};
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+/// Scratch buffers for repeated differentiation of problems of the same size.
+///
+/// The perturbed parameter vector, the gradient and the Jacobian are allocated once in `new` and
+/// reused by every call. The only remaining allocations are the ones made by the function itself,
+/// for instance the `Vec` returned by a vector valued function. This is intended for optimizers
+/// which compute derivatives of the same problem many times.
+///
+/// The results are borrowed from the context and are overwritten by the next call.
+///
+/// ```
+/// use finitediff::DiffContext;
+///
+/// let fs = |x: &Vec<f64>| vec![x[0] * x[1], x[1].powi(2), x[0] + x[1]];
+/// let mut ctx = DiffContext::new(2, 3);
+///
+/// for k in 0..10 {
+///     let x = vec![1.0f64, k as f64];
+///     let jacobian = ctx.forward_jacobian(&x, &fs).unwrap();
+/// #   assert!((jacobian[0][0] - k as f64).abs() < 1e-6);
+/// #   assert!((jacobian[1][1] - 2.0 * k as f64).abs() < 1e-6);
+/// }
+///
+/// // parameters of the wrong length are rejected
+/// assert!(ctx.forward_jacobian(&vec![1.0f64; 3], &fs).is_err());
+/// ```
+pub struct DiffContext<F> {
+    xt: Vec<F>,
+    grad: Vec<F>,
+    jacobian: Vec<Vec<F>>,
+    m: usize,
+}
+
+impl<F: FloatEps> DiffContext<F> {
+    /// Context for `n` parameters and vector valued functions with `m` values
+    pub fn new(n: usize, m: usize) -> Self {
+        DiffContext {
+            xt: vec![F::zero(); n],
+            grad: vec![F::zero(); n],
+            jacobian: vec![vec![F::zero(); m]; n],
+            m,
+        }
+    }
+
+    /// Number of parameters
+    pub fn n(&self) -> usize {
+        self.xt.len()
+    }
+
+    /// Number of function values
+    pub fn m(&self) -> usize {
+        self.m
+    }
+
+    /// Same as `FiniteDiff::forward_diff`, but reuses the buffers of the context.
+    ///
+    /// Returns an error if `x` does not have `n` entries.
+    pub fn forward_diff(
+        &mut self,
+        x: &Vec<F>,
+        f: &dyn Fn(&Vec<F>) -> F,
+    ) -> Result<&[F], FiniteDiffError> {
+        check_dim(self.n(), x.len())?;
+        self.xt.copy_from_slice(x);
+        let fx = (f)(x);
+        for i in 0..x.len() {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut self.xt, f, i, h);
+            self.grad[i] = (fx1 - fx) / h;
+        }
+        Ok(&self.grad)
+    }
+
+    /// Same as `FiniteDiff::forward_jacobian`, but reuses the buffers of the context.
+    ///
+    /// Returns an error if `x` does not have `n` entries or if `fs` does not return `m` values.
+    pub fn forward_jacobian(
+        &mut self,
+        x: &Vec<F>,
+        fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    ) -> Result<&[Vec<F>], FiniteDiffError> {
+        check_dim(self.n(), x.len())?;
+        let m = self.m;
+        self.xt.copy_from_slice(x);
+        let fx = (fs)(x);
+        check_dim(m, fx.len())?;
+        for (i, row) in self.jacobian.iter_mut().enumerate() {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut self.xt, fs, i, h);
+            check_dim(m, fx1.len())?;
+            for (o, (&a, &b)) in row.iter_mut().zip(fx1.iter().zip(fx.iter())) {
+                *o = (a - b) / h;
+            }
+        }
+        Ok(&self.jacobian)
+    }
+
+    /// Same as `FiniteDiff::central_jacobian`, but reuses the buffers of the context.
+    ///
+    /// Returns an error if `x` does not have `n` entries or if `fs` does not return `m` values.
+    pub fn central_jacobian(
+        &mut self,
+        x: &Vec<F>,
+        fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    ) -> Result<&[Vec<F>], FiniteDiffError> {
+        check_dim(self.n(), x.len())?;
+        let m = self.m;
+        self.xt.copy_from_slice(x);
+        for (i, row) in self.jacobian.iter_mut().enumerate() {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut self.xt, fs, i, h);
+            let fx2 = mod_and_calc_vec(&mut self.xt, fs, i, -h);
+            check_dim(m, fx1.len())?;
+            check_dim(m, fx2.len())?;
+            for (o, (&a, &b)) in row.iter_mut().zip(fx1.iter().zip(fx2.iter())) {
+                *o = (a - b) / (F::from(2.0).unwrap() * h);
+            }
+        }
+        Ok(&self.jacobian)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FiniteDiff;
+
+    fn fs(x: &Vec<f64>) -> Vec<f64> {
+        vec![x[0] * x[1], x[1].exp(), x[0] + x[2].powi(2), x[2]]
+    }
+
+    fn f(x: &Vec<f64>) -> f64 {
+        x[0] + x[1].powi(2) + x[2].sin()
+    }
+
+    #[test]
+    fn test_diff_context_matches_finite_diff() {
+        let mut ctx = DiffContext::new(3, 4);
+        assert_eq!((ctx.n(), ctx.m()), (3, 4));
+        for k in 0..3 {
+            let x = vec![1.0f64, 0.5 * k as f64, 2.0];
+            assert_eq!(ctx.forward_diff(&x, &f).unwrap(), &x.forward_diff(&f)[..]);
+            assert_eq!(
+                ctx.forward_jacobian(&x, &fs).unwrap(),
+                &x.forward_jacobian(&fs)[..]
+            );
+            assert_eq!(
+                ctx.central_jacobian(&x, &fs).unwrap(),
+                &x.central_jacobian(&fs)[..]
+            );
+        }
+    }
+
+    #[test]
+    fn test_diff_context_parameter_mismatch() {
+        let mut ctx = DiffContext::new(3, 4);
+        let x = vec![1.0f64, 2.0];
+        let err = FiniteDiffError::DimensionMismatch {
+            expected: 3,
+            got: 2,
+        };
+        assert_eq!(ctx.forward_diff(&x, &f), Err(err.clone()));
+        assert_eq!(ctx.forward_jacobian(&x, &fs), Err(err.clone()));
+        assert_eq!(ctx.central_jacobian(&x, &fs), Err(err));
+    }
+
+    #[test]
+    fn test_diff_context_output_mismatch() {
+        let mut ctx = DiffContext::new(3, 2);
+        let x = vec![1.0f64, 2.0, 3.0];
+        let err = FiniteDiffError::DimensionMismatch {
+            expected: 2,
+            got: 4,
+        };
+        assert_eq!(ctx.forward_jacobian(&x, &fs), Err(err.clone()));
+        assert_eq!(ctx.central_jacobian(&x, &fs), Err(err));
+    }
+}
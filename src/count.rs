@@ -0,0 +1,108 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::cell::Cell;
+
+/// Wrapper around a function which counts how often it was called.
+///
+/// All methods of `FiniteDiff` take `&dyn Fn`, therefore the counter uses interior mutability.
+/// The count includes every evaluation, including the one at the unperturbed position `f(x)`.
+///
+/// ```
+/// use finitediff::{CountingFn, FiniteDiff};
+///
+/// let f = CountingFn::new(|x: &Vec<f64>| x[0] + x[1].powi(2));
+///
+/// let x = vec![1.0f64, 1.0];
+/// let _grad = x.forward_diff(&|x| f.call(x));
+///
+/// // n+1 evaluations for a parameter vector of length n
+/// assert_eq!(f.count(), 3);
+/// ```
+pub struct CountingFn<F> {
+    f: F,
+    count: Cell<usize>,
+}
+
+impl<F> CountingFn<F> {
+    /// Wrap the function `f`
+    pub fn new(f: F) -> Self {
+        CountingFn {
+            f,
+            count: Cell::new(0),
+        }
+    }
+
+    /// Evaluate the wrapped function and increment the counter
+    pub fn call<P: ?Sized, T>(&self, x: &P) -> T
+    where
+        F: Fn(&P) -> T,
+    {
+        self.count.set(self.count.get() + 1);
+        (self.f)(x)
+    }
+
+    /// Number of evaluations since creation or the last call to `reset`
+    pub fn count(&self) -> usize {
+        self.count.get()
+    }
+
+    /// Set the counter to zero
+    pub fn reset(&self) {
+        self.count.set(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FiniteDiff;
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
+    fn f(x: &Vec<f64>) -> f64 {
+        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
+    }
+
+    fn g(x: &Vec<f64>) -> Vec<f64> {
+        vec![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    }
+
+    fn x() -> Vec<f64> {
+        vec![1.0f64, 1.0, 1.0, 1.0]
+    }
+
+    #[test]
+    fn test_counting_fn_diff() {
+        let cf = CountingFn::new(f);
+        x().forward_diff(&|x| cf.call(x));
+        assert_eq!(cf.count(), 5);
+
+        cf.reset();
+        x().central_diff(&|x| cf.call(x));
+        assert_eq!(cf.count(), 8);
+    }
+
+    #[test]
+    fn test_counting_fn_jacobian() {
+        let cg = CountingFn::new(g);
+        x().forward_jacobian(&|x| cg.call(x));
+        assert_eq!(cg.count(), 5);
+
+        cg.reset();
+        x().central_jacobian(&|x| cg.call(x));
+        assert_eq!(cg.count(), 8);
+    }
+
+    #[test]
+    fn test_counting_fn_hessian_nograd() {
+        let cf = CountingFn::new(f);
+        x().forward_hessian_nograd(&|x| cf.call(x));
+        // f(x), f(x + h * e_i), f(x + h * e_i + h * e_j) for all j < i and f(x +/- d * e_i)
+        assert_eq!(cf.count(), 1 + 4 + 4 * 3 / 2 + 2 * 4);
+    }
+}
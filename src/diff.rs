@@ -5,76 +5,1181 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use crate::config::Method;
 use crate::utils::*;
-use crate::EPS_F64;
+use crate::{FiniteDiffError, FloatEps};
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
 
-pub fn forward_diff_vec_f64(x: &Vec<f64>, f: &dyn Fn(&Vec<f64>) -> f64) -> Vec<f64> {
-    let fx = (f)(&x);
+/// Order of accuracy of the stencils of `forward_diff_order` and `central_diff_order`.
+///
+/// Each order corresponds to a table of the standard finite difference coefficients. The default
+/// step of a stencil of order `p` is `EPS^(1/(p + 1))`, which balances truncation and rounding
+/// errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StencilOrder {
+    /// Truncation error of order `h^2`
+    Order2,
+    /// Truncation error of order `h^4`
+    Order4,
+    /// Truncation error of order `h^6`
+    Order6,
+}
+
+// Stencils as pairs of offsets (in multiples of the step) and weights
+const FORWARD_2: [(i32, f64); 3] = [(0, -1.5), (1, 2.0), (2, -0.5)];
+const FORWARD_4: [(i32, f64); 5] = [
+    (0, -25.0 / 12.0),
+    (1, 4.0),
+    (2, -3.0),
+    (3, 4.0 / 3.0),
+    (4, -0.25),
+];
+const FORWARD_6: [(i32, f64); 7] = [
+    (0, -49.0 / 20.0),
+    (1, 6.0),
+    (2, -7.5),
+    (3, 20.0 / 3.0),
+    (4, -3.75),
+    (5, 1.2),
+    (6, -1.0 / 6.0),
+];
+const CENTRAL_2: [(i32, f64); 2] = [(-1, -0.5), (1, 0.5)];
+const CENTRAL_4: [(i32, f64); 4] = [
+    (-2, 1.0 / 12.0),
+    (-1, -2.0 / 3.0),
+    (1, 2.0 / 3.0),
+    (2, -1.0 / 12.0),
+];
+const CENTRAL_6: [(i32, f64); 6] = [
+    (-3, -1.0 / 60.0),
+    (-2, 0.15),
+    (-1, -0.75),
+    (1, 0.75),
+    (2, -0.15),
+    (3, 1.0 / 60.0),
+];
+
+impl StencilOrder {
+    /// One-sided stencil with offsets `0..=p`
+    pub(crate) fn forward_stencil(self) -> &'static [(i32, f64)] {
+        match self {
+            StencilOrder::Order2 => &FORWARD_2,
+            StencilOrder::Order4 => &FORWARD_4,
+            StencilOrder::Order6 => &FORWARD_6,
+        }
+    }
+
+    /// Symmetric stencil with offsets `-p/2..=p/2`, without the unperturbed point
+    pub(crate) fn central_stencil(self) -> &'static [(i32, f64)] {
+        match self {
+            StencilOrder::Order2 => &CENTRAL_2,
+            StencilOrder::Order4 => &CENTRAL_4,
+            StencilOrder::Order6 => &CENTRAL_6,
+        }
+    }
+
+    /// Default step `EPS^(1/(p + 1))`
+    pub fn step<F: FloatEps>(self) -> F {
+        match self {
+            StencilOrder::Order2 => eps_3(),
+            StencilOrder::Order4 => eps_5(),
+            StencilOrder::Order6 => F::EPS.powf(F::from(1.0 / 7.0).unwrap()),
+        }
+    }
+}
+
+pub fn forward_diff_vec<F: FloatEps>(x: &Vec<F>, f: &dyn Fn(&Vec<F>) -> F) -> Vec<F> {
+    forward_diff_generic(x, f)
+}
+
+/// Same as `FiniteDiff::forward_diff` for `Vec<F>`, but generic over the cost function.
+///
+/// The trait methods take the cost function as a trait object, which is called via dynamic
+/// dispatch. Here, the function is monomorphized and the compiler may inline it, which matters
+/// for cheap cost functions.
+/// For a parameter vector of length `n`, this requires `n+1` evaluations of `f`.
+///
+/// ```rust
+/// use finitediff::forward_diff_generic;
+///
+/// let grad = forward_diff_generic(&vec![1.0f64, 2.0], |x: &Vec<f64>| x[0] + x[1].powi(2));
+/// # assert!((grad[0] - 1.0).abs() < 1e-6);
+/// # assert!((grad[1] - 4.0).abs() < 1e-6);
+/// ```
+pub fn forward_diff_generic<F: FloatEps, G: Fn(&Vec<F>) -> F>(x: &Vec<F>, f: G) -> Vec<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, &f, i, h);
+            (fx1 - fx) / h
+        })
+        .collect()
+}
+
+/// Lazily computes the forward difference gradient at each of the `points`, for instance for
+/// plotting a gradient field or along a trajectory.
+///
+/// Each point is perturbed in place and the gradient is returned in the allocation of the point,
+/// such that only a single scratch buffer for the gradient is allocated in total. The gradients
+/// are identical to the ones of `FiniteDiff::forward_diff`.
+/// For a parameter vector of length `n`, this requires `n+1` evaluations of `f` per point.
+///
+/// ```rust
+/// use finitediff::forward_diff_map;
+///
+/// let f = |x: &Vec<f64>| x[0] + x[1].powi(2);
+/// let points = (0..3).map(|k| vec![1.0f64, k as f64]);
+/// for (k, grad) in forward_diff_map(points, &f).enumerate() {
+///     assert!((grad[1] - 2.0 * k as f64).abs() < 1e-6);
+/// }
+/// ```
+pub fn forward_diff_map<'a, F: FloatEps, I>(
+    points: I,
+    f: &'a dyn Fn(&Vec<F>) -> F,
+) -> impl Iterator<Item = Vec<F>> + 'a
+where
+    I: IntoIterator<Item = Vec<F>>,
+    I::IntoIter: 'a,
+{
+    let mut grad = Vec::new();
+    points.into_iter().map(move |mut x| {
+        let fx = (f)(&x);
+        grad.clear();
+        for i in 0..x.len() {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut x, f, i, h);
+            grad.push((fx1 - fx) / h);
+        }
+        x.copy_from_slice(&grad);
+        x
+    })
+}
+
+/// Same as `forward_diff_vec`, but returns an error instead of a non-finite derivative, for
+/// instance if `f` returns values close to `F::max_value()`.
+pub fn try_forward_diff_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+) -> Result<Vec<F>, FiniteDiffError> {
+    let fx = (f)(x);
     let mut xt = x.clone();
     (0..x.len())
         .map(|i| {
-            let fx1 = mod_and_calc_vec_f64(&mut xt, f, i, EPS_F64.sqrt());
-            (fx1 - fx) / (EPS_F64.sqrt())
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, h);
+            checked_quotient(fx1, fx, h).ok_or(FiniteDiffError::NonFinite { index: i })
         })
         .collect()
 }
 
-pub fn central_diff_vec_f64(x: &Vec<f64>, f: &dyn Fn(&Vec<f64>) -> f64) -> Vec<f64> {
+pub fn forward_diff_precomputed_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    fx: F,
+) -> Vec<F> {
     let mut xt = x.clone();
     (0..x.len())
         .map(|i| {
-            let fx1 = mod_and_calc_vec_f64(&mut xt, f, i, EPS_F64.sqrt());
-            let fx2 = mod_and_calc_vec_f64(&mut xt, f, i, -EPS_F64.sqrt());
-            (fx1 - fx2) / (2.0 * EPS_F64.sqrt())
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, h);
+            (fx1 - fx) / h
         })
         .collect()
 }
 
+/// Same as `forward_diff_vec`, but additionally returns `f(x)` and the values
+/// `f(x + h_i * e_i)` of all perturbed positions, where `h_i = sqrt(EPS) * max(|x_i|, 1)`.
+pub fn forward_diff_with_samples_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+) -> (Vec<F>, F, Vec<F>) {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let samples: Vec<F> = (0..x.len())
+        .map(|i| mod_and_calc_vec(&mut xt, f, i, step_for(x[i], F::EPS)))
+        .collect();
+    let grad = samples
+        .iter()
+        .zip(x.iter())
+        .map(|(&fx1, &xi)| (fx1 - fx) / step_for(xi, F::EPS))
+        .collect();
+    (grad, fx, samples)
+}
+
+/// Forward difference of the parameters in `indices` only. All other entries of the gradient are
+/// zero.
+pub fn forward_diff_subset_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    indices: &[usize],
+) -> Vec<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let mut out = vec![F::zero(); x.len()];
+    for &i in indices {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_vec(&mut xt, f, i, h);
+        out[i] = (fx1 - fx) / h;
+    }
+    out
+}
+
+pub fn forward_diff_with_error_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+) -> (Vec<F>, Vec<F>) {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let h2 = h / F::from(2.0).unwrap();
+            let g = (mod_and_calc_vec(&mut xt, f, i, h) - fx) / h;
+            let g2 = (mod_and_calc_vec(&mut xt, f, i, h2) - fx) / h2;
+            (g, (g - g2).abs())
+        })
+        .unzip()
+}
+
+pub fn backward_diff_vec<F: FloatEps>(x: &Vec<F>, f: &dyn Fn(&Vec<F>) -> F) -> Vec<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, -h);
+            (fx - fx1) / h
+        })
+        .collect()
+}
+
+pub fn central_diff_vec<F: FloatEps>(x: &Vec<F>, f: &dyn Fn(&Vec<F>) -> F) -> Vec<F> {
+    central_diff_generic(x, f)
+}
+
+/// Entry `i` of `diff_mixed`, where `eval(h)` evaluates `f` with the parameter `i`, whose value is
+/// `xi`, shifted by `h`. `fx` is only used by `Method::Forward` and `Method::Backward`.
+#[inline(always)]
+pub fn mixed_entry<F: FloatEps>(method: Method, xi: F, fx: F, mut eval: impl FnMut(F) -> F) -> F {
+    let h = step_for(xi, F::EPS);
+    match method {
+        Method::Forward => (eval(h) - fx) / h,
+        Method::Backward => (fx - eval(-h)) / h,
+        Method::Central => {
+            let h = central_step(xi);
+            let fx1 = eval(h);
+            let fx2 = eval(-h);
+            (fx1 - fx2) / (F::from(2.0).unwrap() * h)
+        }
+    }
+}
+
+/// `f(x)` if any entry of `policy` needs it, zero otherwise.
+#[inline(always)]
+pub fn mixed_fx<F: FloatEps, X>(x: &X, f: &dyn Fn(&X) -> F, policy: &[Method]) -> F {
+    if policy.iter().any(|&m| m != Method::Central) {
+        (f)(x)
+    } else {
+        F::zero()
+    }
+}
+
+pub fn diff_mixed_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    policy: &[Method],
+) -> Vec<F> {
+    assert_eq!(x.len(), policy.len());
+    let fx = mixed_fx(x, f, policy);
+    let mut xt = x.clone();
+    policy
+        .iter()
+        .enumerate()
+        .map(|(i, &method)| mixed_entry(method, x[i], fx, |h| mod_and_calc_vec(&mut xt, f, i, h)))
+        .collect()
+}
+
+/// Same as `FiniteDiff::central_diff` for `Vec<F>`, but generic over the cost function, see
+/// `forward_diff_generic`.
+/// For a parameter vector of length `n`, this requires `2*n` evaluations of `f`.
+pub fn central_diff_generic<F: FloatEps, G: Fn(&Vec<F>) -> F>(x: &Vec<F>, f: G) -> Vec<F> {
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = central_step(x[i]);
+            let fx1 = mod_and_calc_vec(&mut xt, &f, i, h);
+            let fx2 = mod_and_calc_vec(&mut xt, &f, i, -h);
+            (fx1 - fx2) / (F::from(2.0).unwrap() * h)
+        })
+        .collect()
+}
+
+/// Forward difference of a function of a slice.
+///
+/// Unlike the `FiniteDiff` implementation for `Vec<F>`, the parameters can be borrowed from any
+/// contiguous storage. The parameters are copied into an internal scratch buffer which is perturbed
+/// in place. Use `forward_diff_slice_scratch` to avoid all allocations.
+/// For a parameter vector of length `n`, this requires `n+1` evaluations of `f`.
+///
+/// ```rust
+/// use finitediff::forward_diff_slice;
+///
+/// let arena = [0.0f64, 1.0, 2.0, 3.0];
+/// let grad = forward_diff_slice(&arena[1..3], &|x: &[f64]| x[0] + x[1].powi(2));
+/// # assert!((grad[0] - 1.0).abs() < 1e-6);
+/// # assert!((grad[1] - 4.0).abs() < 1e-6);
+/// ```
+pub fn forward_diff_slice<F: FloatEps>(x: &[F], f: &dyn Fn(&[F]) -> F) -> Vec<F> {
+    let mut scratch = x.to_vec();
+    let mut grad = vec![F::zero(); x.len()];
+    forward_diff_slice_scratch(x, f, &mut scratch, &mut grad);
+    grad
+}
+
+/// Same as `forward_diff_slice`, but uses the caller-provided `scratch` buffer for the
+/// perturbations and writes the gradient into `grad`. This does not allocate.
+///
+/// # Panics
+///
+/// Panics if `scratch` or `grad` do not have the same length as `x`.
+pub fn forward_diff_slice_scratch<F: FloatEps>(
+    x: &[F],
+    f: &dyn Fn(&[F]) -> F,
+    scratch: &mut [F],
+    grad: &mut [F],
+) {
+    assert_eq!(x.len(), scratch.len());
+    assert_eq!(x.len(), grad.len());
+    scratch.copy_from_slice(x);
+    let fx = (f)(x);
+    for i in 0..x.len() {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_slice(scratch, f, i, h);
+        grad[i] = (fx1 - fx) / h;
+    }
+}
+
+/// Central difference of a function of a slice. See `forward_diff_slice` for details.
+/// For a parameter vector of length `n`, this requires `2*n` evaluations of `f`.
+pub fn central_diff_slice<F: FloatEps>(x: &[F], f: &dyn Fn(&[F]) -> F) -> Vec<F> {
+    let mut scratch = x.to_vec();
+    let mut grad = vec![F::zero(); x.len()];
+    central_diff_slice_scratch(x, f, &mut scratch, &mut grad);
+    grad
+}
+
+/// Same as `central_diff_slice`, but uses the caller-provided `scratch` buffer for the
+/// perturbations and writes the gradient into `grad`. This does not allocate.
+///
+/// # Panics
+///
+/// Panics if `scratch` or `grad` do not have the same length as `x`.
+pub fn central_diff_slice_scratch<F: FloatEps>(
+    x: &[F],
+    f: &dyn Fn(&[F]) -> F,
+    scratch: &mut [F],
+    grad: &mut [F],
+) {
+    assert_eq!(x.len(), scratch.len());
+    assert_eq!(x.len(), grad.len());
+    scratch.copy_from_slice(x);
+    for i in 0..x.len() {
+        let h = central_step(x[i]);
+        let fx1 = mod_and_calc_slice(scratch, f, i, h);
+        let fx2 = mod_and_calc_slice(scratch, f, i, -h);
+        grad[i] = (fx1 - fx2) / (F::from(2.0).unwrap() * h);
+    }
+}
+
+pub fn forward_diff_scaled_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    steps: &Vec<F>,
+) -> Vec<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, steps[i]);
+            (fx1 - fx) / steps[i]
+        })
+        .collect()
+}
+
+/// Forward difference with the step `sqrt(EPS) * scale[i]` for the `i`th parameter.
+///
+/// Parameters with `scale[i] == 0` are not perturbed and their derivative is set to zero.
+pub fn forward_diff_with_scaling_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    scale: &Vec<F>,
+) -> Vec<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = F::EPS.sqrt() * scale[i];
+            if h == F::zero() {
+                return F::zero();
+            }
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, h);
+            (fx1 - fx) / h
+        })
+        .collect()
+}
+
+/// Forward difference which switches to a backward difference for parameters close to their
+/// upper bound, such that `f` is only evaluated within `[lower, upper]`.
+pub fn forward_diff_bounded_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    lower: &Vec<F>,
+    upper: &Vec<F>,
+) -> Vec<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = bounded_step(x[i], lower[i], upper[i]);
+            if h == F::zero() {
+                return F::zero();
+            }
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, h);
+            (fx1 - fx) / h
+        })
+        .collect()
+}
+
+pub fn central_diff_scaled_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    steps: &Vec<F>,
+) -> Vec<F> {
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, steps[i]);
+            let fx2 = mod_and_calc_vec(&mut xt, f, i, -steps[i]);
+            (fx1 - fx2) / (F::from(2.0).unwrap() * steps[i])
+        })
+        .collect()
+}
+
+pub fn central_diff_5_vec<F: FloatEps>(x: &Vec<F>, f: &dyn Fn(&Vec<F>) -> F) -> Vec<F> {
+    central_diff_5_scaled_vec(x, f, &vec![eps_5::<F>(); x.len()])
+}
+
+pub fn central_diff_5_scaled_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    steps: &Vec<F>,
+) -> Vec<F> {
+    let two = F::from(2.0).unwrap();
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = steps[i];
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, two * h);
+            let fx2 = mod_and_calc_vec(&mut xt, f, i, h);
+            let fx3 = mod_and_calc_vec(&mut xt, f, i, -h);
+            let fx4 = mod_and_calc_vec(&mut xt, f, i, -two * h);
+            (-fx1 + F::from(8.0).unwrap() * (fx2 - fx3) + fx4) / (F::from(12.0).unwrap() * h)
+        })
+        .collect()
+}
+
+pub fn forward_diff_2_vec<F: FloatEps>(x: &Vec<F>, f: &dyn Fn(&Vec<F>) -> F) -> Vec<F> {
+    let two = F::from(2.0).unwrap();
+    let h = eps_3::<F>();
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, h);
+            let fx2 = mod_and_calc_vec(&mut xt, f, i, two * h);
+            (F::from(4.0).unwrap() * fx1 - F::from(3.0).unwrap() * fx - fx2) / (two * h)
+        })
+        .collect()
+}
+
+pub fn forward_diff_order_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    order: StencilOrder,
+) -> Vec<F> {
+    stencil_diff_vec(x, f, order.forward_stencil(), order.step())
+}
+
+pub fn central_diff_order_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    order: StencilOrder,
+) -> Vec<F> {
+    stencil_diff_vec(x, f, order.central_stencil(), order.step())
+}
+
+/// Applies `stencil` with step `h` along each parameter. The function is evaluated at the
+/// unperturbed position only if the stencil contains the offset zero.
+fn stencil_diff_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    stencil: &[(i32, f64)],
+    h: F,
+) -> Vec<F> {
+    let fx = if stencil.iter().any(|&(offset, _)| offset == 0) {
+        Some((f)(x))
+    } else {
+        None
+    };
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            stencil.iter().fold(F::zero(), |acc, &(offset, w)| {
+                let fxo = if offset == 0 {
+                    fx.unwrap()
+                } else {
+                    mod_and_calc_vec(&mut xt, f, i, F::from(offset).unwrap() * h)
+                };
+                acc + F::from(w).unwrap() * fxo
+            }) / h
+        })
+        .collect()
+}
+
+pub fn forward_directional_diff_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    p: &Vec<F>,
+) -> F {
+    if p.iter().all(|pi| pi.is_zero()) {
+        return F::zero();
+    }
+    let fx = (f)(x);
+    let x1 = x
+        .iter()
+        .zip(p.iter())
+        .map(|(&xi, &pi)| xi + F::EPS.sqrt() * pi)
+        .collect();
+    let fx1 = (f)(&x1);
+    (fx1 - fx) / F::EPS.sqrt()
+}
+
+pub fn central_directional_diff_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    p: &Vec<F>,
+) -> F {
+    if p.iter().all(|pi| pi.is_zero()) {
+        return F::zero();
+    }
+    let x1 = x
+        .iter()
+        .zip(p.iter())
+        .map(|(&xi, &pi)| xi + F::EPS.sqrt() * pi)
+        .collect();
+    let x2 = x
+        .iter()
+        .zip(p.iter())
+        .map(|(&xi, &pi)| xi - F::EPS.sqrt() * pi)
+        .collect();
+    let fx1 = (f)(&x1);
+    let fx2 = (f)(&x2);
+    (fx1 - fx2) / (F::from(2.0).unwrap() * F::EPS.sqrt())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::CountingFn;
 
     const COMP_ACC: f64 = 1e-6;
+    const COMP_ACC_F32: f32 = 1e-3;
 
     fn f(x: &Vec<f64>) -> f64 {
         x[0] + x[1].powi(2)
     }
 
+    fn f_scaled(x: &Vec<f64>) -> f64 {
+        (1e-6 * x[0]).powi(2) + (1e3 * x[1]).powi(2)
+    }
+
     #[test]
     fn test_forward_diff_vec_f64() {
         let p = vec![1.0f64, 1.0f64];
-        let grad = forward_diff_vec_f64(&p, &f);
-        let res = vec![1.0f64, 2.0];
+        let grad = forward_diff_vec(&p, &f);
+        let res = [1.0f64, 2.0];
 
         (0..2)
             .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
             .count();
 
         let p = vec![1.0f64, 2.0f64];
-        let grad = forward_diff_vec_f64(&p, &f);
-        let res = vec![1.0f64, 4.0];
+        let grad = forward_diff_vec(&p, &f);
+        let res = [1.0f64, 4.0];
 
         (0..2)
             .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
             .count();
     }
 
+    #[test]
+    fn test_forward_diff_map_vec_f64() {
+        let points = vec![vec![1.0f64, 1.0], vec![1.0, 2.0], vec![-3.0, 0.5]];
+        let grads: Vec<Vec<f64>> = forward_diff_map(points.clone(), &f).collect();
+        assert_eq!(grads.len(), 3);
+        for (p, grad) in points.iter().zip(grads.iter()) {
+            assert_eq!(grad, &forward_diff_vec(p, &f));
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_subset_vec_f64() {
+        let f = CountingFn::new(|x: &Vec<f64>| x[0] + x[1].powi(2) + x[2].powi(3) + x[3].exp());
+        let p = vec![1.0f64, 2.0, 3.0, 0.0];
+        let grad = forward_diff_subset_vec(&p, &|x| f.call(x), &[1, 3]);
+        assert_eq!(f.count(), 3);
+
+        let full = forward_diff_vec(&p, &|x| f.call(x));
+        assert_eq!(grad[0], 0.0);
+        assert_eq!(grad[2], 0.0);
+        assert_eq!(grad[1], full[1]);
+        assert_eq!(grad[3], full[3]);
+    }
+
+    #[test]
+    fn test_forward_diff_with_error_vec_f64() {
+        let p = vec![1.0f64, 1.0f64];
+        let (grad, err) = forward_diff_with_error_vec(&p, &f);
+        let res = [1.0f64, 2.0];
+
+        (0..2)
+            .map(|i| {
+                assert!((res[i] - grad[i]).abs() < COMP_ACC);
+                assert!(err[i] < COMP_ACC);
+            })
+            .count();
+
+        // the steps `h` and `h/2` end up on different sides of the kink of `|x_0|`
+        let f_kink = |x: &Vec<f64>| x[0].abs() + x[1].powi(2);
+        let p = vec![-1e-8f64, 1.0f64];
+        let (_, err) = forward_diff_with_error_vec(&p, &f_kink);
+        assert!(err[0] > 0.1);
+        assert!(err[1] < COMP_ACC);
+    }
+
+    #[test]
+    fn test_backward_diff_vec_f64() {
+        let p = vec![1.0f64, 1.0f64];
+        let grad = backward_diff_vec(&p, &f);
+        let res = [1.0f64, 2.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+
+        let p = vec![1.0f64, 2.0f64];
+        let grad = backward_diff_vec(&p, &f);
+        let res = [1.0f64, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    #[test]
+    fn test_diff_mixed_vec_f64() {
+        let g = |x: &Vec<f64>| x[0].exp() + x[1].powi(3) + x[0] * x[2];
+        let p = vec![1.0f64, 2.0, 0.5];
+        let forward = forward_diff_vec(&p, &g);
+        let backward = backward_diff_vec(&p, &g);
+        let central = central_diff_vec(&p, &g);
+
+        let policy = [Method::Forward, Method::Central, Method::Backward];
+        let grad = diff_mixed_vec(&p, &g, &policy);
+        assert_eq!(grad, vec![forward[0], central[1], backward[2]]);
+        let policy = [Method::Central, Method::Forward, Method::Central];
+        let grad = diff_mixed_vec(&p, &g, &policy);
+        assert_eq!(grad, vec![central[0], forward[1], central[2]]);
+
+        let g = CountingFn::new(g);
+        diff_mixed_vec(&p, &|x| g.call(x), &policy);
+        assert_eq!(g.count(), 6);
+        let g = CountingFn::new(|x: &Vec<f64>| x[0]);
+        diff_mixed_vec(&p, &|x| g.call(x), &[Method::Central; 3]);
+        assert_eq!(g.count(), 6);
+    }
+
     #[test]
     fn test_central_diff_vec_f64() {
         let p = vec![1.0f64, 1.0f64];
-        let grad = central_diff_vec_f64(&p, &f);
-        let res = vec![1.0f64, 2.0];
+        let grad = central_diff_vec(&p, &f);
+        let res = [1.0f64, 2.0];
 
         (0..2)
             .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
             .count();
 
         let p = vec![1.0f64, 2.0f64];
-        let grad = central_diff_vec_f64(&p, &f);
-        let res = vec![1.0f64, 4.0];
+        let grad = central_diff_vec(&p, &f);
+        let res = [1.0f64, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    #[test]
+    fn test_central_diff_vec_f64_step() {
+        let g = |x: &Vec<f64>| x.iter().map(|xi| xi.exp()).sum::<f64>();
+        let p = vec![-1.3f64, -0.2, 0.7, 1.1, 2.9];
+        let grad = central_diff_vec(&p, &g);
+
+        // central difference with the step of forward differences
+        let h = f64::EPSILON.sqrt();
+        let grad_sqrt: Vec<f64> = p
+            .iter()
+            .map(|&xi| ((xi + h).exp() - (xi - h).exp()) / (2.0 * h))
+            .collect();
+
+        let mut err = 0.0;
+        let mut err_sqrt = 0.0;
+        for i in 0..p.len() {
+            err += ((grad[i] - p[i].exp()) / p[i].exp()).abs();
+            err_sqrt += ((grad_sqrt[i] - p[i].exp()) / p[i].exp()).abs();
+        }
+        assert!(err < 1e-9);
+        assert!(err < 0.1 * err_sqrt);
+    }
+
+    fn f_slice(x: &[f64]) -> f64 {
+        x[0] + x[1].powi(2)
+    }
+
+    #[test]
+    fn test_forward_diff_slice_f64() {
+        let arena = [5.0f64, 1.0, 2.0, 5.0];
+        let grad = forward_diff_slice(&arena[1..3], &f_slice);
+        let res = [1.0f64, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
 
+        let mut scratch = [0.0f64; 2];
+        let mut grad = [0.0f64; 2];
+        forward_diff_slice_scratch(&arena[1..3], &f_slice, &mut scratch, &mut grad);
         (0..2)
             .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
             .count();
+        // the scratch buffer is restored after each perturbation
+        assert_eq!(scratch, [1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_central_diff_slice_f64() {
+        let arena = [5.0f64, 1.0, 2.0, 5.0];
+        let grad = central_diff_slice(&arena[1..3], &f_slice);
+        let res = [1.0f64, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+
+        let mut scratch = [0.0f64; 2];
+        let mut grad = [0.0f64; 2];
+        central_diff_slice_scratch(&arena[1..3], &f_slice, &mut scratch, &mut grad);
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_forward_diff_slice_scratch_wrong_len() {
+        let mut scratch = [0.0f64; 1];
+        let mut grad = [0.0f64; 2];
+        forward_diff_slice_scratch(&[1.0, 2.0], &f_slice, &mut scratch, &mut grad);
+    }
+
+    #[test]
+    fn test_forward_diff_scaled_vec_f64() {
+        let p = vec![1e6f64, 1e-3];
+        let steps = vec![1e-2f64, 1e-11];
+        let grad = forward_diff_scaled_vec(&p, &f_scaled, &steps);
+        let res = [2e-6f64, 2e3];
+
+        (0..2)
+            .map(|i| assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    #[test]
+    fn test_forward_diff_with_scaling_vec_f64() {
+        let p = vec![1e6f64, 1e-3];
+        let grad = forward_diff_with_scaling_vec(&p, &f_scaled, &vec![1e6, 1e-3]);
+        let res = [2e-6f64, 2e3];
+
+        (0..2)
+            .map(|i| assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    #[test]
+    fn test_forward_diff_with_scaling_vec_uniform() {
+        let p = vec![1.0f64, 1.0];
+        let grad = forward_diff_with_scaling_vec(&p, &f, &vec![1.0, 1.0]);
+        assert_eq!(grad, forward_diff_vec(&p, &f));
+    }
+
+    #[test]
+    fn test_forward_diff_with_scaling_vec_zero_scale() {
+        let f = CountingFn::new(f);
+        let grad =
+            forward_diff_with_scaling_vec(&vec![1.0f64, 1.0], &|x| f.call(x), &vec![0.0, 1.0]);
+        assert_eq!(grad[0], 0.0);
+        assert!((grad[1] - 2.0).abs() < COMP_ACC);
+        assert_eq!(f.count(), 2);
+    }
+
+    #[test]
+    fn test_central_diff_scaled_vec_f64() {
+        let p = vec![1e6f64, 1e-3];
+        let steps = vec![1e-2f64, 1e-11];
+        let grad = central_diff_scaled_vec(&p, &f_scaled, &steps);
+        let res = [2e-6f64, 2e3];
+
+        (0..2)
+            .map(|i| assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    fn f_large(x: &Vec<f64>) -> f64 {
+        x[0].ln() + x[1].powi(2)
+    }
+
+    #[test]
+    fn test_diff_vec_f64_large_parameters() {
+        let p = vec![1e8f64, 1.0];
+        let res = [1e-8f64, 2.0];
+
+        // an absolute step of `sqrt(EPS)` would be lost when added to `1e8`, which silently gave a
+        // zero derivative before the steps were scaled by `max(|x_i|, 1)`
+        for grad in [
+            forward_diff_vec(&p, &f_large),
+            central_diff_vec(&p, &f_large),
+        ] {
+            (0..2)
+                .map(|i| assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC))
+                .count();
+        }
+    }
+
+    fn f_f32(x: &Vec<f32>) -> f32 {
+        x[0] + x[1].powi(2)
+    }
+
+    #[test]
+    fn test_forward_diff_vec_f32() {
+        let p = vec![1.0f32, 1.0f32];
+        let grad = forward_diff_vec(&p, &f_f32);
+        let res = [1.0f32, 2.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC_F32))
+            .count();
+
+        let p = vec![1.0f32, 2.0f32];
+        let grad = forward_diff_vec(&p, &f_f32);
+        // the truncation error of `x_1^2` equals the step, which is scaled by `x_1 = 2`
+        let res = [1.0f32, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < 2.0 * COMP_ACC_F32))
+            .count();
+    }
+
+    #[test]
+    fn test_backward_diff_vec_f32() {
+        let p = vec![1.0f32, 1.0f32];
+        let grad = backward_diff_vec(&p, &f_f32);
+        let res = [1.0f32, 2.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC_F32))
+            .count();
+
+        let p = vec![1.0f32, 2.0f32];
+        let grad = backward_diff_vec(&p, &f_f32);
+        // the truncation error of `x_1^2` equals the step, which is scaled by `x_1 = 2`
+        let res = [1.0f32, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < 2.0 * COMP_ACC_F32))
+            .count();
+    }
+
+    #[test]
+    fn test_central_diff_vec_f32() {
+        let p = vec![1.0f32, 1.0f32];
+        let grad = central_diff_vec(&p, &f_f32);
+        let res = [1.0f32, 2.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC_F32))
+            .count();
+
+        let p = vec![1.0f32, 2.0f32];
+        let grad = central_diff_vec(&p, &f_f32);
+        let res = [1.0f32, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC_F32))
+            .count();
+    }
+
+    #[test]
+    fn test_central_diff_5_vec_f64() {
+        let p = vec![1.0f64, 2.0f64];
+        let grad = central_diff_5_vec(&p, &f);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_5_vec_f64_exp() {
+        let f_exp = |x: &Vec<f64>| x[0].exp() + x[1].exp();
+        let p = vec![2.0f64, -1.5f64];
+        let grad3 = central_diff_vec(&p, &f_exp);
+        let grad5 = central_diff_5_vec(&p, &f_exp);
+
+        for i in 0..2 {
+            let err3 = (p[i].exp() - grad3[i]).abs();
+            let err5 = (p[i].exp() - grad5[i]).abs();
+            assert!(err5 < err3);
+            assert!(err5 < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_2_vec_f64_exp() {
+        let f_exp = |x: &Vec<f64>| x[0].exp() + x[1].exp();
+        let p = vec![2.0f64, -1.5f64];
+        let grad1 = forward_diff_vec(&p, &f_exp);
+        let grad2 = forward_diff_2_vec(&p, &f_exp);
+
+        for i in 0..2 {
+            let err1 = (p[i].exp() - grad1[i]).abs();
+            let err2 = (p[i].exp() - grad2[i]).abs();
+            assert!(err2 * 10.0 < err1);
+            assert!(err2 < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_diff_order_vec_f64_sin() {
+        let f_sin = |x: &Vec<f64>| x[0].sin();
+        let p = vec![1.0f64];
+        for &order in [
+            StencilOrder::Order2,
+            StencilOrder::Order4,
+            StencilOrder::Order6,
+        ]
+        .iter()
+        {
+            let forward = forward_diff_order_vec(&p, &f_sin, order);
+            let central = central_diff_order_vec(&p, &f_sin, order);
+            assert!((1.0f64.cos() - forward[0]).abs() < 1e-9);
+            assert!((1.0f64.cos() - central[0]).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_diff_order_vec_f64_convergence() {
+        // with a large step the truncation error dominates and decreases with the order
+        let f_sin = |x: &Vec<f64>| x[0].sin();
+        let p = vec![1.0f64];
+        let h = 0.1;
+        for stencil in [StencilOrder::forward_stencil, StencilOrder::central_stencil].iter() {
+            let errs: Vec<f64> = [
+                StencilOrder::Order2,
+                StencilOrder::Order4,
+                StencilOrder::Order6,
+            ]
+            .iter()
+            .map(|&order| (1.0f64.cos() - stencil_diff_vec(&p, &f_sin, stencil(order), h)[0]).abs())
+            .collect();
+            assert!(errs[0] > 100.0 * errs[1]);
+            assert!(errs[1] > 100.0 * errs[2]);
+        }
+    }
+
+    #[test]
+    fn test_diff_order_vec_f64_eval_count() {
+        let f = CountingFn::new(|x: &Vec<f64>| x[0].cos() + x[1].sin());
+        let p = vec![1.0f64, 2.0];
+        forward_diff_order_vec(&p, &|x| f.call(x), StencilOrder::Order6);
+        assert_eq!(f.count(), 6 * 2 + 1);
+        f.reset();
+        central_diff_order_vec(&p, &|x| f.call(x), StencilOrder::Order4);
+        assert_eq!(f.count(), 4 * 2);
+    }
+
+    #[test]
+    fn test_stencil_weights() {
+        // weights of derivative stencils sum to zero and reproduce the derivative of x
+        for &order in [
+            StencilOrder::Order2,
+            StencilOrder::Order4,
+            StencilOrder::Order6,
+        ]
+        .iter()
+        {
+            for stencil in [order.forward_stencil(), order.central_stencil()].iter() {
+                let sum: f64 = stencil.iter().map(|&(_, w)| w).sum();
+                let first: f64 = stencil.iter().map(|&(o, w)| f64::from(o) * w).sum();
+                assert!(sum.abs() < 1e-14);
+                assert!((first - 1.0).abs() < 1e-14);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_directional_diff_vec_f64() {
+        let p = vec![1.0f64, 2.0f64];
+        let d = forward_directional_diff_vec(&p, &f, &vec![3.0, -1.0]);
+        assert!((d - (3.0 - 4.0)).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_central_directional_diff_vec_f64() {
+        let p = vec![1.0f64, 2.0f64];
+        let d = central_directional_diff_vec(&p, &f, &vec![3.0, -1.0]);
+        assert!((d - (3.0 - 4.0)).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_directional_diff_vec_f64_zero_direction() {
+        let p = vec![1.0f64, 2.0f64];
+        assert_eq!(forward_directional_diff_vec(&p, &f, &vec![0.0, 0.0]), 0.0);
+        assert_eq!(central_directional_diff_vec(&p, &f, &vec![0.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_forward_diff_precomputed_vec_f64() {
+        let p = vec![1.0f64, 2.0f64];
+        let grad = forward_diff_precomputed_vec(&p, &f, f(&p));
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_with_samples_vec_f64() {
+        let p = vec![1.0f64, 2.0f64];
+        let (grad, fx, samples) = forward_diff_with_samples_vec(&p, &f);
+        assert_eq!(grad, forward_diff_vec(&p, &f));
+        assert_eq!(fx, f(&p));
+        assert_eq!(
+            samples,
+            vec![
+                f(&vec![1.0 + f64::EPSILON.sqrt(), 2.0]),
+                f(&vec![1.0, 2.0 + 2.0 * f64::EPSILON.sqrt()])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forward_diff_vec_f64_eval_count() {
+        let evals = core::cell::Cell::new(0);
+        let f_counted = |x: &Vec<f64>| {
+            evals.set(evals.get() + 1);
+            f(x)
+        };
+        let p = vec![1.0f64; 10];
+
+        forward_diff_vec(&p, &f_counted);
+        assert_eq!(evals.get(), 11);
+
+        evals.set(0);
+        forward_diff_precomputed_vec(&p, &f_counted, f(&p));
+        assert_eq!(evals.get(), 10);
+    }
+
+    #[test]
+    fn test_diff_vec_f64_empty() {
+        let x: Vec<f64> = vec![];
+        let f = |_: &Vec<f64>| 1.0f64;
+        assert!(forward_diff_vec(&x, &f).is_empty());
+        assert!(backward_diff_vec(&x, &f).is_empty());
+        assert!(central_diff_vec(&x, &f).is_empty());
+    }
+
+    #[test]
+    fn test_try_forward_diff_vec_f64_overflow() {
+        // the derivative of a steep function is representable...
+        let steep = |x: &Vec<f64>| 0.5 * f64::MAX * x[0];
+        let grad = try_forward_diff_vec(&vec![0.5f64, 1.0], &steep).unwrap();
+        assert!((grad[0] / (0.5 * f64::MAX) - 1.0).abs() < COMP_ACC);
+        assert_eq!(grad[1], 0.0);
+
+        // ...but a jump from -f64::MAX to f64::MAX overflows
+        let jump = |x: &Vec<f64>| x[0] + f64::MAX * (x[1] - 0.5).signum();
+        let x = vec![1.0f64, 0.5 - 1e-10];
+        assert!(forward_diff_vec(&x, &jump)[1].is_infinite());
+        assert_eq!(
+            try_forward_diff_vec(&x, &jump),
+            Err(FiniteDiffError::NonFinite { index: 1 })
+        );
+    }
+
+    #[test]
+    fn test_try_forward_diff_vec_f64_large_parameters() {
+        // relative steps are not lost to rounding, not even for huge parameters
+        let f = |x: &Vec<f64>| x[0].powi(2);
+        let grad = try_forward_diff_vec(&vec![1e20f64], &f).unwrap();
+        assert!(((grad[0] - 2e20) / 2e20).abs() < COMP_ACC);
+        let f = |x: &Vec<f64>| x[0] + x[1];
+        assert_eq!(
+            try_forward_diff_vec(&vec![1.0f64, f64::INFINITY], &f),
+            Err(FiniteDiffError::NonFinite { index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_diff_generic_vec_f64() {
+        let x = vec![1.0f64, 1.0];
+        assert_eq!(
+            forward_diff_generic(&x, f),
+            forward_diff_precomputed_vec(&x, &f, f(&x))
+        );
+        assert_eq!(
+            central_diff_generic(&x, |x: &Vec<f64>| f(x)),
+            central_diff_vec(&x, &f)
+        );
+    }
+
+    #[test]
+    fn test_forward_diff_bounded_vec_f64() {
+        let f = |x: &Vec<f64>| {
+            assert!(x[0] <= 1.0 && x[1] >= -1.0, "infeasible evaluation");
+            x[0].powi(2) + x[1] + x[2]
+        };
+        let lower = vec![0.0f64, -1.0, 2.0];
+        let upper = vec![1.0f64, 1.0, 2.0];
+        // the first parameter sits on its upper bound, the second one on its lower bound and the
+        // last one is fixed
+        let x = vec![1.0f64, -1.0, 2.0];
+        let grad = forward_diff_bounded_vec(&x, &f, &lower, &upper);
+        assert!((grad[0] - 2.0).abs() < COMP_ACC);
+        assert!((grad[1] - 1.0).abs() < COMP_ACC);
+        assert_eq!(grad[2], 0.0);
     }
 }
@@ -0,0 +1,706 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::config::Method;
+use crate::diff::{mixed_entry, mixed_fx, StencilOrder};
+use crate::utils::*;
+use crate::{FiniteDiffError, FloatEps};
+
+pub fn forward_diff_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> nalgebra::DVector<F> {
+    let fx = (f)(x);
+    forward_diff_precomputed_nalgebra(x, f, fx)
+}
+
+/// Same as `forward_diff_nalgebra`, but returns an error instead of a non-finite derivative.
+pub fn try_forward_diff_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> Result<nalgebra::DVector<F>, FiniteDiffError> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let grad = (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, h);
+            checked_quotient(fx1, fx, h).ok_or(FiniteDiffError::NonFinite { index: i })
+        })
+        .collect::<Result<Vec<F>, _>>()?;
+    Ok(nalgebra::DVector::from_vec(grad))
+}
+
+pub fn forward_diff_precomputed_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    fx: F,
+) -> nalgebra::DVector<F> {
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, h);
+            (fx1 - fx) / h
+        }),
+    )
+}
+
+/// Same as `forward_diff_nalgebra`, but additionally returns `f(x)` and the values
+/// `f(x + h_i * e_i)` of all perturbed positions, where `h_i = sqrt(EPS) * max(|x_i|, 1)`.
+pub fn forward_diff_with_samples_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> (nalgebra::DVector<F>, F, Vec<F>) {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let samples: Vec<F> = (0..x.len())
+        .map(|i| mod_and_calc_nalgebra(&mut xt, f, i, step_for(x[i], F::EPS)))
+        .collect();
+    let grad = nalgebra::DVector::from_iterator(
+        x.len(),
+        samples
+            .iter()
+            .zip(x.iter())
+            .map(|(&fx1, &xi)| (fx1 - fx) / step_for(xi, F::EPS)),
+    );
+    (grad, fx, samples)
+}
+
+/// Forward difference of the parameters in `indices` only. All other entries of the gradient are
+/// zero.
+pub fn forward_diff_subset_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    indices: &[usize],
+) -> nalgebra::DVector<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let mut out = nalgebra::DVector::zeros(x.len());
+    for &i in indices {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, h);
+        out[i] = (fx1 - fx) / h;
+    }
+    out
+}
+
+pub fn forward_diff_with_error_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> (nalgebra::DVector<F>, nalgebra::DVector<F>) {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let (grad, err): (Vec<F>, Vec<F>) = (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let h2 = h / F::from(2.0).unwrap();
+            let g = (mod_and_calc_nalgebra(&mut xt, f, i, h) - fx) / h;
+            let g2 = (mod_and_calc_nalgebra(&mut xt, f, i, h2) - fx) / h2;
+            (g, (g - g2).abs())
+        })
+        .unzip();
+    (
+        nalgebra::DVector::from_vec(grad),
+        nalgebra::DVector::from_vec(err),
+    )
+}
+
+pub fn backward_diff_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> nalgebra::DVector<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, -h);
+            (fx - fx1) / h
+        }),
+    )
+}
+
+pub fn diff_mixed_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    policy: &[Method],
+) -> nalgebra::DVector<F> {
+    assert_eq!(x.len(), policy.len());
+    let fx = mixed_fx(x, f, policy);
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        policy.iter().enumerate().map(|(i, &method)| {
+            mixed_entry(method, x[i], fx, |h| {
+                mod_and_calc_nalgebra(&mut xt, f, i, h)
+            })
+        }),
+    )
+}
+
+pub fn central_diff_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> nalgebra::DVector<F> {
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let h = central_step(x[i]);
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, h);
+            let fx2 = mod_and_calc_nalgebra(&mut xt, f, i, -h);
+            (fx1 - fx2) / (F::from(2.0).unwrap() * h)
+        }),
+    )
+}
+
+pub fn forward_diff_scaled_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    steps: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, steps[i]);
+            (fx1 - fx) / steps[i]
+        }),
+    )
+}
+
+/// Forward difference with the step `sqrt(EPS) * scale[i]` for the `i`th parameter. See
+/// `forward_diff_with_scaling_vec`.
+pub fn forward_diff_with_scaling_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    scale: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let h = F::EPS.sqrt() * scale[i];
+            if h == F::zero() {
+                return F::zero();
+            }
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, h);
+            (fx1 - fx) / h
+        }),
+    )
+}
+
+/// Forward difference which switches to a backward difference for parameters close to their
+/// upper bound, such that `f` is only evaluated within `[lower, upper]`.
+pub fn forward_diff_bounded_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    lower: &nalgebra::DVector<F>,
+    upper: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let h = bounded_step(x[i], lower[i], upper[i]);
+            if h == F::zero() {
+                return F::zero();
+            }
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, h);
+            (fx1 - fx) / h
+        }),
+    )
+}
+
+pub fn central_diff_scaled_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    steps: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, steps[i]);
+            let fx2 = mod_and_calc_nalgebra(&mut xt, f, i, -steps[i]);
+            (fx1 - fx2) / (F::from(2.0).unwrap() * steps[i])
+        }),
+    )
+}
+
+pub fn central_diff_5_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> nalgebra::DVector<F> {
+    central_diff_5_scaled_nalgebra(
+        x,
+        f,
+        &nalgebra::DVector::from_element(x.len(), eps_5::<F>()),
+    )
+}
+
+pub fn central_diff_5_scaled_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    steps: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let two = F::from(2.0).unwrap();
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let h = steps[i];
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, two * h);
+            let fx2 = mod_and_calc_nalgebra(&mut xt, f, i, h);
+            let fx3 = mod_and_calc_nalgebra(&mut xt, f, i, -h);
+            let fx4 = mod_and_calc_nalgebra(&mut xt, f, i, -two * h);
+            (-fx1 + F::from(8.0).unwrap() * (fx2 - fx3) + fx4) / (F::from(12.0).unwrap() * h)
+        }),
+    )
+}
+
+pub fn forward_diff_2_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> nalgebra::DVector<F> {
+    let two = F::from(2.0).unwrap();
+    let h = eps_3::<F>();
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, h);
+            let fx2 = mod_and_calc_nalgebra(&mut xt, f, i, two * h);
+            (F::from(4.0).unwrap() * fx1 - F::from(3.0).unwrap() * fx - fx2) / (two * h)
+        }),
+    )
+}
+
+pub fn forward_diff_order_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    order: StencilOrder,
+) -> nalgebra::DVector<F> {
+    stencil_diff_nalgebra(x, f, order.forward_stencil(), order.step())
+}
+
+pub fn central_diff_order_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    order: StencilOrder,
+) -> nalgebra::DVector<F> {
+    stencil_diff_nalgebra(x, f, order.central_stencil(), order.step())
+}
+
+/// Applies `stencil` with step `h` along each parameter, see `stencil_diff_vec`.
+fn stencil_diff_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    stencil: &[(i32, f64)],
+    h: F,
+) -> nalgebra::DVector<F> {
+    let fx = if stencil.iter().any(|&(offset, _)| offset == 0) {
+        Some((f)(x))
+    } else {
+        None
+    };
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            stencil.iter().fold(F::zero(), |acc, &(offset, w)| {
+                let fxo = if offset == 0 {
+                    fx.unwrap()
+                } else {
+                    mod_and_calc_nalgebra(&mut xt, f, i, F::from(offset).unwrap() * h)
+                };
+                acc + F::from(w).unwrap() * fxo
+            }) / h
+        }),
+    )
+}
+
+pub fn forward_directional_diff_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    p: &nalgebra::DVector<F>,
+) -> F {
+    if p.iter().all(|pi| pi.is_zero()) {
+        return F::zero();
+    }
+    let fx = (f)(x);
+    let x1 = x.zip_map(p, |xi, pi| xi + F::EPS.sqrt() * pi);
+    let fx1 = (f)(&x1);
+    (fx1 - fx) / F::EPS.sqrt()
+}
+
+pub fn central_directional_diff_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    p: &nalgebra::DVector<F>,
+) -> F {
+    if p.iter().all(|pi| pi.is_zero()) {
+        return F::zero();
+    }
+    let x1 = x.zip_map(p, |xi, pi| xi + F::EPS.sqrt() * pi);
+    let x2 = x.zip_map(p, |xi, pi| xi - F::EPS.sqrt() * pi);
+    let fx1 = (f)(&x1);
+    let fx2 = (f)(&x2);
+    (fx1 - fx2) / (F::from(2.0).unwrap() * F::EPS.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMP_ACC: f64 = 1e-6;
+    const COMP_ACC_F32: f32 = 1e-3;
+
+    fn f(x: &nalgebra::DVector<f64>) -> f64 {
+        x[0] + x[1].powi(2)
+    }
+
+    fn f_scaled(x: &nalgebra::DVector<f64>) -> f64 {
+        (1e-6 * x[0]).powi(2) + (1e3 * x[1]).powi(2)
+    }
+
+    #[test]
+    fn test_forward_diff_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 1.0f64]);
+
+        let grad = forward_diff_nalgebra(&p, &f);
+        let res = [1.0f64, 2.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 2.0f64]);
+        let grad = forward_diff_nalgebra(&p, &f);
+        let res = [1.0f64, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    #[test]
+    fn test_forward_diff_subset_nalgebra_f64() {
+        let f = |x: &nalgebra::DVector<f64>| x[0] + x[1].powi(2) + x[2].powi(3);
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 2.0, 3.0]);
+        let grad = forward_diff_subset_nalgebra(&p, &f, &[1]);
+        let full = forward_diff_nalgebra(&p, &f);
+        assert_eq!(grad[0], 0.0);
+        assert_eq!(grad[1], full[1]);
+        assert_eq!(grad[2], 0.0);
+    }
+
+    #[test]
+    fn test_forward_diff_with_error_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 1.0f64]);
+        let (grad, err) = forward_diff_with_error_nalgebra(&p, &f);
+        let res = [1.0f64, 2.0];
+
+        (0..2)
+            .map(|i| {
+                assert!((res[i] - grad[i]).abs() < COMP_ACC);
+                assert!(err[i] < COMP_ACC);
+            })
+            .count();
+
+        // the steps `h` and `h/2` end up on different sides of the kink of `|x_0|`
+        let f_kink = |x: &nalgebra::DVector<f64>| x[0].abs() + x[1].powi(2);
+        let p = nalgebra::DVector::from_vec(vec![-1e-8f64, 1.0f64]);
+        let (_, err) = forward_diff_with_error_nalgebra(&p, &f_kink);
+        assert!(err[0] > 0.1);
+        assert!(err[1] < COMP_ACC);
+    }
+
+    #[test]
+    fn test_backward_diff_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 1.0f64]);
+
+        let grad = backward_diff_nalgebra(&p, &f);
+        let res = [1.0f64, 2.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 2.0f64]);
+        let grad = backward_diff_nalgebra(&p, &f);
+        let res = [1.0f64, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+    }
+    #[test]
+    fn test_central_diff_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 1.0f64]);
+
+        let grad = central_diff_nalgebra(&p, &f);
+        let res = [1.0f64, 2.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 2.0f64]);
+        let grad = central_diff_nalgebra(&p, &f);
+        let res = [1.0f64, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    #[test]
+    fn test_forward_diff_scaled_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1e6f64, 1e-3]);
+        let steps = nalgebra::DVector::from_vec(vec![1e-2f64, 1e-11]);
+        let grad = forward_diff_scaled_nalgebra(&p, &f_scaled, &steps);
+        let res = [2e-6f64, 2e3];
+
+        (0..2)
+            .map(|i| assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    #[test]
+    fn test_forward_diff_with_scaling_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1e6f64, 1e-3]);
+        let grad = forward_diff_with_scaling_nalgebra(
+            &p,
+            &f_scaled,
+            &nalgebra::DVector::from_vec(vec![1e6, 1e-3]),
+        );
+        let res = [2e-6f64, 2e3];
+
+        (0..2)
+            .map(|i| assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    #[test]
+    fn test_forward_diff_with_scaling_nalgebra_zero_scale() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 1.0]);
+        let grad = forward_diff_with_scaling_nalgebra(
+            &p,
+            &f,
+            &nalgebra::DVector::from_vec(vec![0.0, 1.0]),
+        );
+        assert_eq!(grad[0], 0.0);
+        assert!((grad[1] - 2.0).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_central_diff_scaled_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1e6f64, 1e-3]);
+        let steps = nalgebra::DVector::from_vec(vec![1e-2f64, 1e-11]);
+        let grad = central_diff_scaled_nalgebra(&p, &f_scaled, &steps);
+        let res = [2e-6f64, 2e3];
+
+        (0..2)
+            .map(|i| assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC))
+            .count();
+    }
+
+    fn f_large(x: &nalgebra::DVector<f64>) -> f64 {
+        x[0].ln() + x[1].powi(2)
+    }
+
+    #[test]
+    fn test_diff_nalgebra_f64_large_parameters() {
+        let p = nalgebra::DVector::from_vec(vec![1e8f64, 1.0]);
+        let res = [1e-8f64, 2.0];
+
+        // an absolute step of `sqrt(EPS)` would be lost when added to `1e8`, which silently gave a
+        // zero derivative before the steps were scaled by `max(|x_i|, 1)`
+        for grad in [
+            forward_diff_nalgebra(&p, &f_large),
+            central_diff_nalgebra(&p, &f_large),
+        ] {
+            (0..2)
+                .map(|i| assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC))
+                .count();
+        }
+    }
+
+    fn f_f32(x: &nalgebra::DVector<f32>) -> f32 {
+        x[0] + x[1].powi(2)
+    }
+
+    #[test]
+    fn test_forward_diff_nalgebra_f32() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f32, 1.0f32]);
+        let grad = forward_diff_nalgebra(&p, &f_f32);
+        let res = [1.0f32, 2.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC_F32))
+            .count();
+
+        let p = nalgebra::DVector::from_vec(vec![1.0f32, 2.0f32]);
+        let grad = forward_diff_nalgebra(&p, &f_f32);
+        // the truncation error of `x_1^2` equals the step, which is scaled by `x_1 = 2`
+        let res = [1.0f32, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < 2.0 * COMP_ACC_F32))
+            .count();
+    }
+
+    #[test]
+    fn test_backward_diff_nalgebra_f32() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f32, 1.0f32]);
+        let grad = backward_diff_nalgebra(&p, &f_f32);
+        let res = [1.0f32, 2.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC_F32))
+            .count();
+
+        let p = nalgebra::DVector::from_vec(vec![1.0f32, 2.0f32]);
+        let grad = backward_diff_nalgebra(&p, &f_f32);
+        // the truncation error of `x_1^2` equals the step, which is scaled by `x_1 = 2`
+        let res = [1.0f32, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < 2.0 * COMP_ACC_F32))
+            .count();
+    }
+
+    #[test]
+    fn test_central_diff_nalgebra_f32() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f32, 1.0f32]);
+        let grad = central_diff_nalgebra(&p, &f_f32);
+        let res = [1.0f32, 2.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC_F32))
+            .count();
+
+        let p = nalgebra::DVector::from_vec(vec![1.0f32, 2.0f32]);
+        let grad = central_diff_nalgebra(&p, &f_f32);
+        let res = [1.0f32, 4.0];
+
+        (0..2)
+            .map(|i| assert!((res[i] - grad[i]).abs() < COMP_ACC_F32))
+            .count();
+    }
+
+    #[test]
+    fn test_central_diff_5_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 2.0f64]);
+        let grad = central_diff_5_nalgebra(&p, &f);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_5_nalgebra_f64_exp() {
+        let f_exp = |x: &nalgebra::DVector<f64>| x[0].exp() + x[1].exp();
+        let p = nalgebra::DVector::from_vec(vec![2.0f64, -1.5f64]);
+        let grad3 = central_diff_nalgebra(&p, &f_exp);
+        let grad5 = central_diff_5_nalgebra(&p, &f_exp);
+
+        for i in 0..2 {
+            let err3 = (p[i].exp() - grad3[i]).abs();
+            let err5 = (p[i].exp() - grad5[i]).abs();
+            assert!(err5 < err3);
+            assert!(err5 < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_2_nalgebra_f64_exp() {
+        let f_exp = |x: &nalgebra::DVector<f64>| x[0].exp() + x[1].exp();
+        let p = nalgebra::DVector::from_vec(vec![2.0f64, -1.5f64]);
+        let grad1 = forward_diff_nalgebra(&p, &f_exp);
+        let grad2 = forward_diff_2_nalgebra(&p, &f_exp);
+
+        for i in 0..2 {
+            let err1 = (p[i].exp() - grad1[i]).abs();
+            let err2 = (p[i].exp() - grad2[i]).abs();
+            assert!(err2 * 10.0 < err1);
+            assert!(err2 < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_diff_order_nalgebra_f64_sin() {
+        let f_sin = |x: &nalgebra::DVector<f64>| x[0].sin();
+        let p = nalgebra::DVector::from_vec(vec![1.0f64]);
+        for &order in [
+            StencilOrder::Order2,
+            StencilOrder::Order4,
+            StencilOrder::Order6,
+        ]
+        .iter()
+        {
+            let forward = forward_diff_order_nalgebra(&p, &f_sin, order);
+            let central = central_diff_order_nalgebra(&p, &f_sin, order);
+            assert!((1.0f64.cos() - forward[0]).abs() < 1e-9);
+            assert!((1.0f64.cos() - central[0]).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_forward_directional_diff_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 2.0f64]);
+        let dir = nalgebra::DVector::from_vec(vec![3.0f64, -1.0]);
+        let d = forward_directional_diff_nalgebra(&p, &f, &dir);
+        assert!((d - (3.0 - 4.0)).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_central_directional_diff_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 2.0f64]);
+        let dir = nalgebra::DVector::from_vec(vec![3.0f64, -1.0]);
+        let d = central_directional_diff_nalgebra(&p, &f, &dir);
+        assert!((d - (3.0 - 4.0)).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_directional_diff_nalgebra_f64_zero_direction() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 2.0f64]);
+        let dir = nalgebra::DVector::from_vec(vec![0.0f64, 0.0]);
+        assert_eq!(forward_directional_diff_nalgebra(&p, &f, &dir), 0.0);
+        assert_eq!(central_directional_diff_nalgebra(&p, &f, &dir), 0.0);
+    }
+
+    #[test]
+    fn test_forward_diff_precomputed_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 2.0f64]);
+        let grad = forward_diff_precomputed_nalgebra(&p, &f, f(&p));
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_diff_nalgebra_f64_empty() {
+        let x: nalgebra::DVector<f64> = nalgebra::DVector::zeros(0);
+        let f = |_: &nalgebra::DVector<f64>| 1.0f64;
+        assert_eq!(forward_diff_nalgebra(&x, &f).len(), 0);
+        assert_eq!(backward_diff_nalgebra(&x, &f).len(), 0);
+        assert_eq!(central_diff_nalgebra(&x, &f).len(), 0);
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::PackedSymmetric;
+#[cfg(not(feature = "std"))]
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+use num_traits::Float;
+
+/// Default number of decimal places of `PrettyMatrix`
+const DEFAULT_PRECISION: usize = 6;
+
+/// Matrix with a `Display` implementation which prints one row per line with aligned columns.
+///
+/// Created via `Pretty::pretty` from a Jacobian or Hessian. The entries are printed with a fixed
+/// number of decimal places, which is 6 by default and can be changed with `with_precision` or
+/// with the precision of the format string.
+///
+/// ```
+/// use finitediff::Pretty;
+///
+/// let jacobian = vec![vec![1.0f64, -2.5], vec![10.25, 0.0]];
+/// assert_eq!(
+///     format!("{:.2}", jacobian.pretty()),
+///     " 1.00  -2.50\n10.25   0.00"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrettyMatrix {
+    rows: Vec<Vec<f64>>,
+    precision: usize,
+}
+
+impl PrettyMatrix {
+    /// Creates a `PrettyMatrix` from rows of equal length
+    pub fn new(rows: Vec<Vec<f64>>) -> Self {
+        PrettyMatrix {
+            rows,
+            precision: DEFAULT_PRECISION,
+        }
+    }
+
+    /// Sets the number of decimal places, unless overridden by the format string
+    pub fn with_precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+}
+
+impl fmt::Display for PrettyMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = f.precision().unwrap_or(self.precision);
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|v| format!("{:.*}", precision, v)).collect())
+            .collect();
+        let n_cols = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..n_cols)
+            .map(|j| {
+                cells
+                    .iter()
+                    .filter_map(|row| row.get(j))
+                    .map(|cell| cell.len())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        for (i, row) in cells.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            for (j, cell) in row.iter().enumerate() {
+                if j > 0 {
+                    write!(f, "  ")?;
+                }
+                write!(f, "{:>width$}", cell, width = widths[j])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Conversion of Jacobians and Hessians into a `PrettyMatrix` for printing
+pub trait Pretty {
+    /// Aligned, fixed precision representation of the matrix
+    fn pretty(&self) -> PrettyMatrix;
+}
+
+impl<F: Float> Pretty for Vec<Vec<F>> {
+    fn pretty(&self) -> PrettyMatrix {
+        PrettyMatrix::new(
+            self.iter()
+                .map(|row| row.iter().map(|v| v.to_f64().unwrap()).collect())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<F: Float> Pretty for ndarray::Array2<F> {
+    fn pretty(&self) -> PrettyMatrix {
+        PrettyMatrix::new(
+            self.outer_iter()
+                .map(|row| row.iter().map(|v| v.to_f64().unwrap()).collect())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<F: Float + nalgebra::Scalar> Pretty for nalgebra::DMatrix<F> {
+    fn pretty(&self) -> PrettyMatrix {
+        PrettyMatrix::new(
+            self.row_iter()
+                .map(|row| row.iter().map(|v| v.to_f64().unwrap()).collect())
+                .collect(),
+        )
+    }
+}
+
+impl<F: Float> Pretty for PackedSymmetric<F> {
+    fn pretty(&self) -> PrettyMatrix {
+        PrettyMatrix::new(
+            (0..self.n)
+                .map(|i| {
+                    (0..self.n)
+                        .map(|j| self.at(i, j).to_f64().unwrap())
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    #[test]
+    fn test_pretty_matrix() {
+        let mat = vec![vec![1.0f64, -2.5, 100.0], vec![10.25, 0.0, -0.125]];
+        assert_eq!(
+            format!("{}", mat.pretty().with_precision(3)),
+            " 1.000  -2.500  100.000\n10.250   0.000   -0.125"
+        );
+        assert_eq!(
+            format!("{:.1}", mat.pretty()),
+            " 1.0  -2.5  100.0\n10.2   0.0   -0.1"
+        );
+        assert_eq!(format!("{}", vec![vec![1.0f32]].pretty()), "1.000000");
+        assert_eq!(format!("{}", Vec::<Vec<f64>>::new().pretty()), "");
+    }
+
+    #[test]
+    fn test_pretty_packed() {
+        let packed = PackedSymmetric::from_upper(2, vec![1.0f64, 2.0, 3.0]);
+        assert_eq!(format!("{:.0}", packed.pretty()), "1  2\n2  3");
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_pretty_ndarray() {
+        let mat = ndarray::array![[1.0f64, -2.5], [10.25, 0.0]];
+        assert_eq!(format!("{:.2}", mat.pretty()), " 1.00  -2.50\n10.25   0.00");
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_pretty_nalgebra() {
+        let mat = nalgebra::DMatrix::from_row_slice(2, 2, &[1.0f64, -2.5, 10.25, 0.0]);
+        assert_eq!(format!("{:.2}", mat.pretty()), " 1.00  -2.50\n10.25   0.00");
+    }
+}
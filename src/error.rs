@@ -0,0 +1,313 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::fmt;
+use num_traits::Float;
+
+/// Errors returned by the `try_*` methods of `FiniteDiff`, `FiniteDiffConfig`,
+/// `PerturbationVector::validate` and `ComplexStep::complex_step_diff_checked`
+#[derive(Debug, Clone, PartialEq)]
+pub enum FiniteDiffError {
+    /// An input vector does not have the expected length
+    DimensionMismatch {
+        /// Expected length
+        expected: usize,
+        /// Actual length
+        got: usize,
+    },
+    /// The result contains a `NaN` or infinite value
+    NonFinite {
+        /// Index of the first non-finite entry
+        index: usize,
+    },
+    /// The Jacobian contains a `NaN` or infinite value
+    NonFiniteJacobian {
+        /// Position `(row, col)` of the first non-finite entry, in the layout returned by the
+        /// Jacobian methods of `FiniteDiff`
+        index: (usize, usize),
+    },
+    /// An index of a `PerturbationVector` is out of range
+    IndexOutOfBounds {
+        /// Offending index
+        index: usize,
+        /// Length of the vector the index refers to
+        dim: usize,
+    },
+    /// The stencil order is not available for the selected finite difference method
+    UnsupportedOrder {
+        /// Requested order
+        order: usize,
+    },
+    /// A step is lost to rounding because it is too small compared to the parameter, i.e.
+    /// `x_i + h == x_i` in floating point (or `x_i - h == x_i` for central differences).
+    ///
+    /// Only `try_forward_diff_scaled` and `try_central_diff_scaled` return this error, because
+    /// they perturb the parameters by user-supplied absolute steps. The default steps are scaled
+    /// with the magnitude of each parameter and therefore never lost to rounding.
+    StepUnderflow {
+        /// Index of the first affected parameter
+        index: usize,
+    },
+    /// The complex-step derivative disagrees with a real finite difference, which usually means
+    /// that the function is not holomorphic
+    NonHolomorphic {
+        /// Index of the first parameter for which the derivatives disagree
+        index: usize,
+    },
+    /// Perturbing a parameter changed the number of values returned by a vector valued function
+    OutputLengthChanged {
+        /// Index of the perturbed parameter, i.e. the column of the Jacobian `J_ij = dfs_i/dx_j`
+        /// (the row of the Jacobians returned by this crate)
+        col: usize,
+        /// Number of function values at the unperturbed parameters
+        expected: usize,
+        /// Number of function values at the perturbed parameters
+        got: usize,
+    },
+}
+
+impl fmt::Display for FiniteDiffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FiniteDiffError::DimensionMismatch { expected, got } => write!(
+                f,
+                "dimension mismatch: expected length {}, got {}",
+                expected, got
+            ),
+            FiniteDiffError::NonFinite { index } => {
+                write!(f, "non-finite value at index {}", index)
+            }
+            FiniteDiffError::NonFiniteJacobian { index: (row, col) } => {
+                write!(f, "non-finite value in the Jacobian at ({}, {})", row, col)
+            }
+            FiniteDiffError::IndexOutOfBounds { index, dim } => {
+                write!(f, "index {} out of bounds for length {}", index, dim)
+            }
+            FiniteDiffError::UnsupportedOrder { order } => {
+                write!(f, "stencil order {} is not supported by this method", order)
+            }
+            FiniteDiffError::StepUnderflow { index } => write!(
+                f,
+                "step is too small to perturb the parameter at index {}",
+                index
+            ),
+            FiniteDiffError::NonHolomorphic { index } => write!(
+                f,
+                "complex-step derivative disagrees with a real finite difference at index {}",
+                index
+            ),
+            FiniteDiffError::OutputLengthChanged { col, expected, got } => write!(
+                f,
+                "perturbing the parameter at index {} changed the number of function values from {} to {}",
+                col, expected, got
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FiniteDiffError {}
+
+#[inline(always)]
+pub fn check_dim(expected: usize, got: usize) -> Result<(), FiniteDiffError> {
+    if expected != got {
+        return Err(FiniteDiffError::DimensionMismatch { expected, got });
+    }
+    Ok(())
+}
+
+#[inline(always)]
+pub fn check_finite<'a, F: Float + 'a>(
+    mut vals: impl Iterator<Item = &'a F>,
+) -> Result<(), FiniteDiffError> {
+    match vals.position(|v| !v.is_finite()) {
+        Some(index) => Err(FiniteDiffError::NonFinite { index }),
+        None => Ok(()),
+    }
+}
+
+/// Checks whether adding the steps actually changes the parameters, as required by forward
+/// differences, which only evaluate `f` at `x_i + h`.
+#[inline(always)]
+pub fn check_forward_steps<'a, F: Float + 'a>(
+    x: impl Iterator<Item = &'a F>,
+    steps: impl Iterator<Item = &'a F>,
+) -> Result<(), FiniteDiffError> {
+    for (index, (&xi, &h)) in x.zip(steps).enumerate() {
+        if xi + h == xi {
+            return Err(FiniteDiffError::StepUnderflow { index });
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether adding and subtracting the steps actually changes the parameters, as required
+/// by central differences.
+#[inline(always)]
+pub fn check_steps<'a, F: Float + 'a>(
+    x: impl Iterator<Item = &'a F>,
+    steps: impl Iterator<Item = &'a F>,
+) -> Result<(), FiniteDiffError> {
+    for (index, (&xi, &h)) in x.zip(steps).enumerate() {
+        if xi + h == xi || xi - h == xi {
+            return Err(FiniteDiffError::StepUnderflow { index });
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether perturbing the parameter at `col` kept the number of function values.
+#[inline(always)]
+pub fn check_output_len(col: usize, expected: usize, got: usize) -> Result<(), FiniteDiffError> {
+    if expected != got {
+        return Err(FiniteDiffError::OutputLengthChanged { col, expected, got });
+    }
+    Ok(())
+}
+
+/// Same as `check_finite`, but for the entries of a Jacobian together with their position.
+#[inline(always)]
+pub fn check_finite_jacobian<'a, F: Float + 'a>(
+    mut vals: impl Iterator<Item = ((usize, usize), &'a F)>,
+) -> Result<(), FiniteDiffError> {
+    match vals.find(|(_, v)| !v.is_finite()) {
+        Some((index, _)) => Err(FiniteDiffError::NonFiniteJacobian { index }),
+        None => Ok(()),
+    }
+}
+
+/// Iterates over the entries of a matrix stored row by row together with their position.
+#[inline(always)]
+pub fn indexed_rows<'a, F: 'a, R: AsRef<[F]>>(
+    mat: &'a [R],
+) -> impl Iterator<Item = ((usize, usize), &'a F)> {
+    mat.iter().enumerate().flat_map(|(i, row)| {
+        row.as_ref()
+            .iter()
+            .enumerate()
+            .map(move |(j, v)| ((i, j), v))
+    })
+}
+
+/// Iterates over the entries of a `nalgebra::DMatrix<F>` row by row together with their
+/// position.
+#[cfg(feature = "nalgebra")]
+#[inline(always)]
+pub fn indexed_nalgebra<F: nalgebra::Scalar>(
+    mat: &nalgebra::DMatrix<F>,
+) -> impl Iterator<Item = ((usize, usize), &F)> {
+    let ncols = mat.ncols();
+    (0..mat.nrows()).flat_map(move |i| (0..ncols).map(move |j| ((i, j), &mat[(i, j)])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, vec};
+
+    #[test]
+    fn test_check_dim() {
+        assert_eq!(check_dim(3, 3), Ok(()));
+        assert_eq!(
+            check_dim(3, 2),
+            Err(FiniteDiffError::DimensionMismatch {
+                expected: 3,
+                got: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_finite() {
+        assert_eq!(check_finite([1.0f64, 2.0].iter()), Ok(()));
+        assert_eq!(
+            check_finite([1.0f64, f64::NAN].iter()),
+            Err(FiniteDiffError::NonFinite { index: 1 })
+        );
+        assert_eq!(
+            check_finite([f64::INFINITY, 2.0].iter()),
+            Err(FiniteDiffError::NonFinite { index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_check_finite_jacobian() {
+        let mat = vec![vec![1.0f64, 2.0], vec![3.0, 4.0]];
+        assert_eq!(check_finite_jacobian(indexed_rows(&mat)), Ok(()));
+        let mat = vec![vec![1.0f64, 2.0], vec![f64::NAN, f64::INFINITY]];
+        assert_eq!(
+            check_finite_jacobian(indexed_rows(&mat)),
+            Err(FiniteDiffError::NonFiniteJacobian { index: (1, 0) })
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let err = FiniteDiffError::DimensionMismatch {
+            expected: 3,
+            got: 2,
+        };
+        assert_eq!(
+            format!("{}", err),
+            "dimension mismatch: expected length 3, got 2"
+        );
+        let err = FiniteDiffError::NonFiniteJacobian { index: (1, 2) };
+        assert_eq!(
+            format!("{}", err),
+            "non-finite value in the Jacobian at (1, 2)"
+        );
+    }
+
+    #[test]
+    fn test_check_steps() {
+        let h = f64::EPSILON.sqrt();
+        assert_eq!(check_steps([1.0f64, -1e5].iter(), [h, h].iter()), Ok(()));
+        assert_eq!(
+            check_steps([1.0f64, 1e20, -1e20].iter(), [h, h, h].iter()),
+            Err(FiniteDiffError::StepUnderflow { index: 1 })
+        );
+        assert_eq!(check_steps([1.0f64, 1e20].iter(), [h, 1e5].iter()), Ok(()));
+        assert_eq!(
+            check_steps([1.0f64].iter(), [0.0].iter()),
+            Err(FiniteDiffError::StepUnderflow { index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_check_forward_steps() {
+        let h = f64::EPSILON.sqrt();
+        assert_eq!(
+            check_forward_steps([1.0f64, -1e5].iter(), [h, h].iter()),
+            Ok(())
+        );
+        assert_eq!(
+            check_forward_steps([1.0f64, 1e20].iter(), [h, h].iter()),
+            Err(FiniteDiffError::StepUnderflow { index: 1 })
+        );
+        // `-1 + h` is representable, `-1 - h` rounds back to `-1`
+        let h = f64::EPSILON / 2.0;
+        assert_eq!(check_forward_steps([-1.0f64].iter(), [h].iter()), Ok(()));
+        assert_eq!(
+            check_steps([-1.0f64].iter(), [h].iter()),
+            Err(FiniteDiffError::StepUnderflow { index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_check_output_len() {
+        assert_eq!(check_output_len(3, 2, 2), Ok(()));
+        assert_eq!(
+            check_output_len(3, 2, 1),
+            Err(FiniteDiffError::OutputLengthChanged {
+                col: 3,
+                expected: 2,
+                got: 1
+            })
+        );
+    }
+}
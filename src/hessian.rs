@@ -5,141 +5,556 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use crate::diff::central_diff_vec;
+use crate::packed::PackedSymmetric;
 use crate::utils::*;
-use crate::EPS_F64;
+use crate::FloatEps;
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Dense Hessian with convenience methods for downstream linear algebra.
+///
+/// Any Hessian returned by `FiniteDiff` can be converted via `From`, independent of the backend:
+///
+/// ```
+/// use finitediff::{FiniteDiff, SymmetricHessian};
+///
+/// let f = |x: &Vec<f64>| x[0].powi(2) + x[0] * x[1];
+/// let hessian = SymmetricHessian::from(vec![1.0f64, 1.0].central_hessian_nograd(&f));
+/// assert!(hessian.is_symmetric(1e-6));
+/// # let diag = hessian.diagonal();
+/// # assert!((diag[0] - 2.0).abs() < 1e-3);
+/// # assert!(diag[1].abs() < 1e-3);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymmetricHessian<F> {
+    /// Rows of the Hessian
+    pub data: Vec<Vec<F>>,
+}
+
+impl<F: FloatEps> SymmetricHessian<F> {
+    /// Number of rows and columns
+    pub fn dim(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Diagonal entries `d^2f/dx_i^2`
+    pub fn diagonal(&self) -> Vec<F> {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, row)| row[i])
+            .collect()
+    }
+
+    /// Returns `true` if the matrix is square and `|h_ij - h_ji| <= tol` for all `i` and `j`.
+    ///
+    /// The Hessian methods of `FiniteDiff` restore the symmetry of their result. A check which
+    /// fails on a Hessian which was assembled differently, for instance from `forward_jacobian` of
+    /// the gradient, indicates that the derivatives are dominated by noise.
+    pub fn is_symmetric(&self, tol: F) -> bool {
+        let n = self.dim();
+        self.data.iter().all(|row| row.len() == n)
+            && (0..n).all(|i| (0..i).all(|j| (self.data[i][j] - self.data[j][i]).abs() <= tol))
+    }
+
+    /// Returns the rows of the Hessian
+    pub fn into_inner(self) -> Vec<Vec<F>> {
+        self.data
+    }
+
+    /// Converts to an `ndarray::Array2`
+    #[cfg(feature = "ndarray")]
+    pub fn to_ndarray(&self) -> ndarray::Array2<F> {
+        let n = self.dim();
+        ndarray::Array2::from_shape_fn((n, n), |(i, j)| self.data[i][j])
+    }
+}
+
+impl<F> From<Vec<Vec<F>>> for SymmetricHessian<F> {
+    fn from(data: Vec<Vec<F>>) -> Self {
+        SymmetricHessian { data }
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<F: Copy> From<ndarray::Array2<F>> for SymmetricHessian<F> {
+    fn from(mat: ndarray::Array2<F>) -> Self {
+        SymmetricHessian {
+            data: mat.outer_iter().map(|row| row.to_vec()).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<F: nalgebra::Scalar + Copy> From<nalgebra::DMatrix<F>> for SymmetricHessian<F> {
+    fn from(mat: nalgebra::DMatrix<F>) -> Self {
+        SymmetricHessian {
+            data: mat
+                .row_iter()
+                .map(|row| row.iter().copied().collect())
+                .collect(),
+        }
+    }
+}
 
 /// I wish this wasn't necessary!
-const EPS_F64_NOGRAD: f64 = EPS_F64 * 2.0;
+#[inline(always)]
+fn eps_nograd<F: FloatEps>() -> F {
+    F::EPS * F::from(2.0).unwrap()
+}
 
-pub fn forward_hessian_vec_f64(
-    x: &Vec<f64>,
-    grad: &dyn Fn(&Vec<f64>) -> Vec<f64>,
-) -> Vec<Vec<f64>> {
+/// Diagonal entry `(f(x + h e_i) - 2 f(x) + f(x - h e_i)) / h^2` with `h = EPS^(1/4)` of the
+/// Hessians without gradient, where `eval(h)` evaluates `f` with the parameter `i` shifted by `h`.
+///
+/// The forward stencil `(f(x + 2h e_i) - 2 f(x + h e_i) + f(x)) / h^2` with `h = sqrt(2 EPS)`,
+/// which is used for the off-diagonal entries, is only first order accurate and loses most
+/// significant digits on the diagonal.
+#[inline(always)]
+pub fn nograd_diagonal<F: FloatEps>(fx: F, mut eval: impl FnMut(F) -> F) -> F {
+    let h = eps_second::<F>();
+    let fx1 = eval(h);
+    let fx2 = eval(-h);
+    (fx1 - F::from(2.0).unwrap() * fx + fx2) / (h * h)
+}
+
+pub fn forward_hessian_vec<F: FloatEps>(
+    x: &Vec<F>,
+    grad: &dyn Fn(&Vec<F>) -> Vec<F>,
+) -> Vec<Vec<F>> {
+    forward_hessian_sym_vec(x, grad, true)
+}
+
+/// Same as `forward_hessian_vec`, but the symmetry of the Hessian is only restored if `restore` is
+/// `true`. Otherwise, the row `i` is the forward difference of `grad` with respect to the `i`th
+/// parameter.
+pub fn forward_hessian_sym_vec<F: FloatEps>(
+    x: &Vec<F>,
+    grad: &dyn Fn(&Vec<F>) -> Vec<F>,
+    restore: bool,
+) -> Vec<Vec<F>> {
     let fx = (grad)(x);
     let mut xt = x.clone();
-    let out: Vec<Vec<f64>> = (0..x.len())
+    let out: Vec<Vec<F>> = (0..x.len())
         .map(|i| {
-            let fx1 = mod_and_calc_vec_f64(&mut xt, grad, i, EPS_F64.sqrt());
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, grad, i, h);
             fx1.iter()
                 .zip(fx.iter())
-                .map(|(a, b)| (a - b) / (EPS_F64.sqrt()))
-                .collect::<Vec<f64>>()
+                .map(|(&a, &b)| (a - b) / h)
+                .collect::<Vec<F>>()
         })
         .collect();
 
+    if restore {
+        restore_symmetry_vec(out)
+    } else {
+        out
+    }
+}
+
+/// Same as `forward_hessian_vec`, but for a fallible gradient. Returns the first error of `grad`.
+#[allow(clippy::type_complexity)]
+pub fn try_forward_hessian_vec<F: FloatEps, E>(
+    x: &Vec<F>,
+    grad: &dyn Fn(&Vec<F>) -> Result<Vec<F>, E>,
+) -> Result<Vec<Vec<F>>, E> {
+    let fx = (grad)(x)?;
+    let mut xt = x.clone();
+    let out = (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, grad, i, h)?;
+            Ok(fx1
+                .iter()
+                .zip(fx.iter())
+                .map(|(&a, &b)| (a - b) / h)
+                .collect::<Vec<F>>())
+        })
+        .collect::<Result<Vec<Vec<F>>, E>>()?;
+
     // restore symmetry
-    restore_symmetry_vec_f64(out)
+    Ok(restore_symmetry_vec(out))
 }
 
-pub fn central_hessian_vec_f64(
-    x: &Vec<f64>,
-    grad: &dyn Fn(&Vec<f64>) -> Vec<f64>,
-) -> Vec<Vec<f64>> {
+pub fn central_hessian_vec<F: FloatEps>(
+    x: &Vec<F>,
+    grad: &dyn Fn(&Vec<F>) -> Vec<F>,
+) -> Vec<Vec<F>> {
     let mut xt = x.clone();
-    let out: Vec<Vec<f64>> = (0..x.len())
+    let out: Vec<Vec<F>> = (0..x.len())
         .map(|i| {
-            let fx1 = mod_and_calc_vec_f64(&mut xt, grad, i, EPS_F64.sqrt());
-            let fx2 = mod_and_calc_vec_f64(&mut xt, grad, i, -EPS_F64.sqrt());
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, grad, i, h);
+            let fx2 = mod_and_calc_vec(&mut xt, grad, i, -h);
             fx1.iter()
                 .zip(fx2.iter())
-                .map(|(a, b)| (a - b) / (2.0 * EPS_F64.sqrt()))
-                .collect::<Vec<f64>>()
+                .map(|(&a, &b)| (a - b) / (F::from(2.0).unwrap() * h))
+                .collect::<Vec<F>>()
         })
         .collect();
 
     // restore symmetry
-    restore_symmetry_vec_f64(out)
+    restore_symmetry_vec(out)
 }
 
-pub fn forward_hessian_vec_prod_vec_f64(
-    x: &Vec<f64>,
-    grad: &dyn Fn(&Vec<f64>) -> Vec<f64>,
-    p: &Vec<f64>,
-) -> Vec<f64> {
+pub fn forward_hessian_vec_prod_vec<F: FloatEps>(
+    x: &Vec<F>,
+    grad: &dyn Fn(&Vec<F>) -> Vec<F>,
+    p: &Vec<F>,
+) -> Vec<F> {
     let fx = (grad)(x);
-    let out: Vec<f64> = {
+    let out: Vec<F> = {
         let x1 = x
             .iter()
             .zip(p.iter())
-            .map(|(xi, pi)| xi + pi * EPS_F64.sqrt())
+            .map(|(&xi, &pi)| xi + pi * F::EPS.sqrt())
             .collect();
         let fx1 = (grad)(&x1);
         fx1.iter()
             .zip(fx.iter())
-            .map(|(a, b)| (a - b) / (EPS_F64.sqrt()))
-            .collect::<Vec<f64>>()
+            .map(|(&a, &b)| (a - b) / (F::EPS.sqrt()))
+            .collect::<Vec<F>>()
     };
     out
 }
 
-pub fn central_hessian_vec_prod_vec_f64(
-    x: &Vec<f64>,
-    grad: &dyn Fn(&Vec<f64>) -> Vec<f64>,
-    p: &Vec<f64>,
-) -> Vec<f64> {
-    let out: Vec<f64> = {
+pub fn central_hessian_vec_prod_vec<F: FloatEps>(
+    x: &Vec<F>,
+    grad: &dyn Fn(&Vec<F>) -> Vec<F>,
+    p: &Vec<F>,
+) -> Vec<F> {
+    let out: Vec<F> = {
         let x1 = x
             .iter()
             .zip(p.iter())
-            .map(|(xi, pi)| xi + pi * EPS_F64.sqrt())
+            .map(|(&xi, &pi)| xi + pi * F::EPS.sqrt())
             .collect();
         let x2 = x
             .iter()
             .zip(p.iter())
-            .map(|(xi, pi)| xi - pi * EPS_F64.sqrt())
+            .map(|(&xi, &pi)| xi - pi * F::EPS.sqrt())
             .collect();
         let fx1 = (grad)(&x1);
         let fx2 = (grad)(&x2);
         fx1.iter()
             .zip(fx2.iter())
-            .map(|(a, b)| (a - b) / (2.0 * EPS_F64.sqrt()))
-            .collect::<Vec<f64>>()
+            .map(|(&a, &b)| (a - b) / (F::from(2.0).unwrap() * F::EPS.sqrt()))
+            .collect::<Vec<F>>()
     };
     out
 }
 
-pub fn forward_hessian_nograd_vec_f64(x: &Vec<f64>, f: &dyn Fn(&Vec<f64>) -> f64) -> Vec<Vec<f64>> {
+pub fn forward_hessian_vec_prod_nograd_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    p: &Vec<F>,
+) -> Vec<F> {
+    let h = eps_3::<F>();
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let mut xp: Vec<F> = x
+        .iter()
+        .zip(p.iter())
+        .map(|(&xi, &pi)| xi + pi * h)
+        .collect();
+    let fxp = (f)(&xp);
+    (0..x.len())
+        .map(|i| {
+            let fxei = mod_and_calc_vec(&mut xt, f, i, h);
+            let fxpei = mod_and_calc_vec(&mut xp, f, i, h);
+            (fxpei - fxei - fxp + fx) / (h * h)
+        })
+        .collect()
+}
+
+pub fn central_hessian_vec_prod_nograd_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    p: &Vec<F>,
+) -> Vec<F> {
+    let h = eps_second::<F>();
+    let mut xp: Vec<F> = x
+        .iter()
+        .zip(p.iter())
+        .map(|(&xi, &pi)| xi + pi * h)
+        .collect();
+    let mut xm: Vec<F> = x
+        .iter()
+        .zip(p.iter())
+        .map(|(&xi, &pi)| xi - pi * h)
+        .collect();
+    (0..x.len())
+        .map(|i| {
+            let fpp = mod_and_calc_vec(&mut xp, f, i, h);
+            let fpm = mod_and_calc_vec(&mut xp, f, i, -h);
+            let fmp = mod_and_calc_vec(&mut xm, f, i, h);
+            let fmm = mod_and_calc_vec(&mut xm, f, i, -h);
+            (fpp - fpm - fmp + fmm) / (F::from(4.0).unwrap() * h * h)
+        })
+        .collect()
+}
+
+pub fn forward_hessian_nograd_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+) -> Vec<Vec<F>> {
+    forward_grad_and_hessian_nograd_vec(x, f).1
+}
+
+/// Same as `forward_hessian_nograd_vec`, but returns `None` if `cancel` is set. The flag is
+/// checked before every row of the Hessian.
+pub fn forward_hessian_nograd_cancellable_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    cancel: &AtomicBool,
+) -> Option<Vec<Vec<F>>> {
+    if cancel.load(Ordering::Relaxed) {
+        return None;
+    }
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_vec(&mut xt, f, i, eps_nograd::<F>().sqrt()))
+        .collect();
+
+    let mut out = vec![vec![F::zero(); n]; n];
+    for i in 0..n {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        for j in 0..=i {
+            let t = if i == j {
+                nograd_diagonal(fx, |h| mod_and_calc_vec(&mut xt, f, i, h))
+            } else {
+                let fxij = mod_and_calc2_vec(
+                    &mut xt,
+                    f,
+                    i,
+                    j,
+                    eps_nograd::<F>().sqrt(),
+                    eps_nograd::<F>().sqrt(),
+                );
+                (fxij - fxei[i] - fxei[j] + fx) / eps_nograd::<F>()
+            };
+            out[i][j] = t;
+            out[j][i] = t;
+        }
+    }
+    Some(out)
+}
+
+/// Same as `forward_hessian_nograd_vec`, but the Hessian is passed to `on_block` in blocks of
+/// `block_rows` rows together with the index of the first row of the block. The last block may
+/// be shorter. At most one block is held in memory at a time.
+///
+/// Since a block contains complete rows, the symmetry of the Hessian can only be exploited within
+/// a block, and all other entries are computed twice. The entries are identical to the ones of
+/// `forward_hessian_nograd_vec`.
+///
+/// Panics if `block_rows` is zero.
+pub fn forward_hessian_nograd_blocks_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    block_rows: usize,
+    on_block: &mut dyn FnMut(usize, &[Vec<F>]),
+) {
+    assert!(block_rows > 0, "block_rows must be positive");
     let fx = (f)(x);
     let n = x.len();
     let mut xt = x.clone();
 
     // Precompute f(x + sqrt(EPS) * e_i) for all i
-    let fxei: Vec<f64> = (0..n)
-        .map(|i| mod_and_calc_vec_f64(&mut xt, f, i, EPS_F64_NOGRAD.sqrt()))
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_vec(&mut xt, f, i, eps_nograd::<F>().sqrt()))
         .collect();
 
-    let mut out: Vec<Vec<f64>> = vec![vec![0.0; n]; n];
+    let mut block = vec![vec![F::zero(); n]; block_rows.min(n)];
+    for start in (0..n).step_by(block_rows) {
+        let rows = block_rows.min(n - start);
+        for (k, row) in block[..rows].iter_mut().enumerate() {
+            let i = start + k;
+            for j in 0..n {
+                // Within the block, reuse the lower triangle
+                if j >= start && j < i {
+                    continue;
+                }
+                // Same order of operations as in the dense version to obtain identical entries
+                let (a, b) = if j <= i { (i, j) } else { (j, i) };
+                row[j] = if a == b {
+                    nograd_diagonal(fx, |h| mod_and_calc_vec(&mut xt, f, a, h))
+                } else {
+                    let fxij = mod_and_calc2_vec(
+                        &mut xt,
+                        f,
+                        a,
+                        b,
+                        eps_nograd::<F>().sqrt(),
+                        eps_nograd::<F>().sqrt(),
+                    );
+                    (fxij - fxei[a] - fxei[b] + fx) / eps_nograd::<F>()
+                };
+            }
+        }
+        for k in 0..rows {
+            for l in 0..k {
+                let t = block[l][start + k];
+                block[k][start + l] = t;
+            }
+        }
+        (on_block)(start, &block[..rows]);
+    }
+}
+
+pub fn forward_grad_and_hessian_nograd_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+) -> (Vec<F>, Vec<Vec<F>>) {
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_vec(&mut xt, f, i, eps_nograd::<F>().sqrt()))
+        .collect();
+
+    let mut out: Vec<Vec<F>> = vec![vec![F::zero(); n]; n];
 
     for i in 0..n {
         for j in 0..=i {
-            let t = {
-                let xti = xt[i];
-                let xtj = xt[j];
-                xt[i] += EPS_F64_NOGRAD.sqrt();
-                xt[j] += EPS_F64_NOGRAD.sqrt();
-                let fxij = (f)(&xt);
-                xt[i] = xti;
-                xt[j] = xtj;
-                (fxij - fxei[i] - fxei[j] + fx) / EPS_F64_NOGRAD
+            let t = if i == j {
+                nograd_diagonal(fx, |h| mod_and_calc_vec(&mut xt, f, i, h))
+            } else {
+                let fxij = mod_and_calc2_vec(
+                    &mut xt,
+                    f,
+                    i,
+                    j,
+                    eps_nograd::<F>().sqrt(),
+                    eps_nograd::<F>().sqrt(),
+                );
+                (fxij - fxei[i] - fxei[j] + fx) / eps_nograd::<F>()
             };
             out[i][j] = t;
             out[j][i] = t;
         }
     }
+
+    // The gradient only needs the evaluations which are already available
+    let grad = fxei
+        .iter()
+        .map(|&fxi| (fxi - fx) / eps_nograd::<F>().sqrt())
+        .collect();
+    (grad, out)
+}
+
+pub fn forward_hessian_nograd_packed_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+) -> PackedSymmetric<F> {
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+    let h = eps_nograd::<F>().sqrt();
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let fxei: Vec<F> = (0..n).map(|i| mod_and_calc_vec(&mut xt, f, i, h)).collect();
+
+    // Same stencil and order of operations as `forward_hessian_nograd_vec`, which computes the
+    // lower triangle
+    let mut data = Vec::with_capacity(n * (n + 1) / 2);
+    for i in 0..n {
+        for j in i..n {
+            if i == j {
+                data.push(nograd_diagonal(fx, |h| mod_and_calc_vec(&mut xt, f, i, h)));
+                continue;
+            }
+            let fxij = mod_and_calc2_vec(&mut xt, f, j, i, h, h);
+            data.push((fxij - fxei[j] - fxei[i] + fx) / eps_nograd::<F>());
+        }
+    }
+    PackedSymmetric::from_upper(n, data)
+}
+
+/// Function value, central difference gradient and central difference Hessian, where `f(x)` is
+/// evaluated once and shared.
+pub fn central_derivatives_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+) -> (F, Vec<F>, Vec<Vec<F>>) {
+    let fx = (f)(x);
+    let grad = central_diff_vec(x, f);
+    (fx, grad, central_hessian_nograd_shared_vec(x, f, Some(fx)))
+}
+
+pub fn central_hessian_nograd_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+) -> Vec<Vec<F>> {
+    central_hessian_nograd_shared_vec(x, f, None)
+}
+
+/// Same as `central_hessian_nograd_vec`, but uses `fx = f(x)` for the diagonal entries if it is
+/// given.
+fn central_hessian_nograd_shared_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    fx: Option<F>,
+) -> Vec<Vec<F>> {
+    let n = x.len();
+    let h = eps_second::<F>();
+    let mut xt = x.clone();
+    let mut out: Vec<Vec<F>> = vec![vec![F::zero(); n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let t = central_hessian_nograd_entry_vec(&mut xt, f, i, j, h, fx);
+            out[i][j] = t;
+            out[j][i] = t;
+        }
+    }
     out
 }
 
-pub fn forward_hessian_nograd_sparse_vec_f64(
-    x: &Vec<f64>,
-    f: &dyn Fn(&Vec<f64>) -> f64,
+/// Four-point central difference approximation of the `(i, j)` entry of the Hessian. `x` is
+/// restored before returning. On the diagonal, two of the four points are `x` itself, for which
+/// `fx` is used if it is given.
+#[inline(always)]
+fn central_hessian_nograd_entry_vec<F: FloatEps>(
+    x: &mut Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    i: usize,
+    j: usize,
+    h: F,
+    fx: Option<F>,
+) -> F {
+    let mut eval = |si: F, sj: F| mod_and_calc2_vec(x, f, i, j, si, sj);
+    let fpp = eval(h, h);
+    let (fpm, fmp) = match fx {
+        Some(fx) if i == j => (fx, fx),
+        _ => (eval(h, -h), eval(-h, h)),
+    };
+    let fmm = eval(-h, -h);
+    (fpp - fpm - fmp + fmm) / (F::from(4.0).unwrap() * h * h)
+}
+
+pub fn forward_hessian_nograd_sparse_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
     indices: Vec<[usize; 2]>,
-) -> Vec<Vec<f64>> {
+) -> Vec<Vec<F>> {
     let fx = (f)(x);
     let n = x.len();
     let mut xt = x.clone();
 
+    // Only the off-diagonal entries need f(x + sqrt(EPS) * e_i)
     let mut idxs: Vec<usize> = indices
         .iter()
+        .filter(|[i, j]| i != j)
         .flat_map(|i| i.iter())
         .cloned()
         .collect::<Vec<usize>>();
@@ -151,24 +566,27 @@ pub fn forward_hessian_nograd_sparse_vec_f64(
     for idx in idxs.iter() {
         fxei.set(
             *idx,
-            mod_and_calc_vec_f64(&mut xt, f, *idx, EPS_F64_NOGRAD.sqrt()),
+            mod_and_calc_vec(&mut xt, f, *idx, eps_nograd::<F>().sqrt()),
         );
     }
 
-    let mut out: Vec<Vec<f64>> = vec![vec![0.0; n]; n];
+    let mut out: Vec<Vec<F>> = vec![vec![F::zero(); n]; n];
     for [i, j] in indices {
-        let t = {
-            let xti = xt[i];
-            let xtj = xt[j];
-            xt[i] += EPS_F64_NOGRAD.sqrt();
-            xt[j] += EPS_F64_NOGRAD.sqrt();
-            let fxij = (f)(&xt);
-            xt[i] = xti;
-            xt[j] = xtj;
+        let t = if i == j {
+            nograd_diagonal(fx, |h| mod_and_calc_vec(&mut xt, f, i, h))
+        } else {
+            let fxij = mod_and_calc2_vec(
+                &mut xt,
+                f,
+                i,
+                j,
+                eps_nograd::<F>().sqrt(),
+                eps_nograd::<F>().sqrt(),
+            );
 
             let fxi = fxei.get(i).unwrap();
             let fxj = fxei.get(j).unwrap();
-            (fxij - fxi - fxj + fx) / EPS_F64_NOGRAD
+            (fxij - fxi - fxj + fx) / eps_nograd::<F>()
         };
         out[i][j] = t;
         out[j][i] = t;
@@ -176,11 +594,96 @@ pub fn forward_hessian_nograd_sparse_vec_f64(
     out
 }
 
+/// Hessian of a cost function `f(x) = sum_k g_k(x)`, assembled from the Hessians of the terms
+/// `g_k` with respect to the parameters they depend on.
+#[allow(clippy::type_complexity)]
+pub fn forward_hessian_nograd_additive_vec<F: FloatEps>(
+    x: &Vec<F>,
+    terms: &[(&dyn Fn(&Vec<F>) -> F, Vec<usize>)],
+) -> Vec<Vec<F>> {
+    let n = x.len();
+    let h = eps_nograd::<F>().sqrt();
+    let mut xt = x.clone();
+    let mut out = vec![vec![F::zero(); n]; n];
+    for (g, indices) in terms {
+        let fx = (g)(x);
+        let fxei: Vec<F> = indices
+            .iter()
+            .map(|&i| mod_and_calc_vec(&mut xt, *g, i, h))
+            .collect();
+        for (a, &i) in indices.iter().enumerate() {
+            for (b, &j) in indices[..=a].iter().enumerate() {
+                if i == j {
+                    out[i][i] += nograd_diagonal(fx, |h| mod_and_calc_vec(&mut xt, *g, i, h));
+                    continue;
+                }
+                let fxij = mod_and_calc2_vec(&mut xt, *g, i.max(j), i.min(j), h, h);
+                let t = (fxij - fxei[a] - fxei[b] + fx) / eps_nograd::<F>();
+                out[i][j] += t;
+                out[j][i] += t;
+            }
+        }
+    }
+    out
+}
+
+pub fn central_hessian_nograd_sparse_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    indices: Vec<[usize; 2]>,
+) -> Vec<Vec<F>> {
+    let n = x.len();
+    let h = eps_second::<F>();
+    let mut xt = x.clone();
+    let mut out: Vec<Vec<F>> = vec![vec![F::zero(); n]; n];
+    for [i, j] in indices {
+        let t = central_hessian_nograd_entry_vec(&mut xt, f, i, j, h, None);
+        out[i][j] = t;
+        out[j][i] = t;
+    }
+    out
+}
+
+pub fn forward_hessian_diagonal_nograd_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+) -> Vec<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| nograd_diagonal(fx, |h| mod_and_calc_vec(&mut xt, f, i, h)))
+        .collect()
+}
+
+/// Central difference gradient and diagonal of the Hessian from the same evaluations
+/// `f(x)` and `f(x +/- h * e_i)` with `h = EPS^(1/4)`. The diagonal is identical to the one of
+/// `forward_hessian_diagonal_nograd_vec`.
+pub fn central_grad_and_diag_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+) -> (Vec<F>, Vec<F>) {
+    let fx = (f)(x);
+    let h = eps_second::<F>();
+    let mut xt = x.clone();
+    let (grad, diag): (Vec<F>, Vec<F>) = (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_vec(&mut xt, f, i, h);
+            let fx2 = mod_and_calc_vec(&mut xt, f, i, -h);
+            (
+                (fx1 - fx2) / (F::from(2.0).unwrap() * h),
+                (fx1 - F::from(2.0).unwrap() * fx + fx2) / (h * h),
+            )
+        })
+        .unzip();
+    (grad, diag)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     const COMP_ACC: f64 = 1e-6;
+    const COMP_ACC_F32: f32 = 1e-3;
 
     fn f(x: &Vec<f64>) -> f64 {
         x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
@@ -213,7 +716,7 @@ mod tests {
 
     #[test]
     fn test_forward_hessian_vec_f64() {
-        let hessian = forward_hessian_vec_f64(&x(), &g);
+        let hessian = forward_hessian_vec(&x(), &g);
         let res = res1();
         // println!("hessian:\n{:#?}", hessian);
         // println!("diff:\n{:#?}", diff);
@@ -224,9 +727,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_hessian_vec_f64_large_parameters() {
+        // an absolute step of `sqrt(EPS)` is lost when added to `1e10`
+        let g = |x: &Vec<f64>| vec![2.0 * x[0]];
+        let p = vec![1e10f64];
+        assert!((forward_hessian_vec(&p, &g)[0][0] - 2.0).abs() < COMP_ACC);
+        assert!((central_hessian_vec(&p, &g)[0][0] - 2.0).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_forward_hessian_sym_vec_f64() {
+        // gradient of exp(x_0 * x_1) + x_1^3
+        let g = |x: &Vec<f64>| {
+            let e = (x[0] * x[1]).exp();
+            vec![x[1] * e, x[0] * e + 3.0 * x[1].powi(2)]
+        };
+        let p = vec![0.5f64, 1.3];
+        let raw = forward_hessian_sym_vec(&p, &g, false);
+        assert!((raw[0][1] - raw[1][0]).abs() > 1e-12);
+
+        let restored = forward_hessian_sym_vec(&p, &g, true);
+        assert_eq!(restored[0][1], restored[1][0]);
+        assert_eq!(restored[0][1], (raw[0][1] + raw[1][0]) / 2.0);
+        assert_eq!(restored, forward_hessian_vec(&p, &g));
+    }
+
     #[test]
     fn test_central_hessian_vec_f64() {
-        let hessian = central_hessian_vec_f64(&x(), &g);
+        let hessian = central_hessian_vec(&x(), &g);
         let res = res1();
         // println!("hessian:\n{:#?}", hessian);
         // println!("diff:\n{:#?}", diff);
@@ -237,9 +766,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_forward_hessian_vec_f64_mixed_magnitudes() {
+        // gradient of `ln(x_0) + x_1^2`
+        let g = |x: &Vec<f64>| vec![1.0 / x[0], 2.0 * x[1]];
+        let hessian = forward_hessian_vec(&vec![1e8f64, 1.0], &g);
+        let res = [[-1e-16f64, 0.0], [0.0, 2.0]];
+        // println!("{:?}", hessian);
+        for i in 0..2 {
+            for j in 0..2 {
+                // entries of very different magnitudes, therefore compare relative errors
+                let scale = if res[i][j] == 0.0 {
+                    1.0
+                } else {
+                    res[i][j].abs()
+                };
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC * scale)
+            }
+        }
+    }
+
     #[test]
     fn test_forward_hessian_vec_prod_vec_f64() {
-        let hessian = forward_hessian_vec_prod_vec_f64(&x(), &g, &p());
+        let hessian = forward_hessian_vec_prod_vec(&x(), &g, &p());
         let res = res2();
         // println!("hessian:\n{:#?}", hessian);
         // println!("diff:\n{:#?}", diff);
@@ -250,7 +799,7 @@ mod tests {
 
     #[test]
     fn test_central_hessian_vec_prod_vec_f64() {
-        let hessian = central_hessian_vec_prod_vec_f64(&x(), &g, &p());
+        let hessian = central_hessian_vec_prod_vec(&x(), &g, &p());
         let res = res2();
         // println!("hessian:\n{:#?}", hessian);
         // println!("diff:\n{:#?}", diff);
@@ -259,9 +808,155 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_forward_hessian_vec_prod_nograd_vec_f64() {
+        let f = crate::CountingFn::new(f);
+        let hessian = forward_hessian_vec_prod_nograd_vec(&x(), &|x| f.call(x), &p());
+        let res = res2();
+        // the stencil is only first-order accurate
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < 1e-3)
+        }
+        // 2*n + 2
+        assert_eq!(f.count(), 10);
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_nograd_vec_f64() {
+        let f = crate::CountingFn::new(f);
+        let hessian = central_hessian_vec_prod_nograd_vec(&x(), &|x| f.call(x), &p());
+        let res = res2();
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+        // 4*n
+        assert_eq!(f.count(), 16);
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_nograd_vec_quartic() {
+        let f = |x: &Vec<f64>| x[0].powi(4) + (x[0] * x[1]).powi(2) + x[1].powi(4);
+        let x = vec![1.0f64, 2.0];
+        let p = vec![1.0f64, -1.0];
+        // H(x) = [[20, 8], [8, 50]]
+        let res = [12.0, -42.0];
+        let central = central_hessian_vec_prod_nograd_vec(&x, &f, &p);
+        let forward = forward_hessian_vec_prod_nograd_vec(&x, &f, &p);
+        for i in 0..2 {
+            let err = (res[i] - central[i]).abs();
+            assert!(err < 1e-5);
+            assert!(err < 1e-2 * (res[i] - forward[i]).abs());
+        }
+    }
+
+    #[test]
+    fn test_central_derivatives_vec_quartic() {
+        let f = |x: &Vec<f64>| x[0].powi(4) + (x[0] * x[1]).powi(2) + x[1].powi(4);
+        let counted = crate::CountingFn::new(f);
+        let x = vec![1.0f64, 2.0];
+        let (value, grad, hessian) = central_derivatives_vec(&x, &|x| counted.call(x));
+        // 2*n^2 + 2*n + 1
+        assert_eq!(counted.count(), 13);
+
+        assert_eq!(value, 21.0);
+        let res = [12.0, 36.0];
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC);
+        }
+        let res = [[20.0, 8.0], [8.0, 50.0]];
+        for i in 0..2 {
+            for j in 0..2 {
+                assert!((res[i][j] - hessian[i][j]).abs() < 1e-4);
+            }
+        }
+        assert_eq!(grad, central_diff_vec(&x, &f));
+        assert_eq!(hessian[0][1], central_hessian_nograd_vec(&x, &f)[0][1]);
+    }
+
     #[test]
     fn test_forward_hessian_nograd_vec_f64() {
-        let hessian = forward_hessian_nograd_vec_f64(&x(), &f);
+        let hessian = forward_hessian_nograd_vec(&x(), &f);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_diagonal_entries_vec_f64() {
+        let f = |x: &Vec<f64>| x[1].powi(2);
+        let x = vec![1.0f64, 3.0];
+        // The forward stencil `(f(x + 2h e_1) - 2 f(x + h e_1) + f(x)) / h^2`, which was used on
+        // the diagonal before, is dominated by rounding errors
+        let h = eps_nograd::<f64>().sqrt();
+        let before = (f(&vec![1.0, 3.0 + h + h]) - 2.0 * f(&vec![1.0, 3.0 + h]) + f(&x))
+            / eps_nograd::<f64>();
+        assert!((before - 2.0).abs() > 1.0);
+        let hessian = forward_hessian_nograd_vec(&x, &f);
+        assert!((hessian[1][1] - 2.0).abs() < COMP_ACC);
+        assert_eq!(
+            hessian[1][1],
+            forward_hessian_diagonal_nograd_vec(&x, &f)[1]
+        );
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_packed_vec_f64() {
+        let f = crate::CountingFn::new(f);
+        let dense = forward_hessian_nograd_vec(&x(), &|x| f.call(x));
+        let count = f.count();
+        f.reset();
+        let packed = forward_hessian_nograd_packed_vec(&x(), &|x| f.call(x));
+        assert_eq!(f.count(), count);
+        assert_eq!(packed.data.len(), 10);
+        // same stencil and operations, therefore identical values
+        assert_eq!(packed.to_dense(), dense);
+        let res = res1();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - packed.at(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_blocks_vec_f64() {
+        let f = |x: &Vec<f64>| f(x) + x[0] * x[1].exp() * x[2].sin();
+        let dense = forward_hessian_nograd_vec(&p(), &f);
+        for block_rows in 1..=5 {
+            let mut rows = vec![];
+            forward_hessian_nograd_blocks_vec(&p(), &f, block_rows, &mut |start, block| {
+                assert_eq!(start, rows.len());
+                assert!(block.len() <= block_rows);
+                rows.extend_from_slice(block);
+            });
+            assert_eq!(rows, dense);
+        }
+    }
+
+    #[test]
+    fn test_forward_grad_and_hessian_nograd_vec_f64() {
+        let f = crate::CountingFn::new(f);
+        let (grad, hessian) = forward_grad_and_hessian_nograd_vec(&x(), &|x| f.call(x));
+        let res_grad = g(&x());
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            assert!((res_grad[i] - grad[i]).abs() < COMP_ACC);
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+        // 1 + n + n*(n-1)/2 + 2*n
+        assert_eq!(f.count(), 19);
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_vec_f64() {
+        let hessian = central_hessian_nograd_vec(&x(), &f);
         let res = res1();
         // println!("hessian:\n{:#?}", hessian);
         for i in 0..4 {
@@ -271,10 +966,86 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_central_hessian_nograd_vec_f64_accuracy() {
+        let p = vec![1.3f64, 0.7, 2.1, 1.9];
+        let res = [
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 2.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 2.0 * p[3]],
+            [0.0, 0.0, 2.0 * p[3], 2.0 * p[2]],
+        ];
+        let forward = forward_hessian_nograd_vec(&p, &f);
+        let central = central_hessian_nograd_vec(&p, &f);
+        let mut err_forward = 0.0f64;
+        let mut err_central = 0.0f64;
+        for i in 0..4 {
+            for j in 0..4 {
+                err_forward = err_forward.max((res[i][j] - forward[i][j]).abs());
+                err_central = err_central.max((res[i][j] - central[i][j]).abs());
+            }
+        }
+        assert!(err_central < err_forward);
+        assert!(err_central < COMP_ACC);
+    }
+
     #[test]
     fn test_forward_hessian_nograd_sparse_vec_f64() {
         let indices = vec![[1, 1], [2, 3], [3, 3]];
-        let hessian = forward_hessian_nograd_sparse_vec_f64(&x(), &f, indices);
+        let hessian = forward_hessian_nograd_sparse_vec(&x(), &f, indices);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_additive_vec_f64() {
+        // `f` split into two terms
+        let g1 = |x: &Vec<f64>| x[0] + x[1].powi(2);
+        let g2 = |x: &Vec<f64>| x[2] * x[3].powi(2);
+        let terms = [
+            (&g1 as &dyn Fn(&Vec<f64>) -> f64, vec![0, 1]),
+            (&g2, vec![3, 2]),
+        ];
+        let hessian = forward_hessian_nograd_additive_vec(&x(), &terms);
+        let res = res1();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+
+        // terms sharing the parameter 2
+        let g1 = |x: &Vec<f64>| x[1].powi(2) * x[2] + x[2].powi(2);
+        let g2 = |x: &Vec<f64>| x[2].powi(2) * x[3];
+        let terms = [
+            (&g1 as &dyn Fn(&Vec<f64>) -> f64, vec![1, 2]),
+            (&g2, vec![2, 3]),
+        ];
+        let hessian = forward_hessian_nograd_additive_vec(&x(), &terms);
+        let res = [
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 2.0, 2.0, 0.0],
+            [0.0, 2.0, 4.0, 2.0],
+            [0.0, 0.0, 2.0, 0.0],
+        ];
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+        assert_eq!(hessian[0], vec![0.0; 4]);
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_sparse_vec_f64() {
+        let indices = vec![[1, 1], [2, 3], [3, 3]];
+        let hessian = central_hessian_nograd_sparse_vec(&x(), &f, indices);
         let res = res1();
         // println!("hessian:\n{:#?}", hessian);
         // println!("diff:\n{:#?}", diff);
@@ -284,4 +1055,135 @@ mod tests {
             }
         }
     }
+
+    fn f_f32(x: &Vec<f32>) -> f32 {
+        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
+    }
+
+    fn g_f32(x: &Vec<f32>) -> Vec<f32> {
+        vec![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    }
+
+    fn x_f32() -> Vec<f32> {
+        vec![1.0f32, 1.0, 1.0, 1.0]
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_f32() {
+        let hessian = forward_hessian_vec(&x_f32(), &g_f32);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] as f32 - hessian[i][j]).abs() < COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_vec_f32() {
+        let hessian = central_hessian_vec(&x_f32(), &g_f32);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] as f32 - hessian[i][j]).abs() < COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_vec_f32() {
+        let hessian = forward_hessian_nograd_vec(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] as f32 - hessian[i][j]).abs() < COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_diagonal_nograd_vec_f64() {
+        let q = |x: &Vec<f64>| 3.0 * x[0].powi(2) + x[0] * x[1] + 0.5 * x[1].powi(2) + x[2];
+        let p = vec![1.0f64, -2.0, 3.0];
+        let diag = forward_hessian_diagonal_nograd_vec(&p, &q);
+        let res = [6.0f64, 1.0, 0.0];
+
+        for i in 0..3 {
+            assert!((res[i] - diag[i]).abs() < COMP_ACC)
+        }
+
+        let diag = forward_hessian_diagonal_nograd_vec(&x(), &f);
+        let res = res1();
+        for i in 0..4 {
+            assert!((res[i][i] - diag[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_grad_and_diag_vec_f64() {
+        let q = crate::CountingFn::new(|x: &Vec<f64>| {
+            3.0 * x[0].powi(2) + x[0] * x[1] + 0.5 * x[1].powi(2) + x[2]
+        });
+        let p = vec![1.0f64, -2.0, 3.0];
+        let (grad, diag) = central_grad_and_diag_vec(&p, &|x| q.call(x));
+        assert_eq!(q.count(), 2 * 3 + 1);
+        q.reset();
+        assert_eq!(
+            diag,
+            forward_hessian_diagonal_nograd_vec(&p, &|x| q.call(x))
+        );
+
+        let res_grad = [4.0f64, -1.0, 1.0];
+        let res_diag = [6.0f64, 1.0, 0.0];
+        for i in 0..3 {
+            assert!((res_grad[i] - grad[i]).abs() < COMP_ACC);
+            assert!((res_diag[i] - diag[i]).abs() < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_hessian_vec_f64_empty() {
+        let x: Vec<f64> = vec![];
+        let f = |_: &Vec<f64>| 1.0f64;
+        let g = |_: &Vec<f64>| Vec::<f64>::new();
+        assert!(forward_hessian_vec(&x, &g).is_empty());
+        assert!(central_hessian_vec(&x, &g).is_empty());
+        assert!(forward_hessian_vec_prod_vec(&x, &g, &x).is_empty());
+        assert!(central_hessian_vec_prod_vec(&x, &g, &x).is_empty());
+        assert!(forward_hessian_nograd_vec(&x, &f).is_empty());
+        assert!(central_hessian_nograd_vec(&x, &f).is_empty());
+        assert!(forward_hessian_diagonal_nograd_vec(&x, &f).is_empty());
+    }
+
+    #[test]
+    fn test_symmetric_hessian() {
+        let hessian = SymmetricHessian::from(forward_hessian_vec(&x(), &g));
+        assert_eq!(hessian.dim(), 4);
+        assert!(hessian.is_symmetric(0.0));
+        let res = res1();
+        for (i, d) in hessian.diagonal().iter().enumerate() {
+            assert!((res[i][i] - d).abs() < COMP_ACC);
+        }
+
+        // the Jacobian of the gradient is not symmetrized
+        let jacobian = crate::jacobian::forward_jacobian_vec(&x(), &g);
+        let hessian = SymmetricHessian::from(jacobian);
+        assert!(hessian.is_symmetric(1e-6));
+        assert!(!hessian.is_symmetric(0.0));
+
+        let ragged = SymmetricHessian::from(vec![vec![1.0f64, 0.0], vec![0.0]]);
+        assert!(!ragged.is_symmetric(1.0));
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_symmetric_hessian_to_ndarray() {
+        let hessian = SymmetricHessian::from(forward_hessian_vec(&x(), &g));
+        let mat = hessian.to_ndarray();
+        assert_eq!(mat.dim(), (4, 4));
+        assert_eq!(SymmetricHessian::from(mat), hessian);
+    }
 }
@@ -0,0 +1,843 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::diff_nalgebra::central_diff_nalgebra;
+use crate::hessian::nograd_diagonal;
+use crate::packed::PackedSymmetric;
+use crate::utils::*;
+use crate::FloatEps;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// I wish this wasn't necessary!
+#[inline(always)]
+fn eps_nograd<F: FloatEps>() -> F {
+    F::EPS * F::from(2.0).unwrap()
+}
+
+pub fn forward_hessian_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    grad: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    forward_hessian_sym_nalgebra(x, grad, true)
+}
+
+/// Same as `forward_hessian_nalgebra`, but the symmetry of the Hessian is only restored if `restore` is
+/// `true`. Otherwise, the row `i` is the forward difference of `grad` with respect to the `i`th
+/// parameter.
+pub fn forward_hessian_sym_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    grad: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    restore: bool,
+) -> nalgebra::DMatrix<F> {
+    let mut xt = x.clone();
+    let fx = (grad)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, grad, i, h);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    if restore {
+        restore_symmetry_nalgebra(out)
+    } else {
+        out
+    }
+}
+
+/// Same as `forward_hessian_nalgebra`, but for a fallible gradient. Returns the first error of
+/// `grad`.
+#[allow(clippy::type_complexity)]
+pub fn try_forward_hessian_nalgebra<F: FloatEps + nalgebra::Scalar, E>(
+    x: &nalgebra::DVector<F>,
+    grad: &dyn Fn(&nalgebra::DVector<F>) -> Result<nalgebra::DVector<F>, E>,
+) -> Result<nalgebra::DMatrix<F>, E> {
+    let mut xt = x.clone();
+    let fx = (grad)(x)?;
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, grad, i, h)?;
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    // restore symmetry
+    Ok(restore_symmetry_nalgebra(out))
+}
+
+pub fn central_hessian_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    grad: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    let mut xt = x.clone();
+    // TODO: get rid of this!
+    let fx = (grad)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, grad, i, h);
+        let fx2 = mod_and_calc_nalgebra(&mut xt, grad, i, -h);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx2[j]) / (F::from(2.0).unwrap() * h);
+        }
+    }
+    // restore symmetry
+    restore_symmetry_nalgebra(out)
+}
+
+pub fn forward_hessian_vec_prod_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    grad: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    p: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let fx = (grad)(x);
+    let x1 = x.zip_map(p, |xi, pi| xi + pi * F::EPS.sqrt());
+    let fx1 = (grad)(&x1);
+    fx1.zip_map(&fx, |a, b| (a - b) / F::EPS.sqrt())
+}
+
+pub fn central_hessian_vec_prod_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    grad: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    p: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let x1 = x.zip_map(p, |xi, pi| xi + pi * F::EPS.sqrt());
+    let x2 = x.zip_map(p, |xi, pi| xi - pi * F::EPS.sqrt());
+    let fx1 = (grad)(&x1);
+    let fx2 = (grad)(&x2);
+    fx1.zip_map(&fx2, |a, b| {
+        (a - b) / (F::from(2.0).unwrap() * F::EPS.sqrt())
+    })
+}
+
+pub fn forward_hessian_vec_prod_nograd_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    p: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let h = eps_3::<F>();
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let mut xp = x.zip_map(p, |xi, pi| xi + pi * h);
+    let fxp = (f)(&xp);
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let fxei = mod_and_calc_nalgebra(&mut xt, f, i, h);
+            let fxpei = mod_and_calc_nalgebra(&mut xp, f, i, h);
+            (fxpei - fxei - fxp + fx) / (h * h)
+        }),
+    )
+}
+
+pub fn central_hessian_vec_prod_nograd_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    p: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let h = eps_second::<F>();
+    let mut xp = x.zip_map(p, |xi, pi| xi + pi * h);
+    let mut xm = x.zip_map(p, |xi, pi| xi - pi * h);
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let fpp = mod_and_calc_nalgebra(&mut xp, f, i, h);
+            let fpm = mod_and_calc_nalgebra(&mut xp, f, i, -h);
+            let fmp = mod_and_calc_nalgebra(&mut xm, f, i, h);
+            let fmm = mod_and_calc_nalgebra(&mut xm, f, i, -h);
+            (fpp - fpm - fmp + fmm) / (F::from(4.0).unwrap() * h * h)
+        }),
+    )
+}
+
+pub fn forward_hessian_nograd_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> nalgebra::DMatrix<F> {
+    forward_grad_and_hessian_nograd_nalgebra(x, f).1
+}
+
+/// Same as `forward_hessian_nograd_nalgebra`, but returns `None` if `cancel` is set. The flag is
+/// checked before every row of the Hessian.
+pub fn forward_hessian_nograd_cancellable_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    cancel: &AtomicBool,
+) -> Option<nalgebra::DMatrix<F>> {
+    if cancel.load(Ordering::Relaxed) {
+        return None;
+    }
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_nalgebra(&mut xt, f, i, eps_nograd::<F>().sqrt()))
+        .collect();
+
+    let mut out = nalgebra::DMatrix::zeros(n, n);
+    for i in 0..n {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        for j in 0..=i {
+            let t = if i == j {
+                nograd_diagonal(fx, |h| mod_and_calc_nalgebra(&mut xt, f, i, h))
+            } else {
+                let fxij = mod_and_calc2_nalgebra(
+                    &mut xt,
+                    f,
+                    i,
+                    j,
+                    eps_nograd::<F>().sqrt(),
+                    eps_nograd::<F>().sqrt(),
+                );
+                (fxij - fxei[i] - fxei[j] + fx) / eps_nograd::<F>()
+            };
+            out[(i, j)] = t;
+            out[(j, i)] = t;
+        }
+    }
+    Some(out)
+}
+
+/// Same as `forward_hessian_nograd_nalgebra`, but the Hessian is passed to `on_block` in blocks of
+/// `block_rows` rows together with the index of the first row of the block. The last block may
+/// be shorter. At most one block is held in memory at a time.
+///
+/// Since a block contains complete rows, the symmetry of the Hessian can only be exploited within
+/// a block, and all other entries are computed twice. The entries are identical to the ones of
+/// `forward_hessian_nograd_nalgebra`.
+///
+/// Panics if `block_rows` is zero.
+pub fn forward_hessian_nograd_blocks_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    block_rows: usize,
+    on_block: &mut dyn FnMut(usize, &[Vec<F>]),
+) {
+    assert!(block_rows > 0, "block_rows must be positive");
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_nalgebra(&mut xt, f, i, eps_nograd::<F>().sqrt()))
+        .collect();
+
+    let mut block = vec![vec![F::zero(); n]; block_rows.min(n)];
+    for start in (0..n).step_by(block_rows) {
+        let rows = block_rows.min(n - start);
+        for (k, row) in block[..rows].iter_mut().enumerate() {
+            let i = start + k;
+            for j in 0..n {
+                // Within the block, reuse the lower triangle
+                if j >= start && j < i {
+                    continue;
+                }
+                // Same order of operations as in the dense version to obtain identical entries
+                let (a, b) = if j <= i { (i, j) } else { (j, i) };
+                row[j] = if a == b {
+                    nograd_diagonal(fx, |h| mod_and_calc_nalgebra(&mut xt, f, a, h))
+                } else {
+                    let fxij = mod_and_calc2_nalgebra(
+                        &mut xt,
+                        f,
+                        a,
+                        b,
+                        eps_nograd::<F>().sqrt(),
+                        eps_nograd::<F>().sqrt(),
+                    );
+                    (fxij - fxei[a] - fxei[b] + fx) / eps_nograd::<F>()
+                };
+            }
+        }
+        for k in 0..rows {
+            for l in 0..k {
+                let t = block[l][start + k];
+                block[k][start + l] = t;
+            }
+        }
+        (on_block)(start, &block[..rows]);
+    }
+}
+
+pub fn forward_grad_and_hessian_nograd_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> (nalgebra::DVector<F>, nalgebra::DMatrix<F>) {
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_nalgebra(&mut xt, f, i, eps_nograd::<F>().sqrt()))
+        .collect();
+
+    let mut out = nalgebra::DMatrix::zeros(n, n);
+    for i in 0..n {
+        for j in 0..=i {
+            let t = if i == j {
+                nograd_diagonal(fx, |h| mod_and_calc_nalgebra(&mut xt, f, i, h))
+            } else {
+                let fxij = mod_and_calc2_nalgebra(
+                    &mut xt,
+                    f,
+                    i,
+                    j,
+                    eps_nograd::<F>().sqrt(),
+                    eps_nograd::<F>().sqrt(),
+                );
+                (fxij - fxei[i] - fxei[j] + fx) / eps_nograd::<F>()
+            };
+            out[(i, j)] = t;
+            out[(j, i)] = t;
+        }
+    }
+
+    // The gradient only needs the evaluations which are already available
+    let grad = nalgebra::DVector::from_iterator(
+        n,
+        fxei.iter()
+            .map(|&fxi| (fxi - fx) / eps_nograd::<F>().sqrt()),
+    );
+    (grad, out)
+}
+
+pub fn forward_hessian_nograd_packed_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> PackedSymmetric<F> {
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+    let h = eps_nograd::<F>().sqrt();
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_nalgebra(&mut xt, f, i, h))
+        .collect();
+
+    // Same stencil and order of operations as `forward_hessian_nograd_nalgebra`, which computes the
+    // lower triangle
+    let mut data = Vec::with_capacity(n * (n + 1) / 2);
+    for i in 0..n {
+        for j in i..n {
+            if i == j {
+                data.push(nograd_diagonal(fx, |h| {
+                    mod_and_calc_nalgebra(&mut xt, f, i, h)
+                }));
+                continue;
+            }
+            let fxij = mod_and_calc2_nalgebra(&mut xt, f, j, i, h, h);
+            data.push((fxij - fxei[j] - fxei[i] + fx) / eps_nograd::<F>());
+        }
+    }
+    PackedSymmetric::from_upper(n, data)
+}
+
+/// Function value, central difference gradient and central difference Hessian, where `f(x)` is
+/// evaluated once and shared.
+pub fn central_derivatives_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> (F, nalgebra::DVector<F>, nalgebra::DMatrix<F>) {
+    let fx = (f)(x);
+    let grad = central_diff_nalgebra(x, f);
+    (
+        fx,
+        grad,
+        central_hessian_nograd_shared_nalgebra(x, f, Some(fx)),
+    )
+}
+
+pub fn central_hessian_nograd_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> nalgebra::DMatrix<F> {
+    central_hessian_nograd_shared_nalgebra(x, f, None)
+}
+
+/// Same as `central_hessian_nograd_nalgebra`, but uses `fx = f(x)` for the diagonal entries if it is
+/// given.
+fn central_hessian_nograd_shared_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    fx: Option<F>,
+) -> nalgebra::DMatrix<F> {
+    let n = x.len();
+    let h = eps_second::<F>();
+    let mut xt = x.clone();
+    let mut out = nalgebra::DMatrix::zeros(n, n);
+    for i in 0..n {
+        for j in 0..=i {
+            let t = central_hessian_nograd_entry_nalgebra(&mut xt, f, i, j, h, fx);
+            out[(i, j)] = t;
+            out[(j, i)] = t;
+        }
+    }
+    out
+}
+
+/// Four-point central difference approximation of the `(i, j)` entry of the Hessian. `x` is
+/// restored before returning. On the diagonal, two of the four points are `x` itself, for which
+/// `fx` is used if it is given.
+#[inline(always)]
+fn central_hessian_nograd_entry_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &mut nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    i: usize,
+    j: usize,
+    h: F,
+    fx: Option<F>,
+) -> F {
+    let mut eval = |si: F, sj: F| mod_and_calc2_nalgebra(x, f, i, j, si, sj);
+    let fpp = eval(h, h);
+    let (fpm, fmp) = match fx {
+        Some(fx) if i == j => (fx, fx),
+        _ => (eval(h, -h), eval(-h, h)),
+    };
+    let fmm = eval(-h, -h);
+    (fpp - fpm - fmp + fmm) / (F::from(4.0).unwrap() * h * h)
+}
+
+pub fn forward_hessian_nograd_sparse_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    indices: Vec<[usize; 2]>,
+) -> nalgebra::DMatrix<F> {
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+
+    // Only the off-diagonal entries need f(x + sqrt(EPS) * e_i)
+    let mut idxs: Vec<usize> = indices
+        .iter()
+        .filter(|[i, j]| i != j)
+        .flat_map(|i| i.iter())
+        .cloned()
+        .collect::<Vec<usize>>();
+    idxs.sort();
+    idxs.dedup();
+
+    let mut fxei = KV::new(idxs.len());
+
+    for idx in idxs.iter() {
+        fxei.set(
+            *idx,
+            mod_and_calc_nalgebra(&mut xt, f, *idx, eps_nograd::<F>().sqrt()),
+        );
+    }
+
+    let mut out = nalgebra::DMatrix::zeros(n, n);
+    for [i, j] in indices {
+        let t = if i == j {
+            nograd_diagonal(fx, |h| mod_and_calc_nalgebra(&mut xt, f, i, h))
+        } else {
+            let fxij = mod_and_calc2_nalgebra(
+                &mut xt,
+                f,
+                i,
+                j,
+                eps_nograd::<F>().sqrt(),
+                eps_nograd::<F>().sqrt(),
+            );
+
+            let fxi = fxei.get(i).unwrap();
+            let fxj = fxei.get(j).unwrap();
+
+            (fxij - fxi - fxj + fx) / eps_nograd::<F>()
+        };
+        out[(i, j)] = t;
+        out[(j, i)] = t;
+    }
+    out
+}
+
+/// Hessian of a cost function `f(x) = sum_k g_k(x)`, assembled from the Hessians of the terms
+/// `g_k` with respect to the parameters they depend on.
+#[allow(clippy::type_complexity)]
+pub fn forward_hessian_nograd_additive_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    terms: &[(&dyn Fn(&nalgebra::DVector<F>) -> F, Vec<usize>)],
+) -> nalgebra::DMatrix<F> {
+    let n = x.len();
+    let h = eps_nograd::<F>().sqrt();
+    let mut xt = x.clone();
+    let mut out = nalgebra::DMatrix::zeros(n, n);
+    for (g, indices) in terms {
+        let fx = (g)(x);
+        let fxei: Vec<F> = indices
+            .iter()
+            .map(|&i| mod_and_calc_nalgebra(&mut xt, *g, i, h))
+            .collect();
+        for (a, &i) in indices.iter().enumerate() {
+            for (b, &j) in indices[..=a].iter().enumerate() {
+                if i == j {
+                    out[(i, i)] +=
+                        nograd_diagonal(fx, |h| mod_and_calc_nalgebra(&mut xt, *g, i, h));
+                    continue;
+                }
+                let fxij = mod_and_calc2_nalgebra(&mut xt, *g, i.max(j), i.min(j), h, h);
+                let t = (fxij - fxei[a] - fxei[b] + fx) / eps_nograd::<F>();
+                out[(i, j)] += t;
+                out[(j, i)] += t;
+            }
+        }
+    }
+    out
+}
+
+pub fn central_hessian_nograd_sparse_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    indices: Vec<[usize; 2]>,
+) -> nalgebra::DMatrix<F> {
+    let n = x.len();
+    let h = eps_second::<F>();
+    let mut xt = x.clone();
+    let mut out = nalgebra::DMatrix::zeros(n, n);
+    for [i, j] in indices {
+        let t = central_hessian_nograd_entry_nalgebra(&mut xt, f, i, j, h, None);
+        out[(i, j)] = t;
+        out[(j, i)] = t;
+    }
+    out
+}
+
+pub fn forward_hessian_diagonal_nograd_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> nalgebra::DVector<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| nograd_diagonal(fx, |h| mod_and_calc_nalgebra(&mut xt, f, i, h))),
+    )
+}
+
+/// Central difference gradient and diagonal of the Hessian from the same evaluations
+/// `f(x)` and `f(x +/- h * e_i)` with `h = EPS^(1/4)`. The diagonal is identical to the one of
+/// `forward_hessian_diagonal_nograd_nalgebra`.
+pub fn central_grad_and_diag_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+) -> (nalgebra::DVector<F>, nalgebra::DVector<F>) {
+    let fx = (f)(x);
+    let h = eps_second::<F>();
+    let mut xt = x.clone();
+    let (grad, diag): (Vec<F>, Vec<F>) = (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_nalgebra(&mut xt, f, i, h);
+            let fx2 = mod_and_calc_nalgebra(&mut xt, f, i, -h);
+            (
+                (fx1 - fx2) / (F::from(2.0).unwrap() * h),
+                (fx1 - F::from(2.0).unwrap() * fx + fx2) / (h * h),
+            )
+        })
+        .unzip();
+    (
+        nalgebra::DVector::from_vec(grad),
+        nalgebra::DVector::from_vec(diag),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::{dvector, DVector};
+
+    const COMP_ACC: f64 = 1e-6;
+    const COMP_ACC_F32: f32 = 1e-3;
+
+    fn f(x: &DVector<f64>) -> f64 {
+        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
+    }
+
+    fn g(x: &DVector<f64>) -> DVector<f64> {
+        dvector![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    }
+
+    fn x() -> DVector<f64> {
+        dvector![1.0f64, 1.0, 1.0, 1.0]
+    }
+
+    fn p() -> DVector<f64> {
+        dvector![2.0, 3.0, 4.0, 5.0]
+    }
+
+    fn res1() -> Vec<Vec<f64>> {
+        vec![
+            vec![0.0, 0.0, 0.0, 0.0],
+            vec![0.0, 2.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0, 2.0],
+            vec![0.0, 0.0, 2.0, 2.0],
+        ]
+    }
+
+    fn res2() -> Vec<f64> {
+        vec![0.0, 6.0, 10.0, 18.0]
+    }
+
+    #[test]
+    fn test_forward_hessian_nalgebra_f64() {
+        let hessian = forward_hessian_nalgebra(&x(), &g);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nalgebra_f64() {
+        let hessian = central_hessian_nalgebra(&x(), &g);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_prod_nalgebra_f64() {
+        let hessian = forward_hessian_vec_prod_nalgebra(&x(), &g, &p());
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_nalgebra_f64() {
+        let hessian = central_hessian_vec_prod_nalgebra(&x(), &g, &p());
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_prod_nograd_nalgebra_f64() {
+        let f = crate::CountingFn::new(f);
+        let hessian = forward_hessian_vec_prod_nograd_nalgebra(&x(), &|x| f.call(x), &p());
+        let res = res2();
+        // the stencil is only first-order accurate
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < 1e-3)
+        }
+        // 2*n + 2
+        assert_eq!(f.count(), 10);
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_nograd_nalgebra_f64() {
+        let hessian = central_hessian_vec_prod_nograd_nalgebra(&x(), &f, &p());
+        let res = res2();
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_nalgebra_f64() {
+        let hessian = forward_hessian_nograd_nalgebra(&x(), &f);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_packed_nalgebra_f64() {
+        let dense = forward_hessian_nograd_nalgebra(&x(), &f);
+        let packed = forward_hessian_nograd_packed_nalgebra(&x(), &f);
+        assert_eq!(packed.data.len(), 10);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_eq!(dense[(i, j)], packed.at(i, j));
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_blocks_nalgebra_f64() {
+        let p = nalgebra::DVector::from_vec(vec![2.0f64, 3.0, 4.0, 5.0]);
+        let dense = forward_hessian_nograd_nalgebra(&p, &f);
+        let mut rows: Vec<Vec<f64>> = vec![];
+        forward_hessian_nograd_blocks_nalgebra(&p, &f, 3, &mut |start, block| {
+            assert_eq!(start, rows.len());
+            rows.extend_from_slice(block);
+        });
+        assert_eq!(rows.len(), 4);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_eq!(rows[i][j], dense[(i, j)]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_grad_and_hessian_nograd_nalgebra_f64() {
+        let (grad, hessian) = forward_grad_and_hessian_nograd_nalgebra(&x(), &f);
+        let res_grad = g(&x());
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            assert!((res_grad[i] - grad[i]).abs() < COMP_ACC);
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_nalgebra_f64() {
+        let hessian = central_hessian_nograd_nalgebra(&x(), &f);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_sparse_nalgebra_f64() {
+        let indices = vec![[1, 1], [2, 3], [3, 3]];
+        let hessian = forward_hessian_nograd_sparse_nalgebra(&x(), &f, indices);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_sparse_nalgebra_f64() {
+        let indices = vec![[1, 1], [2, 3], [3, 3]];
+        let hessian = central_hessian_nograd_sparse_nalgebra(&x(), &f, indices);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    fn f_f32(x: &DVector<f32>) -> f32 {
+        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
+    }
+
+    fn g_f32(x: &DVector<f32>) -> DVector<f32> {
+        dvector![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    }
+
+    fn x_f32() -> DVector<f32> {
+        dvector![1.0f32, 1.0, 1.0, 1.0]
+    }
+
+    #[test]
+    fn test_forward_hessian_nalgebra_f32() {
+        let hessian = forward_hessian_nalgebra(&x_f32(), &g_f32);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] as f32 - hessian[(i, j)]).abs() < COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nalgebra_f32() {
+        let hessian = central_hessian_nalgebra(&x_f32(), &g_f32);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] as f32 - hessian[(i, j)]).abs() < COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_nalgebra_f32() {
+        let hessian = forward_hessian_nograd_nalgebra(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] as f32 - hessian[(i, j)]).abs() < COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_diagonal_nograd_nalgebra_f64() {
+        let q = |x: &DVector<f64>| 3.0 * x[0].powi(2) + x[0] * x[1] + 0.5 * x[1].powi(2) + x[2];
+        let p = dvector![1.0f64, -2.0, 3.0];
+        let diag = forward_hessian_diagonal_nograd_nalgebra(&p, &q);
+        let res = [6.0f64, 1.0, 0.0];
+
+        for i in 0..3 {
+            assert!((res[i] - diag[i]).abs() < COMP_ACC)
+        }
+
+        let diag = forward_hessian_diagonal_nograd_nalgebra(&x(), &f);
+        let res = res1();
+        for i in 0..4 {
+            assert!((res[i][i] - diag[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_hessian_nalgebra_f64_empty() {
+        let x: DVector<f64> = DVector::zeros(0);
+        let f = |_: &DVector<f64>| 1.0f64;
+        let g = |_: &DVector<f64>| DVector::zeros(0);
+        assert_eq!(forward_hessian_nalgebra(&x, &g).shape(), (0, 0));
+        assert_eq!(central_hessian_nalgebra(&x, &g).shape(), (0, 0));
+        assert_eq!(forward_hessian_vec_prod_nalgebra(&x, &g, &x).len(), 0);
+        assert_eq!(central_hessian_vec_prod_nalgebra(&x, &g, &x).len(), 0);
+        assert_eq!(forward_hessian_nograd_nalgebra(&x, &f).shape(), (0, 0));
+        assert_eq!(central_hessian_nograd_nalgebra(&x, &f).shape(), (0, 0));
+        assert_eq!(forward_hessian_diagonal_nograd_nalgebra(&x, &f).len(), 0);
+    }
+}
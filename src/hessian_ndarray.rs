@@ -5,123 +5,429 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use crate::diff_ndarray::central_diff_ndarray;
+use crate::hessian::nograd_diagonal;
+use crate::packed::PackedSymmetric;
 use crate::utils::*;
-use crate::EPS_F64;
+use crate::FloatEps;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 /// I wish this wasn't necessary!
-const EPS_F64_NOGRAD: f64 = EPS_F64 * 2.0;
+#[inline(always)]
+fn eps_nograd<F: FloatEps>() -> F {
+    F::EPS * F::from(2.0).unwrap()
+}
+
+pub fn forward_hessian_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    grad: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
+    forward_hessian_sym_ndarray(x, grad, true)
+}
 
-pub fn forward_hessian_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    grad: &dyn Fn(&ndarray::Array1<f64>) -> ndarray::Array1<f64>,
-) -> ndarray::Array2<f64> {
+/// Same as `forward_hessian_ndarray`, but the symmetry of the Hessian is only restored if `restore` is
+/// `true`. Otherwise, the row `i` is the forward difference of `grad` with respect to the `i`th
+/// parameter.
+pub fn forward_hessian_sym_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    grad: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    restore: bool,
+) -> ndarray::Array2<F> {
     // use ndarray::s;
     let mut xt = x.clone();
-    let fx = (grad)(&x);
+    let fx = (grad)(x);
     let rn = fx.len();
     let n = x.len();
     let mut out = unsafe { ndarray::Array2::uninitialized((n, rn)) };
     for i in 0..n {
-        let fx1 = mod_and_calc_ndarray_f64(&mut xt, grad, i, EPS_F64.sqrt());
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, grad, i, h);
         // unfortunately, this is slower than iterating :/
         // out.slice_mut(s![i, ..])
-        //     .assign(&((fx1 - &fx) / EPS_F64.sqrt()));
+        //     .assign(&((fx1 - &fx) / h));
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    if restore {
+        restore_symmetry_ndarray(out)
+    } else {
+        out
+    }
+}
+
+/// Same as `forward_hessian_ndarray`, but for a fallible gradient. Returns the first error of
+/// `grad`.
+#[allow(clippy::type_complexity)]
+pub fn try_forward_hessian_ndarray<F: FloatEps + ndarray::ScalarOperand, E>(
+    x: &ndarray::Array1<F>,
+    grad: &dyn Fn(&ndarray::Array1<F>) -> Result<ndarray::Array1<F>, E>,
+) -> Result<ndarray::Array2<F>, E> {
+    let mut xt = x.clone();
+    let fx = (grad)(x)?;
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = ndarray::Array2::zeros((n, rn));
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, grad, i, h)?;
         for j in 0..rn {
-            out[(i, j)] = (fx1[j] - fx[j]) / EPS_F64.sqrt();
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
         }
     }
     // restore symmetry
-    restore_symmetry_ndarray_f64(out)
+    Ok(restore_symmetry_ndarray(out))
 }
 
-pub fn central_hessian_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    grad: &dyn Fn(&ndarray::Array1<f64>) -> ndarray::Array1<f64>,
-) -> ndarray::Array2<f64> {
+pub fn central_hessian_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    grad: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
     let mut xt = x.clone();
     // TODO: get rid of this!
-    let fx = (grad)(&x);
+    let fx = (grad)(x);
     let rn = fx.len();
     let n = x.len();
     let mut out = ndarray::Array2::zeros((n, rn));
     for i in 0..n {
-        let fx1 = mod_and_calc_ndarray_f64(&mut xt, grad, i, EPS_F64.sqrt());
-        let fx2 = mod_and_calc_ndarray_f64(&mut xt, grad, i, -EPS_F64.sqrt());
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, grad, i, h);
+        let fx2 = mod_and_calc_ndarray(&mut xt, grad, i, -h);
         for j in 0..rn {
-            out[(i, j)] = (fx1[j] - fx2[j]) / (2.0 * EPS_F64.sqrt());
+            out[(i, j)] = (fx1[j] - fx2[j]) / (F::from(2.0).unwrap() * h);
         }
     }
     // restore symmetry
-    restore_symmetry_ndarray_f64(out)
+    restore_symmetry_ndarray(out)
 }
 
-pub fn forward_hessian_vec_prod_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    grad: &dyn Fn(&ndarray::Array1<f64>) -> ndarray::Array1<f64>,
-    p: &ndarray::Array1<f64>,
-) -> ndarray::Array1<f64> {
-    let fx = (grad)(&x);
-    let x1 = x + &(p.mapv(|pi| pi * EPS_F64.sqrt()));
+pub fn forward_hessian_vec_prod_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    grad: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    p: &ndarray::Array1<F>,
+) -> ndarray::Array1<F> {
+    let fx = (grad)(x);
+    let x1 = x + &(p.mapv(|pi| pi * F::EPS.sqrt()));
     let fx1 = (grad)(&x1);
-    (fx1 - fx) / EPS_F64.sqrt()
+    (fx1 - fx) / F::EPS.sqrt()
 }
 
-pub fn central_hessian_vec_prod_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    grad: &dyn Fn(&ndarray::Array1<f64>) -> ndarray::Array1<f64>,
-    p: &ndarray::Array1<f64>,
-) -> ndarray::Array1<f64> {
-    let x1 = x + &(p.mapv(|pi| pi * EPS_F64.sqrt()));
-    let x2 = x - &(p.mapv(|pi| pi * EPS_F64.sqrt()));
+pub fn central_hessian_vec_prod_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    grad: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    p: &ndarray::Array1<F>,
+) -> ndarray::Array1<F> {
+    let x1 = x + &(p.mapv(|pi| pi * F::EPS.sqrt()));
+    let x2 = x - &(p.mapv(|pi| pi * F::EPS.sqrt()));
     let fx1 = (grad)(&x1);
     let fx2 = (grad)(&x2);
-    (fx1 - fx2) / (2.0 * EPS_F64.sqrt())
+    (fx1 - fx2) / (F::from(2.0).unwrap() * F::EPS.sqrt())
+}
+
+pub fn forward_hessian_vec_prod_nograd_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+    p: &ndarray::Array1<F>,
+) -> ndarray::Array1<F> {
+    let h = eps_3::<F>();
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let mut xp = x + &(p.mapv(|pi| pi * h));
+    let fxp = (f)(&xp);
+    (0..x.len())
+        .map(|i| {
+            let fxei = mod_and_calc_ndarray(&mut xt, f, i, h);
+            let fxpei = mod_and_calc_ndarray(&mut xp, f, i, h);
+            (fxpei - fxei - fxp + fx) / (h * h)
+        })
+        .collect()
+}
+
+pub fn central_hessian_vec_prod_nograd_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+    p: &ndarray::Array1<F>,
+) -> ndarray::Array1<F> {
+    let h = eps_second::<F>();
+    let mut xp = x + &(p.mapv(|pi| pi * h));
+    let mut xm = x - &(p.mapv(|pi| pi * h));
+    (0..x.len())
+        .map(|i| {
+            let fpp = mod_and_calc_ndarray(&mut xp, f, i, h);
+            let fpm = mod_and_calc_ndarray(&mut xp, f, i, -h);
+            let fmp = mod_and_calc_ndarray(&mut xm, f, i, h);
+            let fmm = mod_and_calc_ndarray(&mut xm, f, i, -h);
+            (fpp - fpm - fmp + fmm) / (F::from(4.0).unwrap() * h * h)
+        })
+        .collect()
+}
+
+pub fn forward_hessian_nograd_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+) -> ndarray::Array2<F> {
+    forward_grad_and_hessian_nograd_ndarray(x, f).1
+}
+
+/// Same as `forward_hessian_nograd_ndarray`, but returns `None` if `cancel` is set. The flag is
+/// checked before every row of the Hessian.
+pub fn forward_hessian_nograd_cancellable_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+    cancel: &AtomicBool,
+) -> Option<ndarray::Array2<F>> {
+    if cancel.load(Ordering::Relaxed) {
+        return None;
+    }
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_ndarray(&mut xt, f, i, eps_nograd::<F>().sqrt()))
+        .collect();
+
+    let mut out = ndarray::Array2::zeros((n, n));
+    for i in 0..n {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        for j in 0..=i {
+            let t = if i == j {
+                nograd_diagonal(fx, |h| mod_and_calc_ndarray(&mut xt, f, i, h))
+            } else {
+                let fxij = mod_and_calc2_ndarray(
+                    &mut xt,
+                    f,
+                    i,
+                    j,
+                    eps_nograd::<F>().sqrt(),
+                    eps_nograd::<F>().sqrt(),
+                );
+                (fxij - fxei[i] - fxei[j] + fx) / eps_nograd::<F>()
+            };
+            out[(i, j)] = t;
+            out[(j, i)] = t;
+        }
+    }
+    Some(out)
+}
+
+/// Same as `forward_hessian_nograd_ndarray`, but the Hessian is passed to `on_block` in blocks of
+/// `block_rows` rows together with the index of the first row of the block. The last block may
+/// be shorter. At most one block is held in memory at a time.
+///
+/// Since a block contains complete rows, the symmetry of the Hessian can only be exploited within
+/// a block, and all other entries are computed twice. The entries are identical to the ones of
+/// `forward_hessian_nograd_ndarray`.
+///
+/// Panics if `block_rows` is zero.
+pub fn forward_hessian_nograd_blocks_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+    block_rows: usize,
+    on_block: &mut dyn FnMut(usize, &[Vec<F>]),
+) {
+    assert!(block_rows > 0, "block_rows must be positive");
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_ndarray(&mut xt, f, i, eps_nograd::<F>().sqrt()))
+        .collect();
+
+    let mut block = vec![vec![F::zero(); n]; block_rows.min(n)];
+    for start in (0..n).step_by(block_rows) {
+        let rows = block_rows.min(n - start);
+        for (k, row) in block[..rows].iter_mut().enumerate() {
+            let i = start + k;
+            for j in 0..n {
+                // Within the block, reuse the lower triangle
+                if j >= start && j < i {
+                    continue;
+                }
+                // Same order of operations as in the dense version to obtain identical entries
+                let (a, b) = if j <= i { (i, j) } else { (j, i) };
+                row[j] = if a == b {
+                    nograd_diagonal(fx, |h| mod_and_calc_ndarray(&mut xt, f, a, h))
+                } else {
+                    let fxij = mod_and_calc2_ndarray(
+                        &mut xt,
+                        f,
+                        a,
+                        b,
+                        eps_nograd::<F>().sqrt(),
+                        eps_nograd::<F>().sqrt(),
+                    );
+                    (fxij - fxei[a] - fxei[b] + fx) / eps_nograd::<F>()
+                };
+            }
+        }
+        for k in 0..rows {
+            for l in 0..k {
+                let t = block[l][start + k];
+                block[k][start + l] = t;
+            }
+        }
+        (on_block)(start, &block[..rows]);
+    }
 }
 
-pub fn forward_hessian_nograd_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    f: &dyn Fn(&ndarray::Array1<f64>) -> f64,
-) -> ndarray::Array2<f64> {
+pub fn forward_grad_and_hessian_nograd_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+) -> (ndarray::Array1<F>, ndarray::Array2<F>) {
     let fx = (f)(x);
     let n = x.len();
     let mut xt = x.clone();
 
     // Precompute f(x + sqrt(EPS) * e_i) for all i
-    let fxei: Vec<f64> = (0..n)
-        .map(|i| mod_and_calc_ndarray_f64(&mut xt, f, i, EPS_F64_NOGRAD.sqrt()))
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_ndarray(&mut xt, f, i, eps_nograd::<F>().sqrt()))
         .collect();
 
     let mut out = ndarray::Array2::zeros((n, n));
     for i in 0..n {
         for j in 0..=i {
-            let t = {
-                let xti = xt[i];
-                let xtj = xt[j];
-                xt[i] += EPS_F64_NOGRAD.sqrt();
-                xt[j] += EPS_F64_NOGRAD.sqrt();
-                let fxij = (f)(&xt);
-                xt[i] = xti;
-                xt[j] = xtj;
-                (fxij - fxei[i] - fxei[j] + fx) / EPS_F64_NOGRAD
+            let t = if i == j {
+                nograd_diagonal(fx, |h| mod_and_calc_ndarray(&mut xt, f, i, h))
+            } else {
+                let fxij = mod_and_calc2_ndarray(
+                    &mut xt,
+                    f,
+                    i,
+                    j,
+                    eps_nograd::<F>().sqrt(),
+                    eps_nograd::<F>().sqrt(),
+                );
+                (fxij - fxei[i] - fxei[j] + fx) / eps_nograd::<F>()
             };
             out[(i, j)] = t;
             out[(j, i)] = t;
         }
     }
+
+    // The gradient only needs the evaluations which are already available
+    let grad = fxei
+        .iter()
+        .map(|&fxi| (fxi - fx) / eps_nograd::<F>().sqrt())
+        .collect();
+    (grad, out)
+}
+
+pub fn forward_hessian_nograd_packed_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+) -> PackedSymmetric<F> {
+    let fx = (f)(x);
+    let n = x.len();
+    let mut xt = x.clone();
+    let h = eps_nograd::<F>().sqrt();
+
+    // Precompute f(x + sqrt(EPS) * e_i) for all i
+    let fxei: Vec<F> = (0..n)
+        .map(|i| mod_and_calc_ndarray(&mut xt, f, i, h))
+        .collect();
+
+    // Same stencil and order of operations as `forward_hessian_nograd_ndarray`, which computes the
+    // lower triangle
+    let mut data = Vec::with_capacity(n * (n + 1) / 2);
+    for i in 0..n {
+        for j in i..n {
+            if i == j {
+                data.push(nograd_diagonal(fx, |h| {
+                    mod_and_calc_ndarray(&mut xt, f, i, h)
+                }));
+                continue;
+            }
+            let fxij = mod_and_calc2_ndarray(&mut xt, f, j, i, h, h);
+            data.push((fxij - fxei[j] - fxei[i] + fx) / eps_nograd::<F>());
+        }
+    }
+    PackedSymmetric::from_upper(n, data)
+}
+
+/// Function value, central difference gradient and central difference Hessian, where `f(x)` is
+/// evaluated once and shared.
+pub fn central_derivatives_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+) -> (F, ndarray::Array1<F>, ndarray::Array2<F>) {
+    let fx = (f)(x);
+    let grad = central_diff_ndarray(x, f);
+    (
+        fx,
+        grad,
+        central_hessian_nograd_shared_ndarray(x, f, Some(fx)),
+    )
+}
+
+pub fn central_hessian_nograd_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+) -> ndarray::Array2<F> {
+    central_hessian_nograd_shared_ndarray(x, f, None)
+}
+
+/// Same as `central_hessian_nograd_ndarray`, but uses `fx = f(x)` for the diagonal entries if it is
+/// given.
+fn central_hessian_nograd_shared_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+    fx: Option<F>,
+) -> ndarray::Array2<F> {
+    let n = x.len();
+    let h = eps_second::<F>();
+    let mut xt = x.clone();
+    let mut out = ndarray::Array2::zeros((n, n));
+    for i in 0..n {
+        for j in 0..=i {
+            let t = central_hessian_nograd_entry_ndarray(&mut xt, f, i, j, h, fx);
+            out[(i, j)] = t;
+            out[(j, i)] = t;
+        }
+    }
     out
 }
 
-pub fn forward_hessian_nograd_sparse_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    f: &dyn Fn(&ndarray::Array1<f64>) -> f64,
+/// Four-point central difference approximation of the `(i, j)` entry of the Hessian. `x` is
+/// restored before returning. On the diagonal, two of the four points are `x` itself, for which
+/// `fx` is used if it is given.
+#[inline(always)]
+fn central_hessian_nograd_entry_ndarray<F: FloatEps>(
+    x: &mut ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+    i: usize,
+    j: usize,
+    h: F,
+    fx: Option<F>,
+) -> F {
+    let mut eval = |si: F, sj: F| mod_and_calc2_ndarray(x, f, i, j, si, sj);
+    let fpp = eval(h, h);
+    let (fpm, fmp) = match fx {
+        Some(fx) if i == j => (fx, fx),
+        _ => (eval(h, -h), eval(-h, h)),
+    };
+    let fmm = eval(-h, -h);
+    (fpp - fpm - fmp + fmm) / (F::from(4.0).unwrap() * h * h)
+}
+
+pub fn forward_hessian_nograd_sparse_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
     indices: Vec<[usize; 2]>,
-) -> ndarray::Array2<f64> {
+) -> ndarray::Array2<F> {
     let fx = (f)(x);
     let n = x.len();
     let mut xt = x.clone();
 
+    // Only the off-diagonal entries need f(x + sqrt(EPS) * e_i)
     let mut idxs: Vec<usize> = indices
         .iter()
+        .filter(|[i, j]| i != j)
         .flat_map(|i| i.iter())
         .cloned()
         .collect::<Vec<usize>>();
@@ -133,25 +439,28 @@ pub fn forward_hessian_nograd_sparse_ndarray_f64(
     for idx in idxs.iter() {
         fxei.set(
             *idx,
-            mod_and_calc_ndarray_f64(&mut xt, f, *idx, EPS_F64_NOGRAD.sqrt()),
+            mod_and_calc_ndarray(&mut xt, f, *idx, eps_nograd::<F>().sqrt()),
         );
     }
 
     let mut out = ndarray::Array2::zeros((n, n));
     for [i, j] in indices {
-        let t = {
-            let xti = xt[i];
-            let xtj = xt[j];
-            xt[i] += EPS_F64_NOGRAD.sqrt();
-            xt[j] += EPS_F64_NOGRAD.sqrt();
-            let fxij = (f)(&xt);
-            xt[i] = xti;
-            xt[j] = xtj;
+        let t = if i == j {
+            nograd_diagonal(fx, |h| mod_and_calc_ndarray(&mut xt, f, i, h))
+        } else {
+            let fxij = mod_and_calc2_ndarray(
+                &mut xt,
+                f,
+                i,
+                j,
+                eps_nograd::<F>().sqrt(),
+                eps_nograd::<F>().sqrt(),
+            );
 
             let fxi = fxei.get(i).unwrap();
             let fxj = fxei.get(j).unwrap();
 
-            (fxij - fxi - fxj + fx) / EPS_F64_NOGRAD
+            (fxij - fxi - fxj + fx) / eps_nograd::<F>()
         };
         out[(i, j)] = t;
         out[(j, i)] = t;
@@ -159,13 +468,98 @@ pub fn forward_hessian_nograd_sparse_ndarray_f64(
     out
 }
 
+/// Hessian of a cost function `f(x) = sum_k g_k(x)`, assembled from the Hessians of the terms
+/// `g_k` with respect to the parameters they depend on.
+#[allow(clippy::type_complexity)]
+pub fn forward_hessian_nograd_additive_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    terms: &[(&dyn Fn(&ndarray::Array1<F>) -> F, Vec<usize>)],
+) -> ndarray::Array2<F> {
+    let n = x.len();
+    let h = eps_nograd::<F>().sqrt();
+    let mut xt = x.clone();
+    let mut out = ndarray::Array2::zeros((n, n));
+    for (g, indices) in terms {
+        let fx = (g)(x);
+        let fxei: Vec<F> = indices
+            .iter()
+            .map(|&i| mod_and_calc_ndarray(&mut xt, *g, i, h))
+            .collect();
+        for (a, &i) in indices.iter().enumerate() {
+            for (b, &j) in indices[..=a].iter().enumerate() {
+                if i == j {
+                    out[(i, i)] += nograd_diagonal(fx, |h| mod_and_calc_ndarray(&mut xt, *g, i, h));
+                    continue;
+                }
+                let fxij = mod_and_calc2_ndarray(&mut xt, *g, i.max(j), i.min(j), h, h);
+                let t = (fxij - fxei[a] - fxei[b] + fx) / eps_nograd::<F>();
+                out[(i, j)] += t;
+                out[(j, i)] += t;
+            }
+        }
+    }
+    out
+}
+
+pub fn central_hessian_nograd_sparse_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+    indices: Vec<[usize; 2]>,
+) -> ndarray::Array2<F> {
+    let n = x.len();
+    let h = eps_second::<F>();
+    let mut xt = x.clone();
+    let mut out = ndarray::Array2::zeros((n, n));
+    for [i, j] in indices {
+        let t = central_hessian_nograd_entry_ndarray(&mut xt, f, i, j, h, None);
+        out[(i, j)] = t;
+        out[(j, i)] = t;
+    }
+    out
+}
+
+pub fn forward_hessian_diagonal_nograd_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+) -> ndarray::Array1<F> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| nograd_diagonal(fx, |h| mod_and_calc_ndarray(&mut xt, f, i, h)))
+        .collect()
+}
+
+/// Central difference gradient and diagonal of the Hessian from the same evaluations
+/// `f(x)` and `f(x +/- h * e_i)` with `h = EPS^(1/4)`. The diagonal is identical to the one of
+/// `forward_hessian_diagonal_nograd_ndarray`.
+pub fn central_grad_and_diag_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+) -> (ndarray::Array1<F>, ndarray::Array1<F>) {
+    let fx = (f)(x);
+    let h = eps_second::<F>();
+    let mut xt = x.clone();
+    let (grad, diag): (Vec<F>, Vec<F>) = (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_ndarray(&mut xt, f, i, h);
+            let fx2 = mod_and_calc_ndarray(&mut xt, f, i, -h);
+            (
+                (fx1 - fx2) / (F::from(2.0).unwrap() * h),
+                (fx1 - F::from(2.0).unwrap() * fx + fx2) / (h * h),
+            )
+        })
+        .unzip();
+    (ndarray::Array1::from(grad), ndarray::Array1::from(diag))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ndarray;
+
     use ndarray::{array, Array1};
 
     const COMP_ACC: f64 = 1e-6;
+    const COMP_ACC_F32: f32 = 1e-3;
 
     fn f(x: &Array1<f64>) -> f64 {
         x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
@@ -198,7 +592,7 @@ mod tests {
 
     #[test]
     fn test_forward_hessian_ndarray_f64() {
-        let hessian = forward_hessian_ndarray_f64(&x(), &g);
+        let hessian = forward_hessian_ndarray(&x(), &g);
         let res = res1();
         // println!("hessian:\n{:#?}", hessian);
         // println!("diff:\n{:#?}", diff);
@@ -211,7 +605,7 @@ mod tests {
 
     #[test]
     fn test_central_hessian_ndarray_f64() {
-        let hessian = central_hessian_ndarray_f64(&x(), &g);
+        let hessian = central_hessian_ndarray(&x(), &g);
         let res = res1();
         // println!("hessian:\n{:#?}", hessian);
         // println!("diff:\n{:#?}", diff);
@@ -224,7 +618,7 @@ mod tests {
 
     #[test]
     fn test_forward_hessian_vec_prod_ndarray_f64() {
-        let hessian = forward_hessian_vec_prod_ndarray_f64(&x(), &g, &p());
+        let hessian = forward_hessian_vec_prod_ndarray(&x(), &g, &p());
         let res = res2();
         // println!("hessian:\n{:#?}", hessian);
         // println!("diff:\n{:#?}", diff);
@@ -235,7 +629,7 @@ mod tests {
 
     #[test]
     fn test_central_hessian_vec_prod_ndarray_f64() {
-        let hessian = central_hessian_vec_prod_ndarray_f64(&x(), &g, &p());
+        let hessian = central_hessian_vec_prod_ndarray(&x(), &g, &p());
         let res = res2();
         // println!("hessian:\n{:#?}", hessian);
         // println!("diff:\n{:#?}", diff);
@@ -244,9 +638,86 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_forward_hessian_vec_prod_nograd_ndarray_f64() {
+        let f = crate::CountingFn::new(f);
+        let hessian = forward_hessian_vec_prod_nograd_ndarray(&x(), &|x| f.call(x), &p());
+        let res = res2();
+        // the stencil is only first-order accurate
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < 1e-3)
+        }
+        // 2*n + 2
+        assert_eq!(f.count(), 10);
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_nograd_ndarray_f64() {
+        let hessian = central_hessian_vec_prod_nograd_ndarray(&x(), &f, &p());
+        let res = res2();
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
     #[test]
     fn test_forward_hessian_nograd_ndarray_f64() {
-        let hessian = forward_hessian_nograd_ndarray_f64(&x(), &f);
+        let hessian = forward_hessian_nograd_ndarray(&x(), &f);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_packed_ndarray_f64() {
+        let dense = forward_hessian_nograd_ndarray(&x(), &f);
+        let packed = forward_hessian_nograd_packed_ndarray(&x(), &f);
+        assert_eq!(packed.data.len(), 10);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_eq!(dense[(i, j)], packed.at(i, j));
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_blocks_ndarray_f64() {
+        let p = ndarray::Array1::from(vec![2.0f64, 3.0, 4.0, 5.0]);
+        let dense = forward_hessian_nograd_ndarray(&p, &f);
+        let mut rows: Vec<Vec<f64>> = vec![];
+        forward_hessian_nograd_blocks_ndarray(&p, &f, 3, &mut |start, block| {
+            assert_eq!(start, rows.len());
+            rows.extend_from_slice(block);
+        });
+        assert_eq!(rows.len(), 4);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_eq!(rows[i][j], dense[(i, j)]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_grad_and_hessian_nograd_ndarray_f64() {
+        let (grad, hessian) = forward_grad_and_hessian_nograd_ndarray(&x(), &f);
+        let res_grad = g(&x());
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            assert!((res_grad[i] - grad[i]).abs() < COMP_ACC);
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_ndarray_f64() {
+        let hessian = central_hessian_nograd_ndarray(&x(), &f);
         let res = res1();
         // println!("hessian:\n{:#?}", hessian);
         for i in 0..4 {
@@ -259,7 +730,21 @@ mod tests {
     #[test]
     fn test_forward_hessian_nograd_sparse_ndarray_f64() {
         let indices = vec![[1, 1], [2, 3], [3, 3]];
-        let hessian = forward_hessian_nograd_sparse_ndarray_f64(&x(), &f, indices);
+        let hessian = forward_hessian_nograd_sparse_ndarray(&x(), &f, indices);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_sparse_ndarray_f64() {
+        let indices = vec![[1, 1], [2, 3], [3, 3]];
+        let hessian = central_hessian_nograd_sparse_ndarray(&x(), &f, indices);
         let res = res1();
         // println!("hessian:\n{:#?}", hessian);
         // println!("diff:\n{:#?}", diff);
@@ -269,4 +754,84 @@ mod tests {
             }
         }
     }
+
+    fn f_f32(x: &Array1<f32>) -> f32 {
+        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
+    }
+
+    fn g_f32(x: &Array1<f32>) -> Array1<f32> {
+        array![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    }
+
+    fn x_f32() -> Array1<f32> {
+        array![1.0f32, 1.0, 1.0, 1.0]
+    }
+
+    #[test]
+    fn test_forward_hessian_ndarray_f32() {
+        let hessian = forward_hessian_ndarray(&x_f32(), &g_f32);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] as f32 - hessian[(i, j)]).abs() < COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_ndarray_f32() {
+        let hessian = central_hessian_ndarray(&x_f32(), &g_f32);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] as f32 - hessian[(i, j)]).abs() < COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_ndarray_f32() {
+        let hessian = forward_hessian_nograd_ndarray(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] as f32 - hessian[(i, j)]).abs() < COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_diagonal_nograd_ndarray_f64() {
+        let q = |x: &Array1<f64>| 3.0 * x[0].powi(2) + x[0] * x[1] + 0.5 * x[1].powi(2) + x[2];
+        let p = array![1.0f64, -2.0, 3.0];
+        let diag = forward_hessian_diagonal_nograd_ndarray(&p, &q);
+        let res = [6.0f64, 1.0, 0.0];
+
+        for i in 0..3 {
+            assert!((res[i] - diag[i]).abs() < COMP_ACC)
+        }
+
+        let diag = forward_hessian_diagonal_nograd_ndarray(&x(), &f);
+        let res = res1();
+        for i in 0..4 {
+            assert!((res[i][i] - diag[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_hessian_ndarray_f64_empty() {
+        let x: Array1<f64> = Array1::zeros(0);
+        let f = |_: &Array1<f64>| 1.0f64;
+        let g = |_: &Array1<f64>| Array1::zeros(0);
+        assert_eq!(forward_hessian_ndarray(&x, &g).dim(), (0, 0));
+        assert_eq!(central_hessian_ndarray(&x, &g).dim(), (0, 0));
+        assert_eq!(forward_hessian_vec_prod_ndarray(&x, &g, &x).len(), 0);
+        assert_eq!(central_hessian_vec_prod_ndarray(&x, &g, &x).len(), 0);
+        assert_eq!(forward_hessian_nograd_ndarray(&x, &f).dim(), (0, 0));
+        assert_eq!(central_hessian_nograd_ndarray(&x, &f).dim(), (0, 0));
+        assert_eq!(forward_hessian_diagonal_nograd_ndarray(&x, &f).len(), 0);
+    }
 }
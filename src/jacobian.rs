@@ -5,90 +5,706 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use crate::error::check_output_len;
 use crate::pert::*;
 use crate::utils::*;
-use crate::EPS_F64;
+use crate::{FiniteDiffError, FloatEps};
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+use core::ops::{Index, Range};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
-pub fn forward_jacobian_vec_f64(x: &Vec<f64>, fs: &dyn Fn(&Vec<f64>) -> Vec<f64>) -> Vec<Vec<f64>> {
-    let fx = (fs)(&x);
+/// Sparse Jacobian in compressed sparse row (CSR) format.
+///
+/// The layout follows the dense Jacobians returned by `FiniteDiff`: row `i` corresponds to the
+/// `i`th parameter and column `j` to the `j`th function index. The column indices of row `i` are
+/// stored in `col_idx[row_ptr[i]..row_ptr[i + 1]]` in ascending order, and the corresponding
+/// values at the same positions in `values`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseJacobian<F> {
+    /// Number of rows (parameters)
+    pub n_rows: usize,
+    /// Number of columns (function indices)
+    pub n_cols: usize,
+    /// Offsets of the rows into `col_idx` and `values`, of length `n_rows + 1`
+    pub row_ptr: Vec<usize>,
+    /// Column indices of the stored entries
+    pub col_idx: Vec<usize>,
+    /// Values of the stored entries
+    pub values: Vec<F>,
+}
+
+impl<F: FloatEps> SparseJacobian<F> {
+    /// Assembles the CSR structure from the entries of each row. If a column appears several
+    /// times within a row, the last value is kept.
+    pub(crate) fn from_rows(n_cols: usize, rows: Vec<Vec<(usize, F)>>) -> Self {
+        let mut row_ptr = Vec::with_capacity(rows.len() + 1);
+        let mut col_idx = vec![];
+        let mut values = vec![];
+        row_ptr.push(0);
+        for mut row in rows.into_iter() {
+            // stable sort, therefore the last of several equal columns stays last
+            row.sort_by_key(|&(j, _)| j);
+            for (k, &(j, v)) in row.iter().enumerate() {
+                if k + 1 < row.len() && row[k + 1].0 == j {
+                    continue;
+                }
+                col_idx.push(j);
+                values.push(v);
+            }
+            row_ptr.push(col_idx.len());
+        }
+        SparseJacobian {
+            n_rows: row_ptr.len() - 1,
+            n_cols,
+            row_ptr,
+            col_idx,
+            values,
+        }
+    }
+
+    /// Number of stored entries
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Value at position `(i, j)`. Entries which are not stored are zero.
+    pub fn get(&self, i: usize, j: usize) -> F {
+        let range = self.row_ptr[i]..self.row_ptr[i + 1];
+        match self.col_idx[range.clone()].binary_search(&j) {
+            Ok(k) => self.values[range.start + k],
+            Err(_) => F::zero(),
+        }
+    }
+
+    /// Converts to the dense layout used by `forward_jacobian_pert`
+    pub fn to_dense(&self) -> Vec<Vec<F>> {
+        let mut out = vec![vec![F::zero(); self.n_cols]; self.n_rows];
+        for i in 0..self.n_rows {
+            for k in self.row_ptr[i]..self.row_ptr[i + 1] {
+                out[i][self.col_idx[k]] = self.values[k];
+            }
+        }
+        out
+    }
+}
+
+/// Banded Jacobian in compact storage.
+///
+/// The `j`th function only depends on the parameters `j - kl..=j + ku`, i.e. `kl` and `ku` are the
+/// lower and upper bandwidths of the matrix of partial derivatives `df_j/dx_i`. In the layout of
+/// the dense Jacobians returned by `FiniteDiff` (row `i` is the `i`th parameter, column `j` the
+/// `j`th function index), row `i` therefore only has entries in the columns `i - ku..=i + kl`.
+/// These `kl + ku + 1` values are stored consecutively per row in `data`, where position `(i, j)`
+/// is found at index `i * (kl + ku + 1) + j + ku - i`. Band positions outside of the matrix are
+/// zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandedJacobian<F> {
+    /// Number of rows (parameters)
+    pub n_rows: usize,
+    /// Number of columns (function indices)
+    pub n_cols: usize,
+    /// Lower bandwidth
+    pub kl: usize,
+    /// Upper bandwidth
+    pub ku: usize,
+    /// Band of each row, of length `n_rows * (kl + ku + 1)`
+    pub data: Vec<F>,
+}
+
+impl<F: FloatEps> BandedJacobian<F> {
+    pub(crate) fn zeros(n_rows: usize, n_cols: usize, kl: usize, ku: usize) -> Self {
+        BandedJacobian {
+            n_rows,
+            n_cols,
+            kl,
+            ku,
+            data: vec![F::zero(); n_rows * (kl + ku + 1)],
+        }
+    }
+
+    /// Number of stored values per row, `kl + ku + 1`
+    pub fn width(&self) -> usize {
+        self.kl + self.ku + 1
+    }
+
+    /// Columns of row `i` which lie inside the band and inside the matrix
+    pub fn band(&self, i: usize) -> Range<usize> {
+        i.saturating_sub(self.ku)..(i + self.kl + 1).min(self.n_cols)
+    }
+
+    #[inline(always)]
+    fn idx(&self, i: usize, j: usize) -> usize {
+        i * self.width() + j + self.ku - i
+    }
+
+    /// Value at position `(i, j)`. Entries outside of the band are zero.
+    pub fn get(&self, i: usize, j: usize) -> F {
+        if self.band(i).contains(&j) {
+            self.data[self.idx(i, j)]
+        } else {
+            F::zero()
+        }
+    }
+
+    /// Converts to the dense layout used by `forward_jacobian`
+    pub fn to_dense(&self) -> Vec<Vec<F>> {
+        let mut out = vec![vec![F::zero(); self.n_cols]; self.n_rows];
+        for (i, row) in out.iter_mut().enumerate() {
+            for j in self.band(i) {
+                row[j] = self.data[self.idx(i, j)];
+            }
+        }
+        out
+    }
+
+    /// Fills the band of the parameters `group, group + width, group + 2 * width, ...` from the
+    /// function values `fx1` obtained by perturbing all of them at once, the `i`th parameter by
+    /// `h(i)`. These parameters influence disjoint sets of functions, which is the grouping of
+    /// Curtis, Powell and Reid.
+    pub(crate) fn set_group<T: Index<usize, Output = F>>(
+        &mut self,
+        group: usize,
+        fx: &T,
+        fx1: &T,
+        h: impl Fn(usize) -> F,
+    ) {
+        for i in (group..self.n_rows).step_by(self.width()) {
+            let h = h(i);
+            for j in self.band(i) {
+                let k = self.idx(i, j);
+                self.data[k] = (fx1[j] - fx[j]) / h;
+            }
+        }
+    }
+}
+
+pub fn forward_jacobian_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+) -> Vec<Vec<F>> {
+    forward_jacobian_asref(x, fs)
+}
+
+/// Same as `forward_jacobian_vec`, but returns an error if perturbing a parameter changes the
+/// number of function values instead of silently ignoring the surplus values.
+pub fn try_forward_jacobian_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+) -> Result<Vec<Vec<F>>, FiniteDiffError> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let mut out = Vec::with_capacity(x.len());
+    for i in 0..x.len() {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+        check_output_len(i, fx.len(), fx1.len())?;
+        out.push(
+            fx1.iter()
+                .zip(fx.iter())
+                .map(|(&a, &b)| (a - b) / h)
+                .collect(),
+        );
+    }
+    Ok(out)
+}
+
+/// Forward difference Jacobian of a function whose values are of any type which can be viewed as
+/// a slice, for instance `Box<[F]>`, a fixed size array or a small vector of another crate.
+///
+/// This avoids collecting the values into a `Vec` if this is not their natural type. Note that
+/// `VecDeque` is not contiguous and therefore not supported. Row `i` of the result contains the
+/// derivatives of all outputs with respect to the `i`th parameter, as in
+/// `FiniteDiff::forward_jacobian`.
+/// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+///
+/// ```rust
+/// use finitediff::forward_jacobian_asref;
+///
+/// let fs = |x: &Vec<f64>| vec![x[0] * x[1], x[1].powi(2)].into_boxed_slice();
+/// let jacobian = forward_jacobian_asref(&vec![2.0f64, 3.0], &fs);
+/// # assert!((jacobian[0][0] - 3.0).abs() < 1e-6);
+/// # assert!((jacobian[1][1] - 6.0).abs() < 1e-6);
+/// ```
+pub fn forward_jacobian_asref<F: FloatEps, O: AsRef<[F]>>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> O,
+) -> Vec<Vec<F>> {
+    let fx = (fs)(x);
+    let mut out = vec![vec![F::zero(); fx.as_ref().len()]; x.len()];
+    forward_jacobian_fx_into_vec(x, fs, fx.as_ref(), &mut out);
+    out
+}
+
+/// Forward difference Jacobian of a function with a fixed number `M` of outputs.
+///
+/// The values of `fs` are returned as arrays and therefore do not require a heap allocation per
+/// evaluation. Row `i` of the result contains the derivatives of all outputs with respect to the
+/// `i`th parameter, as in `FiniteDiff::forward_jacobian`.
+/// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+///
+/// ```rust
+/// use finitediff::forward_jacobian_arrayout;
+///
+/// let fs = |x: &Vec<f64>| [x[0] * x[1], x[1].powi(2), x[0]];
+/// let jacobian = forward_jacobian_arrayout(&vec![2.0f64, 3.0], &fs);
+/// # assert!((jacobian[0][0] - 3.0).abs() < 1e-6);
+/// # assert!((jacobian[1][1] - 6.0).abs() < 1e-6);
+/// # assert!((jacobian[0][2] - 1.0).abs() < 1e-6);
+/// ```
+pub fn forward_jacobian_arrayout<F: FloatEps, const M: usize>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> [F; M],
+) -> Vec<[F; M]> {
+    let fx = (fs)(x);
     let mut xt = x.clone();
     (0..x.len())
         .map(|i| {
-            let fx1 = mod_and_calc_vec_f64(&mut xt, fs, i, EPS_F64.sqrt());
+            let h = step_for(x[i], F::EPS);
+            let mut fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+            for (a, &b) in fx1.iter_mut().zip(fx.iter()) {
+                *a = (*a - b) / h;
+            }
+            fx1
+        })
+        .collect()
+}
+
+pub fn forward_jacobian_into_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    out: &mut Vec<Vec<F>>,
+) {
+    let fx = (fs)(x);
+    forward_jacobian_fx_into_vec(x, fs, &fx, out);
+}
+
+fn forward_jacobian_fx_into_vec<F: FloatEps, O: AsRef<[F]>>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> O,
+    fx: &[F],
+    out: &mut Vec<Vec<F>>,
+) {
+    assert_eq!(x.len(), out.len());
+    let mut xt = x.clone();
+    for (i, row) in out.iter_mut().enumerate() {
+        assert_eq!(fx.len(), row.len());
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+        for (o, (&a, &b)) in row.iter_mut().zip(fx1.as_ref().iter().zip(fx.iter())) {
+            *o = (a - b) / h;
+        }
+    }
+}
+
+/// Same as `forward_jacobian_vec`, but returns the transposed Jacobian with one row per function
+/// value. The derivatives with respect to the `i`th parameter are written to column `i`.
+pub fn forward_jacobian_transposed_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+) -> Vec<Vec<F>> {
+    let fx = (fs)(x);
+    let n = x.len();
+    let mut out = vec![vec![F::zero(); n]; fx.len()];
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+        for (row, (&a, &b)) in out.iter_mut().zip(fx1.iter().zip(fx.iter())) {
+            row[i] = (a - b) / h;
+        }
+    }
+    out
+}
+
+/// Same as `forward_jacobian_vec`, but calls `on_col(i)` once the derivatives with respect to the
+/// `i`th parameter are computed. Returns `None` as soon as `on_col` returns `false`.
+pub fn forward_jacobian_with_progress_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    on_col: &mut dyn FnMut(usize) -> bool,
+) -> Option<Vec<Vec<F>>> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let mut out = Vec::with_capacity(x.len());
+    for i in 0..x.len() {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+        out.push(
             fx1.iter()
                 .zip(fx.iter())
-                .map(|(a, b)| (a - b) / EPS_F64.sqrt())
-                .collect::<Vec<f64>>()
+                .map(|(&a, &b)| (a - b) / h)
+                .collect(),
+        );
+        if !on_col(i) {
+            return None;
+        }
+    }
+    Some(out)
+}
+
+/// Same as `forward_jacobian_vec`, but the row of the `i`th parameter is set to exactly zero if
+/// perturbing it changes none of the function values by more than `zero_tol`.
+pub fn forward_jacobian_thresholded_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    zero_tol: F,
+) -> Vec<Vec<F>> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+            let max_change = fx1
+                .iter()
+                .zip(fx.iter())
+                .fold(F::zero(), |acc, (&a, &b)| acc.max((a - b).abs()));
+            if max_change <= zero_tol {
+                return vec![F::zero(); fx.len()];
+            }
+            fx1.iter()
+                .zip(fx.iter())
+                .map(|(&a, &b)| (a - b) / h)
+                .collect()
         })
         .collect()
 }
 
-pub fn central_jacobian_vec_f64(x: &Vec<f64>, fs: &dyn Fn(&Vec<f64>) -> Vec<f64>) -> Vec<Vec<f64>> {
+/// Same as `forward_jacobian_vec`, but only the rows of the parameters which moved by more than
+/// `step_tol` since `x_prev` are recomputed. The remaining rows are copied from `prev`, the
+/// Jacobian at `x_prev`. If no parameter moved, `fs` is not evaluated at all.
+///
+/// # Panics
+///
+/// Panics if `x_prev` or `prev` do not have one entry or row per parameter.
+pub fn forward_jacobian_update_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    prev: &Vec<Vec<F>>,
+    x_prev: &Vec<F>,
+    step_tol: F,
+) -> Vec<Vec<F>> {
+    assert_eq!(x.len(), x_prev.len());
+    assert_eq!(x.len(), prev.len());
+    let moved: Vec<bool> = x
+        .iter()
+        .zip(x_prev.iter())
+        .map(|(&a, &b)| (a - b).abs() > step_tol)
+        .collect();
+    if !moved.contains(&true) {
+        return prev.clone();
+    }
+    let fx = (fs)(x);
     let mut xt = x.clone();
     (0..x.len())
         .map(|i| {
-            let fx1 = mod_and_calc_vec_f64(&mut xt, fs, i, EPS_F64.sqrt());
-            let fx2 = mod_and_calc_vec_f64(&mut xt, fs, i, -EPS_F64.sqrt());
+            if !moved[i] {
+                return prev[i].clone();
+            }
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+            fx1.iter()
+                .zip(fx.iter())
+                .map(|(&a, &b)| (a - b) / h)
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the points at which `forward_jacobian_vec` evaluates the function.
+///
+/// The first point is `x` itself, followed by `x + h_i * e_i` for `i = 0, ..., n-1`, where
+/// `h_i = sqrt(EPS) * max(|x_i|, 1)`.
+/// Evaluating the function at these points, in this order, yields the values expected by
+/// `forward_jacobian_from_values_vec`.
+pub fn forward_jacobian_points_vec<F: FloatEps>(x: &Vec<F>) -> Vec<Vec<F>> {
+    let mut points = Vec::with_capacity(x.len() + 1);
+    points.push(x.clone());
+    for i in 0..x.len() {
+        let mut xt = x.clone();
+        xt[i] += step_for(x[i], F::EPS);
+        points.push(xt);
+    }
+    points
+}
+
+/// Assembles the forward difference Jacobian from the function values at the points returned by
+/// `forward_jacobian_points_vec`, in the same order.
+///
+/// Panics if the number of values is not `n+1` or if the values differ in length.
+pub fn forward_jacobian_from_values_vec<F: FloatEps>(x: &Vec<F>, values: &[Vec<F>]) -> Vec<Vec<F>> {
+    assert_eq!(
+        values.len(),
+        x.len() + 1,
+        "forward_jacobian_from_values: expected one value per evaluation point"
+    );
+    let fx = &values[0];
+    values[1..]
+        .iter()
+        .zip(x.iter())
+        .map(|(fx1, &xi)| {
+            assert_eq!(fx1.len(), fx.len());
+            let h = step_for(xi, F::EPS);
+            fx1.iter()
+                .zip(fx.iter())
+                .map(|(&a, &b)| (a - b) / h)
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(feature = "rayon")]
+pub fn forward_jacobian_par_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &(dyn Fn(&Vec<F>) -> Vec<F> + Sync),
+) -> Vec<Vec<F>> {
+    let fx = (fs)(x);
+    (0..x.len())
+        .into_par_iter()
+        .map_init(
+            || x.clone(),
+            |xt, i| {
+                let h = step_for(x[i], F::EPS);
+                let fx1 = mod_and_calc_vec(xt, fs, i, h);
+                fx1.iter()
+                    .zip(fx.iter())
+                    .map(|(&a, &b)| (a - b) / h)
+                    .collect::<Vec<F>>()
+            },
+        )
+        .collect()
+}
+
+/// Diagonal of the forward difference Jacobian of an elementwise function.
+pub fn forward_jacobian_diagonal_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+) -> Vec<F> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+            (fx1[i] - fx[i]) / h
+        })
+        .collect()
+}
+
+pub fn backward_jacobian_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+) -> Vec<Vec<F>> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, fs, i, -h);
+            fx1.iter()
+                .zip(fx.iter())
+                .map(|(&a, &b)| (b - a) / h)
+                .collect::<Vec<F>>()
+        })
+        .collect()
+}
+
+pub fn central_jacobian_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+) -> Vec<Vec<F>> {
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+            let fx2 = mod_and_calc_vec(&mut xt, fs, i, -h);
+            fx1.iter()
+                .zip(fx2.iter())
+                .map(|(&a, &b)| (a - b) / (F::from(2.0).unwrap() * h))
+                .collect::<Vec<F>>()
+        })
+        .collect()
+}
+
+pub fn central_jacobian_into_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    out: &mut Vec<Vec<F>>,
+) {
+    assert_eq!(x.len(), out.len());
+    let mut xt = x.clone();
+    for (i, row) in out.iter_mut().enumerate() {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+        let fx2 = mod_and_calc_vec(&mut xt, fs, i, -h);
+        assert_eq!(fx1.len(), row.len());
+        for (o, (&a, &b)) in row.iter_mut().zip(fx1.iter().zip(fx2.iter())) {
+            *o = (a - b) / (F::from(2.0).unwrap() * h);
+        }
+    }
+}
+
+pub fn forward_jacobian_scaled_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    steps: &Vec<F>,
+) -> Vec<Vec<F>> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_vec(&mut xt, fs, i, steps[i]);
+            fx1.iter()
+                .zip(fx.iter())
+                .map(|(&a, &b)| (a - b) / steps[i])
+                .collect::<Vec<F>>()
+        })
+        .collect()
+}
+
+pub fn central_jacobian_scaled_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    steps: &Vec<F>,
+) -> Vec<Vec<F>> {
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_vec(&mut xt, fs, i, steps[i]);
+            let fx2 = mod_and_calc_vec(&mut xt, fs, i, -steps[i]);
             fx1.iter()
                 .zip(fx2.iter())
-                .map(|(a, b)| (a - b) / (2.0 * EPS_F64.sqrt()))
-                .collect::<Vec<f64>>()
+                .map(|(&a, &b)| (a - b) / (F::from(2.0).unwrap() * steps[i]))
+                .collect::<Vec<F>>()
         })
         .collect()
 }
 
-pub fn forward_jacobian_vec_prod_vec_f64(
-    x: &Vec<f64>,
-    fs: &dyn Fn(&Vec<f64>) -> Vec<f64>,
-    p: &Vec<f64>,
-) -> Vec<f64> {
-    let fx = (fs)(&x);
+pub fn forward_jacobian_vec_prod_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    p: &Vec<F>,
+) -> Vec<F> {
+    let fx = (fs)(x);
+    forward_jacobian_vec_prod_at_vec(x, fs, p, &fx)
+}
+
+/// Same as `forward_jacobian_vec_prod_vec`, but `p` is scaled to unit length for the difference
+/// quotient and the result is scaled back by `|p|`. A zero vector `p` yields a zero product.
+pub fn forward_jacobian_vec_prod_normalized_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    p: &Vec<F>,
+) -> Vec<F> {
+    let norm = p.iter().fold(F::zero(), |acc, &pi| acc + pi * pi).sqrt();
+    if norm == F::zero() {
+        return forward_jacobian_vec_prod_vec(x, fs, p);
+    }
+    let p_hat = p.iter().map(|&pi| pi / norm).collect();
+    forward_jacobian_vec_prod_vec(x, fs, &p_hat)
+        .iter()
+        .map(|&v| v * norm)
+        .collect()
+}
+
+pub fn forward_jacobian_vec_prod_at_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    p: &Vec<F>,
+    fx: &Vec<F>,
+) -> Vec<F> {
     let x1 = x
         .iter()
         .zip(p.iter())
-        .map(|(xi, pi)| xi + EPS_F64.sqrt() * pi)
+        .map(|(&xi, &pi)| xi + F::EPS.sqrt() * pi)
         .collect();
     let fx1 = (fs)(&x1);
     fx1.iter()
         .zip(fx.iter())
-        .map(|(a, b)| (a - b) / EPS_F64.sqrt())
-        .collect::<Vec<f64>>()
+        .map(|(&a, &b)| (a - b) / F::EPS.sqrt())
+        .collect::<Vec<F>>()
 }
 
-pub fn central_jacobian_vec_prod_vec_f64(
-    x: &Vec<f64>,
-    fs: &dyn Fn(&Vec<f64>) -> Vec<f64>,
-    p: &Vec<f64>,
-) -> Vec<f64> {
+pub fn central_jacobian_vec_prod_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    p: &Vec<F>,
+) -> Vec<F> {
     let x1 = x
         .iter()
         .zip(p.iter())
-        .map(|(xi, pi)| xi + EPS_F64.sqrt() * pi)
+        .map(|(&xi, &pi)| xi + F::EPS.sqrt() * pi)
         .collect();
     let x2 = x
         .iter()
         .zip(p.iter())
-        .map(|(xi, pi)| xi - EPS_F64.sqrt() * pi)
+        .map(|(&xi, &pi)| xi - F::EPS.sqrt() * pi)
         .collect();
     let fx1 = (fs)(&x1);
     let fx2 = (fs)(&x2);
     fx1.iter()
         .zip(fx2.iter())
-        .map(|(a, b)| (a - b) / (2.0 * EPS_F64.sqrt()))
-        .collect::<Vec<f64>>()
+        .map(|(&a, &b)| (a - b) / (F::from(2.0).unwrap() * F::EPS.sqrt()))
+        .collect::<Vec<F>>()
+}
+
+pub fn forward_vjp_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    v: &Vec<F>,
+) -> Vec<F> {
+    // Row `i` of the Jacobian holds the derivatives with respect to `x_i`, therefore `J^T*v` is
+    // the product of each row with `v`. The products may be large and of opposite sign, therefore
+    // they are summed with compensation.
+    forward_jacobian_vec(x, fs)
+        .iter()
+        .map(|row| compensated_sum(row.iter().zip(v.iter()).map(|(&a, &b)| a * b)))
+        .collect()
+}
+
+pub fn forward_jacobian_pert_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    pert: &PerturbationVectors,
+) -> Vec<Vec<F>> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let mut out = vec![vec![F::zero(); fx.len()]; x.len()];
+    for pert_item in pert.iter() {
+        for j in pert_item.x_idx.iter() {
+            xt[*j] += step_for(x[*j], F::EPS);
+        }
+
+        let fx1 = (fs)(&xt);
+
+        for j in pert_item.x_idx.iter() {
+            xt[*j] = x[*j];
+        }
+
+        for (k, x_idx) in pert_item.x_idx.iter().enumerate() {
+            let h = step_for(x[*x_idx], F::EPS);
+            for j in pert_item.r_idx[k].iter() {
+                out[*x_idx][*j] = (fx1[*j] - fx[*j]) / h;
+            }
+        }
+    }
+    out
 }
 
-pub fn forward_jacobian_pert_vec_f64(
-    x: &Vec<f64>,
-    fs: &dyn Fn(&Vec<f64>) -> Vec<f64>,
+pub fn forward_jacobian_pert_sparse_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
     pert: &PerturbationVectors,
-) -> Vec<Vec<f64>> {
-    let fx = (fs)(&x);
+) -> SparseJacobian<F> {
+    let fx = (fs)(x);
     let mut xt = x.clone();
-    let mut out = vec![vec![0.0; x.len()]; fx.len()];
+    let mut rows: Vec<Vec<(usize, F)>> = vec![vec![]; x.len()];
     for pert_item in pert.iter() {
         for j in pert_item.x_idx.iter() {
-            xt[*j] += EPS_F64.sqrt();
+            xt[*j] += step_for(x[*j], F::EPS);
         }
 
         let fx1 = (fs)(&xt);
@@ -98,30 +714,57 @@ pub fn forward_jacobian_pert_vec_f64(
         }
 
         for (k, x_idx) in pert_item.x_idx.iter().enumerate() {
+            let h = step_for(x[*x_idx], F::EPS);
             for j in pert_item.r_idx[k].iter() {
-                out[*x_idx][*j] = (fx1[*j] - fx[*j]) / EPS_F64.sqrt();
+                rows[*x_idx].push((*j, (fx1[*j] - fx[*j]) / h));
             }
         }
     }
+    SparseJacobian::from_rows(fx.len(), rows)
+}
+
+pub fn forward_jacobian_banded_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    kl: usize,
+    ku: usize,
+) -> BandedJacobian<F> {
+    let fx = (fs)(x);
+    let mut out = BandedJacobian::zeros(x.len(), fx.len(), kl, ku);
+    let mut xt = x.clone();
+    let width = out.width();
+    for group in 0..width.min(x.len()) {
+        for i in (group..x.len()).step_by(width) {
+            xt[i] += step_for(x[i], F::EPS);
+        }
+
+        let fx1 = (fs)(&xt);
+
+        for i in (group..x.len()).step_by(width) {
+            xt[i] = x[i];
+        }
+
+        out.set_group(group, &fx, &fx1, |i| step_for(x[i], F::EPS));
+    }
     out
 }
 
-pub fn central_jacobian_pert_vec_f64(
-    x: &Vec<f64>,
-    fs: &dyn Fn(&Vec<f64>) -> Vec<f64>,
+pub fn central_jacobian_pert_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
     pert: &PerturbationVectors,
-) -> Vec<Vec<f64>> {
+) -> Vec<Vec<F>> {
     let mut out = vec![];
     let mut xt = x.clone();
     for (i, pert_item) in pert.iter().enumerate() {
         for j in pert_item.x_idx.iter() {
-            xt[*j] += EPS_F64.sqrt();
+            xt[*j] += step_for(x[*j], F::EPS);
         }
 
         let fx1 = (fs)(&xt);
 
         for j in pert_item.x_idx.iter() {
-            xt[*j] = x[*j] - EPS_F64.sqrt();
+            xt[*j] = x[*j] - step_for(x[*j], F::EPS);
         }
 
         let fx2 = (fs)(&xt);
@@ -131,23 +774,78 @@ pub fn central_jacobian_pert_vec_f64(
         }
 
         if i == 0 {
-            out = vec![vec![0.0; x.len()]; fx1.len()];
+            out = vec![vec![F::zero(); fx1.len()]; x.len()];
         }
 
         for (k, x_idx) in pert_item.x_idx.iter().enumerate() {
+            let h = step_for(x[*x_idx], F::EPS);
             for j in pert_item.r_idx[k].iter() {
-                out[*x_idx][*j] = (fx1[*j] - fx2[*j]) / (2.0 * EPS_F64.sqrt());
+                out[*x_idx][*j] = (fx1[*j] - fx2[*j]) / (F::from(2.0).unwrap() * h);
             }
         }
     }
     out
 }
 
+pub fn central_jacobian_5_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+) -> Vec<Vec<F>> {
+    central_jacobian_5_scaled_vec(x, fs, &vec![eps_5::<F>(); x.len()])
+}
+
+pub fn central_jacobian_5_scaled_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    steps: &Vec<F>,
+) -> Vec<Vec<F>> {
+    let two = F::from(2.0).unwrap();
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = steps[i];
+            let fx1 = mod_and_calc_vec(&mut xt, fs, i, two * h);
+            let fx2 = mod_and_calc_vec(&mut xt, fs, i, h);
+            let fx3 = mod_and_calc_vec(&mut xt, fs, i, -h);
+            let fx4 = mod_and_calc_vec(&mut xt, fs, i, -two * h);
+            (0..fx1.len())
+                .map(|j| {
+                    (-fx1[j] + F::from(8.0).unwrap() * (fx2[j] - fx3[j]) + fx4[j])
+                        / (F::from(12.0).unwrap() * h)
+                })
+                .collect::<Vec<F>>()
+        })
+        .collect()
+}
+
+pub fn forward_jacobian_2_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+) -> Vec<Vec<F>> {
+    let two = F::from(2.0).unwrap();
+    let h = eps_3::<F>();
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let fx1 = mod_and_calc_vec(&mut xt, fs, i, h);
+            let fx2 = mod_and_calc_vec(&mut xt, fs, i, two * h);
+            (0..fx.len())
+                .map(|j| {
+                    (F::from(4.0).unwrap() * fx1[j] - F::from(3.0).unwrap() * fx[j] - fx2[j])
+                        / (two * h)
+                })
+                .collect::<Vec<F>>()
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     const COMP_ACC: f64 = 1e-6;
+    const COMP_ACC_F32: f32 = 1e-3;
 
     fn f(x: &Vec<f64>) -> Vec<f64> {
         vec![
@@ -179,6 +877,10 @@ mod tests {
         vec![1.0f64, 1.0, 1.0, 1.0, 1.0, 1.0]
     }
 
+    fn steps() -> Vec<f64> {
+        vec![1e-8f64, 2e-8, 1e-8, 2e-8, 1e-8, 2e-8]
+    }
+
     fn p() -> Vec<f64> {
         vec![1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0]
     }
@@ -199,7 +901,111 @@ mod tests {
 
     #[test]
     fn test_forward_jacobian_vec_f64() {
-        let jacobian = forward_jacobian_vec_f64(&x(), &f);
+        let jacobian = forward_jacobian_vec(&x(), &f);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_transposed_vec_f64() {
+        let jacobian = forward_jacobian_vec(&x(), &f);
+        let jacobian_t = forward_jacobian_transposed_vec(&x(), &f);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert_eq!(jacobian_t[i][j], jacobian[j][i]);
+            }
+        }
+
+        // more function values than parameters
+        let fs = |x: &Vec<f64>| vec![x[0] * x[1], x[1].exp(), x[0].powi(2)];
+        let x = vec![1.0f64, 2.0];
+        let jacobian = forward_jacobian_vec(&x, &fs);
+        let jacobian_t = forward_jacobian_transposed_vec(&x, &fs);
+        assert_eq!((jacobian_t.len(), jacobian_t[0].len()), (3, 2));
+        for i in 0..3 {
+            for j in 0..2 {
+                assert_eq!(jacobian_t[i][j], jacobian[j][i]);
+            }
+        }
+    }
+
+    fn f_zero_rows(x: &Vec<f64>) -> Vec<f64> {
+        // `exp(x) * exp(-x)` is constant, but its evaluation is subject to rounding noise
+        vec![
+            x[0].powi(2) + x[1].exp() * (-x[1]).exp(),
+            x[3] * x[2].exp() * (-x[2]).exp(),
+            2.0 * x[3],
+        ]
+    }
+
+    #[test]
+    fn test_forward_jacobian_thresholded_vec_f64() {
+        let p = vec![1.0f64, 0.5, 1.7, 2.0];
+        let jacobian = forward_jacobian_thresholded_vec(&p, &f_zero_rows, 1e-12);
+        let res = [[2.0, 0.0, 0.0], [0.0; 3], [0.0; 3], [0.0, 1.0, 2.0]];
+        assert_eq!(jacobian[1], vec![0.0; 3]);
+        assert_eq!(jacobian[2], vec![0.0; 3]);
+        for i in 0..4 {
+            for j in 0..3 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_from_values_vec_f64() {
+        let p = x();
+        let points = forward_jacobian_points_vec(&p);
+        assert_eq!(points.len(), 7);
+        assert_eq!(points[0], p);
+        let values: Vec<Vec<f64>> = points.iter().map(f).collect();
+        let jacobian = forward_jacobian_from_values_vec(&p, &values);
+        assert_eq!(jacobian, forward_jacobian_vec(&p, &f));
+    }
+
+    #[test]
+    fn test_forward_jacobian_from_values_vec_rect() {
+        let p = vec![1.0f64, 2.0, 0.5];
+        let values: Vec<Vec<f64>> = forward_jacobian_points_vec(&p).iter().map(f_rect).collect();
+        let jacobian = forward_jacobian_from_values_vec(&p, &values);
+        assert_eq!(jacobian.len(), 3);
+        assert_eq!(jacobian[0].len(), 5);
+        assert_eq!(jacobian, forward_jacobian_vec(&p, &f_rect));
+    }
+
+    #[test]
+    #[should_panic(expected = "one value per evaluation point")]
+    fn test_forward_jacobian_from_values_vec_missing_value() {
+        let p = x();
+        let values: Vec<Vec<f64>> = forward_jacobian_points_vec(&p)
+            .iter()
+            .skip(1)
+            .map(f)
+            .collect();
+        forward_jacobian_from_values_vec(&p, &values);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_forward_jacobian_par_vec_f64() {
+        let jacobian = forward_jacobian_par_vec(&x(), &f);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_vec_f64() {
+        let jacobian = backward_jacobian_vec(&x(), &f);
         let res = res1();
         // println!("{:?}", jacobian);
         for i in 0..6 {
@@ -211,7 +1017,7 @@ mod tests {
 
     #[test]
     fn test_central_jacobian_vec_f64() {
-        let jacobian = central_jacobian_vec_f64(&x(), &f);
+        let jacobian = central_jacobian_vec(&x(), &f);
         let res = res1();
         // println!("{:?}", jacobian);
         for i in 0..6 {
@@ -221,9 +1027,86 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_forward_jacobian_into_vec_f64() {
+        let mut jacobian = vec![vec![0.0; 6]; 6];
+        let res = res1();
+        // the buffer is reused
+        for _ in 0..2 {
+            forward_jacobian_into_vec(&x(), &f, &mut jacobian);
+            for i in 0..6 {
+                for j in 0..6 {
+                    assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_into_vec_f64() {
+        let mut jacobian = vec![vec![0.0; 6]; 6];
+        central_jacobian_into_vec(&x(), &f, &mut jacobian);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_forward_jacobian_into_vec_f64_shape() {
+        let mut jacobian = vec![vec![0.0; 6]; 5];
+        forward_jacobian_into_vec(&x(), &f, &mut jacobian);
+    }
+
+    #[test]
+    fn test_forward_jacobian_scaled_vec_f64() {
+        let jacobian = forward_jacobian_scaled_vec(&x(), &f, &steps());
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_scaled_vec_f64() {
+        let jacobian = central_jacobian_scaled_vec(&x(), &f, &steps());
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_f64_large_parameters() {
+        let fs = |x: &Vec<f64>| vec![x[0].ln() + x[1], x[1].powi(2)];
+        let jacobian = forward_jacobian_vec(&vec![1e8f64, 1.0], &fs);
+        let res = [[1e-8f64, 0.0], [1.0, 2.0]];
+        // println!("{:?}", jacobian);
+        for i in 0..2 {
+            for j in 0..2 {
+                // entries of very different magnitudes, therefore compare relative errors
+                let scale = if res[i][j] == 0.0 {
+                    1.0
+                } else {
+                    res[i][j].abs()
+                };
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC * scale)
+            }
+        }
+    }
+
     #[test]
     fn test_forward_jacobian_vec_prod_vec_f64() {
-        let jacobian = forward_jacobian_vec_prod_vec_f64(&x(), &f, &p());
+        let jacobian = forward_jacobian_vec_prod_vec(&x(), &f, &p());
         let res = res2();
         // println!("{:?}", jacobian);
         // the accuracy for this is pretty bad!!
@@ -232,9 +1115,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_forward_jacobian_vec_prod_at_vec_f64() {
+        let fx = f(&x());
+        let evals = core::cell::Cell::new(0);
+        let f_counted = |x: &Vec<f64>| {
+            evals.set(evals.get() + 1);
+            f(x)
+        };
+        let jvp = forward_jacobian_vec_prod_at_vec(&x(), &f_counted, &p(), &fx);
+        assert_eq!(evals.get(), 1);
+        assert_eq!(jvp, forward_jacobian_vec_prod_vec(&x(), &f, &p()));
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_normalized_vec_f64() {
+        let fs = |x: &Vec<f64>| vec![x[0].powi(2) * x[1], x[1].exp(), x[0].sin()];
+        let x = vec![1.0f64, 2.0];
+        let p = vec![60.0f64, 80.0];
+        let res = [
+            2.0 * x[0] * x[1] * p[0] + x[0].powi(2) * p[1],
+            x[1].exp() * p[1],
+            x[0].cos() * p[0],
+        ];
+        let err = |jvp: &Vec<f64>| {
+            res.iter()
+                .zip(jvp.iter())
+                .map(|(r, j)| (r - j).abs())
+                .fold(0.0, f64::max)
+        };
+        let raw = err(&forward_jacobian_vec_prod_vec(&x, &fs, &p));
+        let normalized = err(&forward_jacobian_vec_prod_normalized_vec(&x, &fs, &p));
+        // the entries of J*p are of magnitude 100
+        assert!(normalized < 1e-4);
+        assert!(10.0 * normalized < raw);
+
+        let zero = vec![0.0f64, 0.0];
+        assert_eq!(
+            forward_jacobian_vec_prod_normalized_vec(&x, &fs, &zero),
+            vec![0.0; 3]
+        );
+    }
+
     #[test]
     fn test_central_jacobian_vec_prod_vec_f64() {
-        let jacobian = central_jacobian_vec_prod_vec_f64(&x(), &f, &p());
+        let jacobian = central_jacobian_vec_prod_vec(&x(), &f, &p());
         let res = res2();
         // println!("{:?}", jacobian);
         for i in 0..6 {
@@ -242,9 +1167,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_forward_vjp_vec_f64() {
+        let vjp = forward_vjp_vec(&x(), &f, &p());
+        // J^T*v computed by hand
+        let res = [-16.0f64, -2.0, 3.0, 8.0, 13.0, 84.0];
+        // println!("{:?}", vjp);
+        for i in 0..6 {
+            assert!((res[i] - vjp[i]).abs() < 11.0 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_vjp_vec_f64_cancellation() {
+        // all entries of the Jacobian are exactly one at x = 0
+        let fs = |x: &Vec<f64>| vec![x[0]; 4];
+        let v = vec![1e16, 1.0, -1e16, 1.0];
+        assert_eq!(forward_vjp_vec(&vec![0.0f64], &fs, &v), vec![2.0]);
+    }
+
     #[test]
     fn test_forward_jacobian_pert_vec_f64() {
-        let jacobian = forward_jacobian_pert_vec_f64(&x(), &f, &pert());
+        let jacobian = forward_jacobian_pert_vec(&x(), &f, &pert());
         let res = res1();
         // println!("jacobian:\n{:?}", jacobian);
         // println!("res:\n{:?}", res);
@@ -255,9 +1199,84 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_forward_jacobian_pert_sparse_vec_f64() {
+        let jacobian = forward_jacobian_pert_sparse_vec(&x(), &f, &pert());
+        let res = res1();
+        assert_eq!(jacobian.n_rows, 6);
+        assert_eq!(jacobian.n_cols, 6);
+        assert_eq!(jacobian.nnz(), 16);
+        assert_eq!(jacobian.row_ptr, vec![0, 2, 5, 8, 11, 14, 16]);
+        assert_eq!(&jacobian.col_idx[0..5], &[0, 1, 0, 1, 2]);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+        let dense = jacobian.to_dense();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - dense[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_sparse_jacobian_from_rows_duplicates() {
+        let jacobian =
+            SparseJacobian::from_rows(3, vec![vec![(2, 1.0f64), (0, 2.0), (2, 3.0)], vec![]]);
+        assert_eq!(jacobian.row_ptr, vec![0, 2, 2]);
+        assert_eq!(jacobian.col_idx, vec![0, 2]);
+        assert_eq!(jacobian.values, vec![2.0, 3.0]);
+        assert_eq!(jacobian.get(1, 1), 0.0);
+    }
+
+    #[test]
+    fn test_forward_jacobian_banded_vec_f64() {
+        let evals = core::cell::Cell::new(0);
+        let f_counted = |x: &Vec<f64>| {
+            evals.set(evals.get() + 1);
+            f(x)
+        };
+        let jacobian = forward_jacobian_banded_vec(&x(), &f_counted, 1, 1);
+        assert_eq!(evals.get(), 4);
+        assert_eq!(jacobian.width(), 3);
+        assert_eq!(jacobian.data.len(), 18);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+        let dense = jacobian.to_dense();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - dense[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_banded_jacobian_layout() {
+        let mut jacobian = BandedJacobian::zeros(3, 3, 1, 0);
+        jacobian.set_group(0, &vec![0.0f64; 3], &vec![1.0, 2.0, 3.0], |_| 1.0);
+        jacobian.set_group(1, &vec![0.0f64; 3], &vec![4.0, 5.0, 6.0], |_| 1.0);
+        assert_eq!(jacobian.band(0), 0..2);
+        assert_eq!(jacobian.band(2), 2..3);
+        assert_eq!(jacobian.data, vec![1.0, 2.0, 5.0, 6.0, 3.0, 0.0]);
+        assert_eq!(
+            jacobian.to_dense(),
+            vec![
+                vec![1.0, 2.0, 0.0],
+                vec![0.0, 5.0, 6.0],
+                vec![0.0, 0.0, 3.0]
+            ]
+        );
+    }
+
     #[test]
     fn test_central_jacobian_pert_vec_f64() {
-        let jacobian = central_jacobian_pert_vec_f64(&x(), &f, &pert());
+        let jacobian = central_jacobian_pert_vec(&x(), &f, &pert());
         let res = res1();
         // println!("jacobian:\n{:?}", jacobian);
         // println!("res:\n{:?}", res);
@@ -267,4 +1286,193 @@ mod tests {
             }
         }
     }
+
+    /// `R^3 -> R^5`
+    fn f_rect(x: &Vec<f64>) -> Vec<f64> {
+        vec![
+            x[0] * x[1],
+            x[1].powi(2),
+            x[2].exp(),
+            x[0] + x[2],
+            x[0] * x[1] * x[2],
+        ]
+    }
+
+    /// Jacobian of `f_rect` at `[1.0, 2.0, 0.5]` with one row per parameter
+    fn res_rect() -> Vec<Vec<f64>> {
+        vec![
+            vec![2.0, 0.0, 0.0, 1.0, 1.0],
+            vec![1.0, 4.0, 0.0, 0.0, 0.5],
+            vec![0.0, 0.0, 0.5f64.exp(), 1.0, 2.0],
+        ]
+    }
+
+    #[test]
+    fn test_jacobian_rectangular_vec_f64() {
+        let x = vec![1.0f64, 2.0, 0.5];
+        let pert = vec![
+            PerturbationVector::new().add(0, vec![0, 3, 4]),
+            PerturbationVector::new().add(1, vec![0, 1, 4]),
+            PerturbationVector::new().add(2, vec![2, 3, 4]),
+        ];
+        let mut into = vec![vec![0.0; 5]; 3];
+        forward_jacobian_into_vec(&x, &f_rect, &mut into);
+        let res = res_rect();
+        for jacobian in [
+            forward_jacobian_vec(&x, &f_rect),
+            backward_jacobian_vec(&x, &f_rect),
+            central_jacobian_vec(&x, &f_rect),
+            central_jacobian_5_vec(&x, &f_rect),
+            forward_jacobian_2_vec(&x, &f_rect),
+            forward_jacobian_pert_vec(&x, &f_rect, &pert),
+            central_jacobian_pert_vec(&x, &f_rect, &pert),
+            forward_jacobian_banded_vec(&x, &f_rect, 4, 4).to_dense(),
+            into,
+        ]
+        .iter()
+        {
+            assert_eq!(jacobian.len(), 3);
+            for i in 0..3 {
+                assert_eq!(jacobian[i].len(), 5);
+                for j in 0..5 {
+                    assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC);
+                }
+            }
+        }
+
+        // J(x)*p has the length of the output, J(x)^T*v the length of the parameters
+        let jvp = central_jacobian_vec_prod_vec(&x, &f_rect, &vec![1.0, 0.0, 1.0]);
+        let vjp = forward_vjp_vec(&x, &f_rect, &vec![1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(jvp.len(), 5);
+        assert_eq!(vjp.len(), 3);
+        for j in 0..5 {
+            assert!((res[0][j] + res[2][j] - jvp[j]).abs() < COMP_ACC);
+        }
+        for i in 0..3 {
+            assert!((res[i][0] + res[i][4] - vjp[i]).abs() < 10.0 * COMP_ACC);
+        }
+    }
+
+    fn f_f32(x: &Vec<f32>) -> Vec<f32> {
+        vec![
+            2.0 * (x[1].powi(3) - x[0].powi(2)),
+            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
+            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
+            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
+            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
+            3.0 * (x[5].powi(3) - x[4].powi(2)),
+        ]
+    }
+
+    fn x_f32() -> Vec<f32> {
+        vec![1.0f32, 1.0, 1.0, 1.0, 1.0, 1.0]
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_f32() {
+        let jacobian = forward_jacobian_vec(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] as f32 - jacobian[i][j]).abs() < 10.0 * COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_vec_f32() {
+        let jacobian = backward_jacobian_vec(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] as f32 - jacobian[i][j]).abs() < 10.0 * COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_vec_f32() {
+        let jacobian = central_jacobian_vec(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] as f32 - jacobian[i][j]).abs() < COMP_ACC_F32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_5_vec_f64() {
+        let jacobian = central_jacobian_5_vec(&x(), &f);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_2_vec_f64() {
+        let jacobian = forward_jacobian_2_vec(&x(), &f);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_f64_eval_count() {
+        let evals = core::cell::Cell::new(0);
+        let f_counted = |x: &Vec<f64>| {
+            evals.set(evals.get() + 1);
+            f(x)
+        };
+        forward_jacobian_vec(&x(), &f_counted);
+        assert_eq!(evals.get(), 7);
+    }
+
+    #[test]
+    fn test_jacobian_vec_f64_empty() {
+        let x: Vec<f64> = vec![];
+        let fs = |_: &Vec<f64>| vec![1.0f64, 2.0];
+        assert!(forward_jacobian_vec(&x, &fs).is_empty());
+        assert!(backward_jacobian_vec(&x, &fs).is_empty());
+        assert!(central_jacobian_vec(&x, &fs).is_empty());
+        assert_eq!(forward_jacobian_vec_prod_vec(&x, &fs, &x), vec![0.0, 0.0]);
+        assert_eq!(central_jacobian_vec_prod_vec(&x, &fs, &x), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_forward_jacobian_arrayout_vec_f64() {
+        let fs = |x: &Vec<f64>| {
+            let fx = f(x);
+            [fx[0], fx[2], fx[5]]
+        };
+        let jacobian = forward_jacobian_arrayout(&x(), &fs);
+        let full = forward_jacobian_vec(&x(), &f);
+        assert_eq!(jacobian.len(), 6);
+        for i in 0..6 {
+            for (k, &j) in [0, 2, 5].iter().enumerate() {
+                assert!((full[i][j] - jacobian[i][k]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_asref_vec_f64() {
+        let full = forward_jacobian_vec(&x(), &f);
+        let boxed = forward_jacobian_asref(&x(), &|x: &Vec<f64>| f(x).into_boxed_slice());
+        assert_eq!(boxed, full);
+        let array = forward_jacobian_asref(&x(), &|x: &Vec<f64>| {
+            let fx = f(x);
+            [fx[0], fx[1], fx[2], fx[3], fx[4], fx[5]]
+        });
+        assert_eq!(array, full);
+    }
 }
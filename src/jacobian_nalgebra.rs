@@ -0,0 +1,1000 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::error::check_output_len;
+use crate::jacobian::{BandedJacobian, SparseJacobian};
+use crate::pert::*;
+use crate::utils::*;
+use crate::{FiniteDiffError, FloatEps};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+pub fn forward_jacobian_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    forward_jacobian_fx_into_nalgebra(x, fs, &fx, &mut out);
+    out
+}
+
+/// Same as `forward_jacobian_nalgebra`, but returns an error if perturbing a parameter changes the
+/// number of function values instead of panicking or silently ignoring the surplus values.
+pub fn try_forward_jacobian_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+) -> Result<nalgebra::DMatrix<F>, FiniteDiffError> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, h);
+        check_output_len(i, rn, fx1.len())?;
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    Ok(out)
+}
+
+/// Same as `forward_jacobian_nalgebra`, but returns the transposed Jacobian with one row per
+/// function value.
+pub fn forward_jacobian_transposed_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(rn, n);
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, h);
+        for j in 0..rn {
+            out[(j, i)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+/// Same as `forward_jacobian_nalgebra`, but calls `on_col(i)` once the derivatives with respect to
+/// the `i`th parameter are computed. Returns `None` as soon as `on_col` returns `false`.
+pub fn forward_jacobian_with_progress_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    on_col: &mut dyn FnMut(usize) -> bool,
+) -> Option<nalgebra::DMatrix<F>> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, h);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+        if !on_col(i) {
+            return None;
+        }
+    }
+    Some(out)
+}
+
+pub fn forward_jacobian_into_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    out: &mut nalgebra::DMatrix<F>,
+) {
+    let fx = (fs)(x);
+    forward_jacobian_fx_into_nalgebra(x, fs, &fx, out);
+}
+
+fn forward_jacobian_fx_into_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    fx: &nalgebra::DVector<F>,
+    out: &mut nalgebra::DMatrix<F>,
+) {
+    let rn = fx.len();
+    let n = x.len();
+    assert_eq!(out.shape(), (n, rn));
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, h);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+}
+
+/// Same as `forward_jacobian_nalgebra`, but the row of the `i`th parameter is set to exactly zero if
+/// perturbing it changes none of the function values by more than `zero_tol`.
+pub fn forward_jacobian_thresholded_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    zero_tol: F,
+) -> nalgebra::DMatrix<F> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, h);
+        let max_change = (0..rn).fold(F::zero(), |acc, j| acc.max((fx1[j] - fx[j]).abs()));
+        if max_change <= zero_tol {
+            continue;
+        }
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+/// Same as `forward_jacobian_nalgebra`, but only the rows of the parameters which moved by more than
+/// `step_tol` since `x_prev` are recomputed. See `forward_jacobian_update_vec`.
+pub fn forward_jacobian_update_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    prev: &nalgebra::DMatrix<F>,
+    x_prev: &nalgebra::DVector<F>,
+    step_tol: F,
+) -> nalgebra::DMatrix<F> {
+    assert_eq!(x.len(), x_prev.len());
+    assert_eq!(x.len(), prev.nrows());
+    let n = x.len();
+    let moved: Vec<bool> = (0..n)
+        .map(|i| (x[i] - x_prev[i]).abs() > step_tol)
+        .collect();
+    let mut out = prev.clone();
+    if !moved.contains(&true) {
+        return out;
+    }
+    let fx = (fs)(x);
+    assert_eq!(fx.len(), out.ncols());
+    let mut xt = x.clone();
+    for i in (0..n).filter(|&i| moved[i]) {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, h);
+        for j in 0..fx.len() {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+/// Returns the points at which `forward_jacobian_nalgebra` evaluates the function: `x` followed by
+/// `x + h_i * e_i` for `i = 0, ..., n-1`. See `forward_jacobian_points_vec`.
+pub fn forward_jacobian_points_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+) -> Vec<nalgebra::DVector<F>> {
+    let mut points = Vec::with_capacity(x.len() + 1);
+    points.push(x.clone());
+    for i in 0..x.len() {
+        let mut xt = x.clone();
+        xt[i] += step_for(x[i], F::EPS);
+        points.push(xt);
+    }
+    points
+}
+
+/// Assembles the forward difference Jacobian from the function values at the points returned by
+/// `forward_jacobian_points_nalgebra`, in the same order. See `forward_jacobian_from_values_vec`.
+pub fn forward_jacobian_from_values_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    values: &[nalgebra::DVector<F>],
+) -> nalgebra::DMatrix<F> {
+    let n = x.len();
+    assert_eq!(
+        values.len(),
+        n + 1,
+        "forward_jacobian_from_values: expected one value per evaluation point"
+    );
+    let fx = &values[0];
+    let rn = fx.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    for (i, fx1) in values[1..].iter().enumerate() {
+        assert_eq!(fx1.len(), rn);
+        let h = step_for(x[i], F::EPS);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+#[cfg(feature = "rayon")]
+pub fn forward_jacobian_par_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &(dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F> + Sync),
+) -> nalgebra::DMatrix<F> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let cols: Vec<nalgebra::DVector<F>> = (0..n)
+        .into_par_iter()
+        .map_init(
+            || x.clone(),
+            |xt, i| mod_and_calc_nalgebra(xt, fs, i, step_for(x[i], F::EPS)),
+        )
+        .collect();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    for (i, fx1) in cols.iter().enumerate() {
+        let h = step_for(x[i], F::EPS);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+/// Diagonal of the forward difference Jacobian of an elementwise function.
+pub fn forward_jacobian_diagonal_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    nalgebra::DVector::from_iterator(
+        x.len(),
+        (0..x.len()).map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, h);
+            (fx1[i] - fx[i]) / h
+        }),
+    )
+}
+
+pub fn backward_jacobian_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, -h);
+        for j in 0..rn {
+            out[(i, j)] = (fx[j] - fx1[j]) / h;
+        }
+    }
+    out
+}
+
+pub fn central_jacobian_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    // TODO: get rid of this! fx is only needed to calculate rn in order to be able to allocate the
+    // array for the jacobian.
+    let fx = (fs)(x);
+
+    let rn = fx.len();
+    let n = x.len();
+
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    central_jacobian_into_nalgebra(x, fs, &mut out);
+    out
+}
+
+pub fn central_jacobian_into_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    out: &mut nalgebra::DMatrix<F>,
+) {
+    let mut xt = x.clone();
+    let n = x.len();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, h);
+        let fx2 = mod_and_calc_nalgebra(&mut xt, fs, i, -h);
+        let rn = fx1.len();
+        assert_eq!(out.shape(), (n, rn));
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx2[j]) / (F::from(2.0).unwrap() * h);
+        }
+    }
+}
+
+pub fn forward_jacobian_scaled_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    steps: &nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    for i in 0..n {
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, steps[i]);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / steps[i];
+        }
+    }
+    out
+}
+
+pub fn central_jacobian_scaled_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    steps: &nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    let mut xt = x.clone();
+
+    // TODO: get rid of this! fx is only needed to calculate rn in order to be able to allocate the
+    // array for the jacobian.
+    let fx = (fs)(x);
+
+    let rn = fx.len();
+    let n = x.len();
+
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    for i in 0..n {
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, steps[i]);
+        let fx2 = mod_and_calc_nalgebra(&mut xt, fs, i, -steps[i]);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx2[j]) / (F::from(2.0).unwrap() * steps[i]);
+        }
+    }
+    out
+}
+
+pub fn forward_jacobian_vec_prod_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    p: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let fx = (fs)(x);
+    forward_jacobian_vec_prod_at_nalgebra(x, fs, p, &fx)
+}
+
+/// Same as `forward_jacobian_vec_prod_nalgebra`, but `p` is scaled to unit length for the difference
+/// quotient and the result is scaled back by `|p|`. A zero vector `p` yields a zero product.
+pub fn forward_jacobian_vec_prod_normalized_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    p: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let norm = p.iter().fold(F::zero(), |acc, &pi| acc + pi * pi).sqrt();
+    if norm == F::zero() {
+        return forward_jacobian_vec_prod_nalgebra(x, fs, p);
+    }
+    forward_jacobian_vec_prod_nalgebra(x, fs, &p.map(|pi| pi / norm)).map(|v| v * norm)
+}
+
+pub fn forward_jacobian_vec_prod_at_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    p: &nalgebra::DVector<F>,
+    fx: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let x1 = x.zip_map(p, |xi, pi| xi + F::EPS.sqrt() * pi);
+    let fx1 = (fs)(&x1);
+    fx1.zip_map(fx, |a, b| (a - b) / F::EPS.sqrt())
+}
+
+pub fn central_jacobian_vec_prod_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    p: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let x1 = x.zip_map(p, |xi, pi| xi + F::EPS.sqrt() * pi);
+    let x2 = x.zip_map(p, |xi, pi| xi - F::EPS.sqrt() * pi);
+    let fx1 = (fs)(&x1);
+    let fx2 = (fs)(&x2);
+    fx1.zip_map(&fx2, |a, b| {
+        (a - b) / (F::from(2.0).unwrap() * F::EPS.sqrt())
+    })
+}
+
+pub fn forward_vjp_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    v: &nalgebra::DVector<F>,
+) -> nalgebra::DVector<F> {
+    let jac = forward_jacobian_nalgebra(x, fs);
+    nalgebra::DVector::from_fn(jac.nrows(), |i, _| {
+        compensated_sum((0..jac.ncols()).map(|j| jac[(i, j)] * v[j]))
+    })
+}
+
+pub fn forward_jacobian_pert_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    pert: &PerturbationVectors,
+) -> nalgebra::DMatrix<F> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let mut out = nalgebra::DMatrix::zeros(x.len(), fx.len());
+    for pert_item in pert.iter() {
+        for j in pert_item.x_idx.iter() {
+            xt[*j] += step_for(x[*j], F::EPS);
+        }
+
+        let fx1 = (fs)(&xt);
+
+        for j in pert_item.x_idx.iter() {
+            xt[*j] = x[*j];
+        }
+
+        for (k, x_idx) in pert_item.x_idx.iter().enumerate() {
+            let h = step_for(x[*x_idx], F::EPS);
+            for j in pert_item.r_idx[k].iter() {
+                out[(*x_idx, *j)] = (fx1[*j] - fx[*j]) / h;
+            }
+        }
+    }
+    out
+}
+
+pub fn forward_jacobian_pert_sparse_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    pert: &PerturbationVectors,
+) -> SparseJacobian<F> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let mut rows: Vec<Vec<(usize, F)>> = vec![vec![]; x.len()];
+    for pert_item in pert.iter() {
+        for j in pert_item.x_idx.iter() {
+            xt[*j] += step_for(x[*j], F::EPS);
+        }
+
+        let fx1 = (fs)(&xt);
+
+        for j in pert_item.x_idx.iter() {
+            xt[*j] = x[*j];
+        }
+
+        for (k, x_idx) in pert_item.x_idx.iter().enumerate() {
+            let h = step_for(x[*x_idx], F::EPS);
+            for j in pert_item.r_idx[k].iter() {
+                rows[*x_idx].push((*j, (fx1[*j] - fx[*j]) / h));
+            }
+        }
+    }
+    SparseJacobian::from_rows(fx.len(), rows)
+}
+
+pub fn forward_jacobian_banded_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    kl: usize,
+    ku: usize,
+) -> BandedJacobian<F> {
+    let fx = (fs)(x);
+    let mut out = BandedJacobian::zeros(x.len(), fx.len(), kl, ku);
+    let mut xt = x.clone();
+    let width = out.width();
+    for group in 0..width.min(x.len()) {
+        for i in (group..x.len()).step_by(width) {
+            xt[i] += step_for(x[i], F::EPS);
+        }
+
+        let fx1 = (fs)(&xt);
+
+        for i in (group..x.len()).step_by(width) {
+            xt[i] = x[i];
+        }
+
+        out.set_group(group, &fx, &fx1, |i| step_for(x[i], F::EPS));
+    }
+    out
+}
+
+pub fn central_jacobian_pert_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    pert: &PerturbationVectors,
+) -> nalgebra::DMatrix<F> {
+    let mut out = nalgebra::DMatrix::zeros(1, 1);
+    let mut xt = x.clone();
+    for (i, pert_item) in pert.iter().enumerate() {
+        for j in pert_item.x_idx.iter() {
+            xt[*j] += step_for(x[*j], F::EPS);
+        }
+
+        let fx1 = (fs)(&xt);
+
+        for j in pert_item.x_idx.iter() {
+            xt[*j] = x[*j] - step_for(x[*j], F::EPS);
+        }
+
+        let fx2 = (fs)(&xt);
+
+        for j in pert_item.x_idx.iter() {
+            xt[*j] = x[*j];
+        }
+
+        if i == 0 {
+            out = nalgebra::DMatrix::zeros(x.len(), fx1.len());
+        }
+
+        for (k, x_idx) in pert_item.x_idx.iter().enumerate() {
+            let h = step_for(x[*x_idx], F::EPS);
+            for j in pert_item.r_idx[k].iter() {
+                out[(*x_idx, *j)] = (fx1[*j] - fx2[*j]) / (F::from(2.0).unwrap() * h);
+            }
+        }
+    }
+    out
+}
+
+pub fn central_jacobian_5_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    central_jacobian_5_scaled_nalgebra(
+        x,
+        fs,
+        &nalgebra::DVector::from_element(x.len(), eps_5::<F>()),
+    )
+}
+
+pub fn central_jacobian_5_scaled_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    steps: &nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    let two = F::from(2.0).unwrap();
+    let mut xt = x.clone();
+
+    // TODO: get rid of this! fx is only needed to calculate rn in order to be able to allocate the
+    // array for the jacobian.
+    let fx = (fs)(x);
+
+    let rn = fx.len();
+    let n = x.len();
+
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    for i in 0..n {
+        let h = steps[i];
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, two * h);
+        let fx2 = mod_and_calc_nalgebra(&mut xt, fs, i, h);
+        let fx3 = mod_and_calc_nalgebra(&mut xt, fs, i, -h);
+        let fx4 = mod_and_calc_nalgebra(&mut xt, fs, i, -two * h);
+        for j in 0..rn {
+            out[(i, j)] = (-fx1[j] + F::from(8.0).unwrap() * (fx2[j] - fx3[j]) + fx4[j])
+                / (F::from(12.0).unwrap() * h);
+        }
+    }
+    out
+}
+
+pub fn forward_jacobian_2_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+) -> nalgebra::DMatrix<F> {
+    let two = F::from(2.0).unwrap();
+    let h = eps_3::<F>();
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = nalgebra::DMatrix::zeros(n, rn);
+    for i in 0..n {
+        let fx1 = mod_and_calc_nalgebra(&mut xt, fs, i, h);
+        let fx2 = mod_and_calc_nalgebra(&mut xt, fs, i, two * h);
+        for j in 0..rn {
+            out[(i, j)] = (F::from(4.0).unwrap() * fx1[j] - F::from(3.0).unwrap() * fx[j] - fx2[j])
+                / (two * h);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::{dvector, DVector};
+
+    const COMP_ACC: f64 = 1e-6;
+    const COMP_ACC_F32: f32 = 1e-3;
+
+    fn f(x: &DVector<f64>) -> DVector<f64> {
+        dvector![
+            2.0 * (x[1].powi(3) - x[0].powi(2)),
+            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
+            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
+            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
+            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
+            3.0 * (x[5].powi(3) - x[4].powi(2)),
+        ]
+    }
+
+    fn res1() -> Vec<Vec<f64>> {
+        vec![
+            vec![-4.0, -6.0, 0.0, 0.0, 0.0, 0.0],
+            vec![6.0, 5.0, -6.0, 0.0, 0.0, 0.0],
+            vec![0.0, 6.0, 5.0, -6.0, 0.0, 0.0],
+            vec![0.0, 0.0, 6.0, 5.0, -6.0, 0.0],
+            vec![0.0, 0.0, 0.0, 6.0, 5.0, -6.0],
+            vec![0.0, 0.0, 0.0, 0.0, 6.0, 9.0],
+        ]
+    }
+
+    fn res2() -> Vec<f64> {
+        vec![8.0, 22.0, 27.0, 32.0, 37.0, 24.0]
+    }
+
+    fn x() -> DVector<f64> {
+        dvector![1.0f64, 1.0, 1.0, 1.0, 1.0, 1.0]
+    }
+
+    fn steps() -> DVector<f64> {
+        dvector![1e-8f64, 2e-8, 1e-8, 2e-8, 1e-8, 2e-8]
+    }
+
+    fn p() -> DVector<f64> {
+        dvector![1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0]
+    }
+
+    fn pert() -> PerturbationVectors {
+        vec![
+            PerturbationVector::new()
+                .add(0, vec![0, 1])
+                .add(3, vec![2, 3, 4]),
+            PerturbationVector::new()
+                .add(1, vec![0, 1, 2])
+                .add(4, vec![3, 4, 5]),
+            PerturbationVector::new()
+                .add(2, vec![1, 2, 3])
+                .add(5, vec![4, 5]),
+        ]
+    }
+
+    #[test]
+    fn test_forward_jacobian_nalgebra_f64() {
+        let jacobian = forward_jacobian_nalgebra(&x(), &f);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_transposed_nalgebra_f64() {
+        let jacobian = forward_jacobian_nalgebra(&x(), &f);
+        let jacobian_t = forward_jacobian_transposed_nalgebra(&x(), &f);
+        assert_eq!(jacobian_t, jacobian.transpose());
+    }
+
+    #[test]
+    fn test_forward_jacobian_thresholded_nalgebra_f64() {
+        let fs = |x: &nalgebra::DVector<f64>| {
+            nalgebra::DVector::from_vec(vec![
+                x[0].powi(2) + x[1].exp() * (-x[1]).exp(),
+                x[3] * x[2].exp() * (-x[2]).exp(),
+                2.0 * x[3],
+            ])
+        };
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 0.5, 1.7, 2.0]);
+        let jacobian = forward_jacobian_thresholded_nalgebra(&p, &fs, 1e-12);
+        let res = [[2.0, 0.0, 0.0], [0.0; 3], [0.0; 3], [0.0, 1.0, 2.0]];
+        for i in 0..4 {
+            for j in 0..3 {
+                if i == 1 || i == 2 {
+                    assert_eq!(jacobian[(i, j)], 0.0);
+                }
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_from_values_nalgebra_f64() {
+        let p = x();
+        let points = forward_jacobian_points_nalgebra(&p);
+        assert_eq!(points.len(), 7);
+        assert_eq!(points[0], p);
+        let values: Vec<_> = points.iter().map(f).collect();
+        let jacobian = forward_jacobian_from_values_nalgebra(&p, &values);
+        assert_eq!(jacobian, forward_jacobian_nalgebra(&p, &f));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_forward_jacobian_par_nalgebra_f64() {
+        let jacobian = forward_jacobian_par_nalgebra(&x(), &f);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_nalgebra_f64() {
+        let jacobian = backward_jacobian_nalgebra(&x(), &f);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_nalgebra_f64() {
+        let jacobian = central_jacobian_nalgebra(&x(), &f);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_into_nalgebra_f64() {
+        let mut jacobian = nalgebra::DMatrix::zeros(6, 6);
+        let res = res1();
+        // the buffer is reused
+        for _ in 0..2 {
+            forward_jacobian_into_nalgebra(&x(), &f, &mut jacobian);
+            for i in 0..6 {
+                for j in 0..6 {
+                    assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_into_nalgebra_f64() {
+        let mut jacobian = nalgebra::DMatrix::zeros(6, 6);
+        central_jacobian_into_nalgebra(&x(), &f, &mut jacobian);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_forward_jacobian_into_nalgebra_f64_shape() {
+        let mut jacobian = nalgebra::DMatrix::zeros(6, 5);
+        forward_jacobian_into_nalgebra(&x(), &f, &mut jacobian);
+    }
+
+    #[test]
+    fn test_forward_jacobian_scaled_nalgebra_f64() {
+        let jacobian = forward_jacobian_scaled_nalgebra(&x(), &f, &steps());
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_scaled_nalgebra_f64() {
+        let jacobian = central_jacobian_scaled_nalgebra(&x(), &f, &steps());
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_nalgebra_f64() {
+        let jacobian = forward_jacobian_vec_prod_nalgebra(&x(), &f, &p());
+        let res = res2();
+        // println!("{:?}", jacobian);
+        // the accuracy for this is pretty bad!!
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < 11.0 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_at_nalgebra_f64() {
+        let fx = f(&x());
+        let jvp = forward_jacobian_vec_prod_at_nalgebra(&x(), &f, &p(), &fx);
+        assert_eq!(jvp, forward_jacobian_vec_prod_nalgebra(&x(), &f, &p()));
+    }
+
+    #[test]
+    fn test_central_jacobian_vec_prod_nalgebra_f64() {
+        let jacobian = central_jacobian_vec_prod_nalgebra(&x(), &f, &p());
+        let res = res2();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_vjp_nalgebra_f64() {
+        let vjp = forward_vjp_nalgebra(&x(), &f, &p());
+        // J^T*v computed by hand
+        let res = [-16.0f64, -2.0, 3.0, 8.0, 13.0, 84.0];
+        // println!("{:?}", vjp);
+        for i in 0..6 {
+            assert!((res[i] - vjp[i]).abs() < 11.0 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_vjp_nalgebra_f64_cancellation() {
+        let fs = |x: &nalgebra::DVector<f64>| nalgebra::DVector::from_element(4, x[0]);
+        let v = nalgebra::DVector::from_vec(vec![1e16, 1.0, -1e16, 1.0]);
+        let vjp = forward_vjp_nalgebra(&nalgebra::DVector::from_vec(vec![0.0f64]), &fs, &v);
+        assert_eq!(vjp, nalgebra::DVector::from_vec(vec![2.0]));
+    }
+
+    #[test]
+    fn test_forward_jacobian_pert_nalgebra_f64() {
+        let jacobian = forward_jacobian_pert_nalgebra(&x(), &f, &pert());
+        let res = res1();
+        // println!("jacobian:\n{:?}", jacobian);
+        // println!("res:\n{:?}", res);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_pert_sparse_nalgebra_f64() {
+        let jacobian = forward_jacobian_pert_sparse_nalgebra(&x(), &f, &pert());
+        let res = res1();
+        assert_eq!(jacobian.nnz(), 16);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_banded_nalgebra_f64() {
+        let jacobian = forward_jacobian_banded_nalgebra(&x(), &f, 1, 1);
+        let res = res1();
+        assert_eq!(jacobian.data.len(), 18);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_pert_nalgebra_f64() {
+        let jacobian = central_jacobian_pert_nalgebra(&x(), &f, &pert());
+        let res = res1();
+        // println!("jacobian:\n{:?}", jacobian);
+        // println!("res:\n{:?}", res);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    fn f_f32(x: &DVector<f32>) -> DVector<f32> {
+        dvector![
+            2.0 * (x[1].powi(3) - x[0].powi(2)),
+            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
+            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
+            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
+            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
+            3.0 * (x[5].powi(3) - x[4].powi(2)),
+        ]
+    }
+
+    fn x_f32() -> DVector<f32> {
+        dvector![1.0f32, 1.0, 1.0, 1.0, 1.0, 1.0]
+    }
+
+    #[test]
+    fn test_forward_jacobian_nalgebra_f32() {
+        let jacobian = forward_jacobian_nalgebra(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] as f32 - jacobian[(i, j)]).abs() < 10.0 * COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_nalgebra_f32() {
+        let jacobian = backward_jacobian_nalgebra(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] as f32 - jacobian[(i, j)]).abs() < 10.0 * COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_nalgebra_f32() {
+        let jacobian = central_jacobian_nalgebra(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] as f32 - jacobian[(i, j)]).abs() < COMP_ACC_F32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_5_nalgebra_f64() {
+        let jacobian = central_jacobian_5_nalgebra(&x(), &f);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_2_nalgebra_f64() {
+        let jacobian = forward_jacobian_2_nalgebra(&x(), &f);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_jacobian_nalgebra_f64_empty() {
+        let x: DVector<f64> = DVector::zeros(0);
+        let fs = |_: &DVector<f64>| dvector![1.0f64, 2.0];
+        assert_eq!(forward_jacobian_nalgebra(&x, &fs).shape(), (0, 2));
+        assert_eq!(backward_jacobian_nalgebra(&x, &fs).shape(), (0, 2));
+        assert_eq!(central_jacobian_nalgebra(&x, &fs).shape(), (0, 2));
+        assert_eq!(
+            forward_jacobian_vec_prod_nalgebra(&x, &fs, &x),
+            dvector![0.0, 0.0]
+        );
+        assert_eq!(
+            central_jacobian_vec_prod_nalgebra(&x, &fs, &x),
+            dvector![0.0, 0.0]
+        );
+    }
+}
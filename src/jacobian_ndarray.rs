@@ -5,88 +5,464 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use crate::error::check_output_len;
+use crate::jacobian::{BandedJacobian, SparseJacobian};
 use crate::pert::*;
 use crate::utils::*;
-use crate::EPS_F64;
+use crate::{FiniteDiffError, FloatEps};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
-pub fn forward_jacobian_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    fs: &dyn Fn(&ndarray::Array1<f64>) -> ndarray::Array1<f64>,
-) -> ndarray::Array2<f64> {
-    let fx = (fs)(&x);
+pub fn forward_jacobian_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = ndarray::Array2::zeros((n, rn));
+    forward_jacobian_fx_into_ndarray(x, fs, &fx, &mut out);
+    out
+}
+
+/// Same as `forward_jacobian_ndarray`, but returns an error if perturbing a parameter changes the
+/// number of function values instead of panicking or silently ignoring the surplus values.
+pub fn try_forward_jacobian_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> Result<ndarray::Array2<F>, FiniteDiffError> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = ndarray::Array2::zeros((n, rn));
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+        check_output_len(i, rn, fx1.len())?;
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    Ok(out)
+}
+
+/// Same as `forward_jacobian_ndarray`, but for parameters given as a view, for instance a row of a
+/// larger array.
+///
+/// The view is copied once into an owned array, which is evaluated at the unperturbed position and
+/// then perturbed in place. This avoids a call to `to_owned` for every Jacobian.
+/// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+///
+/// ```rust
+/// use finitediff::forward_jacobian_view;
+/// use ndarray::{array, Array1};
+///
+/// let params = array![[1.0f64, 2.0], [3.0, 4.0]];
+/// let fs = |x: &Array1<f64>| array![x[0] * x[1], x[1].powi(2)];
+/// let jacobian = forward_jacobian_view(params.row(1), &fs);
+/// # assert!((jacobian[(0, 0)] - 4.0).abs() < 1e-6);
+/// # assert!((jacobian[(1, 0)] - 3.0).abs() < 1e-6);
+/// # assert!((jacobian[(1, 1)] - 8.0).abs() < 1e-6);
+/// ```
+pub fn forward_jacobian_view<F: FloatEps + ndarray::ScalarOperand>(
+    x: ndarray::ArrayView1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
+    let mut xt = x.to_owned();
+    let fx = (fs)(&xt);
+    let rn = fx.len();
+    let n = xt.len();
+    let mut out = ndarray::Array2::zeros((n, rn));
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+/// Same as `forward_jacobian_ndarray`, but returns the transposed Jacobian with one row per
+/// function value.
+pub fn forward_jacobian_transposed_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = ndarray::Array2::zeros((rn, n));
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+        for j in 0..rn {
+            out[(j, i)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+/// Same as `forward_jacobian_ndarray`, but calls `on_col(i)` once the derivatives with respect to
+/// the `i`th parameter are computed. Returns `None` as soon as `on_col` returns `false`.
+pub fn forward_jacobian_with_progress_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    on_col: &mut dyn FnMut(usize) -> bool,
+) -> Option<ndarray::Array2<F>> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = ndarray::Array2::zeros((n, rn));
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+        if !on_col(i) {
+            return None;
+        }
+    }
+    Some(out)
+}
+
+pub fn forward_jacobian_into_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    out: &mut ndarray::Array2<F>,
+) {
+    let fx = (fs)(x);
+    forward_jacobian_fx_into_ndarray(x, fs, &fx, out);
+}
+
+fn forward_jacobian_fx_into_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    fx: &ndarray::Array1<F>,
+    out: &mut ndarray::Array2<F>,
+) {
+    let rn = fx.len();
+    let n = x.len();
+    assert_eq!(out.dim(), (n, rn));
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+}
+
+/// Same as `forward_jacobian_ndarray`, but the row of the `i`th parameter is set to exactly zero if
+/// perturbing it changes none of the function values by more than `zero_tol`.
+pub fn forward_jacobian_thresholded_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    zero_tol: F,
+) -> ndarray::Array2<F> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = ndarray::Array2::zeros((n, rn));
+    let mut xt = x.clone();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+        let max_change = (0..rn).fold(F::zero(), |acc, j| acc.max((fx1[j] - fx[j]).abs()));
+        if max_change <= zero_tol {
+            continue;
+        }
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+/// Same as `forward_jacobian_ndarray`, but only the rows of the parameters which moved by more than
+/// `step_tol` since `x_prev` are recomputed. See `forward_jacobian_update_vec`.
+pub fn forward_jacobian_update_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    prev: &ndarray::Array2<F>,
+    x_prev: &ndarray::Array1<F>,
+    step_tol: F,
+) -> ndarray::Array2<F> {
+    assert_eq!(x.len(), x_prev.len());
+    assert_eq!(x.len(), prev.nrows());
+    let n = x.len();
+    let moved: Vec<bool> = (0..n)
+        .map(|i| (x[i] - x_prev[i]).abs() > step_tol)
+        .collect();
+    let mut out = prev.clone();
+    if !moved.contains(&true) {
+        return out;
+    }
+    let fx = (fs)(x);
+    assert_eq!(fx.len(), out.ncols());
+    let mut xt = x.clone();
+    for i in (0..n).filter(|&i| moved[i]) {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+        for j in 0..fx.len() {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+/// Returns the points at which `forward_jacobian_ndarray` evaluates the function: `x` followed by
+/// `x + h_i * e_i` for `i = 0, ..., n-1`. See `forward_jacobian_points_vec`.
+pub fn forward_jacobian_points_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+) -> Vec<ndarray::Array1<F>> {
+    let mut points = Vec::with_capacity(x.len() + 1);
+    points.push(x.clone());
+    for i in 0..x.len() {
+        let mut xt = x.clone();
+        xt[i] += step_for(x[i], F::EPS);
+        points.push(xt);
+    }
+    points
+}
+
+/// Assembles the forward difference Jacobian from the function values at the points returned by
+/// `forward_jacobian_points_ndarray`, in the same order. See `forward_jacobian_from_values_vec`.
+pub fn forward_jacobian_from_values_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    values: &[ndarray::Array1<F>],
+) -> ndarray::Array2<F> {
+    let n = x.len();
+    assert_eq!(
+        values.len(),
+        n + 1,
+        "forward_jacobian_from_values: expected one value per evaluation point"
+    );
+    let fx = &values[0];
+    let rn = fx.len();
+    let mut out = ndarray::Array2::zeros((n, rn));
+    for (i, fx1) in values[1..].iter().enumerate() {
+        assert_eq!(fx1.len(), rn);
+        let h = step_for(x[i], F::EPS);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+#[cfg(feature = "rayon")]
+pub fn forward_jacobian_par_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &(dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F> + Sync),
+) -> ndarray::Array2<F> {
+    let fx = (fs)(x);
+    let rn = fx.len();
+    let n = x.len();
+    let cols: Vec<ndarray::Array1<F>> = (0..n)
+        .into_par_iter()
+        .map_init(
+            || x.clone(),
+            |xt, i| mod_and_calc_ndarray(xt, fs, i, step_for(x[i], F::EPS)),
+        )
+        .collect();
+    let mut out = unsafe { ndarray::Array2::uninitialized((n, rn)) };
+    for (i, fx1) in cols.iter().enumerate() {
+        let h = step_for(x[i], F::EPS);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / h;
+        }
+    }
+    out
+}
+
+/// Diagonal of the forward difference Jacobian of an elementwise function.
+pub fn forward_jacobian_diagonal_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> ndarray::Array1<F> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    (0..x.len())
+        .map(|i| {
+            let h = step_for(x[i], F::EPS);
+            let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+            (fx1[i] - fx[i]) / h
+        })
+        .collect()
+}
+
+pub fn backward_jacobian_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
+    let fx = (fs)(x);
     let mut xt = x.clone();
     let rn = fx.len();
     let n = x.len();
     let mut out = unsafe { ndarray::Array2::uninitialized((n, rn)) };
     for i in 0..n {
-        let fx1 = mod_and_calc_ndarray_f64(&mut xt, fs, i, EPS_F64.sqrt());
-        // out.slice_mut(s![i, ..])
-        //     .assign(&((fx1 - &fx) / EPS_F64.sqrt()));
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, -h);
         for j in 0..rn {
-            out[(i, j)] = (fx1[j] - fx[j]) / EPS_F64.sqrt();
+            out[(i, j)] = (fx[j] - fx1[j]) / h;
         }
     }
     out
 }
 
-pub fn central_jacobian_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    fs: &dyn Fn(&ndarray::Array1<f64>) -> ndarray::Array1<f64>,
-) -> ndarray::Array2<f64> {
+pub fn central_jacobian_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
+    // TODO: get rid of this! fx is only needed to calculate rn in order to be able to allocate the
+    // array for the jacobian.
+    let fx = (fs)(x);
+
+    let rn = fx.len();
+    let n = x.len();
+
+    let mut out = ndarray::Array2::zeros((n, rn));
+    central_jacobian_into_ndarray(x, fs, &mut out);
+    out
+}
+
+pub fn central_jacobian_into_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    out: &mut ndarray::Array2<F>,
+) {
+    let mut xt = x.clone();
+    let n = x.len();
+    for i in 0..n {
+        let h = step_for(x[i], F::EPS);
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+        let fx2 = mod_and_calc_ndarray(&mut xt, fs, i, -h);
+        let rn = fx1.len();
+        assert_eq!(out.dim(), (n, rn));
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx2[j]) / (F::from(2.0).unwrap() * h);
+        }
+    }
+}
+
+pub fn forward_jacobian_scaled_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    steps: &ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = unsafe { ndarray::Array2::uninitialized((n, rn)) };
+    for i in 0..n {
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, steps[i]);
+        for j in 0..rn {
+            out[(i, j)] = (fx1[j] - fx[j]) / steps[i];
+        }
+    }
+    out
+}
+
+pub fn central_jacobian_scaled_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    steps: &ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
     let mut xt = x.clone();
 
     // TODO: get rid of this! fx is only needed to calculate rn in order to be able to allocate the
     // array for the jacobian.
-    let fx = (fs)(&x);
+    let fx = (fs)(x);
 
     let rn = fx.len();
     let n = x.len();
 
     let mut out = unsafe { ndarray::Array2::uninitialized((n, rn)) };
     for i in 0..n {
-        let fx1 = mod_and_calc_ndarray_f64(&mut xt, fs, i, EPS_F64.sqrt());
-        let fx2 = mod_and_calc_ndarray_f64(&mut xt, fs, i, -EPS_F64.sqrt());
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, steps[i]);
+        let fx2 = mod_and_calc_ndarray(&mut xt, fs, i, -steps[i]);
         for j in 0..rn {
-            out[(i, j)] = (fx1[j] - fx2[j]) / (2.0 * EPS_F64.sqrt());
+            out[(i, j)] = (fx1[j] - fx2[j]) / (F::from(2.0).unwrap() * steps[i]);
         }
     }
     out
 }
 
-pub fn forward_jacobian_vec_prod_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    fs: &dyn Fn(&ndarray::Array1<f64>) -> ndarray::Array1<f64>,
-    p: &ndarray::Array1<f64>,
-) -> ndarray::Array1<f64> {
-    let fx = (fs)(&x);
-    let x1 = x + &p.mapv(|pi| EPS_F64.sqrt() * pi);
+pub fn forward_jacobian_vec_prod_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    p: &ndarray::Array1<F>,
+) -> ndarray::Array1<F> {
+    let fx = (fs)(x);
+    forward_jacobian_vec_prod_at_ndarray(x, fs, p, &fx)
+}
+
+/// Same as `forward_jacobian_vec_prod_ndarray`, but `p` is scaled to unit length for the difference
+/// quotient and the result is scaled back by `|p|`. A zero vector `p` yields a zero product.
+pub fn forward_jacobian_vec_prod_normalized_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    p: &ndarray::Array1<F>,
+) -> ndarray::Array1<F> {
+    let norm = p.iter().fold(F::zero(), |acc, &pi| acc + pi * pi).sqrt();
+    if norm == F::zero() {
+        return forward_jacobian_vec_prod_ndarray(x, fs, p);
+    }
+    forward_jacobian_vec_prod_ndarray(x, fs, &(p / norm)) * norm
+}
+
+pub fn forward_jacobian_vec_prod_at_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    p: &ndarray::Array1<F>,
+    fx: &ndarray::Array1<F>,
+) -> ndarray::Array1<F> {
+    let x1 = x + &p.mapv(|pi| F::EPS.sqrt() * pi);
     let fx1 = (fs)(&x1);
-    (fx1 - fx) / EPS_F64.sqrt()
+    (fx1 - fx) / F::EPS.sqrt()
 }
 
-pub fn central_jacobian_vec_prod_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    fs: &dyn Fn(&ndarray::Array1<f64>) -> ndarray::Array1<f64>,
-    p: &ndarray::Array1<f64>,
-) -> ndarray::Array1<f64> {
-    let x1 = x + &p.mapv(|pi| EPS_F64.sqrt() * pi);
-    let x2 = x + &p.mapv(|pi| -EPS_F64.sqrt() * pi);
+pub fn central_jacobian_vec_prod_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    p: &ndarray::Array1<F>,
+) -> ndarray::Array1<F> {
+    let x1 = x + &p.mapv(|pi| F::EPS.sqrt() * pi);
+    let x2 = x + &p.mapv(|pi| -F::EPS.sqrt() * pi);
     let fx1 = (fs)(&x1);
     let fx2 = (fs)(&x2);
-    (fx1 - fx2) / (2.0 * EPS_F64.sqrt())
+    (fx1 - fx2) / (F::from(2.0).unwrap() * F::EPS.sqrt())
+}
+
+pub fn forward_vjp_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    v: &ndarray::Array1<F>,
+) -> ndarray::Array1<F> {
+    forward_jacobian_ndarray(x, fs)
+        .outer_iter()
+        .map(|row| compensated_sum(row.iter().zip(v.iter()).map(|(&a, &b)| a * b)))
+        .collect()
 }
 
-pub fn forward_jacobian_pert_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    fs: &dyn Fn(&ndarray::Array1<f64>) -> ndarray::Array1<f64>,
+pub fn forward_jacobian_pert_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
     pert: &PerturbationVectors,
-) -> ndarray::Array2<f64> {
-    let fx = (fs)(&x);
+) -> ndarray::Array2<F> {
+    let fx = (fs)(x);
     let mut xt = x.clone();
-    let mut out = ndarray::Array2::zeros((fx.len(), x.len()));
+    let mut out = ndarray::Array2::zeros((x.len(), fx.len()));
     for pert_item in pert.iter() {
         for j in pert_item.x_idx.iter() {
-            xt[*j] += EPS_F64.sqrt();
+            xt[*j] += step_for(x[*j], F::EPS);
         }
 
         let fx1 = (fs)(&xt);
@@ -96,30 +472,86 @@ pub fn forward_jacobian_pert_ndarray_f64(
         }
 
         for (k, x_idx) in pert_item.x_idx.iter().enumerate() {
+            let h = step_for(x[*x_idx], F::EPS);
             for j in pert_item.r_idx[k].iter() {
-                out[(*x_idx, *j)] = (fx1[*j] - fx[*j]) / EPS_F64.sqrt();
+                out[(*x_idx, *j)] = (fx1[*j] - fx[*j]) / h;
             }
         }
     }
     out
 }
 
-pub fn central_jacobian_pert_ndarray_f64(
-    x: &ndarray::Array1<f64>,
-    fs: &dyn Fn(&ndarray::Array1<f64>) -> ndarray::Array1<f64>,
+pub fn forward_jacobian_pert_sparse_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
     pert: &PerturbationVectors,
-) -> ndarray::Array2<f64> {
+) -> SparseJacobian<F> {
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let mut rows: Vec<Vec<(usize, F)>> = vec![vec![]; x.len()];
+    for pert_item in pert.iter() {
+        for j in pert_item.x_idx.iter() {
+            xt[*j] += step_for(x[*j], F::EPS);
+        }
+
+        let fx1 = (fs)(&xt);
+
+        for j in pert_item.x_idx.iter() {
+            xt[*j] = x[*j];
+        }
+
+        for (k, x_idx) in pert_item.x_idx.iter().enumerate() {
+            let h = step_for(x[*x_idx], F::EPS);
+            for j in pert_item.r_idx[k].iter() {
+                rows[*x_idx].push((*j, (fx1[*j] - fx[*j]) / h));
+            }
+        }
+    }
+    SparseJacobian::from_rows(fx.len(), rows)
+}
+
+pub fn forward_jacobian_banded_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    kl: usize,
+    ku: usize,
+) -> BandedJacobian<F> {
+    let fx = (fs)(x);
+    let mut out = BandedJacobian::zeros(x.len(), fx.len(), kl, ku);
+    let mut xt = x.clone();
+    let width = out.width();
+    for group in 0..width.min(x.len()) {
+        for i in (group..x.len()).step_by(width) {
+            xt[i] += step_for(x[i], F::EPS);
+        }
+
+        let fx1 = (fs)(&xt);
+
+        for i in (group..x.len()).step_by(width) {
+            xt[i] = x[i];
+        }
+
+        out.set_group(group, &fx, &fx1, |i| step_for(x[i], F::EPS));
+    }
+    out
+}
+
+pub fn central_jacobian_pert_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    pert: &PerturbationVectors,
+) -> ndarray::Array2<F> {
     let mut out = ndarray::Array2::zeros((1, 1));
     let mut xt = x.clone();
     for (i, pert_item) in pert.iter().enumerate() {
         for j in pert_item.x_idx.iter() {
-            xt[*j] += EPS_F64.sqrt();
+            xt[*j] += step_for(x[*j], F::EPS);
         }
 
         let fx1 = (fs)(&xt);
 
         for j in pert_item.x_idx.iter() {
-            xt[*j] = x[*j] - EPS_F64.sqrt();
+            xt[*j] = x[*j] - step_for(x[*j], F::EPS);
         }
 
         let fx2 = (fs)(&xt);
@@ -129,25 +561,86 @@ pub fn central_jacobian_pert_ndarray_f64(
         }
 
         if i == 0 {
-            out = ndarray::Array2::zeros((fx1.len(), x.len()));
+            out = ndarray::Array2::zeros((x.len(), fx1.len()));
         }
 
         for (k, x_idx) in pert_item.x_idx.iter().enumerate() {
+            let h = step_for(x[*x_idx], F::EPS);
             for j in pert_item.r_idx[k].iter() {
-                out[(*x_idx, *j)] = (fx1[*j] - fx2[*j]) / (2.0 * EPS_F64.sqrt());
+                out[(*x_idx, *j)] = (fx1[*j] - fx2[*j]) / (F::from(2.0).unwrap() * h);
             }
         }
     }
     out
 }
 
+pub fn central_jacobian_5_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
+    central_jacobian_5_scaled_ndarray(x, fs, &ndarray::Array1::from_elem(x.len(), eps_5::<F>()))
+}
+
+pub fn central_jacobian_5_scaled_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    steps: &ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
+    let two = F::from(2.0).unwrap();
+    let mut xt = x.clone();
+
+    // TODO: get rid of this! fx is only needed to calculate rn in order to be able to allocate the
+    // array for the jacobian.
+    let fx = (fs)(x);
+
+    let rn = fx.len();
+    let n = x.len();
+
+    let mut out = unsafe { ndarray::Array2::uninitialized((n, rn)) };
+    for i in 0..n {
+        let h = steps[i];
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, two * h);
+        let fx2 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+        let fx3 = mod_and_calc_ndarray(&mut xt, fs, i, -h);
+        let fx4 = mod_and_calc_ndarray(&mut xt, fs, i, -two * h);
+        for j in 0..rn {
+            out[(i, j)] = (-fx1[j] + F::from(8.0).unwrap() * (fx2[j] - fx3[j]) + fx4[j])
+                / (F::from(12.0).unwrap() * h);
+        }
+    }
+    out
+}
+
+pub fn forward_jacobian_2_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+) -> ndarray::Array2<F> {
+    let two = F::from(2.0).unwrap();
+    let h = eps_3::<F>();
+    let fx = (fs)(x);
+    let mut xt = x.clone();
+    let rn = fx.len();
+    let n = x.len();
+    let mut out = unsafe { ndarray::Array2::uninitialized((n, rn)) };
+    for i in 0..n {
+        let fx1 = mod_and_calc_ndarray(&mut xt, fs, i, h);
+        let fx2 = mod_and_calc_ndarray(&mut xt, fs, i, two * h);
+        for j in 0..rn {
+            out[(i, j)] = (F::from(4.0).unwrap() * fx1[j] - F::from(3.0).unwrap() * fx[j] - fx2[j])
+                / (two * h);
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ndarray;
+
     use ndarray::{array, Array1};
 
     const COMP_ACC: f64 = 1e-6;
+    const COMP_ACC_F32: f32 = 1e-3;
 
     fn f(x: &Array1<f64>) -> Array1<f64> {
         array![
@@ -179,6 +672,10 @@ mod tests {
         array![1.0f64, 1.0, 1.0, 1.0, 1.0, 1.0]
     }
 
+    fn steps() -> Array1<f64> {
+        array![1e-8f64, 2e-8, 1e-8, 2e-8, 1e-8, 2e-8]
+    }
+
     fn p() -> Array1<f64> {
         array![1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0]
     }
@@ -199,7 +696,103 @@ mod tests {
 
     #[test]
     fn test_forward_jacobian_ndarray_f64() {
-        let jacobian = forward_jacobian_ndarray_f64(&x(), &f);
+        let jacobian = forward_jacobian_ndarray(&x(), &f);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_transposed_ndarray_f64() {
+        let jacobian = forward_jacobian_ndarray(&x(), &f);
+        let jacobian_t = forward_jacobian_transposed_ndarray(&x(), &f);
+        assert_eq!(jacobian_t, jacobian.t().to_owned());
+    }
+
+    #[test]
+    fn test_forward_jacobian_view_ndarray_f64() {
+        let mut params = ndarray::Array2::zeros((3, 6));
+        params.row_mut(1).assign(&x());
+        let jacobian = forward_jacobian_view(params.row(1), &f);
+        assert_eq!(jacobian, forward_jacobian_ndarray(&x(), &f));
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_thresholded_ndarray_f64() {
+        let fs = |x: &ndarray::Array1<f64>| {
+            ndarray::Array1::from(vec![
+                x[0].powi(2) + x[1].exp() * (-x[1]).exp(),
+                x[3] * x[2].exp() * (-x[2]).exp(),
+                2.0 * x[3],
+            ])
+        };
+        let p = ndarray::Array1::from(vec![1.0f64, 0.5, 1.7, 2.0]);
+        let jacobian = forward_jacobian_thresholded_ndarray(&p, &fs, 1e-12);
+        let res = [[2.0, 0.0, 0.0], [0.0; 3], [0.0; 3], [0.0, 1.0, 2.0]];
+        for i in 0..4 {
+            for j in 0..3 {
+                if i == 1 || i == 2 {
+                    assert_eq!(jacobian[(i, j)], 0.0);
+                }
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_update_ndarray_f64() {
+        let x_prev = array![0.5f64, 0.75, 1.25, 1.5, 0.25, 2.0];
+        let prev = forward_jacobian_ndarray(&x_prev, &f);
+        let jacobian = forward_jacobian_update_ndarray(&x(), &f, &prev, &x_prev, 0.0);
+        assert_eq!(jacobian, forward_jacobian_ndarray(&x(), &f));
+
+        let mut x = x_prev.clone();
+        x[2] = 1.0;
+        let jacobian = forward_jacobian_update_ndarray(&x, &f, &prev, &x_prev, 1e-3);
+        let full = forward_jacobian_ndarray(&x, &f);
+        for i in 0..6 {
+            let expected = if i == 2 { full.row(i) } else { prev.row(i) };
+            assert_eq!(jacobian.row(i), expected);
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_from_values_ndarray_f64() {
+        let p = x();
+        let points = forward_jacobian_points_ndarray(&p);
+        assert_eq!(points.len(), 7);
+        assert_eq!(points[0], p);
+        let values: Vec<_> = points.iter().map(f).collect();
+        let jacobian = forward_jacobian_from_values_ndarray(&p, &values);
+        assert_eq!(jacobian, forward_jacobian_ndarray(&p, &f));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_forward_jacobian_par_ndarray_f64() {
+        let jacobian = forward_jacobian_par_ndarray(&x(), &f);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_ndarray_f64() {
+        let jacobian = backward_jacobian_ndarray(&x(), &f);
         let res = res1();
         // println!("{:?}", jacobian);
         for i in 0..6 {
@@ -211,7 +804,65 @@ mod tests {
 
     #[test]
     fn test_central_jacobian_ndarray_f64() {
-        let jacobian = central_jacobian_ndarray_f64(&x(), &f);
+        let jacobian = central_jacobian_ndarray(&x(), &f);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_into_ndarray_f64() {
+        let mut jacobian = ndarray::Array2::zeros((6, 6));
+        let res = res1();
+        // the buffer is reused
+        for _ in 0..2 {
+            forward_jacobian_into_ndarray(&x(), &f, &mut jacobian);
+            for i in 0..6 {
+                for j in 0..6 {
+                    assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_into_ndarray_f64() {
+        let mut jacobian = ndarray::Array2::zeros((6, 6));
+        central_jacobian_into_ndarray(&x(), &f, &mut jacobian);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_forward_jacobian_into_ndarray_f64_shape() {
+        let mut jacobian = ndarray::Array2::zeros((6, 5));
+        forward_jacobian_into_ndarray(&x(), &f, &mut jacobian);
+    }
+
+    #[test]
+    fn test_forward_jacobian_scaled_ndarray_f64() {
+        let jacobian = forward_jacobian_scaled_ndarray(&x(), &f, &steps());
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_scaled_ndarray_f64() {
+        let jacobian = central_jacobian_scaled_ndarray(&x(), &f, &steps());
         let res = res1();
         // println!("{:?}", jacobian);
         for i in 0..6 {
@@ -223,7 +874,7 @@ mod tests {
 
     #[test]
     fn test_forward_jacobian_vec_prod_ndarray_f64() {
-        let jacobian = forward_jacobian_vec_prod_ndarray_f64(&x(), &f, &p());
+        let jacobian = forward_jacobian_vec_prod_ndarray(&x(), &f, &p());
         let res = res2();
         // println!("{:?}", jacobian);
         // the accuracy for this is pretty bad!!
@@ -232,9 +883,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_forward_jacobian_vec_prod_at_ndarray_f64() {
+        let fx = f(&x());
+        let jvp = forward_jacobian_vec_prod_at_ndarray(&x(), &f, &p(), &fx);
+        assert_eq!(jvp, forward_jacobian_vec_prod_ndarray(&x(), &f, &p()));
+    }
+
     #[test]
     fn test_central_jacobian_vec_prod_ndarray_f64() {
-        let jacobian = central_jacobian_vec_prod_ndarray_f64(&x(), &f, &p());
+        let jacobian = central_jacobian_vec_prod_ndarray(&x(), &f, &p());
         let res = res2();
         // println!("{:?}", jacobian);
         for i in 0..6 {
@@ -242,9 +900,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_forward_vjp_ndarray_f64() {
+        let vjp = forward_vjp_ndarray(&x(), &f, &p());
+        // J^T*v computed by hand
+        let res = [-16.0f64, -2.0, 3.0, 8.0, 13.0, 84.0];
+        // println!("{:?}", vjp);
+        for i in 0..6 {
+            assert!((res[i] - vjp[i]).abs() < 11.0 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_vjp_ndarray_f64_cancellation() {
+        let fs = |x: &ndarray::Array1<f64>| ndarray::Array1::from(vec![x[0]; 4]);
+        let v = ndarray::Array1::from(vec![1e16, 1.0, -1e16, 1.0]);
+        let vjp = forward_vjp_ndarray(&ndarray::Array1::from(vec![0.0f64]), &fs, &v);
+        assert_eq!(vjp, ndarray::Array1::from(vec![2.0]));
+    }
+
     #[test]
     fn test_forward_jacobian_pert_ndarray_f64() {
-        let jacobian = forward_jacobian_pert_ndarray_f64(&x(), &f, &pert());
+        let jacobian = forward_jacobian_pert_ndarray(&x(), &f, &pert());
         let res = res1();
         // println!("jacobian:\n{:?}", jacobian);
         // println!("res:\n{:?}", res);
@@ -255,9 +932,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_forward_jacobian_pert_sparse_ndarray_f64() {
+        let jacobian = forward_jacobian_pert_sparse_ndarray(&x(), &f, &pert());
+        let res = res1();
+        assert_eq!(jacobian.nnz(), 16);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_banded_ndarray_f64() {
+        let jacobian = forward_jacobian_banded_ndarray(&x(), &f, 1, 1);
+        let res = res1();
+        assert_eq!(jacobian.data.len(), 18);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
     #[test]
     fn test_central_jacobian_pert_ndarray_f64() {
-        let jacobian = central_jacobian_pert_ndarray_f64(&x(), &f, &pert());
+        let jacobian = central_jacobian_pert_ndarray(&x(), &f, &pert());
         let res = res1();
         // println!("jacobian:\n{:?}", jacobian);
         // println!("res:\n{:?}", res);
@@ -267,4 +968,158 @@ mod tests {
             }
         }
     }
+
+    /// `R^3 -> R^5`
+    fn f_rect(x: &Array1<f64>) -> Array1<f64> {
+        array![
+            x[0] * x[1],
+            x[1].powi(2),
+            x[2].exp(),
+            x[0] + x[2],
+            x[0] * x[1] * x[2]
+        ]
+    }
+
+    /// Jacobian of `f_rect` at `[1.0, 2.0, 0.5]` with one row per parameter
+    fn res_rect() -> Vec<Vec<f64>> {
+        vec![
+            vec![2.0, 0.0, 0.0, 1.0, 1.0],
+            vec![1.0, 4.0, 0.0, 0.0, 0.5],
+            vec![0.0, 0.0, 0.5f64.exp(), 1.0, 2.0],
+        ]
+    }
+
+    #[test]
+    fn test_jacobian_rectangular_ndarray_f64() {
+        let x = array![1.0f64, 2.0, 0.5];
+        let pert = vec![
+            PerturbationVector::new().add(0, vec![0, 3, 4]),
+            PerturbationVector::new().add(1, vec![0, 1, 4]),
+            PerturbationVector::new().add(2, vec![2, 3, 4]),
+        ];
+        let mut into = ndarray::Array2::zeros((3, 5));
+        forward_jacobian_into_ndarray(&x, &f_rect, &mut into);
+        let res = res_rect();
+        for jacobian in [
+            forward_jacobian_ndarray(&x, &f_rect),
+            backward_jacobian_ndarray(&x, &f_rect),
+            central_jacobian_ndarray(&x, &f_rect),
+            central_jacobian_5_ndarray(&x, &f_rect),
+            forward_jacobian_2_ndarray(&x, &f_rect),
+            forward_jacobian_pert_ndarray(&x, &f_rect, &pert),
+            central_jacobian_pert_ndarray(&x, &f_rect, &pert),
+            into,
+        ]
+        .iter()
+        {
+            assert_eq!(jacobian.dim(), (3, 5));
+            for i in 0..3 {
+                for j in 0..5 {
+                    assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+                }
+            }
+        }
+
+        // J(x)*p has the length of the output, J(x)^T*v the length of the parameters
+        let jvp = central_jacobian_vec_prod_ndarray(&x, &f_rect, &array![1.0, 0.0, 1.0]);
+        let vjp = forward_vjp_ndarray(&x, &f_rect, &array![1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(jvp.len(), 5);
+        assert_eq!(vjp.len(), 3);
+        for j in 0..5 {
+            assert!((res[0][j] + res[2][j] - jvp[j]).abs() < COMP_ACC);
+        }
+        for i in 0..3 {
+            assert!((res[i][0] + res[i][4] - vjp[i]).abs() < 10.0 * COMP_ACC);
+        }
+    }
+
+    fn f_f32(x: &Array1<f32>) -> Array1<f32> {
+        array![
+            2.0 * (x[1].powi(3) - x[0].powi(2)),
+            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
+            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
+            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
+            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
+            3.0 * (x[5].powi(3) - x[4].powi(2)),
+        ]
+    }
+
+    fn x_f32() -> Array1<f32> {
+        array![1.0f32, 1.0, 1.0, 1.0, 1.0, 1.0]
+    }
+
+    #[test]
+    fn test_forward_jacobian_ndarray_f32() {
+        let jacobian = forward_jacobian_ndarray(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] as f32 - jacobian[(i, j)]).abs() < 10.0 * COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_ndarray_f32() {
+        let jacobian = backward_jacobian_ndarray(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] as f32 - jacobian[(i, j)]).abs() < 10.0 * COMP_ACC_F32)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_ndarray_f32() {
+        let jacobian = central_jacobian_ndarray(&x_f32(), &f_f32);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] as f32 - jacobian[(i, j)]).abs() < COMP_ACC_F32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_5_ndarray_f64() {
+        let jacobian = central_jacobian_5_ndarray(&x(), &f);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_2_ndarray_f64() {
+        let jacobian = forward_jacobian_2_ndarray(&x(), &f);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC);
+            }
+        }
+    }
+
+    #[test]
+    fn test_jacobian_ndarray_f64_empty() {
+        let x: Array1<f64> = Array1::zeros(0);
+        let fs = |_: &Array1<f64>| array![1.0f64, 2.0];
+        assert_eq!(forward_jacobian_ndarray(&x, &fs).dim(), (0, 2));
+        assert_eq!(backward_jacobian_ndarray(&x, &fs).dim(), (0, 2));
+        assert_eq!(central_jacobian_ndarray(&x, &fs).dim(), (0, 2));
+        assert_eq!(
+            forward_jacobian_vec_prod_ndarray(&x, &fs, &x),
+            array![0.0, 0.0]
+        );
+        assert_eq!(
+            central_jacobian_vec_prod_ndarray(&x, &fs, &x),
+            array![0.0, 0.0]
+        );
+    }
 }
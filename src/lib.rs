@@ -7,8 +7,9 @@
 
 //! This crate contains a wide range of methods for the calculation of gradients, Jacobians and
 //! Hessians using forward and central differences.
-//! The methods have been implemented for input vectors of the type `Vec<f64>` and
-//! `ndarray::Array1<f64>`.
+//! The methods have been implemented for input vectors of the type `Vec<f64>`, `Vec<f32>`,
+//! `ndarray::Array1<f64>`, `ndarray::Array1<f32>`, `nalgebra::DVector<f64>` and
+//! `nalgebra::DVector<f32>`.
 //! Central differences are more accurate but require more evaluations of the cost function and are
 //! therefore computationally more expensive.
 //!
@@ -34,6 +35,106 @@
 //! finitediff = { version = "0.1.2", features = ["ndarray"] }
 //! ```
 //!
+//! Similarly, the `nalgebra` feature provides implementations for `nalgebra::DVector<f64>` and
+//! `nalgebra::DVector<f32>`, with Jacobians and Hessians of type `nalgebra::DMatrix`:
+//!
+//! ```toml
+//! [dependencies]
+//! finitediff = { version = "0.1.2", features = ["nalgebra"] }
+//! ```
+//!
+//! Complex-step differentiation (via the `ComplexStep` trait) is available with the `complex`
+//! feature. It yields gradients which are accurate to machine precision, but requires the cost
+//! function to accept complex inputs and to be holomorphic.
+//!
+//! ```toml
+//! [dependencies]
+//! finitediff = { version = "0.1.2", features = ["complex"] }
+//! ```
+//!
+//! With the `rayon` feature, `forward_jacobian_par` computes the columns of the Jacobian in
+//! parallel. This requires the function to be `Sync`.
+//!
+//! ```toml
+//! [dependencies]
+//! finitediff = { version = "0.1.2", features = ["rayon"] }
+//! ```
+//!
+//! The `serde` feature derives `Serialize` and `Deserialize` for `PerturbationVector`, such that
+//! perturbation vectors can be computed once and stored, for instance as JSON.
+//!
+//! ```toml
+//! [dependencies]
+//! finitediff = { version = "0.1.2", features = ["serde"] }
+//! ```
+//!
+//! The `argmin` feature provides `FiniteDiffProblem`, which wraps a cost function of type
+//! `Fn(&Vec<f64>) -> f64` and implements argmin's `CostFunction`, `Gradient` and `Hessian` traits
+//! via `forward_diff` and `forward_hessian_nograd`.
+//!
+//! ```toml
+//! [dependencies]
+//! finitediff = { version = "0.1.2", features = ["argmin"] }
+//! ```
+//!
+//! The crate supports `no_std` environments with an allocator. In this case, the default `std`
+//! feature needs to be disabled and the `libm` feature enabled, which provides the floating
+//! point functions. The `ndarray`, `nalgebra`, `complex`, `rayon` and `argmin` features require
+//! `std`.
+//!
+//! ```toml
+//! [dependencies]
+//! finitediff = { version = "0.1.2", default-features = false, features = ["libm"] }
+//! ```
+//!
+//! `FiniteDiff` is also implemented for fixed-size arrays `[f64; N]`, with Jacobians and
+//! Hessians of type `[[f64; N]; N]`. This avoids heap allocations for small problems.
+//!
+//! With the `ndarray` feature, `FiniteDiff` is also implemented for matrix-valued parameters of
+//! type `ndarray::Array2<f64>`. Gradients have the same shape as the parameters, whereas
+//! Jacobians and Hessians refer to the parameters flattened in row-major order.
+//!
+//! Parameters which are grouped into sublists of possibly different lengths, for instance the
+//! weights of the layers of a model, can be passed as `Vec<Vec<f64>>`. Gradients have the same
+//! nested shape, whereas Jacobians and Hessians refer to the concatenated sublists.
+//!
+//! If the parameters are only available as a slice `&[f64]`, the free functions
+//! `forward_diff_slice` and `central_diff_slice` avoid copying them into a `Vec` first. Their
+//! `_scratch` variants do not allocate at all.
+//!
+//! Gradients of higher accuracy are available via `forward_diff_order` and `central_diff_order`,
+//! which take the order of the stencil as a `StencilOrder` (2, 4 or 6).
+//!
+//! `FiniteDiffConfig` bundles the finite difference scheme (forward, backward or central), the
+//! step and the order of the stencil. It is set up once and then computes gradients and
+//! Jacobians via `gradient` and `jacobian` for all supported parameter vectors. The step is
+//! either absolute, relative to the magnitude of each parameter or set individually for each
+//! parameter (see `StepStrategy`).
+//!
+//! To find out how many times the cost function was evaluated, wrap it in a `CountingFn` and
+//! query `count()` afterwards. If the same points are visited repeatedly, a `MemoizedFn` answers
+//! these evaluations from a cache instead of calling the function again.
+//!
+//! Optimizers which differentiate the same problem many times can avoid repeated allocations
+//! with a `DiffContext`, which allocates the perturbed parameters, the gradient and the Jacobian
+//! once and reuses them in every call.
+//!
+//! Custom difference schemes can be built on `mod_and_calc_vec` and `mod_and_calc2_vec` (and
+//! their `_ndarray` and `_nalgebra` counterparts), which evaluate the function with one or two
+//! parameters shifted in place and restore the parameters afterwards.
+//!
+//! # Evaluation order
+//!
+//! All methods evaluate the function in a fixed order which does not depend on the run or the
+//! platform, therefore the results are reproducible even if the function has side effects or
+//! caches values. The parameters are perturbed in ascending order of their index. Methods which
+//! need the unperturbed value `f(x)` evaluate it first. For instance, `forward_diff` evaluates
+//! `f(x)`, `f(x + h * e_0)`, `f(x + h * e_1)`, ... and `central_diff` evaluates
+//! `f(x + h * e_0)`, `f(x - h * e_0)`, `f(x + h * e_1)`, .... The order of the Hessians without
+//! gradient is documented with the respective methods. The only exception is
+//! `forward_jacobian_par`, which evaluates the columns in parallel in an unspecified order;
+//! its result is nevertheless identical to the one of `forward_jacobian`.
+//!
 //! # Examples
 //!
 //! * [Calculation of the gradient](#calculation-of-the-gradient)
@@ -89,6 +190,8 @@
 //! ### For `ndarray::Array1<f64>`
 //!
 //! ```rust
+//! # #[cfg(feature = "ndarray")]
+//! # {
 //! use ndarray::{array, Array1};
 //! use finitediff::FiniteDiff;
 //!
@@ -115,6 +218,7 @@
 //! #      assert!((res[i] - grad_forward[i]).abs() < 1e-6);
 //! #      assert!((res[i] - grad_central[i]).abs() < 1e-6);
 //! #  }
+//! # }
 //! ```
 //!
 //! ## Calculation of the Jacobian
@@ -263,6 +367,11 @@
 //! #  }
 //! ```
 //!
+//! The Jacobian above is banded: each function only depends on its neighbouring parameters. In
+//! this case the perturbation vectors do not need to be written by hand:
+//! `x.forward_jacobian_banded(&f, 1, 1)` groups the parameters itself and returns a
+//! `BandedJacobian` after `kl + ku + 2 = 4` evaluations of `f`, independent of the length of `x`.
+//!
 //! ## Calculation of the Hessian
 //!
 //! Note that the same interface is also implemented for `ndarray::Array1<f64>` (not shown).
@@ -390,376 +499,9299 @@
 //! #  }
 //! ```
 
-#![allow(clippy::ptr_arg)]
+#![allow(clippy::ptr_arg, clippy::needless_range_loop)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("either the `std` or the `libm` feature needs to be enabled");
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
 
+mod adaptive;
+#[cfg(feature = "argmin")]
+mod argmin_interop;
+mod array;
+mod cache;
+mod check;
+mod compare;
+#[cfg(feature = "complex")]
+mod complex;
+mod config;
+mod context;
+mod count;
 mod diff;
+#[cfg(feature = "nalgebra")]
+mod diff_nalgebra;
 #[cfg(feature = "ndarray")]
 mod diff_ndarray;
+mod display;
+mod error;
 mod hessian;
+#[cfg(feature = "nalgebra")]
+mod hessian_nalgebra;
 #[cfg(feature = "ndarray")]
 mod hessian_ndarray;
 mod jacobian;
+#[cfg(feature = "nalgebra")]
+mod jacobian_nalgebra;
 #[cfg(feature = "ndarray")]
 mod jacobian_ndarray;
+#[cfg(feature = "ndarray")]
+mod ndarray2;
+mod nested;
+mod packed;
 mod pert;
+mod richardson;
+mod types;
 mod utils;
 
+use crate::adaptive::*;
+#[cfg(feature = "argmin")]
+pub use crate::argmin_interop::FiniteDiffProblem;
+use crate::array::*;
+pub use crate::cache::{CacheKey, KeyBits, MemoizedFn};
+use crate::check::*;
+pub use crate::check::{GradCheckFailure, GradCheckReport};
+pub use crate::compare::approx_eq_matrix;
+#[cfg(feature = "ndarray")]
+pub use crate::compare::approx_eq_matrix_ndarray;
+#[cfg(feature = "complex")]
+pub use crate::complex::ComplexStep;
+pub use crate::config::{ConfigInput, FiniteDiffConfig, Method, StepStrategy};
+pub use crate::context::DiffContext;
+pub use crate::count::CountingFn;
 use crate::diff::*;
+pub use crate::diff::{
+    central_diff_generic, central_diff_slice, central_diff_slice_scratch, forward_diff_generic,
+    forward_diff_map, forward_diff_slice, forward_diff_slice_scratch, StencilOrder,
+};
+#[cfg(feature = "nalgebra")]
+use crate::diff_nalgebra::*;
 #[cfg(feature = "ndarray")]
 use crate::diff_ndarray::*;
+pub use crate::display::{Pretty, PrettyMatrix};
+#[cfg(feature = "nalgebra")]
+use crate::error::indexed_nalgebra;
+pub use crate::error::FiniteDiffError;
+use crate::error::{
+    check_dim, check_finite, check_finite_jacobian, check_forward_steps, check_steps, indexed_rows,
+};
+pub use crate::hessian::SymmetricHessian;
 use crate::hessian::*;
+#[cfg(feature = "nalgebra")]
+use crate::hessian_nalgebra::*;
 #[cfg(feature = "ndarray")]
 use crate::hessian_ndarray::*;
 use crate::jacobian::*;
+pub use crate::jacobian::{
+    forward_jacobian_arrayout, forward_jacobian_asref, BandedJacobian, SparseJacobian,
+};
+#[cfg(feature = "nalgebra")]
+use crate::jacobian_nalgebra::*;
+#[cfg(feature = "ndarray")]
+pub use crate::jacobian_ndarray::forward_jacobian_view;
 #[cfg(feature = "ndarray")]
 use crate::jacobian_ndarray::*;
+#[cfg(feature = "ndarray")]
+use crate::ndarray2::*;
+use crate::nested::*;
+pub use crate::packed::PackedSymmetric;
 pub use crate::pert::*;
+use crate::richardson::*;
+pub use crate::types::{Derivatives, Gradient};
+pub use crate::utils::{
+    central_step, default_step, default_step_for, mod_and_calc2_vec, mod_and_calc_vec,
+    restore_symmetry_vec_with, SymmetryMode,
+};
+use crate::utils::{compensated_sum, mask_indices};
+#[cfg(feature = "nalgebra")]
+pub use crate::utils::{
+    mod_and_calc2_nalgebra, mod_and_calc_nalgebra, restore_symmetry_nalgebra_with,
+};
 #[cfg(feature = "ndarray")]
-use ndarray;
+pub use crate::utils::{
+    mod_and_calc2_ndarray, mod_and_calc_ndarray, restore_symmetry_ndarray_with,
+};
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use num_traits::{Float, NumAssignOps};
 
-const EPS_F64: f64 = std::f64::EPSILON;
+/// Machine epsilon from which the perturbation steps for `f64` are derived. The default step of
+/// forward differences is `sqrt(EPS_F64) * max(|x_i|, 1)`, see `default_step_for`, and the one of
+/// `central_diff` is `EPS_F64^(1/3) * max(|x_i|, 1)`.
+pub const EPS_F64: f64 = f64::EPSILON;
+/// Epsilon from which the perturbation steps for `f32` are derived. It is larger than
+/// `f32::EPSILON` to keep the rounding errors of the difference quotients in check.
+pub const EPS_F32: f32 = 4.0 * f32::EPSILON;
+
+/// Floating point types for which finite differences can be computed.
+pub trait FloatEps: Float + NumAssignOps + Send + Sync {
+    /// Machine epsilon from which the perturbation step sizes are derived
+    const EPS: Self;
+}
+
+impl FloatEps for f64 {
+    const EPS: f64 = EPS_F64;
+}
+
+impl FloatEps for f32 {
+    const EPS: f32 = EPS_F32;
+}
 
 pub trait FiniteDiff
 where
     Self: Sized,
 {
+    type Scalar;
     type Jacobian;
     type Hessian;
     type OperatorOutput;
 
     /// Forward difference calculated as
     ///
-    /// `df/dx_i (x) \approx (f(x + sqrt(EPS_F64) * e_i) - f(x))/sqrt(EPS_F64)  \forall i`
+    /// `df/dx_i (x) \approx (f(x + h_i * e_i) - f(x))/h_i  \forall i`
     ///
-    /// where `f` is the cost function and `e_i` is the `i`th unit vector.
+    /// where `f` is the cost function, `e_i` is the `i`th unit vector and
+    /// `h_i = sqrt(EPS_F64) * max(|x_i|, 1)`. Scaling the step with large parameters keeps it from
+    /// being lost to rounding. `f(x)` is evaluated first, followed by the perturbations in
+    /// ascending order of `i`.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `f`.
+    fn forward_diff(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Self;
+
+    /// Same as `forward_diff`, but returns an error if a difference quotient is not finite.
+    ///
+    /// This happens if `f` returns a non-finite value, or if the quotient overflows because `f`
+    /// returns values close to the largest representable number. The error contains the index of
+    /// the first affected parameter.
+    ///
+    /// The steps are relative to the parameters, see `forward_diff`, and are therefore not lost to
+    /// rounding for large `|x_i|`. Infinite parameters lead to a `FiniteDiffError::NonFinite`.
+    /// For a parameter vector of length `n`, this requires at most `n+1` evaluations of `f`.
+    fn try_forward_diff(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Result<Self, FiniteDiffError>;
+
+    /// Same as `forward_diff`, but uses the provided value `fx = f(x)` instead of evaluating `f`
+    /// at the unperturbed position.
+    /// For a parameter vector of length `n`, this requires `n` evaluations of `f`.
+    fn forward_diff_precomputed(&self, f: &dyn Fn(&Self) -> Self::Scalar, fx: Self::Scalar)
+        -> Self;
+
+    /// Returns the value `f(x)` together with the forward difference gradient. `f(x)` is only
+    /// evaluated once and reused for all difference quotients.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `f`.
+    fn value_and_forward_diff(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> (Self::Scalar, Self);
+
+    /// Same as `forward_diff`, but additionally returns the value `f(x)` and the values
+    /// `f(x + sqrt(EPS_F64) * e_i)` at all perturbed positions, which were used to compute the
+    /// gradient. This allows to inspect the function in the vicinity of `x`, for instance in a
+    /// sensitivity analysis, without evaluating it again. For matrices and nested vectors, the
+    /// order of the perturbed values is the one of the flattened parameters.
     /// For a parameter vector of length `n`, this requires `n+1` evaluations of `f`.
-    fn forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self;
+    fn forward_diff_with_samples(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+    ) -> (Self, Self::Scalar, Vec<Self::Scalar>);
+
+    /// Same as `forward_diff`, but passes every evaluation of `f` to `sink`, in the order of
+    /// evaluation, as the parameters and the returned value. For matrices and nested vectors, the
+    /// parameters are passed flattened.
+    ///
+    /// This shows exactly which inputs `f` received, which helps debugging a cost function which
+    /// returns `NaN` or behaves unexpectedly at the perturbed positions.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `f`.
+    ///
+    /// ```
+    /// use finitediff::FiniteDiff;
+    ///
+    /// let f = |x: &Vec<f64>| x[0].ln() + x[1];
+    /// let mut nan_inputs = vec![];
+    /// vec![0.0f64, 1.0].forward_diff_traced(&f, &mut |x, fx| {
+    ///     if fx.is_nan() {
+    ///         nan_inputs.push(x.to_vec());
+    ///     }
+    /// });
+    /// # assert!(nan_inputs.is_empty());
+    /// ```
+    #[allow(clippy::type_complexity)]
+    fn forward_diff_traced(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+        sink: &mut dyn FnMut(&[Self::Scalar], Self::Scalar),
+    ) -> Self;
+
+    /// Same as `forward_diff`, but only the parameters in `indices` are perturbed. The returned
+    /// gradient has the full length, the entries of all other parameters are zero.
+    /// For `k` indices, this requires `k+1` evaluations of `f`.
+    fn forward_diff_subset(&self, f: &dyn Fn(&Self) -> Self::Scalar, indices: &[usize]) -> Self;
+
+    /// Same as `forward_diff_subset`, but the perturbed parameters are given as a mask: only the
+    /// parameters `i` with `active[i] == true` are perturbed, the entries of all other parameters
+    /// are zero.
+    ///
+    /// Returns an error if `active` does not have one entry per parameter.
+    /// For `k` active parameters, this requires `k+1` evaluations of `f`.
+    fn forward_diff_masked(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+        active: &[bool],
+    ) -> Result<Self, FiniteDiffError>;
+
+    /// Same as `forward_diff`, but additionally returns an estimate of the error of each entry of
+    /// the gradient.
+    ///
+    /// The forward difference is evaluated with the steps `h = sqrt(EPS_F64)` and `h/2`. The
+    /// gradient is the one obtained with step `h`, and the error estimate is
+    /// `|grad_h - grad_{h/2}|`. Large estimates indicate unreliable entries.
+    /// For a parameter vector of length `n`, this requires `2*n+1` evaluations of `f`.
+    fn forward_diff_with_error(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> (Self, Self);
+
+    /// Backward difference calculated as
+    ///
+    /// `df/dx_i (x) \approx (f(x) - f(x - h_i * e_i))/h_i  \forall i`
+    ///
+    /// where `f` is the cost function, `e_i` is the `i`th unit vector and
+    /// `h_i = sqrt(EPS_F64) * max(|x_i|, 1)`.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `f`.
+    fn backward_diff(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Self;
 
     /// Central difference calculated as
     ///
-    /// `df/dx_i (x) \approx (f(x + sqrt(EPS_F64) * e_i) - f(x - sqrt(EPS_F64) * e_i))/(2.0 * sqrt(EPS_F64))  \forall i`
+    /// `df/dx_i (x) \approx (f(x + h_i * e_i) - f(x - h_i * e_i))/(2.0 * h_i)  \forall i`
     ///
-    /// where `f` is the cost function and `e_i` is the `i`th unit vector.
+    /// where `f` is the cost function, `e_i` is the `i`th unit vector and
+    /// `h_i = EPS_F64^(1/3) * max(|x_i|, 1)`. The parameters are perturbed in ascending order of
+    /// `i`, first in positive and then in negative direction.
+    ///
+    /// The step differs from the step `sqrt(EPS_F64) * max(|x_i|, 1)` of `forward_diff`: the
+    /// truncation error of forward differences is of first order in `h`, whereas the one of central
+    /// differences is of second order. Balancing it with the rounding error `EPS_F64/h` leads to
+    /// `h ~ EPS_F64^(1/2)` for forward and `h ~ EPS_F64^(1/3)` for central differences.
     /// For a parameter vector of length `n`, this requires `2*n` evaluations of `f`.
-    fn central_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self;
+    fn central_diff(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Self;
 
-    /// Calculation of the Jacobian J(x) of a vector function `fs` using forward differences:
+    /// Gradient with an individual finite difference scheme for each parameter.
     ///
-    /// `dfs/dx_i (x) \approx (fs(x + sqrt(EPS_F64) * e_i) - fs(x))/sqrt(EPS_F64)  \forall i`
+    /// `policy[i]` selects the scheme of the `i`th entry: `Method::Forward`, `Method::Backward`
+    /// or `Method::Central`, each with the step of `forward_diff`, `backward_diff` and
+    /// `central_diff`. This is useful if only some parameters are close to
+    /// kinks of `f`, where one-sided differences are preferable. The entries are identical to
+    /// the corresponding entries of the pure methods.
+    /// This requires one evaluation of `f` per forward or backward entry and two per central
+    /// entry, plus one evaluation at `x` unless all entries are central.
     ///
-    /// where `e_i` is the `i`th unit vector.
-    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
-    fn forward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian;
+    /// Panics if `policy` does not have one entry per parameter.
+    ///
+    /// ```
+    /// use finitediff::{FiniteDiff, Method};
+    ///
+    /// let f = |x: &Vec<f64>| x[0].abs() + x[1].powi(2);
+    /// let grad = vec![0.0f64, 1.0].diff_mixed(&f, &[Method::Forward, Method::Central]);
+    /// # assert!((grad[0] - 1.0).abs() < 1e-6);
+    /// # assert!((grad[1] - 2.0).abs() < 1e-6);
+    /// ```
+    fn diff_mixed(&self, f: &dyn Fn(&Self) -> Self::Scalar, policy: &[Method]) -> Self;
 
-    /// Calculation of the Jacobian J(x) of a vector function `fs` using central differences:
+    /// Central difference using a five-point stencil calculated as
     ///
-    /// `dfs/dx_i (x) \approx (fs(x + sqrt(EPS_F64) * e_i) - fs(x - sqrt(EPS_F64) * e_i))/(2.0 * sqrt(EPS_F64))  \forall i`
+    /// `df/dx_i (x) \approx (-f(x + 2h * e_i) + 8f(x + h * e_i) - 8f(x - h * e_i) + f(x - 2h * e_i))/(12h)  \forall i`
     ///
-    /// where `e_i` is the `i`th unit vector.
-    /// For a parameter vector of length `n`, this requires `2*n` evaluations of `fs`.
-    fn central_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian;
+    /// where `f` is the cost function, `e_i` is the `i`th unit vector and `h = EPS_F64^(1/5)`.
+    /// This is fourth-order accurate, compared to second order for `central_diff`.
+    /// For a parameter vector of length `n`, this requires `4*n` evaluations of `f`.
+    fn central_diff_5(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Self;
 
-    /// Calculation of the product of the Jacobian J(x) of a vector function `fs` with a vector `p`
-    /// using forward differences:
+    /// Second-order accurate forward difference using a three-point stencil:
     ///
-    /// `J(x)*p \approx (fs(x + sqrt(EPS_F64) * p) - fs(x))/sqrt(EPS_F64)  \forall i`
+    /// `df/dx_i (x) \approx (-3f(x) + 4f(x + h * e_i) - f(x + 2h * e_i))/(2h)  \forall i`
     ///
-    /// where `e_i` is the `i`th unit vector.
-    /// This requires 2 evaluations of `fs`.
-    fn forward_jacobian_vec_prod(
-        &self,
-        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
-        p: &Self,
-    ) -> Self;
+    /// where `f` is the cost function, `e_i` is the `i`th unit vector and `h = EPS_F64^(1/3)`.
+    /// Like `forward_diff`, `f` is only evaluated at or above `x`, which is useful if `f` is not
+    /// defined below `x`.
+    /// For a parameter vector of length `n`, this requires `2*n+1` evaluations of `f`.
+    fn forward_diff_2(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Self;
 
-    /// Calculation of the product of the Jacobian J(x) of a vector function `fs` with a vector `p`
-    /// using central differences:
+    /// Forward difference with a one-sided stencil of the given order of accuracy. The step is
+    /// `EPS^(1/(p + 1))` for order `p`.
+    /// For a parameter vector of length `n`, this requires `p*n+1` evaluations of `f`.
+    fn forward_diff_order(&self, f: &dyn Fn(&Self) -> Self::Scalar, order: StencilOrder) -> Self;
+
+    /// Central difference with a symmetric stencil of the given order of accuracy. The step is
+    /// `EPS^(1/(p + 1))` for order `p`.
+    /// For a parameter vector of length `n`, this requires `p*n` evaluations of `f`.
+    fn central_diff_order(&self, f: &dyn Fn(&Self) -> Self::Scalar, order: StencilOrder) -> Self;
+
+    /// Forward difference with a separate step size for each parameter, calculated as
     ///
-    /// `J(x)*p \approx (fs(x + sqrt(EPS_F64) * p) - fs(x - sqrt(EPS_F64) * p))/(2.0 * sqrt(EPS_F64))  \forall i`
+    /// `df/dx_i (x) \approx (f(x + steps_i * e_i) - f(x))/steps_i  \forall i`
     ///
-    /// where `e_i` is the `i`th unit vector.
-    /// This requires 2 evaluations of `fs`.
-    fn central_jacobian_vec_prod(
+    /// where `f` is the cost function and `e_i` is the `i`th unit vector.
+    /// This is useful if the parameters live on very different scales.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `f`.
+    fn forward_diff_scaled(&self, f: &dyn Fn(&Self) -> Self::Scalar, steps: &Self) -> Self;
+
+    /// Forward difference with the default step multiplied by a characteristic length `scale_i`
+    /// of each parameter:
+    ///
+    /// `df/dx_i (x) \approx (f(x + sqrt(EPS_F64) * scale_i * e_i) - f(x))/(sqrt(EPS_F64) * scale_i)  \forall i`
+    ///
+    /// Unlike `forward_diff_scaled`, which takes the absolute steps, `scale` only rescales
+    /// `sqrt(EPS_F64)`. A parameter with `scale_i == 0` is not perturbed and its derivative is
+    /// set to zero.
+    /// For a parameter vector of length `n`, this requires at most `n+1` evaluations of `f`.
+    fn forward_diff_with_scaling(&self, f: &dyn Fn(&Self) -> Self::Scalar, scale: &Self) -> Self;
+
+    /// Forward difference for parameters with box constraints `lower_i <= x_i <= upper_i`, which
+    /// only evaluates `f` at feasible points.
+    ///
+    /// For every parameter with `x_i + sqrt(EPS_F64) > upper_i`, a backward difference is used
+    /// instead. If the interval is narrower than `sqrt(EPS_F64)` on both sides of `x_i`, the step
+    /// is shortened to the larger distance to a bound. Parameters with `lower_i == upper_i` are
+    /// fixed and get a zero derivative. `x` has to satisfy the bounds.
+    /// For a parameter vector of length `n`, this requires at most `n+1` evaluations of `f`.
+    fn forward_diff_bounded(
         &self,
-        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
-        p: &Self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+        lower: &Self,
+        upper: &Self,
     ) -> Self;
 
-    fn forward_jacobian_pert(
+    /// Same as `forward_diff_scaled`, but returns an error if `steps` does not have the same length
+    /// as the parameter vector, if a step is lost to rounding (`x_i + steps_i == x_i`) or if the
+    /// result contains non-finite values.
+    fn try_forward_diff_scaled(
         &self,
-        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
-        pert: &PerturbationVectors,
-    ) -> Self::Jacobian;
+        f: &dyn Fn(&Self) -> Self::Scalar,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError>;
 
-    fn central_jacobian_pert(
+    /// Central difference with a separate step size for each parameter, calculated as
+    ///
+    /// `df/dx_i (x) \approx (f(x + steps_i * e_i) - f(x - steps_i * e_i))/(2.0 * steps_i)  \forall i`
+    ///
+    /// where `f` is the cost function and `e_i` is the `i`th unit vector.
+    /// This is useful if the parameters live on very different scales.
+    /// For a parameter vector of length `n`, this requires `2*n` evaluations of `f`.
+    fn central_diff_scaled(&self, f: &dyn Fn(&Self) -> Self::Scalar, steps: &Self) -> Self;
+
+    /// Same as `central_diff_scaled`, but returns an error if `steps` does not have the same length
+    /// as the parameter vector, if a step is lost to rounding (`x_i + steps_i == x_i` or
+    /// `x_i - steps_i == x_i`) or if the result contains non-finite values.
+    fn try_central_diff_scaled(
         &self,
-        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
-        pert: &PerturbationVectors,
-    ) -> Self::Jacobian;
+        f: &dyn Fn(&Self) -> Self::Scalar,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError>;
 
-    /// Calculation of the Hessian using forward differences
+    /// Same as `forward_diff`, which scales its steps with the magnitude of each parameter.
+    #[deprecated(
+        since = "0.1.3",
+        note = "use `forward_diff`, whose steps are relative as well"
+    )]
+    fn forward_diff_relative(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Self {
+        self.forward_diff(f)
+    }
+
+    /// Central difference improved by Richardson extrapolation
     ///
-    /// `dg/dx_i (x) \approx (g(x + sqrt(EPS_F64) * e_i) - g(x))/sqrt(EPS_F64)  \forall i`
+    /// The central difference is evaluated at the steps `h`, `h/2`, ..., `h/2^(levels-1)` with
+    /// `h = EPS_F64^(1/5)` and the results are combined using the Romberg tableau. Each level
+    /// eliminates the next even power of `h` from the truncation error. `levels` smaller than 1
+    /// is treated as 1, which corresponds to a plain central difference.
+    /// For a parameter vector of length `n`, this requires `2*n*levels` evaluations of `f`.
+    fn richardson_diff(&self, f: &dyn Fn(&Self) -> Self::Scalar, levels: usize) -> Self;
+
+    /// Forward difference with an automatically selected step for each parameter
     ///
-    /// where `g` is a function which computes the gradient of some other function f and `e_i` is
-    /// the `i`th unit vector.
-    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `g`.
-    fn forward_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian;
+    /// For each parameter, forward differences are evaluated at the steps `h`, `h/2`, `h/4`, ...
+    /// with `h = 0.1 * max(|x_i|, 1)` and extrapolated to vanishing step size (Ridders' method).
+    /// The extrapolated value with the smallest estimated truncation and rounding error is
+    /// selected. Smaller steps are no longer tried as soon as successive estimates start to
+    /// diverge.
+    /// For a parameter vector of length `n`, this requires at most `1 + 10*n` evaluations of `f`.
+    fn adaptive_diff(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Self;
 
-    /// Calculation of the Hessian using central differences
+    /// Gradient using forward or central differences, whichever is needed to reach a relative
+    /// error of about `target_rel_err`
     ///
-    /// `dg/dx_i (x) \approx (g(x + sqrt(EPS_F64) * e_i) - g(x - sqrt(EPS_F64) * e_i))/(2.0 * sqrt(EPS_F64))  \forall i`
+    /// The forward difference gradient is computed first. Its error is estimated by comparing two
+    /// evenly spaced components against central differences. If the relative
+    /// deviation of any of them exceeds `target_rel_err`, the whole gradient is recomputed with
+    /// central differences, reusing the probed components. Only a subset of the components is
+    /// sampled in order to keep the overhead bounded, therefore the error of the remaining
+    /// components is not checked.
+    /// For a parameter vector of length `n >= 2`, this requires `n+5` evaluations of `f` if forward
+    /// differences suffice and `3*n+1` otherwise.
+    fn auto_diff(&self, f: &dyn Fn(&Self) -> Self::Scalar, target_rel_err: Self::Scalar) -> Self;
+
+    /// Diagnostic for cost functions with stochastic noise, for instance Monte Carlo estimates.
+    /// This does not compute a derivative.
     ///
-    /// where `g` is a function which computes the gradient of some other function f and `e_i` is
-    /// the `i`th unit vector.
-    /// For a parameter vector of length `n`, this requires `2*n` evaluations of `g`.
-    fn central_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian;
+    /// The forward difference with step `sqrt(EPS_F64)` is evaluated `repeats` times for every
+    /// parameter, and the sample standard deviation of these estimates is returned per parameter.
+    /// If the noise is comparable to the magnitude of the gradient, the differences are dominated
+    /// by noise and the step should be increased, for instance with `forward_diff_scaled`. For a
+    /// deterministic `f`, the result is zero. Panics if `repeats < 2`.
+    /// For a parameter vector of length `n`, this requires `(n+1)*repeats` evaluations of `f`.
+    fn estimate_diff_noise(&self, f: &mut dyn FnMut(&Self) -> Self::Scalar, repeats: usize)
+        -> Self;
 
-    /// Calculation of the product of the Hessian H(x) of a function `g` with a vector `p`
-    /// using forward differences:
+    /// Directional derivative of `f` along `p` using forward differences:
     ///
-    /// `H(x)*p \approx (g(x + sqrt(EPS_F64) * p) - g(x))/sqrt(EPS_F64)  \forall i`
+    /// `D_p f(x) \approx (f(x + sqrt(EPS_F64) * p) - f(x))/sqrt(EPS_F64)`
     ///
-    /// where `g` is a function which computes the gradient of some other function f and `e_i` is
-    /// the `i`th unit vector.
-    /// This requires 2 evaluations of `g`.
-    fn forward_hessian_vec_prod(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput, p: &Self)
-        -> Self;
+    /// Returns zero without evaluating `f` if `p` is zero.
+    /// This requires 2 evaluations of `f`.
+    fn forward_directional_diff(&self, f: &dyn Fn(&Self) -> Self::Scalar, p: &Self)
+        -> Self::Scalar;
 
-    /// Calculation of the product of the Hessian H(x) of a function `g` with a vector `p`
-    /// using central differences:
+    /// Directional derivative of `f` along `p` using central differences:
     ///
-    /// `H(x)*p \approx (g(x + sqrt(EPS_F64) * p) - g(x - sqrt(EPS_F64) * p))/(2.0 * sqrt(EPS_F64))  \forall i`
+    /// `D_p f(x) \approx (f(x + sqrt(EPS_F64) * p) - f(x - sqrt(EPS_F64) * p))/(2.0 * sqrt(EPS_F64))`
     ///
-    /// where `g` is a function which computes the gradient of some other function f and `e_i` is
-    /// the `i`th unit vector.
-    /// This requires 2 evaluations of `g`.
-    fn central_hessian_vec_prod(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput, p: &Self)
-        -> Self;
+    /// Returns zero without evaluating `f` if `p` is zero.
+    /// This requires 2 evaluations of `f`.
+    fn central_directional_diff(&self, f: &dyn Fn(&Self) -> Self::Scalar, p: &Self)
+        -> Self::Scalar;
 
-    /// Calculation of the Hessian using forward differences without knowledge of the gradient:
+    /// Calculation of the Jacobian J(x) of a vector function `fs` using forward differences:
     ///
-    /// `df/(dx_i dx_j) (x) \approx (f(x + sqrt(EPS_F64) * e_i + sqrt(EPS_F64) * e_j) - f(x + sqrt(EPS_F64) + e_i) - f(x + sqrt(EPS_F64) * e_j) + f(x))/EPS_F64  \forall i`
+    /// `dfs/dx_i (x) \approx (fs(x + h_i * e_i) - fs(x))/h_i  \forall i`
     ///
-    /// where `e_i` and `e_j` are the `i`th and `j`th unit vector, respectively.
-    // /// For a parameter vector of length `n`, this requires `n*(n+1)/2` evaluations of `g`.
-    fn forward_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self::Hessian;
+    /// where `e_i` is the `i`th unit vector and `h_i = sqrt(EPS_F64) * max(|x_i|, 1)`. `fs(x)` is
+    /// evaluated first, followed by the perturbations in ascending order of `i`.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+    /// If `fs` returns `m` values, the Jacobian has `n` rows (one per parameter) and `m` columns;
+    /// `m` is taken from the first evaluation of `fs`.
+    fn forward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian;
 
-    /// Calculation of a sparse Hessian using forward differences without knowledge of the gradient:
+    /// Same as `forward_jacobian`, but returns an error if perturbing a parameter changes the
+    /// number of function values, for instance because of a ragged internal model, or if the
+    /// result contains non-finite values.
     ///
-    /// `df/(dx_i dx_j) (x) \approx (f(x + sqrt(EPS_F64) * e_i + sqrt(EPS_F64) * e_j) - f(x + sqrt(EPS_F64) + e_i) - f(x + sqrt(EPS_F64) * e_j) + f(x))/EPS_F64  \forall i`
+    /// ```
+    /// use finitediff::{FiniteDiff, FiniteDiffError};
     ///
-    /// where `e_i` and `e_j` are the `i`th and `j`th unit vector, respectively.
-    /// The indices which are to be evaluated need to be provided via `indices`. Note that due to
-    /// the symmetry of the Hessian, an index `(a, b)` will also compute the value of the Hessian at
-    /// `(b, a)`.
-    // /// For a parameter vector of length `n`, this requires `n*(n+1)/2` evaluations of `g`.
-    fn forward_hessian_nograd_sparse(
+    /// let fs = |x: &Vec<f64>| if x[1] > 2.0 { vec![x[0]] } else { vec![x[0], x[1]] };
+    /// assert_eq!(
+    ///     vec![1.0f64, 2.0].try_forward_jacobian(&fs),
+    ///     Err(FiniteDiffError::OutputLengthChanged { col: 1, expected: 2, got: 1 })
+    /// );
+    /// ```
+    fn try_forward_jacobian(
         &self,
-        f: &dyn Fn(&Self) -> f64,
-        indices: Vec<[usize; 2]>,
-    ) -> Self::Hessian;
-}
-
-impl FiniteDiff for Vec<f64>
-where
-    Self: Sized,
-{
-    type Jacobian = Vec<Vec<f64>>;
-    type Hessian = Vec<Vec<f64>>;
-    type OperatorOutput = Vec<f64>;
-
-    fn forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
-        forward_diff_vec_f64(self, f)
-    }
-
-    fn central_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
-        central_diff_vec_f64(self, f)
-    }
-
-    fn forward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
-        forward_jacobian_vec_f64(self, fs)
-    }
-
-    fn central_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
-        central_jacobian_vec_f64(self, fs)
-    }
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Result<Self::Jacobian, FiniteDiffError>;
 
-    fn forward_jacobian_vec_prod(
+    /// Same as `forward_jacobian`, but returns the transposed Jacobian, which has one row per
+    /// function value and one column per parameter. Entry `[i][j]` is entry `[j][i]` of
+    /// `forward_jacobian`, i.e. the derivative of the `i`th function value with respect to the
+    /// `j`th parameter.
+    ///
+    /// The derivatives with respect to each parameter are written directly to the corresponding
+    /// column, so no separate transpose and no intermediate Jacobian are needed. This is the
+    /// layout expected by many linear algebra routines, for instance for the normal equations.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+    ///
+    /// ```
+    /// use finitediff::FiniteDiff;
+    ///
+    /// let fs = |x: &Vec<f64>| vec![x[0] * x[1], x[1].powi(2), x[0]];
+    /// let jacobian_t = vec![2.0f64, 3.0].forward_jacobian_transposed(&fs);
+    /// assert_eq!(jacobian_t.len(), 3);
+    /// # assert!((jacobian_t[0][0] - 3.0).abs() < 1e-6);
+    /// # assert!((jacobian_t[1][1] - 6.0).abs() < 1e-6);
+    /// # assert!((jacobian_t[2][0] - 1.0).abs() < 1e-6);
+    /// ```
+    fn forward_jacobian_transposed(
         &self,
         fs: &dyn Fn(&Self) -> Self::OperatorOutput,
-        p: &Self,
-    ) -> Self {
-        forward_jacobian_vec_prod_vec_f64(self, fs, p)
-    }
+    ) -> Self::Jacobian;
 
-    fn central_jacobian_vec_prod(
+    /// Same as `forward_jacobian`, but calls `on_col(i)` after the derivatives with respect to the
+    /// `i`th parameter, i.e. the `i`th row of the returned Jacobian, have been computed. This can
+    /// be used to report the progress of expensive functions.
+    ///
+    /// `on_col` returns whether the computation should continue. If it returns `false`, no further
+    /// evaluations are made and `None` is returned, for instance to honor a cancellation request.
+    /// For a parameter vector of length `n`, this requires at most `n+1` evaluations of `fs`.
+    fn forward_jacobian_with_progress(
         &self,
         fs: &dyn Fn(&Self) -> Self::OperatorOutput,
-        p: &Self,
-    ) -> Self {
-        central_jacobian_vec_prod_vec_f64(self, fs, p)
-    }
+        on_col: &mut dyn FnMut(usize) -> bool,
+    ) -> Option<Self::Jacobian>;
 
-    fn forward_jacobian_pert(
+    /// Diagonal of the Jacobian of an elementwise function, calculated as
+    ///
+    /// `dfs_i/dx_i (x) \approx (fs_i(x + h_i * e_i) - fs_i(x))/h_i  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector and `h_i = sqrt(EPS_F64) * max(|x_i|, 1)`. This assumes
+    /// that `fs` is elementwise, i.e. that the `i`th output only depends on the `i`th parameter, in
+    /// which case the Jacobian is diagonal. This is not checked: the off-diagonal entries are never
+    /// computed. Only the `n` diagonal entries are stored, which requires `O(n)` instead of
+    /// `O(n^2)` memory. Panics if `fs` returns fewer values than there are parameters.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+    fn forward_jacobian_diagonal(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self;
+
+    /// Same as `forward_jacobian`, but the row of the `i`th parameter is set to exactly zero if
+    /// perturbing it changes none of the values of `fs` by more than `zero_tol`.
+    ///
+    /// Parameters on which `fs` does not depend therefore yield exact zeros instead of rounding
+    /// noise, which keeps the sparsity structure of the Jacobian intact.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+    fn forward_jacobian_thresholded(
         &self,
         fs: &dyn Fn(&Self) -> Self::OperatorOutput,
-        pert: &PerturbationVectors,
-    ) -> Self::Jacobian {
-        forward_jacobian_pert_vec_f64(self, fs, pert)
-    }
+        zero_tol: Self::Scalar,
+    ) -> Self::Jacobian;
 
-    fn central_jacobian_pert(
+    /// Warm-started `forward_jacobian` for iterative methods, in which the Jacobian changes little
+    /// between iterations.
+    ///
+    /// Only the rows of the parameters which moved by more than `step_tol` since `x_prev`,
+    /// i.e. `|x_i - x_prev_i| > step_tol`, are recomputed. The remaining rows are copied from
+    /// `prev`, the Jacobian at `x_prev`. This is an approximation: a copied row holds the
+    /// derivatives at `x_prev` and ignores how they depend on the other parameters which moved.
+    /// Unlike Broyden's method, the refreshed rows are exact finite differences. For
+    /// `step_tol = 0` and parameters which all moved, the result equals `forward_jacobian`.
+    /// For `k` moved parameters, this requires `k+1` evaluations of `fs`, or none if `k = 0`.
+    ///
+    /// Panics if `x_prev` or `prev` do not match the number of parameters.
+    ///
+    /// ```
+    /// use finitediff::FiniteDiff;
+    ///
+    /// let fs = |x: &Vec<f64>| vec![x[0] * x[1], x[1].powi(2)];
+    /// let x_prev = vec![1.0f64, 2.0];
+    /// let prev = x_prev.forward_jacobian(&fs);
+    ///
+    /// // only the second parameter moved, so only its row is recomputed
+    /// let x = vec![1.0f64, 2.5];
+    /// let jacobian = x.forward_jacobian_update(&fs, &prev, &x_prev, 1e-3);
+    /// assert_eq!(jacobian[0], prev[0]);
+    /// assert!((jacobian[1][1] - 5.0).abs() < 1e-6);
+    /// ```
+    fn forward_jacobian_update(
         &self,
         fs: &dyn Fn(&Self) -> Self::OperatorOutput,
-        pert: &PerturbationVectors,
-    ) -> Self::Jacobian {
-        central_jacobian_pert_vec_f64(self, fs, pert)
-    }
-
-    fn forward_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
-        forward_hessian_vec_f64(self, g)
-    }
+        prev: &Self::Jacobian,
+        x_prev: &Self,
+        step_tol: Self::Scalar,
+    ) -> Self::Jacobian;
 
-    fn central_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
-        central_hessian_vec_f64(self, g)
-    }
+    /// Returns the points at which `forward_jacobian` evaluates `fs`, such that the evaluations
+    /// can be carried out elsewhere, for instance in a single batch.
+    ///
+    /// The first point is `x` itself, followed by `x + h_i * e_i` with
+    /// `h_i = sqrt(EPS_F64) * max(|x_i|, 1)` for `i = 0, ..., n-1`. Pass the values of `fs` at
+    /// these points, in the same order, to `forward_jacobian_from_values`.
+    fn forward_jacobian_points(&self) -> Vec<Self>;
 
-    fn forward_hessian_vec_prod(
-        &self,
-        g: &dyn Fn(&Self) -> Self::OperatorOutput,
-        p: &Self,
-    ) -> Self {
-        forward_hessian_vec_prod_vec_f64(self, g, p)
-    }
+    /// Assembles the forward difference Jacobian from `values`, where `values[k]` is the value of
+    /// `fs` at the `k`th point returned by `forward_jacobian_points`. The result is identical to
+    /// `forward_jacobian`.
+    ///
+    /// Panics if `values` does not contain exactly `n+1` values.
+    fn forward_jacobian_from_values(&self, values: &[Self::OperatorOutput]) -> Self::Jacobian;
 
-    fn central_hessian_vec_prod(
+    /// Same as `forward_jacobian`, but the perturbations of the individual parameters are
+    /// evaluated in parallel using rayon.
+    ///
+    /// Each worker operates on its own copy of the parameter vector.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+    #[cfg(feature = "rayon")]
+    fn forward_jacobian_par(
         &self,
-        g: &dyn Fn(&Self) -> Self::OperatorOutput,
-        p: &Self,
-    ) -> Self {
-        central_hessian_vec_prod_vec_f64(self, g, p)
-    }
+        fs: &(dyn Fn(&Self) -> Self::OperatorOutput + Sync),
+    ) -> Self::Jacobian;
 
-    fn forward_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self::Hessian {
-        forward_hessian_nograd_vec_f64(self, f)
-    }
+    /// Calculation of the Jacobian J(x) of a vector function `fs` using backward differences:
+    ///
+    /// `dfs/dx_i (x) \approx (fs(x) - fs(x - h_i * e_i))/h_i  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector and `h_i = sqrt(EPS_F64) * max(|x_i|, 1)`.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+    fn backward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian;
 
-    fn forward_hessian_nograd_sparse(
+    /// Calculation of the Jacobian J(x) of a vector function `fs` using central differences:
+    ///
+    /// `dfs/dx_i (x) \approx (fs(x + h_i * e_i) - fs(x - h_i * e_i))/(2.0 * h_i)  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector and `h_i = sqrt(EPS_F64) * max(|x_i|, 1)`. The
+    /// parameters are perturbed in ascending order of `i`, first in positive and then in negative
+    /// direction.
+    ///
+    /// Unlike `central_diff`, which perturbs by `central_step(x_i) = EPS_F64^(1/3) * max(|x_i|, 1)`,
+    /// this keeps the step of `forward_jacobian`, see `default_step_for`, so that its results do
+    /// not change compared to earlier versions. The same holds for `central_hessian`, while
+    /// `central_jacobian_vec_prod` and `central_hessian_vec_prod` perturb by `sqrt(EPS_F64)`.
+    /// For a parameter vector of length `n`, this requires `2*n` evaluations of `fs`.
+    fn central_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian;
+
+    /// Same as `forward_jacobian`, but writes the Jacobian into `out` instead of allocating it.
+    /// `out` needs to have the shape of the Jacobian, i.e. one row per parameter and one column
+    /// per function value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` does not have the shape of the Jacobian.
+    fn forward_jacobian_into(
         &self,
-        f: &dyn Fn(&Self) -> f64,
-        indices: Vec<[usize; 2]>,
-    ) -> Self::Hessian {
-        forward_hessian_nograd_sparse_vec_f64(self, f, indices)
-    }
-}
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    );
 
-#[cfg(feature = "ndarray")]
-impl FiniteDiff for ndarray::Array1<f64>
-where
-    Self: Sized,
-{
-    type Jacobian = ndarray::Array2<f64>;
-    type Hessian = ndarray::Array2<f64>;
-    type OperatorOutput = ndarray::Array1<f64>;
+    /// Same as `central_jacobian`, but writes the Jacobian into `out` instead of allocating it.
+    /// See `forward_jacobian_into` for the requirements on `out`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` does not have the shape of the Jacobian.
+    fn central_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    );
 
-    fn forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
-        forward_diff_ndarray_f64(self, f)
-    }
+    /// Calculation of the Jacobian J(x) of a vector function `fs` using a five-point stencil:
+    ///
+    /// `dfs/dx_i (x) \approx (-fs(x + 2h * e_i) + 8fs(x + h * e_i) - 8fs(x - h * e_i) + fs(x - 2h * e_i))/(12h)  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector and `h = EPS_F64^(1/5)`.
+    /// For a parameter vector of length `n`, this requires `4*n` evaluations of `fs`.
+    fn central_jacobian_5(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian;
 
-    fn central_diff(&self, f: &dyn Fn(&ndarray::Array1<f64>) -> f64) -> Self {
-        central_diff_ndarray_f64(self, f)
-    }
+    /// Calculation of the Jacobian J(x) of a vector function `fs` using the second-order accurate
+    /// three-point forward stencil:
+    ///
+    /// `dfs/dx_i (x) \approx (-3fs(x) + 4fs(x + h * e_i) - fs(x + 2h * e_i))/(2h)  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector and `h = EPS_F64^(1/3)`.
+    /// For a parameter vector of length `n`, this requires `2*n+1` evaluations of `fs`.
+    fn forward_jacobian_2(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian;
 
-    fn forward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
-        forward_jacobian_ndarray_f64(self, fs)
-    }
+    /// Calculation of the Jacobian J(x) of a vector function `fs` using forward differences with a
+    /// separate step size for each parameter:
+    ///
+    /// `dfs/dx_i (x) \approx (fs(x + steps_i * e_i) - fs(x))/steps_i  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+    fn forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian;
 
-    fn central_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
-        central_jacobian_ndarray_f64(self, fs)
+    /// Same as `forward_jacobian_scaled`, but returns an error if `steps` does not have the same
+    /// length as the parameter vector, if a step is lost to rounding (`x_i + steps_i == x_i`) or
+    /// if the result contains non-finite values.
+    fn try_forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError>;
+
+    /// Calculation of the Jacobian J(x) of a vector function `fs` using central differences with a
+    /// separate step size for each parameter:
+    ///
+    /// `dfs/dx_i (x) \approx (fs(x + steps_i * e_i) - fs(x - steps_i * e_i))/(2.0 * steps_i)  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector.
+    /// For a parameter vector of length `n`, this requires `2*n` evaluations of `fs`.
+    fn central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian;
+
+    /// Same as `central_jacobian_scaled`, but returns an error if `steps` does not have the same
+    /// length as the parameter vector, if a step is lost to rounding (`x_i + steps_i == x_i` or
+    /// `x_i - steps_i == x_i`) or if the result contains non-finite values.
+    fn try_central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError>;
+
+    /// Same as `forward_jacobian`, which scales its steps with the magnitude of each parameter.
+    #[deprecated(
+        since = "0.1.3",
+        note = "use `forward_jacobian`, whose steps are relative as well"
+    )]
+    fn forward_jacobian_relative(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Self::Jacobian {
+        self.forward_jacobian(fs)
     }
 
+    /// Calculation of the product of the Jacobian J(x) of a vector function `fs` with a vector `p`
+    /// using forward differences:
+    ///
+    /// `J(x)*p \approx (fs(x + sqrt(EPS_F64) * p) - fs(x))/sqrt(EPS_F64)  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector.
+    /// This requires 2 evaluations of `fs`.
     fn forward_jacobian_vec_prod(
         &self,
         fs: &dyn Fn(&Self) -> Self::OperatorOutput,
         p: &Self,
-    ) -> Self {
-        forward_jacobian_vec_prod_ndarray_f64(self, fs, p)
-    }
+    ) -> Self;
+
+    /// Same as `forward_jacobian_vec_prod`, but the direction `p` is normalized:
+    ///
+    /// `J(x)*p \approx |p| * (fs(x + sqrt(EPS_F64) * p/|p|) - fs(x))/sqrt(EPS_F64)`
+    ///
+    /// The parameters are perturbed by `sqrt(EPS_F64) * p`, therefore a long vector `p` moves `x`
+    /// out of the region in which `fs` is approximately linear, and the truncation error grows
+    /// with `|p|^2`. With the normalized direction, the step is `sqrt(EPS_F64)` regardless of
+    /// `|p|` and the error only grows linearly with `|p|`. A zero vector `p` yields a zero
+    /// product.
+    /// This requires 2 evaluations of `fs`.
+    fn forward_jacobian_vec_prod_normalized(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self;
+
+    /// Same as `forward_jacobian_vec_prod`, but uses the provided value `fx = fs(x)` instead of
+    /// evaluating `fs` at the unperturbed position. This pays off if the product is needed for
+    /// many vectors `p` at the same `x`, for instance in Krylov solvers such as GMRES.
+    /// This requires 1 evaluation of `fs`.
+    fn forward_jacobian_vec_prod_at(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+        fx: &Self::OperatorOutput,
+    ) -> Self;
+
+    /// Same as `forward_jacobian_vec_prod`, but returns an error if `p` does not have the same
+    /// length as the parameter vector or if the result contains non-finite values.
+    fn try_forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError>;
 
+    /// Calculation of the product of the Jacobian J(x) of a vector function `fs` with a vector `p`
+    /// using central differences:
+    ///
+    /// `J(x)*p \approx (fs(x + sqrt(EPS_F64) * p) - fs(x - sqrt(EPS_F64) * p))/(2.0 * sqrt(EPS_F64))  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector.
+    /// This requires 2 evaluations of `fs`.
     fn central_jacobian_vec_prod(
         &self,
         fs: &dyn Fn(&Self) -> Self::OperatorOutput,
         p: &Self,
-    ) -> Self {
-        central_jacobian_vec_prod_ndarray_f64(self, fs, p)
-    }
+    ) -> Self;
+
+    /// Same as `central_jacobian_vec_prod`, but returns an error if `p` does not have the same
+    /// length as the parameter vector or if the result contains non-finite values.
+    fn try_central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError>;
+
+    /// Calculation of the product of the transposed Jacobian J(x)^T of a vector function `fs`
+    /// with a vector `v` (vector-Jacobian product) using forward differences.
+    ///
+    /// Finite differences cannot compute this product directly, therefore the Jacobian is
+    /// computed via `forward_jacobian` and contracted with `v` using compensated summation. `v`
+    /// needs to have the same length as the output of `fs`.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs` plus one
+    /// contraction.
+    fn forward_vjp(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        v: &Self::OperatorOutput,
+    ) -> Self;
 
     fn forward_jacobian_pert(
         &self,
         fs: &dyn Fn(&Self) -> Self::OperatorOutput,
         pert: &PerturbationVectors,
-    ) -> Self::Jacobian {
-        forward_jacobian_pert_ndarray_f64(self, fs, pert)
+    ) -> Self::Jacobian;
+
+    /// Same as `forward_jacobian_pert`, but only the entries covered by `pert` are computed and
+    /// stored in a `SparseJacobian` in CSR format. This avoids allocating the dense Jacobian.
+    fn forward_jacobian_pert_sparse(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> SparseJacobian<Self::Scalar>;
+
+    /// Forward Jacobian of a function with a banded Jacobian, where the `j`th function only
+    /// depends on the parameters `j - kl..=j + ku`.
+    ///
+    /// Parameters which are `kl + ku + 1` apart influence disjoint sets of functions and are
+    /// perturbed together (Curtis, Powell and Reid). This requires `kl + ku + 2` evaluations of
+    /// `fs` instead of `n + 1`, independent of the number of parameters `n`. The result is
+    /// returned in compact banded storage.
+    fn forward_jacobian_banded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        kl: usize,
+        ku: usize,
+    ) -> BandedJacobian<Self::Scalar>;
+
+    fn central_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian;
+
+    /// Determines the sparsity pattern of the Jacobian of `fs` and builds `PerturbationVectors`
+    /// which can directly be passed to `forward_jacobian_pert` and `central_jacobian_pert`.
+    ///
+    /// The Jacobian is probed once using forward differences. All entries with an absolute value
+    /// larger than `threshold` are considered nonzero, and parameters which do not share any
+    /// function index are greedily grouped together. Entries which happen to vanish at `x` are not
+    /// detected, therefore `x` should be chosen with care.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+    fn detect_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: Self::Scalar,
+    ) -> PerturbationVectors;
+
+    /// Determines the sparsity pattern of the Jacobian of `fs` in coordinate format.
+    ///
+    /// The Jacobian is probed in the same way as in `detect_sparsity`, but the nonzero entries are
+    /// returned as data, which is useful for diagnostics or for sparse solvers which need the
+    /// pattern before the values. `SparsityPattern::perturbation_vectors` yields the same groups as
+    /// `detect_sparsity`.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `fs`.
+    fn jacobian_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: Self::Scalar,
+    ) -> SparsityPattern;
+
+    /// Calculation of the Hessian using forward differences
+    ///
+    /// `dg/dx_i (x) \approx (g(x + h_i * e_i) - g(x))/h_i  \forall i`
+    ///
+    /// where `g` is a function which computes the gradient of some other function f, `e_i` is
+    /// the `i`th unit vector and `h_i = sqrt(EPS_F64) * max(|x_i|, 1)`. The result is made symmetric by replacing the entries `(i, j)` and
+    /// `(j, i)` by their mean, see `forward_hessian_sym`. `g(x)` is evaluated first, followed by
+    /// the perturbations in ascending order of `i`.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `g`.
+    fn forward_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian;
+
+    /// Same as `forward_hessian`, but the symmetry of the Hessian is only restored if `restore` is
+    /// `true`, in which case the result is identical to the one of `forward_hessian`. Otherwise,
+    /// the raw forward differences of `g` are returned, which are in general slightly asymmetric
+    /// due to the truncation and rounding errors of the finite differences. The asymmetry
+    /// `|H_ij - H_ji|` is an indication of the accuracy of the Hessian.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `g`.
+    fn forward_hessian_sym(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        restore: bool,
+    ) -> Self::Hessian;
+
+    /// Same as `forward_hessian`, but for a gradient which may fail, for instance because an inner
+    /// solver does not converge. The first error returned by `g` is passed on and no further
+    /// evaluations are made.
+    /// For a parameter vector of length `n`, this requires at most `n+1` evaluations of `g`.
+    fn try_forward_hessian<E>(
+        &self,
+        g: &dyn Fn(&Self) -> Result<Self::OperatorOutput, E>,
+    ) -> Result<Self::Hessian, E>;
+
+    /// Calculation of the Hessian using central differences
+    ///
+    /// `dg/dx_i (x) \approx (g(x + h_i * e_i) - g(x - h_i * e_i))/(2.0 * h_i)  \forall i`
+    ///
+    /// where `g` is a function which computes the gradient of some other function f, `e_i` is
+    /// the `i`th unit vector and `h_i = sqrt(EPS_F64) * max(|x_i|, 1)`. The parameters are perturbed in ascending order of `i`, first in
+    /// positive and then in negative direction.
+    /// For a parameter vector of length `n`, this requires `2*n` evaluations of `g`.
+    fn central_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian;
+
+    /// Same as `forward_hessian`, which scales its steps with the magnitude of each parameter.
+    #[deprecated(
+        since = "0.1.3",
+        note = "use `forward_hessian`, whose steps are relative as well"
+    )]
+    fn forward_hessian_relative(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
+        self.forward_hessian(g)
+    }
+
+    /// Calculation of the product of the Hessian H(x) of a function `g` with a vector `p`
+    /// using forward differences:
+    ///
+    /// `H(x)*p \approx (g(x + sqrt(EPS_F64) * p) - g(x))/sqrt(EPS_F64)  \forall i`
+    ///
+    /// where `g` is a function which computes the gradient of some other function f and `e_i` is
+    /// the `i`th unit vector. Unlike `forward_hessian`, the step is not scaled by the magnitude of
+    /// the parameters, because all of them are perturbed at once along `p`. For large parameters,
+    /// the magnitude of `p` has to be chosen accordingly.
+    /// This requires 2 evaluations of `g`.
+    fn forward_hessian_vec_prod(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput, p: &Self)
+        -> Self;
+
+    /// Same as `forward_hessian_vec_prod`, but returns an error if `p` does not have the same
+    /// length as the parameter vector or if the result contains non-finite values.
+    fn try_forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError>;
+
+    /// Calculation of the product of the Hessian H(x) of a function `g` with a vector `p`
+    /// using central differences:
+    ///
+    /// `H(x)*p \approx (g(x + sqrt(EPS_F64) * p) - g(x - sqrt(EPS_F64) * p))/(2.0 * sqrt(EPS_F64))  \forall i`
+    ///
+    /// where `g` is a function which computes the gradient of some other function f and `e_i` is
+    /// the `i`th unit vector. As for `forward_hessian_vec_prod`, the step is not scaled by the
+    /// magnitude of the parameters.
+    /// This requires 2 evaluations of `g`.
+    fn central_hessian_vec_prod(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput, p: &Self)
+        -> Self;
+
+    /// Calculation of the product of the Hessian H(x) of a function `f` with a vector `p`
+    /// without knowledge of the gradient:
+    ///
+    /// `(H(x)*p)_i \approx (f(x + h * e_i + h * p) - f(x + h * e_i) - f(x + h * p) + f(x))/h^2  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector and `h = EPS_F64^(1/3)`. The approximation is only
+    /// first-order accurate in `h`; if a gradient function is available, prefer
+    /// `forward_hessian_vec_prod`.
+    /// For a parameter vector of length `n`, this requires `2*n+2` evaluations of `f`.
+    fn forward_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> Self::Scalar, p: &Self) -> Self;
+
+    /// Calculation of the product of the Hessian H(x) of a function `f` with a vector `p`
+    /// without knowledge of the gradient, using central differences for both the gradient and
+    /// the directional derivative:
+    ///
+    /// `(H(x)*p)_i \approx (f(x + h * p + h * e_i) - f(x + h * p - h * e_i) - f(x - h * p + h * e_i) + f(x - h * p - h * e_i))/(4h^2)  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector and `h = EPS_F64^(1/4)`. This is the central
+    /// difference `(grad(x + h * p) - grad(x - h * p))/(2h)` of central difference gradients.
+    /// Unlike `forward_hessian_vec_prod_nograd`, it is second-order accurate in `h`.
+    /// For a parameter vector of length `n`, this requires `4*n` evaluations of `f`.
+    fn central_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> Self::Scalar, p: &Self) -> Self;
+
+    /// Checks an analytic gradient against the central difference approximation `central_diff`.
+    ///
+    /// An entry passes if either its absolute or its relative error does not exceed `tol`. If any
+    /// entry fails, a `GradCheckReport` with the offending entries is returned.
+    /// For a parameter vector of length `n`, this requires `2*n` evaluations of `f`.
+    fn check_gradient(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+        analytic: &Self,
+        tol: Self::Scalar,
+    ) -> Result<(), GradCheckReport<Self::Scalar>>;
+
+    /// Checks an analytic Jacobian against the central difference approximation
+    /// `central_jacobian`.
+    ///
+    /// The Jacobian needs to be laid out the same way as the one returned by `central_jacobian`.
+    /// See `check_gradient` for details on `tol` and the returned report.
+    /// For a parameter vector of length `n`, this requires `2*n` evaluations of `fs`.
+    fn check_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        analytic: &Self::Jacobian,
+        tol: Self::Scalar,
+    ) -> Result<(), GradCheckReport<Self::Scalar>>;
+
+    /// Same as `central_hessian_vec_prod`, but returns an error if `p` does not have the same
+    /// length as the parameter vector or if the result contains non-finite values.
+    fn try_central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError>;
+
+    /// Calculation of the Hessian using forward differences without knowledge of the gradient:
+    ///
+    /// `df/(dx_i dx_j) (x) \approx (f(x + k * e_i + k * e_j) - f(x + k * e_i) - f(x + k * e_j) + f(x))/k^2  \forall i != j`
+    ///
+    /// where `e_i` and `e_j` are the `i`th and `j`th unit vector, respectively, and
+    /// `k = sqrt(2 * EPS_F64)`. On the diagonal, this stencil is dominated by rounding errors,
+    /// therefore the diagonal entries are computed with the central difference
+    ///
+    /// `df/(dx_i dx_i) (x) \approx (f(x + h * e_i) - 2 * f(x) + f(x - h * e_i))/h^2`
+    ///
+    /// with its own step `h = EPS_F64^(1/4)`, as in `forward_hessian_diagonal_nograd`.
+    ///
+    /// `f(x)` is evaluated first, then `f(x + k * e_i)` for all `i` in ascending order, and
+    /// finally the entries row by row, i.e. `(i, j)` for `i = 0, 1, ...` and
+    /// `j = 0, ..., i`, where the diagonal entry `(i, i)` evaluates `f(x + h * e_i)` and then
+    /// `f(x - h * e_i)`.
+    /// For a parameter vector of length `n`, this requires `(n^2 + 5n)/2 + 1` evaluations of `f`.
+    fn forward_hessian_nograd(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Self::Hessian;
+
+    /// Same as `forward_hessian_nograd`, but returns `None` if `cancel` is set before or during the
+    /// computation. The flag is checked before every row of the Hessian, which allows another
+    /// thread, for instance a user interface, to abort an expensive computation.
+    /// For a parameter vector of length `n`, this requires at most `(n^2 + 5n)/2 + 1` evaluations
+    /// of `f`.
+    fn forward_hessian_nograd_cancellable(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+        cancel: &AtomicBool,
+    ) -> Option<Self::Hessian>;
+
+    /// Same as `forward_hessian_nograd`, but the Hessian is never stored as a whole. Instead, it
+    /// is computed in blocks of `block_rows` consecutive rows, which are passed to `on_block`
+    /// together with the index of the first row of the block. The last block may be shorter.
+    /// This bounds the memory to `block_rows * n` values, which is useful for large `n` when the
+    /// Hessian is consumed row by row, for instance by an iterative solver.
+    ///
+    /// The entries are identical to the ones of `forward_hessian_nograd`. However, the symmetry of
+    /// the Hessian can only be exploited within a block, therefore this requires up to
+    /// `n^2 + 2n + 1` evaluations of `f`. Panics if `block_rows` is zero.
+    #[allow(clippy::type_complexity)]
+    fn forward_hessian_nograd_blocks(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+        block_rows: usize,
+        on_block: &mut dyn FnMut(usize, &[Vec<Self::Scalar>]),
+    );
+
+    /// Same as `forward_hessian_nograd`, but only the upper triangle of the symmetric Hessian is
+    /// stored, which requires `n*(n+1)/2` instead of `n^2` values. The entries are identical to
+    /// the ones of `forward_hessian_nograd`.
+    fn forward_hessian_nograd_packed(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+    ) -> PackedSymmetric<Self::Scalar>;
+
+    /// Calculation of the gradient and the Hessian without knowledge of the gradient in a single
+    /// pass.
+    ///
+    /// The Hessian is computed as in `forward_hessian_nograd`. The gradient is obtained from the
+    /// evaluations `f(x)` and `f(x + sqrt(2 * EPS_F64) * e_i)` which are needed for the Hessian
+    /// anyway:
+    ///
+    /// `df/dx_i (x) \approx (f(x + sqrt(2 * EPS_F64) * e_i) - f(x))/sqrt(2 * EPS_F64)  \forall i`
+    ///
+    /// For a parameter vector of length `n`, this requires `(n^2 + 5n)/2 + 1` evaluations of `f`,
+    /// which is `n+1` less than calling `forward_diff` and `forward_hessian_nograd` separately.
+    fn forward_grad_and_hessian_nograd(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+    ) -> (Self, Self::Hessian);
+
+    /// Calculation of the Hessian using central differences without knowledge of the gradient:
+    ///
+    /// `df/(dx_i dx_j) (x) \approx (f(x + h * e_i + h * e_j) - f(x + h * e_i - h * e_j) - f(x - h * e_i + h * e_j) + f(x - h * e_i - h * e_j))/(4 * h^2)  \forall i, j`
+    ///
+    /// where `e_i` and `e_j` are the `i`th and `j`th unit vector, respectively, and
+    /// `h = EPS_F64^(1/4)`. This is considerably more accurate than `forward_hessian_nograd`.
+    /// The entries `(i, j)` are computed row by row for `i = 0, 1, ...` and `j = 0, ..., i`, each
+    /// with the four evaluations in the order of the formula above.
+    /// For a parameter vector of length `n`, this requires `2*n*(n+1)` evaluations of `f`.
+    fn central_hessian_nograd(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Self::Hessian;
+
+    /// Function value, gradient and Hessian of `f` in a single call, intended for small problems.
+    ///
+    /// The gradient is computed with `central_diff` and the Hessian with `central_hessian_nograd`.
+    /// `f(x)` is evaluated once, returned as the value and reused for the diagonal of the Hessian,
+    /// which would otherwise evaluate `f` at `x` twice per diagonal entry. `f(x)` is evaluated
+    /// first, followed by the evaluations of the gradient and then of the Hessian.
+    /// For a parameter vector of length `n`, this requires `2*n^2 + 2*n + 1` evaluations of `f`,
+    /// compared to `2*n^2 + 4*n + 1` for separate calls.
+    fn derivatives(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+    ) -> Derivatives<Self::Scalar, Self, Self::Hessian>;
+
+    /// Calculation of a sparse Hessian using forward differences without knowledge of the gradient:
+    ///
+    /// `df/(dx_i dx_j) (x) \approx (f(x + k * e_i + k * e_j) - f(x + k * e_i) - f(x + k * e_j) + f(x))/k^2  \forall i != j`
+    ///
+    /// where `e_i` and `e_j` are the `i`th and `j`th unit vector, respectively, and
+    /// `k = sqrt(2 * EPS_F64)`. Diagonal entries `(i, i)` are computed with the central difference
+    /// of `forward_hessian_nograd`.
+    /// The indices which are to be evaluated need to be provided via `indices`. Note that due to
+    /// the symmetry of the Hessian, an index `(a, b)` will also compute the value of the Hessian at
+    /// `(b, a)`.
+    // /// For a parameter vector of length `n`, this requires `n*(n+1)/2` evaluations of `g`.
+    fn forward_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian;
+
+    /// Hessian of a cost function which is a sum of terms, `f(x) = sum_k g_k(x)`, where each
+    /// term only depends on a few parameters.
+    ///
+    /// Each entry of `terms` consists of a term `g_k` and the indices of the parameters it
+    /// depends on, which must not contain duplicates. The Hessian of each term is computed with
+    /// respect to its own parameters with the forward difference formula of
+    /// `forward_hessian_nograd` and added to the corresponding entries of the Hessian of `f`,
+    /// similar to the assembly of finite element matrices. All other entries of the Hessian of a
+    /// term are assumed to be zero.
+    /// A term with `k` indices requires `(k^2 + 5k)/2 + 1` evaluations, compared to
+    /// `(n^2 + 5n)/2 + 1` evaluations of `f` for `forward_hessian_nograd`.
+    ///
+    /// ```
+    /// use finitediff::FiniteDiff;
+    ///
+    /// let g1 = |x: &Vec<f64>| x[0].powi(2) * x[1];
+    /// let g2 = |x: &Vec<f64>| x[1] * x[2].powi(3);
+    ///
+    /// let x = vec![1.0f64, 2.0, 1.0];
+    /// let hessian = x.forward_hessian_nograd_additive(&[(&g1, vec![0, 1]), (&g2, vec![1, 2])]);
+    /// # assert!((hessian[0][0] - 4.0).abs() < 1e-5);
+    /// # assert!((hessian[0][1] - 2.0).abs() < 1e-5);
+    /// # assert!((hessian[1][2] - 3.0).abs() < 1e-5);
+    /// # assert!((hessian[2][2] - 12.0).abs() < 1e-5);
+    /// # assert_eq!(hessian[0][2], 0.0);
+    /// ```
+    #[allow(clippy::type_complexity)]
+    fn forward_hessian_nograd_additive(
+        &self,
+        terms: &[(&dyn Fn(&Self) -> Self::Scalar, Vec<usize>)],
+    ) -> Self::Hessian;
+
+    /// Calculation of a sparse Hessian using central differences without knowledge of the
+    /// gradient:
+    ///
+    /// `df/(dx_i dx_j) (x) \approx (f(x + h * e_i + h * e_j) - f(x + h * e_i - h * e_j) - f(x - h * e_i + h * e_j) + f(x - h * e_i - h * e_j))/(4 * h^2)`
+    ///
+    /// where `e_i` and `e_j` are the `i`th and `j`th unit vector, respectively, and
+    /// `h = EPS_F64^(1/4)`.
+    /// The indices which are to be evaluated need to be provided via `indices`. Note that due to
+    /// the symmetry of the Hessian, an index `(a, b)` will also compute the value of the Hessian at
+    /// `(b, a)`. All other entries are zero.
+    /// This requires 4 evaluations of `f` per index.
+    fn central_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> Self::Scalar,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian;
+
+    /// Calculation of the diagonal of the Hessian without knowledge of the gradient:
+    ///
+    /// `d^2f/dx_i^2 (x) \approx (f(x + h * e_i) - 2 * f(x) + f(x - h * e_i))/h^2  \forall i`
+    ///
+    /// where `e_i` is the `i`th unit vector and `h = EPS_F64^(1/4)`. The result is a vector of the
+    /// same length as the parameter vector, which is useful as a diagonal preconditioner.
+    /// For a parameter vector of length `n`, this requires `2*n+1` evaluations of `f`.
+    fn forward_hessian_diagonal_nograd(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Self;
+
+    /// Central difference gradient together with the diagonal of the Hessian, for instance for a
+    /// diagonal preconditioner of a quasi-Newton method.
+    ///
+    /// Both are computed from the same evaluations `f(x)` and `f(x +/- h * e_i)` with
+    /// `h = EPS_F64^(1/4)`, which is the step of `forward_hessian_diagonal_nograd`:
+    ///
+    /// `df/dx_i (x) \approx (f(x + h * e_i) - f(x - h * e_i))/(2 * h)  \forall i`
+    ///
+    /// `d^2f/dx_i^2 (x) \approx (f(x + h * e_i) - 2 * f(x) + f(x - h * e_i))/h^2  \forall i`
+    ///
+    /// The diagonal is identical to the one of `forward_hessian_diagonal_nograd`. Because of the
+    /// larger step, the gradient differs slightly from the one of `central_diff`.
+    /// For a parameter vector of length `n`, this requires `2*n+1` evaluations of `f`.
+    fn central_grad_and_diag(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> (Self, Self);
+
+    /// Same as `forward_diff`, but accepts a `FnMut` closure, for instance one which caches results or
+    /// counts evaluations.
+    fn forward_diff_mut(&self, f: &mut dyn FnMut(&Self) -> Self::Scalar) -> Self {
+        let f = RefCell::new(f);
+        self.forward_diff(&|x| (f.borrow_mut())(x))
+    }
+
+    /// Same as `backward_diff`, but accepts a `FnMut` closure.
+    fn backward_diff_mut(&self, f: &mut dyn FnMut(&Self) -> Self::Scalar) -> Self {
+        let f = RefCell::new(f);
+        self.backward_diff(&|x| (f.borrow_mut())(x))
+    }
+
+    /// Same as `central_diff`, but accepts a `FnMut` closure.
+    fn central_diff_mut(&self, f: &mut dyn FnMut(&Self) -> Self::Scalar) -> Self {
+        let f = RefCell::new(f);
+        self.central_diff(&|x| (f.borrow_mut())(x))
+    }
+
+    /// Central difference for cost functions with stochastic noise, for instance Monte Carlo
+    /// estimates.
+    ///
+    /// `f` is called in pairs: for every parameter `i`, `f(x + h * e_i)` is immediately followed by
+    /// `f(x - h * e_i)`, without any other evaluation in between. The first call of a pair is call
+    /// number `0, 2, 4, ...`. If both calls of a pair share their randomness (common random
+    /// numbers), for instance by reseeding the RNG before every even call, the noise cancels in the
+    /// difference. It is up to `f` to enforce this; otherwise the result equals `central_diff_mut`.
+    /// For a parameter vector of length `n`, this requires `2*n` evaluations of `f`.
+    fn central_diff_paired(&self, f: &mut dyn FnMut(&Self) -> Self::Scalar) -> Self {
+        self.central_diff_mut(f)
+    }
+
+    /// Same as `forward_jacobian`, but accepts a `FnMut` closure.
+    fn forward_jacobian_mut(
+        &self,
+        fs: &mut dyn FnMut(&Self) -> Self::OperatorOutput,
+    ) -> Self::Jacobian {
+        let fs = RefCell::new(fs);
+        self.forward_jacobian(&|x| (fs.borrow_mut())(x))
+    }
+
+    /// Same as `backward_jacobian`, but accepts a `FnMut` closure.
+    fn backward_jacobian_mut(
+        &self,
+        fs: &mut dyn FnMut(&Self) -> Self::OperatorOutput,
+    ) -> Self::Jacobian {
+        let fs = RefCell::new(fs);
+        self.backward_jacobian(&|x| (fs.borrow_mut())(x))
+    }
+
+    /// Same as `central_jacobian`, but accepts a `FnMut` closure.
+    fn central_jacobian_mut(
+        &self,
+        fs: &mut dyn FnMut(&Self) -> Self::OperatorOutput,
+    ) -> Self::Jacobian {
+        let fs = RefCell::new(fs);
+        self.central_jacobian(&|x| (fs.borrow_mut())(x))
+    }
+
+    /// Same as `forward_hessian`, but accepts a `FnMut` closure.
+    fn forward_hessian_mut(
+        &self,
+        g: &mut dyn FnMut(&Self) -> Self::OperatorOutput,
+    ) -> Self::Hessian {
+        let g = RefCell::new(g);
+        self.forward_hessian(&|x| (g.borrow_mut())(x))
+    }
+
+    /// Same as `central_hessian`, but accepts a `FnMut` closure.
+    fn central_hessian_mut(
+        &self,
+        g: &mut dyn FnMut(&Self) -> Self::OperatorOutput,
+    ) -> Self::Hessian {
+        let g = RefCell::new(g);
+        self.central_hessian(&|x| (g.borrow_mut())(x))
+    }
+
+    /// Same as `forward_hessian_nograd`, but accepts a `FnMut` closure.
+    fn forward_hessian_nograd_mut(
+        &self,
+        f: &mut dyn FnMut(&Self) -> Self::Scalar,
+    ) -> Self::Hessian {
+        let f = RefCell::new(f);
+        self.forward_hessian_nograd(&|x| (f.borrow_mut())(x))
+    }
+
+    /// Same as `forward_jacobian`, but returns `None` if `cancel` is set before or during the
+    /// computation. The flag is checked before every row of the Jacobian, which allows another
+    /// thread, for instance a user interface, to abort an expensive computation.
+    /// For a parameter vector of length `n`, this requires at most `n+1` evaluations of `fs`.
+    fn forward_jacobian_cancellable(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        cancel: &AtomicBool,
+    ) -> Option<Self::Jacobian> {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.forward_jacobian_with_progress(fs, &mut |_| !cancel.load(Ordering::Relaxed))
+    }
+
+    /// Same as `forward_diff`, but for a cost function with additional constant arguments
+    /// `params`, which are passed on unchanged to every evaluation of `f`.
+    ///
+    /// ```
+    /// use finitediff::FiniteDiff;
+    ///
+    /// let f = |x: &Vec<f64>, a: &[f64; 2]| a[0] * x[0] + a[1] * x[1].powi(2);
+    /// let grad = vec![1.0f64, 1.0].forward_diff_with(&f, &[2.0, 3.0]);
+    /// # assert!((grad[0] - 2.0).abs() < 1e-6);
+    /// # assert!((grad[1] - 6.0).abs() < 1e-6);
+    /// ```
+    fn forward_diff_with<P>(&self, f: &dyn Fn(&Self, &P) -> Self::Scalar, params: &P) -> Self {
+        self.forward_diff(&|x| f(x, params))
+    }
+
+    /// Same as `forward_jacobian`, but for a function with additional constant arguments `params`,
+    /// which are passed on unchanged to every evaluation of `fs`.
+    fn forward_jacobian_with<P>(
+        &self,
+        fs: &dyn Fn(&Self, &P) -> Self::OperatorOutput,
+        params: &P,
+    ) -> Self::Jacobian {
+        self.forward_jacobian(&|x| fs(x, params))
+    }
+
+    /// Same as `forward_diff`, but the gradient is wrapped in a `Gradient`, which prevents it from
+    /// being confused with a parameter vector. The entries are identical.
+    /// For a parameter vector of length `n`, this requires `n+1` evaluations of `f`.
+    fn forward_diff_typed(&self, f: &dyn Fn(&Self) -> Self::Scalar) -> Gradient<Self> {
+        Gradient::new(self.forward_diff(f))
+    }
+}
+
+impl<F> FiniteDiff for Vec<F>
+where
+    F: FloatEps,
+{
+    type Scalar = F;
+    type Jacobian = Vec<Vec<F>>;
+    type Hessian = Vec<Vec<F>>;
+    type OperatorOutput = Vec<F>;
+
+    fn forward_diff(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        forward_diff_vec(self, f)
+    }
+
+    fn try_forward_diff(&self, f: &dyn Fn(&Self) -> F) -> Result<Self, FiniteDiffError> {
+        try_forward_diff_vec(self, f)
+    }
+
+    fn forward_diff_precomputed(&self, f: &dyn Fn(&Self) -> F, fx: F) -> Self {
+        forward_diff_precomputed_vec(self, f, fx)
+    }
+
+    fn value_and_forward_diff(&self, f: &dyn Fn(&Self) -> F) -> (F, Self) {
+        let fx = (f)(self);
+        (fx, self.forward_diff_precomputed(f, fx))
+    }
+
+    fn forward_diff_with_samples(&self, f: &dyn Fn(&Self) -> F) -> (Self, F, Vec<F>) {
+        forward_diff_with_samples_vec(self, f)
+    }
+
+    fn forward_diff_traced(&self, f: &dyn Fn(&Self) -> F, sink: &mut dyn FnMut(&[F], F)) -> Self {
+        let sink = RefCell::new(sink);
+        self.forward_diff(&|x| {
+            let fx = f(x);
+            (sink.borrow_mut())(x, fx);
+            fx
+        })
+    }
+
+    fn forward_diff_subset(&self, f: &dyn Fn(&Self) -> F, indices: &[usize]) -> Self {
+        forward_diff_subset_vec(self, f, indices)
+    }
+
+    fn forward_diff_masked(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        active: &[bool],
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), active.len())?;
+        Ok(self.forward_diff_subset(f, &mask_indices(active)))
+    }
+
+    fn forward_diff_with_error(&self, f: &dyn Fn(&Self) -> F) -> (Self, Self) {
+        forward_diff_with_error_vec(self, f)
+    }
+
+    fn backward_diff(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        backward_diff_vec(self, f)
+    }
+
+    fn central_diff(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        central_diff_vec(self, f)
+    }
+
+    fn diff_mixed(&self, f: &dyn Fn(&Self) -> F, policy: &[Method]) -> Self {
+        diff_mixed_vec(self, f, policy)
+    }
+
+    fn central_diff_5(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        central_diff_5_vec(self, f)
+    }
+
+    fn forward_diff_2(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        forward_diff_2_vec(self, f)
+    }
+
+    fn forward_diff_order(&self, f: &dyn Fn(&Self) -> F, order: StencilOrder) -> Self {
+        forward_diff_order_vec(self, f, order)
+    }
+
+    fn central_diff_order(&self, f: &dyn Fn(&Self) -> F, order: StencilOrder) -> Self {
+        central_diff_order_vec(self, f, order)
+    }
+
+    fn forward_diff_scaled(&self, f: &dyn Fn(&Self) -> F, steps: &Self) -> Self {
+        forward_diff_scaled_vec(self, f, steps)
+    }
+
+    fn forward_diff_with_scaling(&self, f: &dyn Fn(&Self) -> F, scale: &Self) -> Self {
+        forward_diff_with_scaling_vec(self, f, scale)
+    }
+
+    fn forward_diff_bounded(&self, f: &dyn Fn(&Self) -> F, lower: &Self, upper: &Self) -> Self {
+        forward_diff_bounded_vec(self, f, lower, upper)
+    }
+
+    fn try_forward_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_forward_steps(self.iter(), steps.iter())?;
+        let out = forward_diff_scaled_vec(self, f, steps);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_diff_scaled(&self, f: &dyn Fn(&Self) -> F, steps: &Self) -> Self {
+        central_diff_scaled_vec(self, f, steps)
+    }
+
+    fn try_central_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_steps(self.iter(), steps.iter())?;
+        let out = central_diff_scaled_vec(self, f, steps);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn richardson_diff(&self, f: &dyn Fn(&Self) -> F, levels: usize) -> Self {
+        richardson_diff_vec(self, f, levels)
+    }
+
+    fn adaptive_diff(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        adaptive_diff_vec(self, f)
+    }
+
+    fn auto_diff(&self, f: &dyn Fn(&Self) -> F, target_rel_err: F) -> Self {
+        auto_diff_vec(self, f, target_rel_err)
+    }
+
+    fn estimate_diff_noise(&self, f: &mut dyn FnMut(&Self) -> F, repeats: usize) -> Self {
+        estimate_diff_noise_vec(self, f, repeats)
+    }
+
+    fn forward_directional_diff(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> F {
+        forward_directional_diff_vec(self, f, p)
+    }
+
+    fn central_directional_diff(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> F {
+        central_directional_diff_vec(self, f, p)
+    }
+
+    fn forward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        forward_jacobian_vec(self, fs)
+    }
+
+    fn try_forward_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        let out = try_forward_jacobian_vec(self, fs)?;
+        check_finite_jacobian(indexed_rows(&out))?;
+        Ok(out)
+    }
+
+    fn forward_jacobian_transposed(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Self::Jacobian {
+        forward_jacobian_transposed_vec(self, fs)
+    }
+
+    fn forward_jacobian_with_progress(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        on_col: &mut dyn FnMut(usize) -> bool,
+    ) -> Option<Self::Jacobian> {
+        forward_jacobian_with_progress_vec(self, fs, on_col)
+    }
+
+    fn forward_jacobian_diagonal(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self {
+        forward_jacobian_diagonal_vec(self, fs)
+    }
+
+    fn forward_jacobian_thresholded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        zero_tol: F,
+    ) -> Self::Jacobian {
+        forward_jacobian_thresholded_vec(self, fs, zero_tol)
+    }
+
+    fn forward_jacobian_update(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        prev: &Self::Jacobian,
+        x_prev: &Self,
+        step_tol: F,
+    ) -> Self::Jacobian {
+        forward_jacobian_update_vec(self, fs, prev, x_prev, step_tol)
+    }
+
+    fn forward_jacobian_points(&self) -> Vec<Self> {
+        forward_jacobian_points_vec(self)
+    }
+
+    fn forward_jacobian_from_values(&self, values: &[Self::OperatorOutput]) -> Self::Jacobian {
+        forward_jacobian_from_values_vec(self, values)
+    }
+
+    #[cfg(feature = "rayon")]
+    fn forward_jacobian_par(
+        &self,
+        fs: &(dyn Fn(&Self) -> Self::OperatorOutput + Sync),
+    ) -> Self::Jacobian {
+        forward_jacobian_par_vec(self, fs)
+    }
+
+    fn backward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        backward_jacobian_vec(self, fs)
+    }
+
+    fn central_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        central_jacobian_vec(self, fs)
+    }
+
+    fn forward_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        forward_jacobian_into_vec(self, fs, out)
+    }
+
+    fn central_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        central_jacobian_into_vec(self, fs, out)
+    }
+
+    fn central_jacobian_5(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        central_jacobian_5_vec(self, fs)
+    }
+
+    fn forward_jacobian_2(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        forward_jacobian_2_vec(self, fs)
+    }
+
+    fn forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        forward_jacobian_scaled_vec(self, fs, steps)
+    }
+
+    fn try_forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_forward_steps(self.iter(), steps.iter())?;
+        let out = forward_jacobian_scaled_vec(self, fs, steps);
+        check_finite_jacobian(indexed_rows(&out))?;
+        Ok(out)
+    }
+
+    fn central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        central_jacobian_scaled_vec(self, fs, steps)
+    }
+
+    fn try_central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_steps(self.iter(), steps.iter())?;
+        let out = central_jacobian_scaled_vec(self, fs, steps);
+        check_finite_jacobian(indexed_rows(&out))?;
+        Ok(out)
+    }
+
+    fn forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        forward_jacobian_vec_prod_vec(self, fs, p)
+    }
+
+    fn forward_jacobian_vec_prod_normalized(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        forward_jacobian_vec_prod_normalized_vec(self, fs, p)
+    }
+
+    fn forward_jacobian_vec_prod_at(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+        fx: &Self::OperatorOutput,
+    ) -> Self {
+        forward_jacobian_vec_prod_at_vec(self, fs, p, fx)
+    }
+
+    fn try_forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = forward_jacobian_vec_prod_vec(self, fs, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        central_jacobian_vec_prod_vec(self, fs, p)
+    }
+
+    fn try_central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = central_jacobian_vec_prod_vec(self, fs, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn forward_vjp(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        v: &Self::OperatorOutput,
+    ) -> Self {
+        forward_vjp_vec(self, fs, v)
+    }
+
+    fn forward_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        forward_jacobian_pert_vec(self, fs, pert)
+    }
+
+    fn forward_jacobian_pert_sparse(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> SparseJacobian<F> {
+        forward_jacobian_pert_sparse_vec(self, fs, pert)
+    }
+
+    fn forward_jacobian_banded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        kl: usize,
+        ku: usize,
+    ) -> BandedJacobian<F> {
+        forward_jacobian_banded_vec(self, fs, kl, ku)
+    }
+
+    fn central_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        central_jacobian_pert_vec(self, fs, pert)
+    }
+
+    fn detect_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: F,
+    ) -> PerturbationVectors {
+        detect_sparsity_vec(self, fs, threshold)
+    }
+
+    fn jacobian_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: F,
+    ) -> SparsityPattern {
+        jacobian_sparsity_vec(self, fs, threshold)
+    }
+
+    fn forward_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
+        forward_hessian_vec(self, g)
+    }
+
+    fn forward_hessian_sym(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        restore: bool,
+    ) -> Self::Hessian {
+        forward_hessian_sym_vec(self, g, restore)
+    }
+
+    fn try_forward_hessian<E>(
+        &self,
+        g: &dyn Fn(&Self) -> Result<Self::OperatorOutput, E>,
+    ) -> Result<Self::Hessian, E> {
+        try_forward_hessian_vec(self, g)
+    }
+
+    fn central_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
+        central_hessian_vec(self, g)
+    }
+
+    fn forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        forward_hessian_vec_prod_vec(self, g, p)
+    }
+
+    fn try_forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = forward_hessian_vec_prod_vec(self, g, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        central_hessian_vec_prod_vec(self, g, p)
+    }
+
+    fn forward_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> Self {
+        forward_hessian_vec_prod_nograd_vec(self, f, p)
+    }
+
+    fn central_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> Self {
+        central_hessian_vec_prod_nograd_vec(self, f, p)
+    }
+
+    fn check_gradient(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        analytic: &Self,
+        tol: F,
+    ) -> Result<(), GradCheckReport<F>> {
+        check_gradient_vec(analytic, &central_diff_vec(self, f), tol)
+    }
+
+    fn check_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        analytic: &Self::Jacobian,
+        tol: F,
+    ) -> Result<(), GradCheckReport<F>> {
+        check_jacobian_vec(analytic, &central_jacobian_vec(self, fs), tol)
+    }
+
+    fn try_central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = central_hessian_vec_prod_vec(self, g, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn forward_hessian_nograd(&self, f: &dyn Fn(&Self) -> F) -> Self::Hessian {
+        forward_hessian_nograd_vec(self, f)
+    }
+
+    fn forward_hessian_nograd_cancellable(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        cancel: &AtomicBool,
+    ) -> Option<Self::Hessian> {
+        forward_hessian_nograd_cancellable_vec(self, f, cancel)
+    }
+
+    fn forward_hessian_nograd_blocks(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        block_rows: usize,
+        on_block: &mut dyn FnMut(usize, &[Vec<F>]),
+    ) {
+        forward_hessian_nograd_blocks_vec(self, f, block_rows, on_block)
+    }
+
+    fn forward_hessian_nograd_packed(&self, f: &dyn Fn(&Self) -> F) -> PackedSymmetric<F> {
+        forward_hessian_nograd_packed_vec(self, f)
+    }
+
+    fn forward_grad_and_hessian_nograd(&self, f: &dyn Fn(&Self) -> F) -> (Self, Self::Hessian) {
+        forward_grad_and_hessian_nograd_vec(self, f)
+    }
+
+    fn central_hessian_nograd(&self, f: &dyn Fn(&Self) -> F) -> Self::Hessian {
+        central_hessian_nograd_vec(self, f)
+    }
+
+    fn derivatives(&self, f: &dyn Fn(&Self) -> F) -> Derivatives<F, Self, Self::Hessian> {
+        let (value, gradient, hessian) = central_derivatives_vec(self, f);
+        Derivatives {
+            value,
+            gradient,
+            hessian,
+        }
+    }
+
+    fn forward_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        forward_hessian_nograd_sparse_vec(self, f, indices)
+    }
+
+    fn forward_hessian_nograd_additive(
+        &self,
+        terms: &[(&dyn Fn(&Self) -> F, Vec<usize>)],
+    ) -> Self::Hessian {
+        forward_hessian_nograd_additive_vec(self, terms)
+    }
+
+    fn central_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        central_hessian_nograd_sparse_vec(self, f, indices)
+    }
+
+    fn forward_hessian_diagonal_nograd(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        forward_hessian_diagonal_nograd_vec(self, f)
+    }
+
+    fn central_grad_and_diag(&self, f: &dyn Fn(&Self) -> F) -> (Self, Self) {
+        central_grad_and_diag_vec(self, f)
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<F> FiniteDiff for ndarray::Array1<F>
+where
+    F: FloatEps + ndarray::ScalarOperand,
+{
+    type Scalar = F;
+    type Jacobian = ndarray::Array2<F>;
+    type Hessian = ndarray::Array2<F>;
+    type OperatorOutput = ndarray::Array1<F>;
+
+    fn forward_diff(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        forward_diff_ndarray(self, f)
+    }
+
+    fn try_forward_diff(&self, f: &dyn Fn(&Self) -> F) -> Result<Self, FiniteDiffError> {
+        try_forward_diff_ndarray(self, f)
+    }
+
+    fn forward_diff_precomputed(&self, f: &dyn Fn(&Self) -> F, fx: F) -> Self {
+        forward_diff_precomputed_ndarray(self, f, fx)
+    }
+
+    fn value_and_forward_diff(&self, f: &dyn Fn(&Self) -> F) -> (F, Self) {
+        let fx = (f)(self);
+        (fx, self.forward_diff_precomputed(f, fx))
+    }
+
+    fn forward_diff_with_samples(&self, f: &dyn Fn(&Self) -> F) -> (Self, F, Vec<F>) {
+        forward_diff_with_samples_ndarray(self, f)
+    }
+
+    fn forward_diff_traced(&self, f: &dyn Fn(&Self) -> F, sink: &mut dyn FnMut(&[F], F)) -> Self {
+        let sink = RefCell::new(sink);
+        self.forward_diff(&|x| {
+            let fx = f(x);
+            (sink.borrow_mut())(&x.to_vec(), fx);
+            fx
+        })
+    }
+
+    fn forward_diff_subset(&self, f: &dyn Fn(&Self) -> F, indices: &[usize]) -> Self {
+        forward_diff_subset_ndarray(self, f, indices)
+    }
+
+    fn forward_diff_masked(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        active: &[bool],
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), active.len())?;
+        Ok(self.forward_diff_subset(f, &mask_indices(active)))
+    }
+
+    fn forward_diff_with_error(&self, f: &dyn Fn(&Self) -> F) -> (Self, Self) {
+        forward_diff_with_error_ndarray(self, f)
+    }
+
+    fn backward_diff(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        backward_diff_ndarray(self, f)
+    }
+
+    fn central_diff(&self, f: &dyn Fn(&ndarray::Array1<F>) -> F) -> Self {
+        central_diff_ndarray(self, f)
+    }
+
+    fn diff_mixed(&self, f: &dyn Fn(&Self) -> F, policy: &[Method]) -> Self {
+        diff_mixed_ndarray(self, f, policy)
+    }
+
+    fn central_diff_5(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        central_diff_5_ndarray(self, f)
+    }
+
+    fn forward_diff_2(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        forward_diff_2_ndarray(self, f)
+    }
+
+    fn forward_diff_order(&self, f: &dyn Fn(&Self) -> F, order: StencilOrder) -> Self {
+        forward_diff_order_ndarray(self, f, order)
+    }
+
+    fn central_diff_order(&self, f: &dyn Fn(&Self) -> F, order: StencilOrder) -> Self {
+        central_diff_order_ndarray(self, f, order)
+    }
+
+    fn forward_diff_scaled(&self, f: &dyn Fn(&Self) -> F, steps: &Self) -> Self {
+        forward_diff_scaled_ndarray(self, f, steps)
+    }
+
+    fn forward_diff_with_scaling(&self, f: &dyn Fn(&Self) -> F, scale: &Self) -> Self {
+        forward_diff_with_scaling_ndarray(self, f, scale)
+    }
+
+    fn forward_diff_bounded(&self, f: &dyn Fn(&Self) -> F, lower: &Self, upper: &Self) -> Self {
+        forward_diff_bounded_ndarray(self, f, lower, upper)
+    }
+
+    fn try_forward_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_forward_steps(self.iter(), steps.iter())?;
+        let out = forward_diff_scaled_ndarray(self, f, steps);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_diff_scaled(&self, f: &dyn Fn(&Self) -> F, steps: &Self) -> Self {
+        central_diff_scaled_ndarray(self, f, steps)
+    }
+
+    fn try_central_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_steps(self.iter(), steps.iter())?;
+        let out = central_diff_scaled_ndarray(self, f, steps);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn richardson_diff(&self, f: &dyn Fn(&Self) -> F, levels: usize) -> Self {
+        richardson_diff_ndarray(self, f, levels)
+    }
+
+    fn adaptive_diff(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        adaptive_diff_ndarray(self, f)
+    }
+
+    fn auto_diff(&self, f: &dyn Fn(&Self) -> F, target_rel_err: F) -> Self {
+        auto_diff_ndarray(self, f, target_rel_err)
+    }
+
+    fn estimate_diff_noise(&self, f: &mut dyn FnMut(&Self) -> F, repeats: usize) -> Self {
+        estimate_diff_noise_ndarray(self, f, repeats)
+    }
+
+    fn forward_directional_diff(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> F {
+        forward_directional_diff_ndarray(self, f, p)
+    }
+
+    fn central_directional_diff(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> F {
+        central_directional_diff_ndarray(self, f, p)
+    }
+
+    fn forward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        forward_jacobian_ndarray(self, fs)
+    }
+
+    fn try_forward_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        let out = try_forward_jacobian_ndarray(self, fs)?;
+        check_finite_jacobian(out.indexed_iter())?;
+        Ok(out)
+    }
+
+    fn forward_jacobian_transposed(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Self::Jacobian {
+        forward_jacobian_transposed_ndarray(self, fs)
+    }
+
+    fn forward_jacobian_with_progress(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        on_col: &mut dyn FnMut(usize) -> bool,
+    ) -> Option<Self::Jacobian> {
+        forward_jacobian_with_progress_ndarray(self, fs, on_col)
+    }
+
+    fn forward_jacobian_diagonal(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self {
+        forward_jacobian_diagonal_ndarray(self, fs)
+    }
+
+    fn forward_jacobian_thresholded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        zero_tol: F,
+    ) -> Self::Jacobian {
+        forward_jacobian_thresholded_ndarray(self, fs, zero_tol)
+    }
+
+    fn forward_jacobian_update(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        prev: &Self::Jacobian,
+        x_prev: &Self,
+        step_tol: F,
+    ) -> Self::Jacobian {
+        forward_jacobian_update_ndarray(self, fs, prev, x_prev, step_tol)
+    }
+
+    fn forward_jacobian_points(&self) -> Vec<Self> {
+        forward_jacobian_points_ndarray(self)
+    }
+
+    fn forward_jacobian_from_values(&self, values: &[Self::OperatorOutput]) -> Self::Jacobian {
+        forward_jacobian_from_values_ndarray(self, values)
+    }
+
+    #[cfg(feature = "rayon")]
+    fn forward_jacobian_par(
+        &self,
+        fs: &(dyn Fn(&Self) -> Self::OperatorOutput + Sync),
+    ) -> Self::Jacobian {
+        forward_jacobian_par_ndarray(self, fs)
+    }
+
+    fn backward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        backward_jacobian_ndarray(self, fs)
+    }
+
+    fn central_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        central_jacobian_ndarray(self, fs)
+    }
+
+    fn forward_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        forward_jacobian_into_ndarray(self, fs, out)
+    }
+
+    fn central_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        central_jacobian_into_ndarray(self, fs, out)
+    }
+
+    fn central_jacobian_5(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        central_jacobian_5_ndarray(self, fs)
+    }
+
+    fn forward_jacobian_2(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        forward_jacobian_2_ndarray(self, fs)
+    }
+
+    fn forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        forward_jacobian_scaled_ndarray(self, fs, steps)
+    }
+
+    fn try_forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_forward_steps(self.iter(), steps.iter())?;
+        let out = forward_jacobian_scaled_ndarray(self, fs, steps);
+        check_finite_jacobian(out.indexed_iter())?;
+        Ok(out)
+    }
+
+    fn central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        central_jacobian_scaled_ndarray(self, fs, steps)
+    }
+
+    fn try_central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_steps(self.iter(), steps.iter())?;
+        let out = central_jacobian_scaled_ndarray(self, fs, steps);
+        check_finite_jacobian(out.indexed_iter())?;
+        Ok(out)
+    }
+
+    fn forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        forward_jacobian_vec_prod_ndarray(self, fs, p)
+    }
+
+    fn forward_jacobian_vec_prod_normalized(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        forward_jacobian_vec_prod_normalized_ndarray(self, fs, p)
+    }
+
+    fn forward_jacobian_vec_prod_at(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+        fx: &Self::OperatorOutput,
+    ) -> Self {
+        forward_jacobian_vec_prod_at_ndarray(self, fs, p, fx)
+    }
+
+    fn try_forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = forward_jacobian_vec_prod_ndarray(self, fs, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        central_jacobian_vec_prod_ndarray(self, fs, p)
+    }
+
+    fn try_central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = central_jacobian_vec_prod_ndarray(self, fs, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn forward_vjp(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        v: &Self::OperatorOutput,
+    ) -> Self {
+        forward_vjp_ndarray(self, fs, v)
+    }
+
+    fn forward_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        forward_jacobian_pert_ndarray(self, fs, pert)
+    }
+
+    fn forward_jacobian_pert_sparse(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> SparseJacobian<F> {
+        forward_jacobian_pert_sparse_ndarray(self, fs, pert)
+    }
+
+    fn forward_jacobian_banded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        kl: usize,
+        ku: usize,
+    ) -> BandedJacobian<F> {
+        forward_jacobian_banded_ndarray(self, fs, kl, ku)
+    }
+
+    fn central_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        central_jacobian_pert_ndarray(self, fs, pert)
+    }
+
+    fn detect_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: F,
+    ) -> PerturbationVectors {
+        detect_sparsity_ndarray(self, fs, threshold)
+    }
+
+    fn jacobian_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: F,
+    ) -> SparsityPattern {
+        jacobian_sparsity_ndarray(self, fs, threshold)
+    }
+
+    fn forward_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        forward_hessian_ndarray(self, g)
+    }
+
+    fn forward_hessian_sym(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        restore: bool,
+    ) -> Self::Hessian {
+        forward_hessian_sym_ndarray(self, g, restore)
+    }
+
+    fn try_forward_hessian<E>(
+        &self,
+        g: &dyn Fn(&Self) -> Result<Self::OperatorOutput, E>,
+    ) -> Result<Self::Hessian, E> {
+        try_forward_hessian_ndarray(self, g)
+    }
+
+    fn central_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        central_hessian_ndarray(self, g)
+    }
+
+    fn forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        forward_hessian_vec_prod_ndarray(self, g, p)
+    }
+
+    fn try_forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = forward_hessian_vec_prod_ndarray(self, g, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        central_hessian_vec_prod_ndarray(self, g, p)
+    }
+
+    fn forward_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> Self {
+        forward_hessian_vec_prod_nograd_ndarray(self, f, p)
+    }
+
+    fn central_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> Self {
+        central_hessian_vec_prod_nograd_ndarray(self, f, p)
+    }
+
+    fn check_gradient(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        analytic: &Self,
+        tol: F,
+    ) -> Result<(), GradCheckReport<F>> {
+        check_gradient_ndarray(analytic, &central_diff_ndarray(self, f), tol)
+    }
+
+    fn check_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        analytic: &Self::Jacobian,
+        tol: F,
+    ) -> Result<(), GradCheckReport<F>> {
+        check_jacobian_ndarray(analytic, &central_jacobian_ndarray(self, fs), tol)
+    }
+
+    fn try_central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = central_hessian_vec_prod_ndarray(self, g, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn forward_hessian_nograd(&self, f: &dyn Fn(&Self) -> F) -> Self::Hessian {
+        forward_hessian_nograd_ndarray(self, f)
+    }
+
+    fn forward_hessian_nograd_cancellable(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        cancel: &AtomicBool,
+    ) -> Option<Self::Hessian> {
+        forward_hessian_nograd_cancellable_ndarray(self, f, cancel)
+    }
+
+    fn forward_hessian_nograd_blocks(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        block_rows: usize,
+        on_block: &mut dyn FnMut(usize, &[Vec<F>]),
+    ) {
+        forward_hessian_nograd_blocks_ndarray(self, f, block_rows, on_block)
+    }
+
+    fn forward_hessian_nograd_packed(&self, f: &dyn Fn(&Self) -> F) -> PackedSymmetric<F> {
+        forward_hessian_nograd_packed_ndarray(self, f)
+    }
+
+    fn forward_grad_and_hessian_nograd(&self, f: &dyn Fn(&Self) -> F) -> (Self, Self::Hessian) {
+        forward_grad_and_hessian_nograd_ndarray(self, f)
+    }
+
+    fn central_hessian_nograd(&self, f: &dyn Fn(&Self) -> F) -> Self::Hessian {
+        central_hessian_nograd_ndarray(self, f)
+    }
+
+    fn derivatives(&self, f: &dyn Fn(&Self) -> F) -> Derivatives<F, Self, Self::Hessian> {
+        let (value, gradient, hessian) = central_derivatives_ndarray(self, f);
+        Derivatives {
+            value,
+            gradient,
+            hessian,
+        }
+    }
+
+    fn forward_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        forward_hessian_nograd_sparse_ndarray(self, f, indices)
+    }
+
+    fn forward_hessian_nograd_additive(
+        &self,
+        terms: &[(&dyn Fn(&Self) -> F, Vec<usize>)],
+    ) -> Self::Hessian {
+        forward_hessian_nograd_additive_ndarray(self, terms)
+    }
+
+    fn central_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        central_hessian_nograd_sparse_ndarray(self, f, indices)
+    }
+
+    fn forward_hessian_diagonal_nograd(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        forward_hessian_diagonal_nograd_ndarray(self, f)
+    }
+
+    fn central_grad_and_diag(&self, f: &dyn Fn(&Self) -> F) -> (Self, Self) {
+        central_grad_and_diag_ndarray(self, f)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<F> FiniteDiff for nalgebra::DVector<F>
+where
+    F: FloatEps + nalgebra::Scalar,
+{
+    type Scalar = F;
+    type Jacobian = nalgebra::DMatrix<F>;
+    type Hessian = nalgebra::DMatrix<F>;
+    type OperatorOutput = nalgebra::DVector<F>;
+
+    fn forward_diff(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        forward_diff_nalgebra(self, f)
+    }
+
+    fn try_forward_diff(&self, f: &dyn Fn(&Self) -> F) -> Result<Self, FiniteDiffError> {
+        try_forward_diff_nalgebra(self, f)
+    }
+
+    fn forward_diff_precomputed(&self, f: &dyn Fn(&Self) -> F, fx: F) -> Self {
+        forward_diff_precomputed_nalgebra(self, f, fx)
+    }
+
+    fn value_and_forward_diff(&self, f: &dyn Fn(&Self) -> F) -> (F, Self) {
+        let fx = (f)(self);
+        (fx, self.forward_diff_precomputed(f, fx))
+    }
+
+    fn forward_diff_with_samples(&self, f: &dyn Fn(&Self) -> F) -> (Self, F, Vec<F>) {
+        forward_diff_with_samples_nalgebra(self, f)
+    }
+
+    fn forward_diff_traced(&self, f: &dyn Fn(&Self) -> F, sink: &mut dyn FnMut(&[F], F)) -> Self {
+        let sink = RefCell::new(sink);
+        self.forward_diff(&|x| {
+            let fx = f(x);
+            (sink.borrow_mut())(x.as_slice(), fx);
+            fx
+        })
+    }
+
+    fn forward_diff_subset(&self, f: &dyn Fn(&Self) -> F, indices: &[usize]) -> Self {
+        forward_diff_subset_nalgebra(self, f, indices)
+    }
+
+    fn forward_diff_masked(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        active: &[bool],
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), active.len())?;
+        Ok(self.forward_diff_subset(f, &mask_indices(active)))
+    }
+
+    fn forward_diff_with_error(&self, f: &dyn Fn(&Self) -> F) -> (Self, Self) {
+        forward_diff_with_error_nalgebra(self, f)
+    }
+
+    fn backward_diff(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        backward_diff_nalgebra(self, f)
+    }
+
+    fn central_diff(&self, f: &dyn Fn(&nalgebra::DVector<F>) -> F) -> Self {
+        central_diff_nalgebra(self, f)
+    }
+
+    fn diff_mixed(&self, f: &dyn Fn(&Self) -> F, policy: &[Method]) -> Self {
+        diff_mixed_nalgebra(self, f, policy)
+    }
+
+    fn central_diff_5(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        central_diff_5_nalgebra(self, f)
+    }
+
+    fn forward_diff_2(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        forward_diff_2_nalgebra(self, f)
+    }
+
+    fn forward_diff_order(&self, f: &dyn Fn(&Self) -> F, order: StencilOrder) -> Self {
+        forward_diff_order_nalgebra(self, f, order)
+    }
+
+    fn central_diff_order(&self, f: &dyn Fn(&Self) -> F, order: StencilOrder) -> Self {
+        central_diff_order_nalgebra(self, f, order)
+    }
+
+    fn forward_diff_scaled(&self, f: &dyn Fn(&Self) -> F, steps: &Self) -> Self {
+        forward_diff_scaled_nalgebra(self, f, steps)
+    }
+
+    fn forward_diff_with_scaling(&self, f: &dyn Fn(&Self) -> F, scale: &Self) -> Self {
+        forward_diff_with_scaling_nalgebra(self, f, scale)
+    }
+
+    fn forward_diff_bounded(&self, f: &dyn Fn(&Self) -> F, lower: &Self, upper: &Self) -> Self {
+        forward_diff_bounded_nalgebra(self, f, lower, upper)
+    }
+
+    fn try_forward_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_forward_steps(self.iter(), steps.iter())?;
+        let out = forward_diff_scaled_nalgebra(self, f, steps);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_diff_scaled(&self, f: &dyn Fn(&Self) -> F, steps: &Self) -> Self {
+        central_diff_scaled_nalgebra(self, f, steps)
+    }
+
+    fn try_central_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_steps(self.iter(), steps.iter())?;
+        let out = central_diff_scaled_nalgebra(self, f, steps);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn richardson_diff(&self, f: &dyn Fn(&Self) -> F, levels: usize) -> Self {
+        richardson_diff_nalgebra(self, f, levels)
+    }
+
+    fn adaptive_diff(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        adaptive_diff_nalgebra(self, f)
+    }
+
+    fn auto_diff(&self, f: &dyn Fn(&Self) -> F, target_rel_err: F) -> Self {
+        auto_diff_nalgebra(self, f, target_rel_err)
+    }
+
+    fn estimate_diff_noise(&self, f: &mut dyn FnMut(&Self) -> F, repeats: usize) -> Self {
+        estimate_diff_noise_nalgebra(self, f, repeats)
+    }
+
+    fn forward_directional_diff(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> F {
+        forward_directional_diff_nalgebra(self, f, p)
+    }
+
+    fn central_directional_diff(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> F {
+        central_directional_diff_nalgebra(self, f, p)
+    }
+
+    fn forward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        forward_jacobian_nalgebra(self, fs)
+    }
+
+    fn try_forward_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        let out = try_forward_jacobian_nalgebra(self, fs)?;
+        check_finite_jacobian(indexed_nalgebra(&out))?;
+        Ok(out)
+    }
+
+    fn forward_jacobian_transposed(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Self::Jacobian {
+        forward_jacobian_transposed_nalgebra(self, fs)
+    }
+
+    fn forward_jacobian_with_progress(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        on_col: &mut dyn FnMut(usize) -> bool,
+    ) -> Option<Self::Jacobian> {
+        forward_jacobian_with_progress_nalgebra(self, fs, on_col)
+    }
+
+    fn forward_jacobian_diagonal(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self {
+        forward_jacobian_diagonal_nalgebra(self, fs)
+    }
+
+    fn forward_jacobian_thresholded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        zero_tol: F,
+    ) -> Self::Jacobian {
+        forward_jacobian_thresholded_nalgebra(self, fs, zero_tol)
+    }
+
+    fn forward_jacobian_update(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        prev: &Self::Jacobian,
+        x_prev: &Self,
+        step_tol: F,
+    ) -> Self::Jacobian {
+        forward_jacobian_update_nalgebra(self, fs, prev, x_prev, step_tol)
+    }
+
+    fn forward_jacobian_points(&self) -> Vec<Self> {
+        forward_jacobian_points_nalgebra(self)
+    }
+
+    fn forward_jacobian_from_values(&self, values: &[Self::OperatorOutput]) -> Self::Jacobian {
+        forward_jacobian_from_values_nalgebra(self, values)
+    }
+
+    #[cfg(feature = "rayon")]
+    fn forward_jacobian_par(
+        &self,
+        fs: &(dyn Fn(&Self) -> Self::OperatorOutput + Sync),
+    ) -> Self::Jacobian {
+        forward_jacobian_par_nalgebra(self, fs)
+    }
+
+    fn backward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        backward_jacobian_nalgebra(self, fs)
+    }
+
+    fn central_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        central_jacobian_nalgebra(self, fs)
+    }
+
+    fn forward_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        forward_jacobian_into_nalgebra(self, fs, out)
+    }
+
+    fn central_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        central_jacobian_into_nalgebra(self, fs, out)
+    }
+
+    fn central_jacobian_5(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        central_jacobian_5_nalgebra(self, fs)
+    }
+
+    fn forward_jacobian_2(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        forward_jacobian_2_nalgebra(self, fs)
+    }
+
+    fn forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        forward_jacobian_scaled_nalgebra(self, fs, steps)
+    }
+
+    fn try_forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_forward_steps(self.iter(), steps.iter())?;
+        let out = forward_jacobian_scaled_nalgebra(self, fs, steps);
+        check_finite_jacobian(indexed_nalgebra(&out))?;
+        Ok(out)
+    }
+
+    fn central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        central_jacobian_scaled_nalgebra(self, fs, steps)
+    }
+
+    fn try_central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        check_dim(self.len(), steps.len())?;
+        check_steps(self.iter(), steps.iter())?;
+        let out = central_jacobian_scaled_nalgebra(self, fs, steps);
+        check_finite_jacobian(indexed_nalgebra(&out))?;
+        Ok(out)
+    }
+
+    fn forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        forward_jacobian_vec_prod_nalgebra(self, fs, p)
+    }
+
+    fn forward_jacobian_vec_prod_normalized(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        forward_jacobian_vec_prod_normalized_nalgebra(self, fs, p)
+    }
+
+    fn forward_jacobian_vec_prod_at(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+        fx: &Self::OperatorOutput,
+    ) -> Self {
+        forward_jacobian_vec_prod_at_nalgebra(self, fs, p, fx)
+    }
+
+    fn try_forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = forward_jacobian_vec_prod_nalgebra(self, fs, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        central_jacobian_vec_prod_nalgebra(self, fs, p)
+    }
+
+    fn try_central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = central_jacobian_vec_prod_nalgebra(self, fs, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn forward_vjp(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        v: &Self::OperatorOutput,
+    ) -> Self {
+        forward_vjp_nalgebra(self, fs, v)
+    }
+
+    fn forward_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        forward_jacobian_pert_nalgebra(self, fs, pert)
+    }
+
+    fn forward_jacobian_pert_sparse(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> SparseJacobian<F> {
+        forward_jacobian_pert_sparse_nalgebra(self, fs, pert)
+    }
+
+    fn forward_jacobian_banded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        kl: usize,
+        ku: usize,
+    ) -> BandedJacobian<F> {
+        forward_jacobian_banded_nalgebra(self, fs, kl, ku)
+    }
+
+    fn central_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        central_jacobian_pert_nalgebra(self, fs, pert)
+    }
+
+    fn detect_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: F,
+    ) -> PerturbationVectors {
+        detect_sparsity_nalgebra(self, fs, threshold)
+    }
+
+    fn jacobian_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: F,
+    ) -> SparsityPattern {
+        jacobian_sparsity_nalgebra(self, fs, threshold)
+    }
+
+    fn forward_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        forward_hessian_nalgebra(self, g)
+    }
+
+    fn forward_hessian_sym(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        restore: bool,
+    ) -> Self::Hessian {
+        forward_hessian_sym_nalgebra(self, g, restore)
+    }
+
+    fn try_forward_hessian<E>(
+        &self,
+        g: &dyn Fn(&Self) -> Result<Self::OperatorOutput, E>,
+    ) -> Result<Self::Hessian, E> {
+        try_forward_hessian_nalgebra(self, g)
+    }
+
+    fn central_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        central_hessian_nalgebra(self, g)
+    }
+
+    fn forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        forward_hessian_vec_prod_nalgebra(self, g, p)
+    }
+
+    fn try_forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = forward_hessian_vec_prod_nalgebra(self, g, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        central_hessian_vec_prod_nalgebra(self, g, p)
+    }
+
+    fn forward_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> Self {
+        forward_hessian_vec_prod_nograd_nalgebra(self, f, p)
+    }
+
+    fn central_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> F, p: &Self) -> Self {
+        central_hessian_vec_prod_nograd_nalgebra(self, f, p)
+    }
+
+    fn check_gradient(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        analytic: &Self,
+        tol: F,
+    ) -> Result<(), GradCheckReport<F>> {
+        check_gradient_nalgebra(analytic, &central_diff_nalgebra(self, f), tol)
+    }
+
+    fn check_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        analytic: &Self::Jacobian,
+        tol: F,
+    ) -> Result<(), GradCheckReport<F>> {
+        check_jacobian_nalgebra(analytic, &central_jacobian_nalgebra(self, fs), tol)
+    }
+
+    fn try_central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), p.len())?;
+        let out = central_hessian_vec_prod_nalgebra(self, g, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn forward_hessian_nograd(&self, f: &dyn Fn(&Self) -> F) -> Self::Hessian {
+        forward_hessian_nograd_nalgebra(self, f)
+    }
+
+    fn forward_hessian_nograd_cancellable(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        cancel: &AtomicBool,
+    ) -> Option<Self::Hessian> {
+        forward_hessian_nograd_cancellable_nalgebra(self, f, cancel)
+    }
+
+    fn forward_hessian_nograd_blocks(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        block_rows: usize,
+        on_block: &mut dyn FnMut(usize, &[Vec<F>]),
+    ) {
+        forward_hessian_nograd_blocks_nalgebra(self, f, block_rows, on_block)
+    }
+
+    fn forward_hessian_nograd_packed(&self, f: &dyn Fn(&Self) -> F) -> PackedSymmetric<F> {
+        forward_hessian_nograd_packed_nalgebra(self, f)
+    }
+
+    fn forward_grad_and_hessian_nograd(&self, f: &dyn Fn(&Self) -> F) -> (Self, Self::Hessian) {
+        forward_grad_and_hessian_nograd_nalgebra(self, f)
+    }
+
+    fn central_hessian_nograd(&self, f: &dyn Fn(&Self) -> F) -> Self::Hessian {
+        central_hessian_nograd_nalgebra(self, f)
+    }
+
+    fn derivatives(&self, f: &dyn Fn(&Self) -> F) -> Derivatives<F, Self, Self::Hessian> {
+        let (value, gradient, hessian) = central_derivatives_nalgebra(self, f);
+        Derivatives {
+            value,
+            gradient,
+            hessian,
+        }
+    }
+
+    fn forward_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        forward_hessian_nograd_sparse_nalgebra(self, f, indices)
+    }
+
+    fn forward_hessian_nograd_additive(
+        &self,
+        terms: &[(&dyn Fn(&Self) -> F, Vec<usize>)],
+    ) -> Self::Hessian {
+        forward_hessian_nograd_additive_nalgebra(self, terms)
+    }
+
+    fn central_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> F,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        central_hessian_nograd_sparse_nalgebra(self, f, indices)
+    }
+
+    fn forward_hessian_diagonal_nograd(&self, f: &dyn Fn(&Self) -> F) -> Self {
+        forward_hessian_diagonal_nograd_nalgebra(self, f)
+    }
+
+    fn central_grad_and_diag(&self, f: &dyn Fn(&Self) -> F) -> (Self, Self) {
+        central_grad_and_diag_nalgebra(self, f)
+    }
+}
+
+/// Stack-allocated parameter vectors. Only square vector functions are supported, because the
+/// Jacobian is of type `[[f64; N]; N]`.
+///
+/// `forward_diff`, `central_diff`, `forward_jacobian`, `central_jacobian` and
+/// `forward_hessian_nograd` operate directly on the array. All other methods are computed via the
+/// `Vec<f64>` implementation and therefore allocate.
+impl<const N: usize> FiniteDiff for [f64; N] {
+    type Scalar = f64;
+    type Jacobian = [[f64; N]; N];
+    type Hessian = [[f64; N]; N];
+    type OperatorOutput = [f64; N];
+
+    fn forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        forward_diff_array(self, f)
+    }
+
+    fn try_forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Result<Self, FiniteDiffError> {
+        try_forward_diff_vec(&self.to_vec(), &|x| f(&vec_to_array(x))).map(|g| vec_to_array(&g))
+    }
+
+    fn forward_diff_precomputed(&self, f: &dyn Fn(&Self) -> f64, fx: f64) -> Self {
+        vec_to_array(&forward_diff_precomputed_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            fx,
+        ))
+    }
+
+    fn value_and_forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> (f64, Self) {
+        let fx = (f)(self);
+        (fx, self.forward_diff_precomputed(f, fx))
+    }
+
+    fn forward_diff_with_samples(&self, f: &dyn Fn(&Self) -> f64) -> (Self, f64, Vec<f64>) {
+        let (grad, fx, samples) =
+            forward_diff_with_samples_vec(&self.to_vec(), &|x| f(&vec_to_array(x)));
+        (vec_to_array(&grad), fx, samples)
+    }
+
+    fn forward_diff_traced(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        sink: &mut dyn FnMut(&[f64], f64),
+    ) -> Self {
+        let sink = RefCell::new(sink);
+        self.forward_diff(&|x| {
+            let fx = f(x);
+            (sink.borrow_mut())(x, fx);
+            fx
+        })
+    }
+
+    fn forward_diff_subset(&self, f: &dyn Fn(&Self) -> f64, indices: &[usize]) -> Self {
+        vec_to_array(&forward_diff_subset_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            indices,
+        ))
+    }
+
+    fn forward_diff_masked(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        active: &[bool],
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(N, active.len())?;
+        Ok(self.forward_diff_subset(f, &mask_indices(active)))
+    }
+
+    fn forward_diff_with_error(&self, f: &dyn Fn(&Self) -> f64) -> (Self, Self) {
+        let (grad, err) = forward_diff_with_error_vec(&self.to_vec(), &|x| f(&vec_to_array(x)));
+        (vec_to_array(&grad), vec_to_array(&err))
+    }
+
+    fn backward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        vec_to_array(&backward_diff_vec(&self.to_vec(), &|x| f(&vec_to_array(x))))
+    }
+
+    fn central_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        central_diff_array(self, f)
+    }
+
+    fn diff_mixed(&self, f: &dyn Fn(&Self) -> f64, policy: &[Method]) -> Self {
+        vec_to_array(&diff_mixed_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            policy,
+        ))
+    }
+
+    fn central_diff_5(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        vec_to_array(&central_diff_5_vec(&self.to_vec(), &|x| {
+            f(&vec_to_array(x))
+        }))
+    }
+
+    fn forward_diff_2(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        vec_to_array(&forward_diff_2_vec(&self.to_vec(), &|x| {
+            f(&vec_to_array(x))
+        }))
+    }
+
+    fn forward_diff_order(&self, f: &dyn Fn(&Self) -> f64, order: StencilOrder) -> Self {
+        vec_to_array(&forward_diff_order_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            order,
+        ))
+    }
+
+    fn central_diff_order(&self, f: &dyn Fn(&Self) -> f64, order: StencilOrder) -> Self {
+        vec_to_array(&central_diff_order_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            order,
+        ))
+    }
+
+    fn forward_diff_scaled(&self, f: &dyn Fn(&Self) -> f64, steps: &Self) -> Self {
+        vec_to_array(&forward_diff_scaled_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            &steps.to_vec(),
+        ))
+    }
+
+    fn forward_diff_with_scaling(&self, f: &dyn Fn(&Self) -> f64, scale: &Self) -> Self {
+        vec_to_array(&forward_diff_with_scaling_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            &scale.to_vec(),
+        ))
+    }
+
+    fn forward_diff_bounded(&self, f: &dyn Fn(&Self) -> f64, lower: &Self, upper: &Self) -> Self {
+        vec_to_array(&forward_diff_bounded_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            &lower.to_vec(),
+            &upper.to_vec(),
+        ))
+    }
+
+    fn try_forward_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_forward_steps(self.iter(), steps.iter())?;
+        let out = self.forward_diff_scaled(f, steps);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_diff_scaled(&self, f: &dyn Fn(&Self) -> f64, steps: &Self) -> Self {
+        vec_to_array(&central_diff_scaled_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            &steps.to_vec(),
+        ))
+    }
+
+    fn try_central_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        check_steps(self.iter(), steps.iter())?;
+        let out = self.central_diff_scaled(f, steps);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn richardson_diff(&self, f: &dyn Fn(&Self) -> f64, levels: usize) -> Self {
+        vec_to_array(&richardson_diff_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            levels,
+        ))
+    }
+
+    fn adaptive_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        vec_to_array(&adaptive_diff_vec(&self.to_vec(), &|x| f(&vec_to_array(x))))
+    }
+
+    fn auto_diff(&self, f: &dyn Fn(&Self) -> f64, target_rel_err: f64) -> Self {
+        vec_to_array(&auto_diff_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            target_rel_err,
+        ))
+    }
+
+    fn estimate_diff_noise(&self, f: &mut dyn FnMut(&Self) -> f64, repeats: usize) -> Self {
+        vec_to_array(&estimate_diff_noise_vec(
+            &self.to_vec(),
+            &mut |x| f(&vec_to_array(x)),
+            repeats,
+        ))
+    }
+
+    fn forward_directional_diff(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> f64 {
+        forward_directional_diff_vec(&self.to_vec(), &|x| f(&vec_to_array(x)), &p.to_vec())
+    }
+
+    fn central_directional_diff(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> f64 {
+        central_directional_diff_vec(&self.to_vec(), &|x| f(&vec_to_array(x)), &p.to_vec())
+    }
+
+    fn forward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        forward_jacobian_array(self, fs)
+    }
+
+    fn try_forward_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        let out = self.forward_jacobian(fs);
+        check_finite_jacobian(indexed_rows(&out))?;
+        Ok(out)
+    }
+
+    fn forward_jacobian_transposed(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Self::Jacobian {
+        mat_to_array(&forward_jacobian_transposed_vec(&self.to_vec(), &|x| {
+            fs(&vec_to_array(x)).to_vec()
+        }))
+    }
+
+    fn forward_jacobian_with_progress(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        on_col: &mut dyn FnMut(usize) -> bool,
+    ) -> Option<Self::Jacobian> {
+        forward_jacobian_with_progress_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            on_col,
+        )
+        .map(|j| mat_to_array(&j))
+    }
+
+    fn forward_jacobian_diagonal(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self {
+        vec_to_array(&forward_jacobian_diagonal_vec(&self.to_vec(), &|x| {
+            fs(&vec_to_array(x)).to_vec()
+        }))
+    }
+
+    fn forward_jacobian_thresholded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        zero_tol: f64,
+    ) -> Self::Jacobian {
+        mat_to_array(&forward_jacobian_thresholded_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            zero_tol,
+        ))
+    }
+
+    fn forward_jacobian_update(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        prev: &Self::Jacobian,
+        x_prev: &Self,
+        step_tol: f64,
+    ) -> Self::Jacobian {
+        mat_to_array(&forward_jacobian_update_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            &array_to_mat(prev),
+            &x_prev.to_vec(),
+            step_tol,
+        ))
+    }
+
+    fn forward_jacobian_points(&self) -> Vec<Self> {
+        forward_jacobian_points_vec(&self.to_vec())
+            .iter()
+            .map(|x| vec_to_array(x))
+            .collect()
+    }
+
+    fn forward_jacobian_from_values(&self, values: &[Self::OperatorOutput]) -> Self::Jacobian {
+        let values: Vec<Vec<f64>> = values.iter().map(|v| v.to_vec()).collect();
+        mat_to_array(&forward_jacobian_from_values_vec(&self.to_vec(), &values))
+    }
+
+    #[cfg(feature = "rayon")]
+    fn forward_jacobian_par(
+        &self,
+        fs: &(dyn Fn(&Self) -> Self::OperatorOutput + Sync),
+    ) -> Self::Jacobian {
+        mat_to_array(&forward_jacobian_par_vec(&self.to_vec(), &|x| {
+            fs(&vec_to_array(x)).to_vec()
+        }))
+    }
+
+    fn backward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        mat_to_array(&backward_jacobian_vec(&self.to_vec(), &|x| {
+            fs(&vec_to_array(x)).to_vec()
+        }))
+    }
+
+    fn central_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        central_jacobian_array(self, fs)
+    }
+
+    fn forward_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        forward_jacobian_into_array(self, fs, out)
+    }
+
+    fn central_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        central_jacobian_into_array(self, fs, out)
+    }
+
+    fn central_jacobian_5(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        mat_to_array(&central_jacobian_5_vec(&self.to_vec(), &|x| {
+            fs(&vec_to_array(x)).to_vec()
+        }))
+    }
+
+    fn forward_jacobian_2(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        mat_to_array(&forward_jacobian_2_vec(&self.to_vec(), &|x| {
+            fs(&vec_to_array(x)).to_vec()
+        }))
+    }
+
+    fn forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        mat_to_array(&forward_jacobian_scaled_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            &steps.to_vec(),
+        ))
+    }
+
+    fn try_forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        check_forward_steps(self.iter(), steps.iter())?;
+        let out = self.forward_jacobian_scaled(fs, steps);
+        check_finite_jacobian(indexed_rows(&out))?;
+        Ok(out)
+    }
+
+    fn central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        mat_to_array(&central_jacobian_scaled_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            &steps.to_vec(),
+        ))
+    }
+
+    fn try_central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        check_steps(self.iter(), steps.iter())?;
+        let out = self.central_jacobian_scaled(fs, steps);
+        check_finite_jacobian(indexed_rows(&out))?;
+        Ok(out)
+    }
+
+    fn forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        vec_to_array(&forward_jacobian_vec_prod_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            &p.to_vec(),
+        ))
+    }
+
+    fn forward_jacobian_vec_prod_normalized(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        vec_to_array(&forward_jacobian_vec_prod_normalized_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            &p.to_vec(),
+        ))
+    }
+
+    fn forward_jacobian_vec_prod_at(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+        fx: &Self::OperatorOutput,
+    ) -> Self {
+        vec_to_array(&forward_jacobian_vec_prod_at_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            &p.to_vec(),
+            &fx.to_vec(),
+        ))
+    }
+
+    fn try_forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let out = self.forward_jacobian_vec_prod(fs, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        vec_to_array(&central_jacobian_vec_prod_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            &p.to_vec(),
+        ))
+    }
+
+    fn try_central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let out = self.central_jacobian_vec_prod(fs, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn forward_vjp(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        v: &Self::OperatorOutput,
+    ) -> Self {
+        let jac = forward_jacobian_array(self, fs);
+        let mut out = [0.0; N];
+        for i in 0..N {
+            out[i] = compensated_sum((0..N).map(|j| jac[i][j] * v[j]));
+        }
+        out
+    }
+
+    fn forward_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        mat_to_array(&forward_jacobian_pert_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            pert,
+        ))
+    }
+
+    fn forward_jacobian_pert_sparse(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> SparseJacobian<f64> {
+        forward_jacobian_pert_sparse_vec(&self.to_vec(), &|x| fs(&vec_to_array(x)).to_vec(), pert)
+    }
+
+    fn forward_jacobian_banded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        kl: usize,
+        ku: usize,
+    ) -> BandedJacobian<f64> {
+        forward_jacobian_banded_vec(&self.to_vec(), &|x| fs(&vec_to_array(x)).to_vec(), kl, ku)
+    }
+
+    fn central_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        mat_to_array(&central_jacobian_pert_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            pert,
+        ))
+    }
+
+    fn detect_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: f64,
+    ) -> PerturbationVectors {
+        detect_sparsity_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            threshold,
+        )
+    }
+
+    fn jacobian_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: f64,
+    ) -> SparsityPattern {
+        jacobian_sparsity_vec(
+            &self.to_vec(),
+            &|x| fs(&vec_to_array(x)).to_vec(),
+            threshold,
+        )
+    }
+
+    fn forward_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
+        mat_to_array(&forward_hessian_vec(&self.to_vec(), &|x| {
+            g(&vec_to_array(x)).to_vec()
+        }))
+    }
+
+    fn forward_hessian_sym(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        restore: bool,
+    ) -> Self::Hessian {
+        mat_to_array(&forward_hessian_sym_vec(
+            &self.to_vec(),
+            &|x| g(&vec_to_array(x)).to_vec(),
+            restore,
+        ))
+    }
+
+    fn try_forward_hessian<E>(
+        &self,
+        g: &dyn Fn(&Self) -> Result<Self::OperatorOutput, E>,
+    ) -> Result<Self::Hessian, E> {
+        try_forward_hessian_vec(&self.to_vec(), &|x| {
+            g(&vec_to_array(x)).map(|gx| gx.to_vec())
+        })
+        .map(|h| mat_to_array(&h))
+    }
+
+    fn central_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
+        mat_to_array(&central_hessian_vec(&self.to_vec(), &|x| {
+            g(&vec_to_array(x)).to_vec()
+        }))
+    }
+
+    fn forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        vec_to_array(&forward_hessian_vec_prod_vec(
+            &self.to_vec(),
+            &|x| g(&vec_to_array(x)).to_vec(),
+            &p.to_vec(),
+        ))
+    }
+
+    fn try_forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let out = self.forward_hessian_vec_prod(g, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        vec_to_array(&central_hessian_vec_prod_vec(
+            &self.to_vec(),
+            &|x| g(&vec_to_array(x)).to_vec(),
+            &p.to_vec(),
+        ))
+    }
+
+    fn forward_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> Self {
+        vec_to_array(&forward_hessian_vec_prod_nograd_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            &p.to_vec(),
+        ))
+    }
+
+    fn central_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> Self {
+        vec_to_array(&central_hessian_vec_prod_nograd_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            &p.to_vec(),
+        ))
+    }
+
+    fn check_gradient(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        analytic: &Self,
+        tol: f64,
+    ) -> Result<(), GradCheckReport<f64>> {
+        check_gradient_vec(
+            &analytic.to_vec(),
+            &central_diff_array(self, f).to_vec(),
+            tol,
+        )
+    }
+
+    fn check_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        analytic: &Self::Jacobian,
+        tol: f64,
+    ) -> Result<(), GradCheckReport<f64>> {
+        check_jacobian_vec(
+            &array_to_mat(analytic),
+            &array_to_mat(&central_jacobian_array(self, fs)),
+            tol,
+        )
+    }
+
+    fn try_central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let out = self.central_hessian_vec_prod(g, p);
+        check_finite(out.iter())?;
+        Ok(out)
+    }
+
+    fn forward_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self::Hessian {
+        forward_hessian_nograd_array(self, f)
+    }
+
+    fn forward_hessian_nograd_cancellable(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        cancel: &AtomicBool,
+    ) -> Option<Self::Hessian> {
+        forward_hessian_nograd_cancellable_vec(&self.to_vec(), &|x| f(&vec_to_array(x)), cancel)
+            .map(|h| mat_to_array(&h))
+    }
+
+    fn forward_hessian_nograd_blocks(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        block_rows: usize,
+        on_block: &mut dyn FnMut(usize, &[Vec<f64>]),
+    ) {
+        forward_hessian_nograd_blocks_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            block_rows,
+            on_block,
+        )
+    }
+
+    fn forward_hessian_nograd_packed(&self, f: &dyn Fn(&Self) -> f64) -> PackedSymmetric<f64> {
+        forward_hessian_nograd_packed_vec(&self.to_vec(), &|x| f(&vec_to_array(x)))
+    }
+
+    fn forward_grad_and_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> (Self, Self::Hessian) {
+        let (grad, hessian) =
+            forward_grad_and_hessian_nograd_vec(&self.to_vec(), &|x| f(&vec_to_array(x)));
+        (vec_to_array(&grad), mat_to_array(&hessian))
+    }
+
+    fn central_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self::Hessian {
+        mat_to_array(&central_hessian_nograd_vec(&self.to_vec(), &|x| {
+            f(&vec_to_array(x))
+        }))
+    }
+
+    fn derivatives(&self, f: &dyn Fn(&Self) -> f64) -> Derivatives<f64, Self, Self::Hessian> {
+        let (value, gradient, hessian) =
+            central_derivatives_vec(&self.to_vec(), &|x| f(&vec_to_array(x)));
+        Derivatives {
+            value,
+            gradient: vec_to_array(&gradient),
+            hessian: mat_to_array(&hessian),
+        }
+    }
+
+    fn forward_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        mat_to_array(&forward_hessian_nograd_sparse_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            indices,
+        ))
+    }
+
+    fn forward_hessian_nograd_additive(
+        &self,
+        terms: &[(&dyn Fn(&Self) -> f64, Vec<usize>)],
+    ) -> Self::Hessian {
+        let wrapped: Vec<_> = terms
+            .iter()
+            .map(|(g, _)| move |x: &Vec<f64>| g(&vec_to_array(x)))
+            .collect();
+        let terms: Vec<_> = wrapped
+            .iter()
+            .zip(terms)
+            .map(|(g, (_, indices))| (g as &dyn Fn(&Vec<f64>) -> f64, indices.clone()))
+            .collect();
+        mat_to_array(&forward_hessian_nograd_additive_vec(&self.to_vec(), &terms))
+    }
+
+    fn central_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        mat_to_array(&central_hessian_nograd_sparse_vec(
+            &self.to_vec(),
+            &|x| f(&vec_to_array(x)),
+            indices,
+        ))
+    }
+
+    fn forward_hessian_diagonal_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        vec_to_array(&forward_hessian_diagonal_nograd_vec(&self.to_vec(), &|x| {
+            f(&vec_to_array(x))
+        }))
+    }
+
+    fn central_grad_and_diag(&self, f: &dyn Fn(&Self) -> f64) -> (Self, Self) {
+        let (grad, diag) = central_grad_and_diag_vec(&self.to_vec(), &|x| f(&vec_to_array(x)));
+        (vec_to_array(&grad), vec_to_array(&diag))
+    }
+}
+
+/// Matrix-valued parameters. Functions `fs` and gradients `g` are matrix-valued as well.
+///
+/// Jacobians and Hessians refer to the parameters and function values flattened in row-major
+/// order, i.e. entry `(i, j)` of a matrix `x` of shape `(n, m)` has the index `i * m + j`.
+/// The same indices are used by `PerturbationVector`s, sparse Hessians and gradient checks.
+///
+/// `forward_diff` and `central_diff` operate directly on the matrix. All other methods are
+/// computed via the `ndarray::Array1<f64>` implementation.
+#[cfg(feature = "ndarray")]
+impl FiniteDiff for ndarray::Array2<f64> {
+    type Scalar = f64;
+    type Jacobian = ndarray::Array2<f64>;
+    type Hessian = ndarray::Array2<f64>;
+    type OperatorOutput = ndarray::Array2<f64>;
+
+    fn forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        forward_diff_ndarray2(self, f)
+    }
+
+    fn try_forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Result<Self, FiniteDiffError> {
+        let dim = self.dim();
+        try_forward_diff_ndarray(&flatten_ndarray2(self), &|x| f(&unflatten_ndarray2(x, dim)))
+            .map(|g| unflatten_ndarray2(&g, dim))
+    }
+
+    fn forward_diff_precomputed(&self, f: &dyn Fn(&Self) -> f64, fx: f64) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self)
+                .forward_diff_precomputed(&|x| f(&unflatten_ndarray2(x, dim)), fx),
+            dim,
+        )
+    }
+
+    fn value_and_forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> (f64, Self) {
+        let fx = (f)(self);
+        (fx, self.forward_diff_precomputed(f, fx))
+    }
+
+    fn forward_diff_with_samples(&self, f: &dyn Fn(&Self) -> f64) -> (Self, f64, Vec<f64>) {
+        let dim = self.dim();
+        let (grad, fx, samples) =
+            flatten_ndarray2(self).forward_diff_with_samples(&|x| f(&unflatten_ndarray2(x, dim)));
+        (unflatten_ndarray2(&grad, dim), fx, samples)
+    }
+
+    fn forward_diff_traced(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        sink: &mut dyn FnMut(&[f64], f64),
+    ) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_diff_traced(&|x| f(&unflatten_ndarray2(x, dim)), sink),
+            dim,
+        )
+    }
+
+    fn forward_diff_subset(&self, f: &dyn Fn(&Self) -> f64, indices: &[usize]) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self)
+                .forward_diff_subset(&|x| f(&unflatten_ndarray2(x, dim)), indices),
+            dim,
+        )
+    }
+
+    fn forward_diff_masked(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        active: &[bool],
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.len(), active.len())?;
+        Ok(self.forward_diff_subset(f, &mask_indices(active)))
+    }
+
+    fn forward_diff_with_error(&self, f: &dyn Fn(&Self) -> f64) -> (Self, Self) {
+        let dim = self.dim();
+        let (grad, err) =
+            flatten_ndarray2(self).forward_diff_with_error(&|x| f(&unflatten_ndarray2(x, dim)));
+        (
+            unflatten_ndarray2(&grad, dim),
+            unflatten_ndarray2(&err, dim),
+        )
+    }
+
+    fn backward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).backward_diff(&|x| f(&unflatten_ndarray2(x, dim))),
+            dim,
+        )
+    }
+
+    fn central_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        central_diff_ndarray2(self, f)
+    }
+
+    fn diff_mixed(&self, f: &dyn Fn(&Self) -> f64, policy: &[Method]) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).diff_mixed(&|x| f(&unflatten_ndarray2(x, dim)), policy),
+            dim,
+        )
+    }
+
+    fn central_diff_5(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).central_diff_5(&|x| f(&unflatten_ndarray2(x, dim))),
+            dim,
+        )
+    }
+
+    fn forward_diff_2(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_diff_2(&|x| f(&unflatten_ndarray2(x, dim))),
+            dim,
+        )
+    }
+
+    fn forward_diff_order(&self, f: &dyn Fn(&Self) -> f64, order: StencilOrder) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_diff_order(&|x| f(&unflatten_ndarray2(x, dim)), order),
+            dim,
+        )
+    }
+
+    fn central_diff_order(&self, f: &dyn Fn(&Self) -> f64, order: StencilOrder) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).central_diff_order(&|x| f(&unflatten_ndarray2(x, dim)), order),
+            dim,
+        )
+    }
+
+    fn forward_diff_scaled(&self, f: &dyn Fn(&Self) -> f64, steps: &Self) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_diff_scaled(
+                &|x| f(&unflatten_ndarray2(x, dim)),
+                &flatten_ndarray2(steps),
+            ),
+            dim,
+        )
+    }
+
+    fn forward_diff_with_scaling(&self, f: &dyn Fn(&Self) -> f64, scale: &Self) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_diff_with_scaling(
+                &|x| f(&unflatten_ndarray2(x, dim)),
+                &flatten_ndarray2(scale),
+            ),
+            dim,
+        )
+    }
+
+    fn forward_diff_bounded(&self, f: &dyn Fn(&Self) -> f64, lower: &Self, upper: &Self) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_diff_bounded(
+                &|x| f(&unflatten_ndarray2(x, dim)),
+                &flatten_ndarray2(lower),
+                &flatten_ndarray2(upper),
+            ),
+            dim,
+        )
+    }
+
+    fn try_forward_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .try_forward_diff_scaled(
+                &|x| f(&unflatten_ndarray2(x, dim)),
+                &flatten_ndarray2(steps),
+            )
+            .map(|out| unflatten_ndarray2(&out, dim))
+    }
+
+    fn central_diff_scaled(&self, f: &dyn Fn(&Self) -> f64, steps: &Self) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).central_diff_scaled(
+                &|x| f(&unflatten_ndarray2(x, dim)),
+                &flatten_ndarray2(steps),
+            ),
+            dim,
+        )
+    }
+
+    fn try_central_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .try_central_diff_scaled(
+                &|x| f(&unflatten_ndarray2(x, dim)),
+                &flatten_ndarray2(steps),
+            )
+            .map(|out| unflatten_ndarray2(&out, dim))
+    }
+
+    fn richardson_diff(&self, f: &dyn Fn(&Self) -> f64, levels: usize) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).richardson_diff(&|x| f(&unflatten_ndarray2(x, dim)), levels),
+            dim,
+        )
+    }
+
+    fn adaptive_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).adaptive_diff(&|x| f(&unflatten_ndarray2(x, dim))),
+            dim,
+        )
+    }
+
+    fn auto_diff(&self, f: &dyn Fn(&Self) -> f64, target_rel_err: f64) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).auto_diff(&|x| f(&unflatten_ndarray2(x, dim)), target_rel_err),
+            dim,
+        )
+    }
+
+    fn estimate_diff_noise(&self, f: &mut dyn FnMut(&Self) -> f64, repeats: usize) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self)
+                .estimate_diff_noise(&mut |x| f(&unflatten_ndarray2(x, dim)), repeats),
+            dim,
+        )
+    }
+
+    fn forward_directional_diff(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> f64 {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .forward_directional_diff(&|x| f(&unflatten_ndarray2(x, dim)), &flatten_ndarray2(p))
+    }
+
+    fn central_directional_diff(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> f64 {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .central_directional_diff(&|x| f(&unflatten_ndarray2(x, dim)), &flatten_ndarray2(p))
+    }
+
+    fn forward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .forward_jacobian(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))))
+    }
+
+    fn try_forward_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .try_forward_jacobian(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))))
+    }
+
+    fn forward_jacobian_transposed(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .forward_jacobian_transposed(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))))
+    }
+
+    fn forward_jacobian_with_progress(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        on_col: &mut dyn FnMut(usize) -> bool,
+    ) -> Option<Self::Jacobian> {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_jacobian_with_progress(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            on_col,
+        )
+    }
+
+    fn forward_jacobian_diagonal(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self {
+        let dim = self.dim();
+        let diag = flatten_ndarray2(self)
+            .forward_jacobian_diagonal(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))));
+        unflatten_ndarray2(&diag, dim)
+    }
+
+    fn forward_jacobian_thresholded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        zero_tol: f64,
+    ) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_jacobian_thresholded(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            zero_tol,
+        )
+    }
+
+    fn forward_jacobian_update(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        prev: &Self::Jacobian,
+        x_prev: &Self,
+        step_tol: f64,
+    ) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_jacobian_update(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            prev,
+            &flatten_ndarray2(x_prev),
+            step_tol,
+        )
+    }
+
+    fn forward_jacobian_points(&self) -> Vec<Self> {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .forward_jacobian_points()
+            .iter()
+            .map(|x| unflatten_ndarray2(x, dim))
+            .collect()
+    }
+
+    fn forward_jacobian_from_values(&self, values: &[Self::OperatorOutput]) -> Self::Jacobian {
+        let values: Vec<ndarray::Array1<f64>> = values.iter().map(flatten_ndarray2).collect();
+        flatten_ndarray2(self).forward_jacobian_from_values(&values)
+    }
+
+    #[cfg(feature = "rayon")]
+    fn forward_jacobian_par(
+        &self,
+        fs: &(dyn Fn(&Self) -> Self::OperatorOutput + Sync),
+    ) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .forward_jacobian_par(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))))
+    }
+
+    fn backward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .backward_jacobian(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))))
+    }
+
+    fn central_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .central_jacobian(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))))
+    }
+
+    fn forward_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .forward_jacobian_into(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))), out)
+    }
+
+    fn central_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .central_jacobian_into(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))), out)
+    }
+
+    fn central_jacobian_5(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .central_jacobian_5(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))))
+    }
+
+    fn forward_jacobian_2(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .forward_jacobian_2(&|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))))
+    }
+
+    fn forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_jacobian_scaled(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            &flatten_ndarray2(steps),
+        )
+    }
+
+    fn try_forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        let dim = self.dim();
+        flatten_ndarray2(self).try_forward_jacobian_scaled(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            &flatten_ndarray2(steps),
+        )
+    }
+
+    fn central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self).central_jacobian_scaled(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            &flatten_ndarray2(steps),
+        )
+    }
+
+    fn try_central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        let dim = self.dim();
+        flatten_ndarray2(self).try_central_jacobian_scaled(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            &flatten_ndarray2(steps),
+        )
+    }
+
+    fn forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        let dim = self.dim();
+        let out_dim = std::cell::Cell::new((0, 0));
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_jacobian_vec_prod(
+                &|x| {
+                    let y = fs(&unflatten_ndarray2(x, dim));
+                    out_dim.set(y.dim());
+                    flatten_ndarray2(&y)
+                },
+                &flatten_ndarray2(p),
+            ),
+            out_dim.get(),
+        )
+    }
+
+    fn forward_jacobian_vec_prod_normalized(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        let norm = p.iter().map(|pi| pi * pi).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            return self.forward_jacobian_vec_prod(fs, p);
+        }
+        self.forward_jacobian_vec_prod(fs, &(p / norm)) * norm
+    }
+
+    fn forward_jacobian_vec_prod_at(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+        fx: &Self::OperatorOutput,
+    ) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_jacobian_vec_prod_at(
+                &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+                &flatten_ndarray2(p),
+                &flatten_ndarray2(fx),
+            ),
+            fx.dim(),
+        )
+    }
+
+    fn try_forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = self.dim();
+        let out_dim = std::cell::Cell::new((0, 0));
+        flatten_ndarray2(self)
+            .try_forward_jacobian_vec_prod(
+                &|x| {
+                    let y = fs(&unflatten_ndarray2(x, dim));
+                    out_dim.set(y.dim());
+                    flatten_ndarray2(&y)
+                },
+                &flatten_ndarray2(p),
+            )
+            .map(|out| unflatten_ndarray2(&out, out_dim.get()))
+    }
+
+    fn central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        let dim = self.dim();
+        let out_dim = std::cell::Cell::new((0, 0));
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).central_jacobian_vec_prod(
+                &|x| {
+                    let y = fs(&unflatten_ndarray2(x, dim));
+                    out_dim.set(y.dim());
+                    flatten_ndarray2(&y)
+                },
+                &flatten_ndarray2(p),
+            ),
+            out_dim.get(),
+        )
+    }
+
+    fn try_central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = self.dim();
+        let out_dim = std::cell::Cell::new((0, 0));
+        flatten_ndarray2(self)
+            .try_central_jacobian_vec_prod(
+                &|x| {
+                    let y = fs(&unflatten_ndarray2(x, dim));
+                    out_dim.set(y.dim());
+                    flatten_ndarray2(&y)
+                },
+                &flatten_ndarray2(p),
+            )
+            .map(|out| unflatten_ndarray2(&out, out_dim.get()))
+    }
+
+    fn forward_vjp(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        v: &Self::OperatorOutput,
+    ) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_vjp(
+                &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+                &flatten_ndarray2(v),
+            ),
+            dim,
+        )
+    }
+
+    fn forward_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_jacobian_pert(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            pert,
+        )
+    }
+
+    fn forward_jacobian_pert_sparse(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> SparseJacobian<f64> {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_jacobian_pert_sparse(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            pert,
+        )
+    }
+
+    fn forward_jacobian_banded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        kl: usize,
+        ku: usize,
+    ) -> BandedJacobian<f64> {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_jacobian_banded(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            kl,
+            ku,
+        )
+    }
+
+    fn central_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        let dim = self.dim();
+        flatten_ndarray2(self).central_jacobian_pert(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            pert,
+        )
+    }
+
+    fn detect_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: f64,
+    ) -> PerturbationVectors {
+        let dim = self.dim();
+        flatten_ndarray2(self).detect_sparsity(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            threshold,
+        )
+    }
+
+    fn jacobian_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: f64,
+    ) -> SparsityPattern {
+        let dim = self.dim();
+        flatten_ndarray2(self).jacobian_sparsity(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            threshold,
+        )
+    }
+
+    fn forward_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .forward_hessian(&|x| flatten_ndarray2(&g(&unflatten_ndarray2(x, dim))))
+    }
+
+    fn forward_hessian_sym(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        restore: bool,
+    ) -> Self::Hessian {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_hessian_sym(
+            &|x| flatten_ndarray2(&g(&unflatten_ndarray2(x, dim))),
+            restore,
+        )
+    }
+
+    fn try_forward_hessian<E>(
+        &self,
+        g: &dyn Fn(&Self) -> Result<Self::OperatorOutput, E>,
+    ) -> Result<Self::Hessian, E> {
+        let dim = self.dim();
+        flatten_ndarray2(self).try_forward_hessian(&|x| {
+            g(&unflatten_ndarray2(x, dim)).map(|gx| flatten_ndarray2(&gx))
+        })
+    }
+
+    fn central_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .central_hessian(&|x| flatten_ndarray2(&g(&unflatten_ndarray2(x, dim))))
+    }
+
+    fn forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_hessian_vec_prod(
+                &|x| flatten_ndarray2(&g(&unflatten_ndarray2(x, dim))),
+                &flatten_ndarray2(p),
+            ),
+            dim,
+        )
+    }
+
+    fn try_forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .try_forward_hessian_vec_prod(
+                &|x| flatten_ndarray2(&g(&unflatten_ndarray2(x, dim))),
+                &flatten_ndarray2(p),
+            )
+            .map(|out| unflatten_ndarray2(&out, dim))
+    }
+
+    fn central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).central_hessian_vec_prod(
+                &|x| flatten_ndarray2(&g(&unflatten_ndarray2(x, dim))),
+                &flatten_ndarray2(p),
+            ),
+            dim,
+        )
+    }
+
+    fn forward_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).forward_hessian_vec_prod_nograd(
+                &|x| f(&unflatten_ndarray2(x, dim)),
+                &flatten_ndarray2(p),
+            ),
+            dim,
+        )
+    }
+
+    fn central_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self).central_hessian_vec_prod_nograd(
+                &|x| f(&unflatten_ndarray2(x, dim)),
+                &flatten_ndarray2(p),
+            ),
+            dim,
+        )
+    }
+
+    fn check_gradient(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        analytic: &Self,
+        tol: f64,
+    ) -> Result<(), GradCheckReport<f64>> {
+        let dim = self.dim();
+        flatten_ndarray2(self).check_gradient(
+            &|x| f(&unflatten_ndarray2(x, dim)),
+            &flatten_ndarray2(analytic),
+            tol,
+        )
+    }
+
+    fn check_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        analytic: &Self::Jacobian,
+        tol: f64,
+    ) -> Result<(), GradCheckReport<f64>> {
+        let dim = self.dim();
+        flatten_ndarray2(self).check_jacobian(
+            &|x| flatten_ndarray2(&fs(&unflatten_ndarray2(x, dim))),
+            analytic,
+            tol,
+        )
+    }
+
+    fn try_central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .try_central_hessian_vec_prod(
+                &|x| flatten_ndarray2(&g(&unflatten_ndarray2(x, dim))),
+                &flatten_ndarray2(p),
+            )
+            .map(|out| unflatten_ndarray2(&out, dim))
+    }
+
+    fn forward_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self::Hessian {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_hessian_nograd(&|x| f(&unflatten_ndarray2(x, dim)))
+    }
+
+    fn forward_hessian_nograd_cancellable(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        cancel: &AtomicBool,
+    ) -> Option<Self::Hessian> {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .forward_hessian_nograd_cancellable(&|x| f(&unflatten_ndarray2(x, dim)), cancel)
+    }
+
+    fn forward_hessian_nograd_blocks(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        block_rows: usize,
+        on_block: &mut dyn FnMut(usize, &[Vec<f64>]),
+    ) {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_hessian_nograd_blocks(
+            &|x| f(&unflatten_ndarray2(x, dim)),
+            block_rows,
+            on_block,
+        )
+    }
+
+    fn forward_hessian_nograd_packed(&self, f: &dyn Fn(&Self) -> f64) -> PackedSymmetric<f64> {
+        let dim = self.dim();
+        flatten_ndarray2(self).forward_hessian_nograd_packed(&|x| f(&unflatten_ndarray2(x, dim)))
+    }
+
+    fn forward_grad_and_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> (Self, Self::Hessian) {
+        let dim = self.dim();
+        let (grad, hessian) = flatten_ndarray2(self)
+            .forward_grad_and_hessian_nograd(&|x| f(&unflatten_ndarray2(x, dim)));
+        (unflatten_ndarray2(&grad, dim), hessian)
+    }
+
+    fn central_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self::Hessian {
+        let dim = self.dim();
+        flatten_ndarray2(self).central_hessian_nograd(&|x| f(&unflatten_ndarray2(x, dim)))
+    }
+
+    fn derivatives(&self, f: &dyn Fn(&Self) -> f64) -> Derivatives<f64, Self, Self::Hessian> {
+        let dim = self.dim();
+        let d = flatten_ndarray2(self).derivatives(&|x| f(&unflatten_ndarray2(x, dim)));
+        Derivatives {
+            value: d.value,
+            gradient: unflatten_ndarray2(&d.gradient, dim),
+            hessian: d.hessian,
+        }
+    }
+
+    fn forward_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .forward_hessian_nograd_sparse(&|x| f(&unflatten_ndarray2(x, dim)), indices)
+    }
+
+    fn forward_hessian_nograd_additive(
+        &self,
+        terms: &[(&dyn Fn(&Self) -> f64, Vec<usize>)],
+    ) -> Self::Hessian {
+        let dim = self.dim();
+        let wrapped: Vec<_> = terms
+            .iter()
+            .map(|(g, _)| move |x: &ndarray::Array1<f64>| g(&unflatten_ndarray2(x, dim)))
+            .collect();
+        let terms: Vec<_> = wrapped
+            .iter()
+            .zip(terms)
+            .map(|(g, (_, indices))| (g as &dyn Fn(&ndarray::Array1<f64>) -> f64, indices.clone()))
+            .collect();
+        flatten_ndarray2(self).forward_hessian_nograd_additive(&terms)
+    }
+
+    fn central_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        let dim = self.dim();
+        flatten_ndarray2(self)
+            .central_hessian_nograd_sparse(&|x| f(&unflatten_ndarray2(x, dim)), indices)
+    }
+
+    fn forward_hessian_diagonal_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        let dim = self.dim();
+        unflatten_ndarray2(
+            &flatten_ndarray2(self)
+                .forward_hessian_diagonal_nograd(&|x| f(&unflatten_ndarray2(x, dim))),
+            dim,
+        )
+    }
+
+    fn central_grad_and_diag(&self, f: &dyn Fn(&Self) -> f64) -> (Self, Self) {
+        let dim = self.dim();
+        let (grad, diag) =
+            flatten_ndarray2(self).central_grad_and_diag(&|x| f(&unflatten_ndarray2(x, dim)));
+        (
+            unflatten_ndarray2(&grad, dim),
+            unflatten_ndarray2(&diag, dim),
+        )
+    }
+}
+
+/// Implementation for parameters grouped into sublists, which may differ in length.
+///
+/// `forward_diff` and `central_diff` operate directly on the nested parameters and return
+/// gradients of the same shape. All other methods are computed via the `Vec<f64>`
+/// implementation of the parameters concatenated sublist by sublist. Jacobians and Hessians
+/// refer to the concatenated parameters (and function values).
+impl FiniteDiff for Vec<Vec<f64>> {
+    type Scalar = f64;
+    type Jacobian = Vec<Vec<f64>>;
+    type Hessian = Vec<Vec<f64>>;
+    type OperatorOutput = Vec<Vec<f64>>;
+
+    fn forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        forward_diff_nested(self, f)
+    }
+
+    fn try_forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Result<Self, FiniteDiffError> {
+        let dim = nested_shape(self);
+        try_forward_diff_vec(&flatten_nested(self), &|x| f(&unflatten_nested(x, &dim)))
+            .map(|g| unflatten_nested(&g, &dim))
+    }
+
+    fn forward_diff_precomputed(&self, f: &dyn Fn(&Self) -> f64, fx: f64) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_diff_precomputed(&|x| f(&unflatten_nested(x, &dim)), fx),
+            &dim,
+        )
+    }
+
+    fn value_and_forward_diff(&self, f: &dyn Fn(&Self) -> f64) -> (f64, Self) {
+        let fx = (f)(self);
+        (fx, self.forward_diff_precomputed(f, fx))
+    }
+
+    fn forward_diff_with_samples(&self, f: &dyn Fn(&Self) -> f64) -> (Self, f64, Vec<f64>) {
+        let dim = nested_shape(self);
+        let (grad, fx, samples) =
+            flatten_nested(self).forward_diff_with_samples(&|x| f(&unflatten_nested(x, &dim)));
+        (unflatten_nested(&grad, &dim), fx, samples)
+    }
+
+    fn forward_diff_traced(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        sink: &mut dyn FnMut(&[f64], f64),
+    ) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_diff_traced(&|x| f(&unflatten_nested(x, &dim)), sink),
+            &dim,
+        )
+    }
+
+    fn forward_diff_subset(&self, f: &dyn Fn(&Self) -> f64, indices: &[usize]) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_diff_subset(&|x| f(&unflatten_nested(x, &dim)), indices),
+            &dim,
+        )
+    }
+
+    fn forward_diff_masked(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        active: &[bool],
+    ) -> Result<Self, FiniteDiffError> {
+        check_dim(self.iter().map(|row| row.len()).sum(), active.len())?;
+        Ok(self.forward_diff_subset(f, &mask_indices(active)))
+    }
+
+    fn forward_diff_with_error(&self, f: &dyn Fn(&Self) -> f64) -> (Self, Self) {
+        let dim = nested_shape(self);
+        let (grad, err) =
+            flatten_nested(self).forward_diff_with_error(&|x| f(&unflatten_nested(x, &dim)));
+        (unflatten_nested(&grad, &dim), unflatten_nested(&err, &dim))
+    }
+
+    fn backward_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).backward_diff(&|x| f(&unflatten_nested(x, &dim))),
+            &dim,
+        )
+    }
+
+    fn central_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        central_diff_nested(self, f)
+    }
+
+    fn diff_mixed(&self, f: &dyn Fn(&Self) -> f64, policy: &[Method]) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).diff_mixed(&|x| f(&unflatten_nested(x, &dim)), policy),
+            &dim,
+        )
+    }
+
+    fn central_diff_5(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).central_diff_5(&|x| f(&unflatten_nested(x, &dim))),
+            &dim,
+        )
+    }
+
+    fn forward_diff_2(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_diff_2(&|x| f(&unflatten_nested(x, &dim))),
+            &dim,
+        )
+    }
+
+    fn forward_diff_order(&self, f: &dyn Fn(&Self) -> f64, order: StencilOrder) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_diff_order(&|x| f(&unflatten_nested(x, &dim)), order),
+            &dim,
+        )
+    }
+
+    fn central_diff_order(&self, f: &dyn Fn(&Self) -> f64, order: StencilOrder) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).central_diff_order(&|x| f(&unflatten_nested(x, &dim)), order),
+            &dim,
+        )
+    }
+
+    fn forward_diff_scaled(&self, f: &dyn Fn(&Self) -> f64, steps: &Self) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self)
+                .forward_diff_scaled(&|x| f(&unflatten_nested(x, &dim)), &flatten_nested(steps)),
+            &dim,
+        )
+    }
+
+    fn forward_diff_with_scaling(&self, f: &dyn Fn(&Self) -> f64, scale: &Self) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_diff_with_scaling(
+                &|x| f(&unflatten_nested(x, &dim)),
+                &flatten_nested(scale),
+            ),
+            &dim,
+        )
+    }
+
+    fn forward_diff_bounded(&self, f: &dyn Fn(&Self) -> f64, lower: &Self, upper: &Self) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_diff_bounded(
+                &|x| f(&unflatten_nested(x, &dim)),
+                &flatten_nested(lower),
+                &flatten_nested(upper),
+            ),
+            &dim,
+        )
+    }
+
+    fn try_forward_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .try_forward_diff_scaled(&|x| f(&unflatten_nested(x, &dim)), &flatten_nested(steps))
+            .map(|out| unflatten_nested(&out, &dim))
+    }
+
+    fn central_diff_scaled(&self, f: &dyn Fn(&Self) -> f64, steps: &Self) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self)
+                .central_diff_scaled(&|x| f(&unflatten_nested(x, &dim)), &flatten_nested(steps)),
+            &dim,
+        )
+    }
+
+    fn try_central_diff_scaled(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        steps: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .try_central_diff_scaled(&|x| f(&unflatten_nested(x, &dim)), &flatten_nested(steps))
+            .map(|out| unflatten_nested(&out, &dim))
+    }
+
+    fn richardson_diff(&self, f: &dyn Fn(&Self) -> f64, levels: usize) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).richardson_diff(&|x| f(&unflatten_nested(x, &dim)), levels),
+            &dim,
+        )
+    }
+
+    fn adaptive_diff(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).adaptive_diff(&|x| f(&unflatten_nested(x, &dim))),
+            &dim,
+        )
+    }
+
+    fn auto_diff(&self, f: &dyn Fn(&Self) -> f64, target_rel_err: f64) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).auto_diff(&|x| f(&unflatten_nested(x, &dim)), target_rel_err),
+            &dim,
+        )
+    }
+
+    fn estimate_diff_noise(&self, f: &mut dyn FnMut(&Self) -> f64, repeats: usize) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self)
+                .estimate_diff_noise(&mut |x| f(&unflatten_nested(x, &dim)), repeats),
+            &dim,
+        )
+    }
+
+    fn forward_directional_diff(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> f64 {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .forward_directional_diff(&|x| f(&unflatten_nested(x, &dim)), &flatten_nested(p))
+    }
+
+    fn central_directional_diff(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> f64 {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .central_directional_diff(&|x| f(&unflatten_nested(x, &dim)), &flatten_nested(p))
+    }
+
+    fn forward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_jacobian(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))))
+    }
+
+    fn try_forward_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .try_forward_jacobian(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))))
+    }
+
+    fn forward_jacobian_transposed(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+    ) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .forward_jacobian_transposed(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))))
+    }
+
+    fn forward_jacobian_with_progress(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        on_col: &mut dyn FnMut(usize) -> bool,
+    ) -> Option<Self::Jacobian> {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_jacobian_with_progress(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            on_col,
+        )
+    }
+
+    fn forward_jacobian_diagonal(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self {
+        let dim = nested_shape(self);
+        let diag = flatten_nested(self)
+            .forward_jacobian_diagonal(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))));
+        unflatten_nested(&diag, &dim)
+    }
+
+    fn forward_jacobian_thresholded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        zero_tol: f64,
+    ) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_jacobian_thresholded(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            zero_tol,
+        )
+    }
+
+    fn forward_jacobian_update(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        prev: &Self::Jacobian,
+        x_prev: &Self,
+        step_tol: f64,
+    ) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_jacobian_update(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            prev,
+            &flatten_nested(x_prev),
+            step_tol,
+        )
+    }
+
+    fn forward_jacobian_points(&self) -> Vec<Self> {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .forward_jacobian_points()
+            .iter()
+            .map(|x| unflatten_nested(x, &dim))
+            .collect()
+    }
+
+    fn forward_jacobian_from_values(&self, values: &[Self::OperatorOutput]) -> Self::Jacobian {
+        let values: Vec<Vec<f64>> = values.iter().map(flatten_nested).collect();
+        flatten_nested(self).forward_jacobian_from_values(&values)
+    }
+
+    #[cfg(feature = "rayon")]
+    fn forward_jacobian_par(
+        &self,
+        fs: &(dyn Fn(&Self) -> Self::OperatorOutput + Sync),
+    ) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .forward_jacobian_par(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))))
+    }
+
+    fn backward_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self).backward_jacobian(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))))
+    }
+
+    fn central_jacobian(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self).central_jacobian(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))))
+    }
+
+    fn forward_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .forward_jacobian_into(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))), out)
+    }
+
+    fn central_jacobian_into(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        out: &mut Self::Jacobian,
+    ) {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .central_jacobian_into(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))), out)
+    }
+
+    fn central_jacobian_5(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .central_jacobian_5(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))))
+    }
+
+    fn forward_jacobian_2(&self, fs: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .forward_jacobian_2(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))))
+    }
+
+    fn forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_jacobian_scaled(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            &flatten_nested(steps),
+        )
+    }
+
+    fn try_forward_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        let dim = nested_shape(self);
+        flatten_nested(self).try_forward_jacobian_scaled(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            &flatten_nested(steps),
+        )
+    }
+
+    fn central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self).central_jacobian_scaled(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            &flatten_nested(steps),
+        )
+    }
+
+    fn try_central_jacobian_scaled(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        steps: &Self,
+    ) -> Result<Self::Jacobian, FiniteDiffError> {
+        let dim = nested_shape(self);
+        flatten_nested(self).try_central_jacobian_scaled(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            &flatten_nested(steps),
+        )
+    }
+
+    fn forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        let dim = nested_shape(self);
+        let out_dim = RefCell::new(Vec::new());
+        let jvp = flatten_nested(self).forward_jacobian_vec_prod(
+            &|x| {
+                let y = fs(&unflatten_nested(x, &dim));
+                *out_dim.borrow_mut() = nested_shape(&y);
+                flatten_nested(&y)
+            },
+            &flatten_nested(p),
+        );
+        unflatten_nested(&jvp, &out_dim.into_inner())
+    }
+
+    fn forward_jacobian_vec_prod_normalized(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        let scale = |v: &Vec<Vec<f64>>, c: f64| -> Vec<Vec<f64>> {
+            v.iter()
+                .map(|row| row.iter().map(|vi| vi * c).collect())
+                .collect()
+        };
+        let norm = p.iter().flatten().map(|pi| pi * pi).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            return self.forward_jacobian_vec_prod(fs, p);
+        }
+        scale(
+            &self.forward_jacobian_vec_prod(fs, &scale(p, 1.0 / norm)),
+            norm,
+        )
+    }
+
+    fn forward_jacobian_vec_prod_at(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+        fx: &Self::OperatorOutput,
+    ) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_jacobian_vec_prod_at(
+                &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+                &flatten_nested(p),
+                &flatten_nested(fx),
+            ),
+            &nested_shape(fx),
+        )
+    }
+
+    fn try_forward_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = nested_shape(self);
+        let out_dim = RefCell::new(Vec::new());
+        flatten_nested(self)
+            .try_forward_jacobian_vec_prod(
+                &|x| {
+                    let y = fs(&unflatten_nested(x, &dim));
+                    *out_dim.borrow_mut() = nested_shape(&y);
+                    flatten_nested(&y)
+                },
+                &flatten_nested(p),
+            )
+            .map(|out| unflatten_nested(&out, &out_dim.borrow()))
+    }
+
+    fn central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        let dim = nested_shape(self);
+        let out_dim = RefCell::new(Vec::new());
+        let jvp = flatten_nested(self).central_jacobian_vec_prod(
+            &|x| {
+                let y = fs(&unflatten_nested(x, &dim));
+                *out_dim.borrow_mut() = nested_shape(&y);
+                flatten_nested(&y)
+            },
+            &flatten_nested(p),
+        );
+        unflatten_nested(&jvp, &out_dim.into_inner())
+    }
+
+    fn try_central_jacobian_vec_prod(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = nested_shape(self);
+        let out_dim = RefCell::new(Vec::new());
+        flatten_nested(self)
+            .try_central_jacobian_vec_prod(
+                &|x| {
+                    let y = fs(&unflatten_nested(x, &dim));
+                    *out_dim.borrow_mut() = nested_shape(&y);
+                    flatten_nested(&y)
+                },
+                &flatten_nested(p),
+            )
+            .map(|out| unflatten_nested(&out, &out_dim.borrow()))
+    }
+
+    fn forward_vjp(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        v: &Self::OperatorOutput,
+    ) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_vjp(
+                &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+                &flatten_nested(v),
+            ),
+            &dim,
+        )
+    }
+
+    fn forward_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .forward_jacobian_pert(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))), pert)
+    }
+
+    fn forward_jacobian_pert_sparse(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> SparseJacobian<f64> {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_jacobian_pert_sparse(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            pert,
+        )
+    }
+
+    fn forward_jacobian_banded(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        kl: usize,
+        ku: usize,
+    ) -> BandedJacobian<f64> {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_jacobian_banded(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            kl,
+            ku,
+        )
+    }
+
+    fn central_jacobian_pert(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        pert: &PerturbationVectors,
+    ) -> Self::Jacobian {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .central_jacobian_pert(&|x| flatten_nested(&fs(&unflatten_nested(x, &dim))), pert)
+    }
+
+    fn detect_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: f64,
+    ) -> PerturbationVectors {
+        let dim = nested_shape(self);
+        flatten_nested(self).detect_sparsity(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            threshold,
+        )
+    }
+
+    fn jacobian_sparsity(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        threshold: f64,
+    ) -> SparsityPattern {
+        let dim = nested_shape(self);
+        flatten_nested(self).jacobian_sparsity(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            threshold,
+        )
+    }
+
+    fn forward_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_hessian(&|x| flatten_nested(&g(&unflatten_nested(x, &dim))))
+    }
+
+    fn forward_hessian_sym(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        restore: bool,
+    ) -> Self::Hessian {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .forward_hessian_sym(&|x| flatten_nested(&g(&unflatten_nested(x, &dim))), restore)
+    }
+
+    fn try_forward_hessian<E>(
+        &self,
+        g: &dyn Fn(&Self) -> Result<Self::OperatorOutput, E>,
+    ) -> Result<Self::Hessian, E> {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .try_forward_hessian(&|x| g(&unflatten_nested(x, &dim)).map(|gx| flatten_nested(&gx)))
+    }
+
+    fn central_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Hessian {
+        let dim = nested_shape(self);
+        flatten_nested(self).central_hessian(&|x| flatten_nested(&g(&unflatten_nested(x, &dim))))
+    }
+
+    fn forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_hessian_vec_prod(
+                &|x| flatten_nested(&g(&unflatten_nested(x, &dim))),
+                &flatten_nested(p),
+            ),
+            &dim,
+        )
+    }
+
+    fn try_forward_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .try_forward_hessian_vec_prod(
+                &|x| flatten_nested(&g(&unflatten_nested(x, &dim))),
+                &flatten_nested(p),
+            )
+            .map(|out| unflatten_nested(&out, &dim))
+    }
+
+    fn central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).central_hessian_vec_prod(
+                &|x| flatten_nested(&g(&unflatten_nested(x, &dim))),
+                &flatten_nested(p),
+            ),
+            &dim,
+        )
+    }
+
+    fn forward_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).forward_hessian_vec_prod_nograd(
+                &|x| f(&unflatten_nested(x, &dim)),
+                &flatten_nested(p),
+            ),
+            &dim,
+        )
+    }
+
+    fn central_hessian_vec_prod_nograd(&self, f: &dyn Fn(&Self) -> f64, p: &Self) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self).central_hessian_vec_prod_nograd(
+                &|x| f(&unflatten_nested(x, &dim)),
+                &flatten_nested(p),
+            ),
+            &dim,
+        )
+    }
+
+    fn check_gradient(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        analytic: &Self,
+        tol: f64,
+    ) -> Result<(), GradCheckReport<f64>> {
+        let dim = nested_shape(self);
+        flatten_nested(self).check_gradient(
+            &|x| f(&unflatten_nested(x, &dim)),
+            &flatten_nested(analytic),
+            tol,
+        )
+    }
+
+    fn check_jacobian(
+        &self,
+        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
+        analytic: &Self::Jacobian,
+        tol: f64,
+    ) -> Result<(), GradCheckReport<f64>> {
+        let dim = nested_shape(self);
+        flatten_nested(self).check_jacobian(
+            &|x| flatten_nested(&fs(&unflatten_nested(x, &dim))),
+            analytic,
+            tol,
+        )
+    }
+
+    fn try_central_hessian_vec_prod(
+        &self,
+        g: &dyn Fn(&Self) -> Self::OperatorOutput,
+        p: &Self,
+    ) -> Result<Self, FiniteDiffError> {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .try_central_hessian_vec_prod(
+                &|x| flatten_nested(&g(&unflatten_nested(x, &dim))),
+                &flatten_nested(p),
+            )
+            .map(|out| unflatten_nested(&out, &dim))
+    }
+
+    fn forward_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self::Hessian {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_hessian_nograd(&|x| f(&unflatten_nested(x, &dim)))
+    }
+
+    fn forward_hessian_nograd_cancellable(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        cancel: &AtomicBool,
+    ) -> Option<Self::Hessian> {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .forward_hessian_nograd_cancellable(&|x| f(&unflatten_nested(x, &dim)), cancel)
+    }
+
+    fn forward_hessian_nograd_blocks(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        block_rows: usize,
+        on_block: &mut dyn FnMut(usize, &[Vec<f64>]),
+    ) {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_hessian_nograd_blocks(
+            &|x| f(&unflatten_nested(x, &dim)),
+            block_rows,
+            on_block,
+        )
+    }
+
+    fn forward_hessian_nograd_packed(&self, f: &dyn Fn(&Self) -> f64) -> PackedSymmetric<f64> {
+        let dim = nested_shape(self);
+        flatten_nested(self).forward_hessian_nograd_packed(&|x| f(&unflatten_nested(x, &dim)))
+    }
+
+    fn forward_grad_and_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> (Self, Self::Hessian) {
+        let dim = nested_shape(self);
+        let (grad, hessian) = flatten_nested(self)
+            .forward_grad_and_hessian_nograd(&|x| f(&unflatten_nested(x, &dim)));
+        (unflatten_nested(&grad, &dim), hessian)
+    }
+
+    fn central_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self::Hessian {
+        let dim = nested_shape(self);
+        flatten_nested(self).central_hessian_nograd(&|x| f(&unflatten_nested(x, &dim)))
+    }
+
+    fn derivatives(&self, f: &dyn Fn(&Self) -> f64) -> Derivatives<f64, Self, Self::Hessian> {
+        let dim = nested_shape(self);
+        let d = flatten_nested(self).derivatives(&|x| f(&unflatten_nested(x, &dim)));
+        Derivatives {
+            value: d.value,
+            gradient: unflatten_nested(&d.gradient, &dim),
+            hessian: d.hessian,
+        }
+    }
+
+    fn forward_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .forward_hessian_nograd_sparse(&|x| f(&unflatten_nested(x, &dim)), indices)
+    }
+
+    fn forward_hessian_nograd_additive(
+        &self,
+        terms: &[(&dyn Fn(&Self) -> f64, Vec<usize>)],
+    ) -> Self::Hessian {
+        let dim = &nested_shape(self);
+        let wrapped: Vec<_> = terms
+            .iter()
+            .map(|(g, _)| move |x: &Vec<f64>| g(&unflatten_nested(x, dim)))
+            .collect();
+        let terms: Vec<_> = wrapped
+            .iter()
+            .zip(terms)
+            .map(|(g, (_, indices))| (g as &dyn Fn(&Vec<f64>) -> f64, indices.clone()))
+            .collect();
+        flatten_nested(self).forward_hessian_nograd_additive(&terms)
+    }
+
+    fn central_hessian_nograd_sparse(
+        &self,
+        f: &dyn Fn(&Self) -> f64,
+        indices: Vec<[usize; 2]>,
+    ) -> Self::Hessian {
+        let dim = nested_shape(self);
+        flatten_nested(self)
+            .central_hessian_nograd_sparse(&|x| f(&unflatten_nested(x, &dim)), indices)
+    }
+
+    fn forward_hessian_diagonal_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self {
+        let dim = nested_shape(self);
+        unflatten_nested(
+            &flatten_nested(self)
+                .forward_hessian_diagonal_nograd(&|x| f(&unflatten_nested(x, &dim))),
+            &dim,
+        )
+    }
+
+    fn central_grad_and_diag(&self, f: &dyn Fn(&Self) -> f64) -> (Self, Self) {
+        let dim = nested_shape(self);
+        let (grad, diag) =
+            flatten_nested(self).central_grad_and_diag(&|x| f(&unflatten_nested(x, &dim)));
+        (unflatten_nested(&grad, &dim), unflatten_nested(&diag, &dim))
+    }
+}
+
+#[cfg(test)]
+mod tests_vec {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    const COMP_ACC: f64 = 1e-6;
+
+    fn f1(x: &Vec<f64>) -> f64 {
+        x[0] + x[1].powi(2)
+    }
+
+    fn f2(x: &Vec<f64>) -> Vec<f64> {
+        vec![
+            2.0 * (x[1].powi(3) - x[0].powi(2)),
+            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
+            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
+            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
+            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
+            3.0 * (x[5].powi(3) - x[4].powi(2)),
+        ]
+    }
+
+    fn f3(x: &Vec<f64>) -> f64 {
+        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
+    }
+
+    fn f4(x: &Vec<f64>) -> f64 {
+        (1e-6 * x[0]).powi(2) + (1e3 * x[1]).powi(2)
+    }
+
+    fn g(x: &Vec<f64>) -> Vec<f64> {
+        vec![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    }
+
+    fn x1() -> Vec<f64> {
+        vec![1.0f64, 1.0f64]
+    }
+
+    fn x2() -> Vec<f64> {
+        vec![1.0f64, 1.0, 1.0, 1.0, 1.0, 1.0]
+    }
+
+    fn x3() -> Vec<f64> {
+        vec![1.0f64, 1.0, 1.0, 1.0]
+    }
+
+    fn res1() -> Vec<Vec<f64>> {
+        vec![
+            vec![-4.0, -6.0, 0.0, 0.0, 0.0, 0.0],
+            vec![6.0, 5.0, -6.0, 0.0, 0.0, 0.0],
+            vec![0.0, 6.0, 5.0, -6.0, 0.0, 0.0],
+            vec![0.0, 0.0, 6.0, 5.0, -6.0, 0.0],
+            vec![0.0, 0.0, 0.0, 6.0, 5.0, -6.0],
+            vec![0.0, 0.0, 0.0, 0.0, 6.0, 9.0],
+        ]
+    }
+
+    fn res2() -> Vec<Vec<f64>> {
+        vec![
+            vec![0.0, 0.0, 0.0, 0.0],
+            vec![0.0, 2.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0, 2.0],
+            vec![0.0, 0.0, 2.0, 2.0],
+        ]
+    }
+
+    fn res3() -> Vec<f64> {
+        vec![8.0, 22.0, 27.0, 32.0, 37.0, 24.0]
+    }
+
+    fn pert() -> PerturbationVectors {
+        vec![
+            PerturbationVector::new()
+                .add(0, vec![0, 1])
+                .add(3, vec![2, 3, 4]),
+            PerturbationVector::new()
+                .add(1, vec![0, 1, 2])
+                .add(4, vec![3, 4, 5]),
+            PerturbationVector::new()
+                .add(2, vec![1, 2, 3])
+                .add(5, vec![4, 5]),
+        ]
+    }
+
+    fn p1() -> Vec<f64> {
+        vec![1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0]
+    }
+
+    fn p2() -> Vec<f64> {
+        vec![2.0, 3.0, 4.0, 5.0]
+    }
+
+    #[test]
+    fn test_forward_diff_vec_f64_trait() {
+        let grad = x1().forward_diff(&f1);
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let p = vec![1.0f64, 2.0f64];
+        let grad = p.forward_diff(&f1);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    /// Perturbations of all inputs of `f` relative to `x` in multiples of `h`, in the order of
+    /// evaluation
+    fn record_evaluations(
+        x: &Vec<f64>,
+        h: f64,
+        run: impl Fn(&dyn Fn(&Vec<f64>) -> f64),
+    ) -> Vec<Vec<i32>> {
+        let inputs = RefCell::new(vec![]);
+        run(&|p: &Vec<f64>| {
+            inputs.borrow_mut().push(p.clone());
+            f1(p)
+        });
+        inputs
+            .into_inner()
+            .iter()
+            .map(|p| {
+                p.iter()
+                    .zip(x.iter())
+                    .map(|(pi, xi)| ((pi - xi) / h).round() as i32)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_evaluation_order_vec_f64_trait() {
+        let x = vec![1.0f64, 2.0];
+        let h = EPS_F64.sqrt();
+        // the steps of gradients and Jacobians are scaled by `max(|x_i|, 1)`
+        let forward = vec![vec![0, 0], vec![1, 0], vec![0, 2]];
+        let central = vec![vec![1, 0], vec![-1, 0], vec![0, 2], vec![0, -2]];
+        assert_eq!(
+            record_evaluations(&x, h, |f| drop(x.forward_diff(f))),
+            forward
+        );
+        assert_eq!(
+            record_evaluations(&x, EPS_F64.cbrt(), |f| drop(x.central_diff(f))),
+            central
+        );
+        assert_eq!(
+            record_evaluations(&x, h, |f| drop(x.forward_jacobian(&|p| vec![f(p)]))),
+            forward
+        );
+        assert_eq!(
+            record_evaluations(&x, h, |f| drop(x.central_jacobian(&|p| vec![f(p)]))),
+            central
+        );
+        // the diagonal of the Hessian is evaluated with its own step `EPS^(1/4)`
+        let h = (2.0 * EPS_F64).sqrt();
+        let d = (EPS_F64.powf(0.25) / h).round() as i32;
+        assert_eq!(
+            record_evaluations(&x, h, |f| drop(x.forward_hessian_nograd(f))),
+            vec![
+                vec![0, 0],
+                vec![1, 0],
+                vec![0, 1],
+                vec![d, 0],
+                vec![-d, 0],
+                vec![1, 1],
+                vec![0, d],
+                vec![0, -d]
+            ]
+        );
+        assert_eq!(
+            record_evaluations(&x, EPS_F64.powf(0.25), |f| drop(
+                x.central_hessian_nograd(f)
+            )),
+            vec![
+                vec![2, 0],
+                vec![0, 0],
+                vec![0, 0],
+                vec![-2, 0],
+                // (i, j) = (1, 0) with the shifts of the formula applied to (x_1, x_0)
+                vec![1, 1],
+                vec![-1, 1],
+                vec![1, -1],
+                vec![-1, -1],
+                vec![0, 2],
+                vec![0, 0],
+                vec![0, 0],
+                vec![0, -2]
+            ]
+        );
+
+        // repeated runs visit the same points
+        let first = RefCell::new(vec![]);
+        let second = RefCell::new(vec![]);
+        x.forward_hessian_nograd(&|p| {
+            first.borrow_mut().push(p.clone());
+            f1(p)
+        });
+        x.forward_hessian_nograd(&|p| {
+            second.borrow_mut().push(p.clone());
+            f1(p)
+        });
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_default_step_vec_f64_trait() {
+        let h = default_step::<f64>();
+        assert_eq!(h, EPS_F64.sqrt());
+        let p = x1();
+        let (mut p0, mut p1) = (p.clone(), p.clone());
+        p0[1] += h;
+        p1[1] -= h;
+        assert_eq!(p.forward_diff(&f1)[1], (f1(&p0) - f1(&p)) / h);
+        assert_eq!(p.backward_diff(&f1)[1], (f1(&p) - f1(&p1)) / h);
+
+        let h = EPS_F64.cbrt();
+        let (mut p0, mut p1) = (p.clone(), p.clone());
+        p0[1] += h;
+        p1[1] -= h;
+        assert_eq!(p.central_diff(&f1)[1], (f1(&p0) - f1(&p1)) / (2.0 * h));
+    }
+
+    #[test]
+    fn test_backward_diff_vec_f64_trait() {
+        let grad = x1().backward_diff(&f1);
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let p = vec![1.0f64, 2.0f64];
+        let grad = p.backward_diff(&f1);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_vec_f64_trait() {
+        let grad = x1().central_diff(&f1);
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let p = vec![1.0f64, 2.0f64];
+        let grad = p.central_diff(&f1);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_f64_trait() {
+        let jacobian = x2().forward_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_forward_jacobian_vec_f64_trait() {
+        assert_eq!(
+            x2().try_forward_jacobian(&f2),
+            Ok(x2().forward_jacobian(&f2))
+        );
+
+        // drops the last value once the third parameter is perturbed
+        let fs = |x: &Vec<f64>| {
+            let mut out = f2(x);
+            if x[2] != x2()[2] {
+                out.pop();
+            }
+            out
+        };
+        assert_eq!(
+            x2().try_forward_jacobian(&fs),
+            Err(FiniteDiffError::OutputLengthChanged {
+                col: 2,
+                expected: 6,
+                got: 5
+            })
+        );
+        // appends a value once the last parameter is perturbed
+        let fs = |x: &Vec<f64>| {
+            let mut out = f2(x);
+            if x[5] != x2()[5] {
+                out.push(0.0);
+            }
+            out
+        };
+        assert_eq!(
+            x2().try_forward_jacobian(&fs),
+            Err(FiniteDiffError::OutputLengthChanged {
+                col: 5,
+                expected: 6,
+                got: 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_forward_jacobian_with_progress_vec_f64_trait() {
+        let mut cols = vec![];
+        let jacobian = x2().forward_jacobian_with_progress(&f2, &mut |i| {
+            cols.push(i);
+            true
+        });
+        assert_eq!(jacobian, Some(x2().forward_jacobian(&f2)));
+        assert_eq!(cols, vec![0, 1, 2, 3, 4, 5]);
+
+        let evals = core::cell::Cell::new(0);
+        let fs = |x: &Vec<f64>| {
+            evals.set(evals.get() + 1);
+            f2(x)
+        };
+        assert_eq!(
+            x2().forward_jacobian_with_progress(&fs, &mut |i| i < 2),
+            None
+        );
+        assert_eq!(evals.get(), 4);
+    }
+
+    #[test]
+    fn test_forward_jacobian_cancellable_vec_f64_trait() {
+        let cancel = AtomicBool::new(false);
+        let jacobian = x2().forward_jacobian_cancellable(&f2, &cancel);
+        assert_eq!(jacobian, Some(x2().forward_jacobian(&f2)));
+
+        // cancel after two parameters
+        let evals = core::cell::Cell::new(0);
+        let fs = |x: &Vec<f64>| {
+            evals.set(evals.get() + 1);
+            if evals.get() == 3 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            f2(x)
+        };
+        assert_eq!(x2().forward_jacobian_cancellable(&fs, &cancel), None);
+        assert_eq!(evals.get(), 3);
+        assert_eq!(x2().forward_jacobian_cancellable(&fs, &cancel), None);
+        assert_eq!(evals.get(), 3);
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_cancellable_vec_f64_trait() {
+        let cancel = AtomicBool::new(false);
+        let hessian = x3().forward_hessian_nograd_cancellable(&f3, &cancel);
+        assert_eq!(hessian, Some(x3().forward_hessian_nograd(&f3)));
+
+        // cancel within the second row
+        let evals = core::cell::Cell::new(0);
+        let fc = |x: &Vec<f64>| {
+            evals.set(evals.get() + 1);
+            if evals.get() == 8 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            f3(x)
+        };
+        assert_eq!(x3().forward_hessian_nograd_cancellable(&fc, &cancel), None);
+        assert_eq!(evals.get(), 10);
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_blocks_vec_f64_trait() {
+        let mut rows = vec![];
+        x3().forward_hessian_nograd_blocks(&f3, 3, &mut |_, block| rows.extend_from_slice(block));
+        assert_eq!(rows, x3().forward_hessian_nograd(&f3));
+    }
+
+    #[test]
+    fn test_forward_jacobian_diagonal_vec_f64_trait() {
+        let diag =
+            x2().forward_jacobian_diagonal(&|x: &Vec<f64>| x.iter().map(|v| v * v).collect());
+        let jacobian = x2().forward_jacobian(&|x: &Vec<f64>| x.iter().map(|v| v * v).collect());
+        assert_eq!(diag.len(), 6);
+        for i in 0..6 {
+            assert!((2.0 * x2()[i] - diag[i]).abs() < COMP_ACC);
+            assert_eq!(diag[i], jacobian[i][i]);
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_thresholded_vec_f64_trait() {
+        let fs = |x: &Vec<f64>| vec![x[0] * x[2], x[1].exp() * (-x[1]).exp(), x[2]];
+        let jacobian = vec![1.0f64, 0.5, 2.0].forward_jacobian_thresholded(&fs, 1e-12);
+        assert_eq!(jacobian[1], vec![0.0; 3]);
+        let res = [[2.0, 0.0, 0.0], [0.0; 3], [1.0, 0.0, 1.0]];
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_update_vec_f64_trait() {
+        let x_prev = vec![0.5f64, 0.75, 1.25, 1.5, 0.25, 2.0];
+        let prev = x_prev.forward_jacobian(&f2);
+
+        // all parameters moved, so everything is recomputed
+        let jacobian = x2().forward_jacobian_update(&f2, &prev, &x_prev, 0.0);
+        assert_eq!(jacobian, x2().forward_jacobian(&f2));
+
+        // only the second and fifth parameter moved by more than the tolerance
+        let mut x = x_prev.clone();
+        x[1] = 1.0;
+        x[3] += 1e-4;
+        x[4] = 1.0;
+        let fs = CountingFn::new(f2);
+        let jacobian = x.forward_jacobian_update(&|x| fs.call(x), &prev, &x_prev, 1e-3);
+        assert_eq!(fs.count(), 3);
+        let full = x.forward_jacobian(&f2);
+        for i in 0..6 {
+            if i == 1 || i == 4 {
+                assert_eq!(jacobian[i], full[i]);
+            } else {
+                assert_eq!(jacobian[i], prev[i]);
+            }
+        }
+
+        // nothing moved
+        let fs = CountingFn::new(f2);
+        let jacobian = x_prev.forward_jacobian_update(&|x| fs.call(x), &prev, &x_prev, 0.0);
+        assert_eq!(fs.count(), 0);
+        assert_eq!(jacobian, prev);
+    }
+
+    #[test]
+    fn test_forward_jacobian_from_values_vec_f64_trait() {
+        let p = x2();
+        let values: Vec<_> = p.forward_jacobian_points().iter().map(f2).collect();
+        assert_eq!(
+            p.forward_jacobian_from_values(&values),
+            p.forward_jacobian(&f2)
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_forward_jacobian_par_vec_f64_trait() {
+        let jacobian = x2().forward_jacobian_par(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_vec_f64_trait() {
+        let jacobian = x2().backward_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_vec_f64_trait() {
+        let jacobian = x2().central_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_into_vec_f64_trait() {
+        let mut jacobian = vec![vec![0.0; 6]; 6];
+        x2().forward_jacobian_into(&f2, &mut jacobian);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_scaled_vec_f64_trait() {
+        let x = vec![1e6f64, 1e-3];
+        let steps = vec![1e-2f64, 1e-11];
+        let grad = x.forward_diff_scaled(&f4, &steps);
+        let res = [2e-6f64, 2e3];
+
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_with_scaling_vec_f64_trait() {
+        let x = vec![1e6f64, 1e-3];
+        let grad = x.forward_diff_with_scaling(&f4, &vec![1e6, 1e-3]);
+        let res = [2e-6f64, 2e3];
+
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_bounded_vec_f64_trait() {
+        let f = |x: &Vec<f64>| {
+            assert!(x[1] <= 1.0, "infeasible evaluation");
+            f1(x)
+        };
+        let grad = x1().forward_diff_bounded(&f, &vec![0.0, 0.0], &vec![2.0, 1.0]);
+        let res = [1.0f64, 2.0];
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_scaled_vec_f64_trait() {
+        let x = vec![1e6f64, 1e-3];
+        let steps = vec![1e-2f64, 1e-11];
+        let grad = x.central_diff_scaled(&f4, &steps);
+        let res = [2e-6f64, 2e3];
+
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_forward_diff_relative_vec_f64_trait() {
+        let p = vec![1e8f64, 1.0];
+        let f = |x: &Vec<f64>| x[0].ln() + x[1].powi(2);
+        let grad = p.forward_diff_relative(&f);
+        assert_eq!(grad, p.forward_diff(&f));
+        let res = [1e-8f64, 2.0];
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_scaled_vec_f64_trait() {
+        let steps = vec![1e-8f64, 2e-8, 1e-8, 2e-8, 1e-8, 2e-8];
+        let jacobian = x2().forward_jacobian_scaled(&f2, &steps);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_scaled_vec_f64_trait() {
+        let steps = vec![1e-8f64, 2e-8, 1e-8, 2e-8, 1e-8, 2e-8];
+        let jacobian = x2().central_jacobian_scaled(&f2, &steps);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_vec_f64_trait() {
+        let jacobian = x2().forward_jacobian_vec_prod(&f2, &p1());
+        let res = res3();
+        // println!("{:?}", jacobian);
+        // the accuracy for this is pretty bad!!
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_normalized_vec_f64_trait() {
+        let jacobian = x2().forward_jacobian_vec_prod_normalized(&f2, &p1());
+        let res = res3();
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_at_vec_f64_trait() {
+        let fx = f2(&x2());
+        let f = CountingFn::new(f2);
+        let res = res3();
+        for &sign in [1.0f64, -1.0, 1.0].iter() {
+            let p = p1().iter().map(|pi| sign * pi).collect();
+            let jacobian = x2().forward_jacobian_vec_prod_at(&|x| f.call(x), &p, &fx);
+            for i in 0..6 {
+                assert!((sign * res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+            }
+        }
+        // only the perturbed positions are evaluated
+        assert_eq!(f.count(), 3);
+    }
+
+    #[test]
+    fn test_central_jacobian_vec_prod_vec_f64_trait() {
+        let jacobian = x2().central_jacobian_vec_prod(&f2, &p1());
+        let res = res3();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_vjp_vec_f64_trait() {
+        let vjp = x2().forward_vjp(&f2, &p1());
+        // J^T*v computed by hand
+        let res = [-16.0f64, -2.0, 3.0, 8.0, 13.0, 84.0];
+        // println!("{:?}", vjp);
+        for i in 0..6 {
+            assert!((res[i] - vjp[i]).abs() < 11.0 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_pert_vec_f64_trait() {
+        let jacobian = x2().forward_jacobian_pert(&f2, &pert());
+        let res = res1();
+        // println!("jacobian:\n{:?}", jacobian);
+        // println!("res:\n{:?}", res);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_pert_vec_f64_trait() {
+        let jacobian = x2().central_jacobian_pert(&f2, &pert());
+        let res = res1();
+        // println!("jacobian:\n{:?}", jacobian);
+        // println!("res:\n{:?}", res);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_f64_trait() {
+        let hessian = x3().forward_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_sym_vec_f64_trait() {
+        let raw = x3().forward_hessian_sym(&g, false);
+        let restored = x3().forward_hessian_sym(&g, true);
+        assert_eq!(restored, x3().forward_hessian(&g));
+        let res = res2();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - raw[i][j]).abs() < COMP_ACC);
+                assert_eq!(restored[i][j], restored[j][i]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_forward_hessian_vec_f64_trait() {
+        let hessian = x3()
+            .try_forward_hessian(&|x: &Vec<f64>| Ok::<_, ()>(g(x)))
+            .unwrap();
+        let res = res2();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+
+        // fails once the third parameter is perturbed
+        let evals = core::cell::Cell::new(0);
+        let x0 = x3();
+        let failing = |x: &Vec<f64>| {
+            evals.set(evals.get() + 1);
+            if x[2] != x0[2] {
+                Err("no convergence")
+            } else {
+                Ok(g(x))
+            }
+        };
+        assert_eq!(x3().try_forward_hessian(&failing), Err("no convergence"));
+        assert_eq!(evals.get(), 4);
+    }
+
+    #[test]
+    fn test_central_hessian_vec_f64_trait() {
+        let hessian = x3().central_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_prod_vec_f64_trait() {
+        let hessian = x3().forward_hessian_vec_prod(&g, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_vec_f64_trait() {
+        let hessian = x3().central_hessian_vec_prod(&g, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_prod_nograd_vec_f64_trait() {
+        let hessian = x3().forward_hessian_vec_prod_nograd(&f3, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < 1e-3)
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_nograd_vec_f64_trait() {
+        let hessian = x3().central_hessian_vec_prod_nograd(&f3, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_vec_f64_trait() {
+        let hessian = x3().forward_hessian_nograd(&f3);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_packed_vec_f64_trait() {
+        let hessian = x3().forward_hessian_nograd_packed(&f3);
+        assert_eq!(hessian.to_dense(), x3().forward_hessian_nograd(&f3));
+    }
+
+    #[test]
+    fn test_forward_grad_and_hessian_nograd_vec_f64_trait() {
+        let (grad, hessian) = x3().forward_grad_and_hessian_nograd(&f3);
+        let res_grad = [1.0f64, 2.0, 1.0, 2.0];
+        let res = res2();
+        for i in 0..4 {
+            assert!((res_grad[i] - grad[i]).abs() < COMP_ACC);
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_vec_f64_trait() {
+        let hessian = x3().central_hessian_nograd(&f3);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_sparse_vec_f64_trait() {
+        let indices = vec![[1, 1], [2, 3], [3, 3]];
+        let hessian = x3().forward_hessian_nograd_sparse(&f3, indices);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_sparse_vec_f64_trait() {
+        let indices = vec![[1, 1], [2, 3], [3, 3]];
+        let hessian = x3().central_hessian_nograd_sparse(&f3, indices);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_diff_5_vec_f64_trait() {
+        let p = vec![2.0f64, -1.5f64];
+        let f_exp = |x: &Vec<f64>| x[0].exp() + x[1].exp();
+        let grad3 = p.central_diff(&f_exp);
+        let grad5 = p.central_diff_5(&f_exp);
+
+        for i in 0..2 {
+            assert!((p[i].exp() - grad5[i]).abs() < (p[i].exp() - grad3[i]).abs())
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_2_vec_f64_trait() {
+        let p = vec![2.0f64, -1.5f64];
+        let f_exp = |x: &Vec<f64>| x[0].exp() + x[1].exp();
+        let grad1 = p.forward_diff(&f_exp);
+        let grad2 = p.forward_diff_2(&f_exp);
+
+        for i in 0..2 {
+            assert!((p[i].exp() - grad2[i]).abs() < (p[i].exp() - grad1[i]).abs())
+        }
+    }
+
+    #[test]
+    fn test_diff_order_vec_f64_trait() {
+        let p = vec![2.0f64, -1.5f64];
+        let f_exp = |x: &Vec<f64>| x[0].exp() + x[1].exp();
+        let grad1 = p.forward_diff(&f_exp);
+        let grad6 = p.forward_diff_order(&f_exp, StencilOrder::Order6);
+        let grad4 = p.central_diff_order(&f_exp, StencilOrder::Order4);
+
+        for i in 0..2 {
+            assert!((p[i].exp() - grad6[i]).abs() < (p[i].exp() - grad1[i]).abs());
+            assert!((p[i].exp() - grad4[i]).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_5_vec_f64_trait() {
+        let jacobian = x2().central_jacobian_5(&f2);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_2_vec_f64_trait() {
+        let jacobian = x2().forward_jacobian_2(&f2);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_richardson_diff_vec_f64_trait() {
+        let p = vec![2.0f64, 0.5];
+        let f = |x: &Vec<f64>| x[0].exp() + x[1].sin();
+        let grad = p.richardson_diff(&f, 3);
+        let res = [p[0].exp(), p[1].cos()];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < 1e-11)
+        }
+    }
+
+    #[test]
+    fn test_adaptive_diff_vec_f64_trait() {
+        let p = vec![2.0f64, 0.5];
+        let f = |x: &Vec<f64>| x[0].exp() + x[1].sin();
+        let grad = p.adaptive_diff(&f);
+        let res = [p[0].exp(), p[1].cos()];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < 1e-9)
+        }
+    }
+
+    #[test]
+    fn test_auto_diff_vec_f64_trait() {
+        let p = vec![1.0f64, 1e-4];
+        let f = |x: &Vec<f64>| x[0].exp() + 1e4 * x[1].powi(2);
+        assert_eq!(p.auto_diff(&f, 1e-6), p.central_diff(&f));
+        assert_eq!(p.auto_diff(&f, 1e-2), p.forward_diff(&f));
+    }
+
+    #[test]
+    fn test_estimate_diff_noise_vec_f64_trait() {
+        let p = vec![1.0f64, 1.0];
+        assert_eq!(p.estimate_diff_noise(&mut |x| f1(x), 3), vec![0.0, 0.0]);
+        let mut k = 0u32;
+        let noise = p.estimate_diff_noise(
+            &mut |x: &Vec<f64>| {
+                k += 1;
+                f1(x) + 1e-6 * (k as f64).sin()
+            },
+            10,
+        );
+        assert!(noise.iter().all(|&s| s > 1.0));
+    }
+
+    #[test]
+    fn test_forward_diff_with_error_vec_f64_trait() {
+        let p = vec![1.0f64, 1.0];
+        let (grad, err) = p.forward_diff_with_error(&|x: &Vec<f64>| x[0] + x[1].powi(2));
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC);
+            assert!(err[i] < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_try_forward_jacobian_vec_prod_vec_f64_trait() {
+        let jacobian = x2().try_forward_jacobian_vec_prod(&f2, &p1()).unwrap();
+        let res = res3();
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+        }
+
+        assert_eq!(
+            x2().try_forward_jacobian_vec_prod(&f2, &p2()),
+            Err(FiniteDiffError::DimensionMismatch {
+                expected: 6,
+                got: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_try_central_hessian_vec_prod_vec_f64_trait() {
+        assert_eq!(
+            x3().try_central_hessian_vec_prod(&g, &p1()),
+            Err(FiniteDiffError::DimensionMismatch {
+                expected: 4,
+                got: 6
+            })
+        );
+    }
+
+    #[test]
+    fn test_try_forward_diff_scaled_vec_f64_trait() {
+        let steps = vec![1e-2f64, 1e-11];
+        let x = vec![1e6f64, 1e-3];
+        assert!(x.try_forward_diff_scaled(&f4, &steps).is_ok());
+
+        let x = vec![f64::NAN, 1e-3];
+        assert_eq!(
+            x.try_forward_diff_scaled(&f4, &steps),
+            Err(FiniteDiffError::NonFinite { index: 0 })
+        );
+
+        let x = vec![1e20f64, 1e-3];
+        assert_eq!(
+            x.try_forward_diff_scaled(&f4, &steps),
+            Err(FiniteDiffError::StepUnderflow { index: 0 })
+        );
+
+        // only `x_0 - steps_0` rounds back to `x_0`, which forward differences never evaluate
+        let x = vec![-(2.0f64.powi(27)), 1e-3];
+        let steps = vec![EPS_F64.sqrt(), 1e-11];
+        assert!(x.try_forward_diff_scaled(&f4, &steps).is_ok());
+        assert_eq!(
+            x.try_central_diff_scaled(&f4, &steps),
+            Err(FiniteDiffError::StepUnderflow { index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_try_forward_diff_vec_f64_trait() {
+        let grad = x1().try_forward_diff(&f1).unwrap();
+        let res = [1.0f64, 2.0];
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let f = |x: &Vec<f64>| f64::MAX * (x[1] - 0.5).signum();
+        let x = vec![1.0f64, 0.5 - 1e-10];
+        assert_eq!(
+            x.try_forward_diff(&f),
+            Err(FiniteDiffError::NonFinite { index: 1 })
+        );
+
+        // the steps are relative to the parameters and therefore not lost to rounding
+        let grad = vec![1.0f64, 1e20].try_forward_diff(&f1).unwrap();
+        assert!(((grad[1] - 2e20) / 2e20).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_try_non_finite_index_vec_f64_trait() {
+        // `f` is finite at `x` but not if `x_1` is perturbed
+        let f = |x: &Vec<f64>| x[0] + if x[1] > 1.0 { f64::INFINITY } else { x[1] };
+        let fs = |x: &Vec<f64>| -> Vec<f64> {
+            vec![if x[1] > 1.0 { f64::INFINITY } else { x[0] }, x[1]]
+        };
+        let x = vec![0.0f64, 1.0];
+        let steps = vec![1e-6f64, 1e-6];
+
+        assert_eq!(
+            x.try_forward_diff_scaled(&f, &steps),
+            Err(FiniteDiffError::NonFinite { index: 1 })
+        );
+        assert_eq!(
+            x.try_forward_jacobian_scaled(&fs, &steps),
+            Err(FiniteDiffError::NonFiniteJacobian { index: (1, 0) })
+        );
+    }
+
+    #[test]
+    fn test_forward_diff_mut_vec_f64_trait() {
+        let mut evals = 0;
+        let grad = x1().forward_diff_mut(&mut |x: &Vec<f64>| {
+            evals += 1;
+            f1(x)
+        });
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+        assert_eq!(evals, 3);
+    }
+
+    #[test]
+    fn test_forward_diff_with_vec_f64_trait() {
+        let f = |x: &Vec<f64>, p: &f64| p * f1(x);
+        let grad = x1().forward_diff_with(&f, &3.0);
+        let res = [3.0f64, 6.0];
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let fs = |x: &Vec<f64>, p: &f64| vec![p * x[0], x[1].powi(2)];
+        let jacobian = x1().forward_jacobian_with(&fs, &3.0);
+        let res = [[3.0f64, 0.0], [0.0, 2.0]];
+        for i in 0..2 {
+            for j in 0..2 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_typed_vec_f64_trait() {
+        let grad = x1().forward_diff_typed(&f1);
+        assert_eq!(grad.len(), 2);
+        assert_eq!(grad.into_inner(), x1().forward_diff(&f1));
+    }
+
+    #[test]
+    fn test_central_diff_paired_vec_f64_trait() {
+        // noise which only depends on the index of the pair of evaluations
+        let mut evals = 0;
+        let grad = x1().central_diff_paired(&mut |x: &Vec<f64>| {
+            let noise = 100.0 * ((evals / 2) as f64 * 1.3).sin();
+            evals += 1;
+            f1(x) + noise
+        });
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+        assert_eq!(evals, 4);
+    }
+
+    #[test]
+    fn test_central_jacobian_mut_vec_f64_trait() {
+        let mut evals = 0;
+        let jacobian = x2().central_jacobian_mut(&mut |x: &Vec<f64>| {
+            evals += 1;
+            f2(x)
+        });
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+        assert_eq!(evals, 12);
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_mut_vec_f64_trait() {
+        let mut evals = 0;
+        let hessian = x3().forward_hessian_nograd_mut(&mut |x: &Vec<f64>| {
+            evals += 1;
+            f3(x)
+        });
+        let res = res2();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+        assert_eq!(evals, 1 + 4 + 6 + 8);
+    }
+
+    #[test]
+    fn test_check_gradient_vec_f64_trait() {
+        assert!(x1().check_gradient(&f1, &vec![1.0, 2.0], COMP_ACC).is_ok());
+
+        let report = x1()
+            .check_gradient(&f1, &vec![1.0, 3.0], COMP_ACC)
+            .unwrap_err();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].index, vec![1]);
+        assert!((report.failures[0].numeric - 2.0).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_check_jacobian_vec_f64_trait() {
+        let mut jacobian = res1();
+        assert!(x2().check_jacobian(&f2, &jacobian, COMP_ACC).is_ok());
+
+        jacobian[2][3] = 1.0;
+        let report = x2().check_jacobian(&f2, &jacobian, COMP_ACC).unwrap_err();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].index, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_directional_diff_vec_f64_trait() {
+        let p = vec![1.0f64, -2.0];
+        let res = 1.0 - 4.0;
+        assert!((res - x1().forward_directional_diff(&f1, &p)).abs() < COMP_ACC);
+        assert!((res - x1().central_directional_diff(&f1, &p)).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_detect_sparsity_vec_f64_trait() {
+        let x = vec![2.0f64; 6];
+        let pert = x.detect_sparsity(&f2, 1e-3);
+        assert_eq!(pert.len(), 3);
+
+        let jacobian = x2().forward_jacobian_pert(&f2, &pert);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_jacobian_sparsity_vec_f64_trait() {
+        let x = vec![2.0f64; 6];
+        let pattern = x.jacobian_sparsity(&f2, 1e-3);
+        assert_eq!(pattern.nnz(), 16);
+        // tridiagonal
+        for (&r, &c) in pattern.row_indices().iter().zip(pattern.col_indices()) {
+            assert!(r.max(c) - r.min(c) <= 1);
+        }
+        assert_eq!(pattern.perturbation_vectors().len(), 3);
+    }
+
+    #[test]
+    fn test_forward_jacobian_pert_sparse_vec_f64_trait() {
+        let jacobian = x2().forward_jacobian_pert_sparse(&f2, &pert());
+        let res = res1();
+        assert_eq!(jacobian.nnz(), 16);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_banded_vec_f64_trait() {
+        let f = CountingFn::new(f2);
+        let jacobian = x2().forward_jacobian_banded(&|x| f.call(x), 1, 1);
+        assert_eq!(f.count(), 4);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_precomputed_vec_f64_trait() {
+        let x = vec![1.0f64, 2.0f64];
+        let grad = x.forward_diff_precomputed(&f1, f1(&x));
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_value_and_forward_diff_vec_f64_trait() {
+        let x = vec![1.0f64, 2.0f64];
+        let f = CountingFn::new(f1);
+        let (fx, grad) = x.value_and_forward_diff(&|x| f.call(x));
+        let res = [1.0f64, 4.0];
+
+        assert_eq!(f.count(), 3);
+        assert_eq!(fx, f1(&x));
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_with_samples_vec_f64_trait() {
+        let x = vec![1.0f64, 2.0f64];
+        let f = CountingFn::new(f1);
+        let (grad, fx, samples) = x.forward_diff_with_samples(&|x| f.call(x));
+
+        assert_eq!(f.count(), 3);
+        assert_eq!(fx, f1(&x));
+        assert_eq!(grad, x.forward_diff(&f1));
+        for (i, s) in samples.iter().enumerate() {
+            assert!(((s - fx) / (EPS_F64.sqrt() * x[i]) - grad[i]).abs() < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_traced_vec_f64_trait() {
+        let x = vec![1.0f64, 2.0f64];
+        let mut trace: Vec<(Vec<f64>, f64)> = vec![];
+        let grad = x.forward_diff_traced(&f1, &mut |p, fp| trace.push((p.to_vec(), fp)));
+
+        assert_eq!(grad, x.forward_diff(&f1));
+        assert_eq!(trace.len(), x.len() + 1);
+        assert_eq!(trace[0], (x.clone(), f1(&x)));
+        for (p, fp) in trace.iter() {
+            assert_eq!(*fp, f1(p));
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_subset_vec_f64_trait() {
+        let x = vec![1.0f64, 2.0f64];
+        let f = CountingFn::new(f1);
+        let grad = x.forward_diff_subset(&|x| f.call(x), &[1]);
+
+        assert_eq!(f.count(), 2);
+        assert_eq!(grad[0], 0.0);
+        assert_eq!(grad[1], x.forward_diff(&f1)[1]);
+    }
+
+    #[test]
+    fn test_forward_diff_masked_vec_f64_trait() {
+        let f = CountingFn::new(f3);
+        let active = [true, false, false, true];
+        let grad = x3().forward_diff_masked(&|x| f.call(x), &active).unwrap();
+
+        assert_eq!(f.count(), 3);
+        let full = x3().forward_diff(&f3);
+        assert_eq!(grad, vec![full[0], 0.0, 0.0, full[3]]);
+
+        assert_eq!(
+            x3().forward_diff_masked(&f3, &[true, false]),
+            Err(FiniteDiffError::DimensionMismatch {
+                expected: 4,
+                got: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_derivatives_vec_f64_trait() {
+        let d = x3().derivatives(&f3);
+        assert_eq!(d.value, f3(&x3()));
+        assert_eq!(d.gradient, x3().central_diff(&f3));
+        let res = res2();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - d.hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_diagonal_nograd_vec_f64_trait() {
+        let diag = x3().forward_hessian_diagonal_nograd(&f3);
+        let res = res2();
+        assert_eq!(diag.len(), 4);
+        for i in 0..4 {
+            assert!((res[i][i] - diag[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_grad_and_diag_vec_f64_trait() {
+        let (grad, diag) = x3().central_grad_and_diag(&f3);
+        assert_eq!(diag, x3().forward_hessian_diagonal_nograd(&f3));
+        let res = x3().central_diff(&f3);
+        for i in 0..4 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_vec_f32 {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    const COMP_ACC: f32 = 1e-3;
+
+    fn f1(x: &Vec<f32>) -> f32 {
+        x[0] + x[1].powi(2)
+    }
+
+    fn f2(x: &Vec<f32>) -> Vec<f32> {
+        vec![
+            2.0 * (x[1].powi(3) - x[0].powi(2)),
+            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
+            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
+            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
+            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
+            3.0 * (x[5].powi(3) - x[4].powi(2)),
+        ]
+    }
+
+    fn g(x: &Vec<f32>) -> Vec<f32> {
+        vec![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    }
+
+    fn x1() -> Vec<f32> {
+        vec![1.0f32, 1.0f32]
+    }
+
+    fn x2() -> Vec<f32> {
+        vec![1.0f32, 1.0, 1.0, 1.0, 1.0, 1.0]
+    }
+
+    fn x3() -> Vec<f32> {
+        vec![1.0f32, 1.0, 1.0, 1.0]
+    }
+
+    fn res1() -> Vec<Vec<f32>> {
+        vec![
+            vec![-4.0, -6.0, 0.0, 0.0, 0.0, 0.0],
+            vec![6.0, 5.0, -6.0, 0.0, 0.0, 0.0],
+            vec![0.0, 6.0, 5.0, -6.0, 0.0, 0.0],
+            vec![0.0, 0.0, 6.0, 5.0, -6.0, 0.0],
+            vec![0.0, 0.0, 0.0, 6.0, 5.0, -6.0],
+            vec![0.0, 0.0, 0.0, 0.0, 6.0, 9.0],
+        ]
+    }
+
+    fn res2() -> Vec<Vec<f32>> {
+        vec![
+            vec![0.0, 0.0, 0.0, 0.0],
+            vec![0.0, 2.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0, 2.0],
+            vec![0.0, 0.0, 2.0, 2.0],
+        ]
+    }
+
+    #[test]
+    fn test_forward_diff_vec_f32_trait() {
+        let grad = x1().forward_diff(&f1);
+        let res = [1.0f32, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_default_step_vec_f32_trait() {
+        let h = default_step::<f32>();
+        assert_eq!(h, EPS_F32.sqrt());
+        let p = x1();
+        let mut p0 = p.clone();
+        p0[0] += h;
+        assert_eq!(p.forward_diff(&f1)[0], (f1(&p0) - f1(&p)) / h);
+    }
+
+    #[test]
+    fn test_backward_diff_vec_f32_trait() {
+        let grad = x1().backward_diff(&f1);
+        let res = [1.0f32, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_vec_f32_trait() {
+        let grad = x1().central_diff(&f1);
+        let res = [1.0f32, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_f32_trait() {
+        let jacobian = x2().forward_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < 10.0 * COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_vec_f32_trait() {
+        let jacobian = x2().backward_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < 10.0 * COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_vec_f32_trait() {
+        let jacobian = x2().central_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_f32_trait() {
+        let hessian = x3().forward_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_vec_f32_trait() {
+        let hessian = x3().central_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ndarray")]
+#[cfg(test)]
+mod tests_ndarray {
+    use super::*;
+
+    use ndarray::{array, Array1, Array2};
+
+    const COMP_ACC: f64 = 1e-6;
+
+    fn f1(x: &Array1<f64>) -> f64 {
+        x[0] + x[1].powi(2)
+    }
+
+    fn f2(x: &Array1<f64>) -> Array1<f64> {
+        array![
+            2.0 * (x[1].powi(3) - x[0].powi(2)),
+            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
+            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
+            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
+            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
+            3.0 * (x[5].powi(3) - x[4].powi(2)),
+        ]
+    }
+
+    fn f3(x: &Array1<f64>) -> f64 {
+        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
+    }
+
+    fn f4(x: &Array1<f64>) -> f64 {
+        (1e-6 * x[0]).powi(2) + (1e3 * x[1]).powi(2)
+    }
+
+    fn g(x: &Array1<f64>) -> Array1<f64> {
+        array![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    }
+
+    fn x1() -> Array1<f64> {
+        array![1.0f64, 1.0f64]
+    }
+
+    fn x2() -> Array1<f64> {
+        array![1.0f64, 1.0, 1.0, 1.0, 1.0, 1.0]
+    }
+
+    fn x3() -> Array1<f64> {
+        array![1.0f64, 1.0, 1.0, 1.0]
+    }
+
+    fn res1() -> Vec<Vec<f64>> {
+        vec![
+            vec![-4.0, -6.0, 0.0, 0.0, 0.0, 0.0],
+            vec![6.0, 5.0, -6.0, 0.0, 0.0, 0.0],
+            vec![0.0, 6.0, 5.0, -6.0, 0.0, 0.0],
+            vec![0.0, 0.0, 6.0, 5.0, -6.0, 0.0],
+            vec![0.0, 0.0, 0.0, 6.0, 5.0, -6.0],
+            vec![0.0, 0.0, 0.0, 0.0, 6.0, 9.0],
+        ]
+    }
+
+    fn res2() -> Vec<Vec<f64>> {
+        vec![
+            vec![0.0, 0.0, 0.0, 0.0],
+            vec![0.0, 2.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0, 2.0],
+            vec![0.0, 0.0, 2.0, 2.0],
+        ]
+    }
+
+    fn res3() -> Vec<f64> {
+        vec![8.0, 22.0, 27.0, 32.0, 37.0, 24.0]
+    }
+
+    fn pert() -> PerturbationVectors {
+        vec![
+            PerturbationVector::new()
+                .add(0, vec![0, 1])
+                .add(3, vec![2, 3, 4]),
+            PerturbationVector::new()
+                .add(1, vec![0, 1, 2])
+                .add(4, vec![3, 4, 5]),
+            PerturbationVector::new()
+                .add(2, vec![1, 2, 3])
+                .add(5, vec![4, 5]),
+        ]
+    }
+
+    fn p1() -> Array1<f64> {
+        array![1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0]
+    }
+
+    fn p2() -> Array1<f64> {
+        array![2.0, 3.0, 4.0, 5.0]
+    }
+
+    #[test]
+    fn test_forward_diff_ndarray_f64_trait() {
+        let grad = x1().forward_diff(&f1);
+        let res = array![1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let x = array![1.0f64, 2.0f64];
+        let grad = x.forward_diff(&f1);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_backward_diff_ndarray_f64_trait() {
+        let grad = x1().backward_diff(&f1);
+        let res = array![1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let x = array![1.0f64, 2.0f64];
+        let grad = x.backward_diff(&f1);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_ndarray_f64_trait() {
+        let grad = x1().central_diff(&f1);
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let x = array![1.0f64, 2.0f64];
+        let grad = x.central_diff(&f1);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_ndarray_f64_trait() {
+        let jacobian = x2().forward_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_forward_jacobian_ndarray_f64_trait() {
+        assert_eq!(
+            x2().try_forward_jacobian(&f2),
+            Ok(x2().forward_jacobian(&f2))
+        );
+
+        // a shorter output would otherwise be indexed out of bounds
+        let fs = |x: &Array1<f64>| {
+            let out = f2(x);
+            if x[2] != x2()[2] {
+                out.slice(ndarray::s![..5]).to_owned()
+            } else {
+                out
+            }
+        };
+        assert_eq!(
+            x2().try_forward_jacobian(&fs),
+            Err(FiniteDiffError::OutputLengthChanged {
+                col: 2,
+                expected: 6,
+                got: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_forward_jacobian_with_progress_ndarray_f64_trait() {
+        let mut cols = vec![];
+        let jacobian = x2().forward_jacobian_with_progress(&f2, &mut |i| {
+            cols.push(i);
+            true
+        });
+        assert_eq!(jacobian, Some(x2().forward_jacobian(&f2)));
+        assert_eq!(cols, vec![0, 1, 2, 3, 4, 5]);
+
+        let evals = core::cell::Cell::new(0);
+        let fs = |x: &Array1<f64>| {
+            evals.set(evals.get() + 1);
+            f2(x)
+        };
+        assert_eq!(
+            x2().forward_jacobian_with_progress(&fs, &mut |i| i < 2),
+            None
+        );
+        assert_eq!(evals.get(), 4);
+    }
+
+    #[test]
+    fn test_forward_jacobian_cancellable_ndarray_f64_trait() {
+        let cancel = AtomicBool::new(false);
+        let jacobian = x2().forward_jacobian_cancellable(&f2, &cancel);
+        assert_eq!(jacobian, Some(x2().forward_jacobian(&f2)));
+
+        // cancel after two parameters
+        let evals = core::cell::Cell::new(0);
+        let fs = |x: &Array1<f64>| {
+            evals.set(evals.get() + 1);
+            if evals.get() == 3 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            f2(x)
+        };
+        assert_eq!(x2().forward_jacobian_cancellable(&fs, &cancel), None);
+        assert_eq!(evals.get(), 3);
+        assert_eq!(x2().forward_jacobian_cancellable(&fs, &cancel), None);
+        assert_eq!(evals.get(), 3);
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_cancellable_ndarray_f64_trait() {
+        let cancel = AtomicBool::new(false);
+        let hessian = x3().forward_hessian_nograd_cancellable(&f3, &cancel);
+        assert_eq!(hessian, Some(x3().forward_hessian_nograd(&f3)));
+
+        // cancel within the second row
+        let evals = core::cell::Cell::new(0);
+        let fc = |x: &Array1<f64>| {
+            evals.set(evals.get() + 1);
+            if evals.get() == 8 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            f3(x)
+        };
+        assert_eq!(x3().forward_hessian_nograd_cancellable(&fc, &cancel), None);
+        assert_eq!(evals.get(), 10);
+    }
+
+    #[test]
+    fn test_forward_jacobian_diagonal_ndarray_f64_trait() {
+        let diag = x2().forward_jacobian_diagonal(&|x: &Array1<f64>| x.map(|v| v * v));
+        let jacobian = x2().forward_jacobian(&|x: &Array1<f64>| x.map(|v| v * v));
+        assert_eq!(diag.len(), 6);
+        for i in 0..6 {
+            assert!((2.0 * x2()[i] - diag[i]).abs() < COMP_ACC);
+            assert_eq!(diag[i], jacobian[(i, i)]);
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_from_values_ndarray_f64_trait() {
+        let p = x2();
+        let values: Vec<_> = p.forward_jacobian_points().iter().map(f2).collect();
+        assert_eq!(
+            p.forward_jacobian_from_values(&values),
+            p.forward_jacobian(&f2)
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_forward_jacobian_par_ndarray_f64_trait() {
+        let jacobian = x2().forward_jacobian_par(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_ndarray_f64_trait() {
+        let jacobian = x2().backward_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_ndarray_f64_trait() {
+        let jacobian = x2().central_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_into_ndarray_f64_trait() {
+        let mut jacobian = ndarray::Array2::zeros((6, 6));
+        x2().forward_jacobian_into(&f2, &mut jacobian);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_scaled_ndarray_f64_trait() {
+        let x = array![1e6f64, 1e-3];
+        let steps = array![1e-2f64, 1e-11];
+        let grad = x.forward_diff_scaled(&f4, &steps);
+        let res = [2e-6f64, 2e3];
+
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_with_scaling_ndarray_f64_trait() {
+        let x = ndarray::Array1::from(vec![1e6f64, 1e-3]);
+        let grad = x.forward_diff_with_scaling(&f4, &ndarray::Array1::from(vec![1e6, 1e-3]));
+        let res = [2e-6f64, 2e3];
+
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_bounded_ndarray_f64_trait() {
+        let f = |x: &Array1<f64>| {
+            assert!(x[1] <= 1.0, "infeasible evaluation");
+            f1(x)
+        };
+        let grad = x1().forward_diff_bounded(&f, &array![0.0, 0.0], &array![2.0, 1.0]);
+        let res = [1.0f64, 2.0];
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_scaled_ndarray_f64_trait() {
+        let x = array![1e6f64, 1e-3];
+        let steps = array![1e-2f64, 1e-11];
+        let grad = x.central_diff_scaled(&f4, &steps);
+        let res = [2e-6f64, 2e3];
+
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_forward_diff_relative_ndarray_f64_trait() {
+        let p = array![1e8f64, 1.0];
+        let f = |x: &Array1<f64>| x[0].ln() + x[1].powi(2);
+        let grad = p.forward_diff_relative(&f);
+        assert_eq!(grad, p.forward_diff(&f));
+        let res = [1e-8f64, 2.0];
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_scaled_ndarray_f64_trait() {
+        let steps = array![1e-8f64, 2e-8, 1e-8, 2e-8, 1e-8, 2e-8];
+        let jacobian = x2().forward_jacobian_scaled(&f2, &steps);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_scaled_ndarray_f64_trait() {
+        let steps = array![1e-8f64, 2e-8, 1e-8, 2e-8, 1e-8, 2e-8];
+        let jacobian = x2().central_jacobian_scaled(&f2, &steps);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_ndarray_f64_trait() {
+        let jacobian = x2().forward_jacobian_vec_prod(&f2, &p1());
+        let res = res3();
+        // println!("{:?}", jacobian);
+        // the accuracy for this is pretty bad!!
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_normalized_ndarray_f64_trait() {
+        let jacobian = x2().forward_jacobian_vec_prod_normalized(&f2, &p1());
+        let res = res3();
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_vec_prod_ndarray_f64_trait() {
+        let jacobian = x2().central_jacobian_vec_prod(&f2, &p1());
+        let res = res3();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_vjp_ndarray_f64_trait() {
+        let vjp = x2().forward_vjp(&f2, &p1());
+        // J^T*v computed by hand
+        let res = [-16.0f64, -2.0, 3.0, 8.0, 13.0, 84.0];
+        // println!("{:?}", vjp);
+        for i in 0..6 {
+            assert!((res[i] - vjp[i]).abs() < 11.0 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_pert_ndarray_f64_trait() {
+        let jacobian = x2().forward_jacobian_pert(&f2, &pert());
+        let res = res1();
+        // println!("jacobian:\n{:?}", jacobian);
+        // println!("res:\n{:?}", res);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_pert_ndarray_f64_trait() {
+        let jacobian = x2().central_jacobian_pert(&f2, &pert());
+        let res = res1();
+        // println!("jacobian:\n{:?}", jacobian);
+        // println!("res:\n{:?}", res);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_ndarray_f64_trait() {
+        let hessian = x3().forward_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_forward_hessian_ndarray_f64_trait() {
+        let hessian = x3()
+            .try_forward_hessian(&|x: &Array1<f64>| Ok::<_, ()>(g(x)))
+            .unwrap();
+        let res = res2();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+
+        // fails once the third parameter is perturbed
+        let evals = core::cell::Cell::new(0);
+        let x0 = x3();
+        let failing = |x: &Array1<f64>| {
+            evals.set(evals.get() + 1);
+            if x[2] != x0[2] {
+                Err("no convergence")
+            } else {
+                Ok(g(x))
+            }
+        };
+        assert_eq!(x3().try_forward_hessian(&failing), Err("no convergence"));
+        assert_eq!(evals.get(), 4);
+    }
+
+    #[test]
+    fn test_central_hessian_ndarray_f64_trait() {
+        let hessian = x3().central_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_prod_ndarray_f64_trait() {
+        let hessian = x3().forward_hessian_vec_prod(&g, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_ndarray_f64_trait() {
+        let hessian = x3().central_hessian_vec_prod(&g, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_prod_nograd_ndarray_f64_trait() {
+        let hessian = x3().forward_hessian_vec_prod_nograd(&f3, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < 1e-3)
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_nograd_ndarray_f64_trait() {
+        let hessian = x3().central_hessian_vec_prod_nograd(&f3, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_ndarray_f64_trait() {
+        let hessian = x3().forward_hessian_nograd(&f3);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_grad_and_hessian_nograd_ndarray_f64_trait() {
+        let (grad, hessian) = x3().forward_grad_and_hessian_nograd(&f3);
+        let res_grad = [1.0f64, 2.0, 1.0, 2.0];
+        let res = res2();
+        for i in 0..4 {
+            assert!((res_grad[i] - grad[i]).abs() < COMP_ACC);
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_ndarray_f64_trait() {
+        let hessian = x3().central_hessian_nograd(&f3);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_sparse_ndarray_f64_trait() {
+        let indices = vec![[1, 1], [2, 3], [3, 3]];
+        let hessian = x3().forward_hessian_nograd_sparse(&f3, indices);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_sparse_ndarray_f64_trait() {
+        let indices = vec![[1, 1], [2, 3], [3, 3]];
+        let hessian = x3().central_hessian_nograd_sparse(&f3, indices);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_diff_5_ndarray_f64_trait() {
+        let p = array![2.0f64, -1.5f64];
+        let f_exp = |x: &Array1<f64>| x[0].exp() + x[1].exp();
+        let grad3 = p.central_diff(&f_exp);
+        let grad5 = p.central_diff_5(&f_exp);
+
+        for i in 0..2 {
+            assert!((p[i].exp() - grad5[i]).abs() < (p[i].exp() - grad3[i]).abs())
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_2_ndarray_f64_trait() {
+        let p = array![2.0f64, -1.5f64];
+        let f_exp = |x: &Array1<f64>| x[0].exp() + x[1].exp();
+        let grad1 = p.forward_diff(&f_exp);
+        let grad2 = p.forward_diff_2(&f_exp);
+
+        for i in 0..2 {
+            assert!((p[i].exp() - grad2[i]).abs() < (p[i].exp() - grad1[i]).abs())
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_5_ndarray_f64_trait() {
+        let jacobian = x2().central_jacobian_5(&f2);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_2_ndarray_f64_trait() {
+        let jacobian = x2().forward_jacobian_2(&f2);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_richardson_diff_ndarray_f64_trait() {
+        let p = array![2.0f64, 0.5];
+        let f = |x: &Array1<f64>| x[0].exp() + x[1].sin();
+        let grad = p.richardson_diff(&f, 3);
+        let res = [p[0].exp(), p[1].cos()];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < 1e-11)
+        }
+    }
+
+    #[test]
+    fn test_adaptive_diff_ndarray_f64_trait() {
+        let p = array![2.0f64, 0.5];
+        let f = |x: &Array1<f64>| x[0].exp() + x[1].sin();
+        let grad = p.adaptive_diff(&f);
+        let res = [p[0].exp(), p[1].cos()];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < 1e-9)
+        }
+    }
+
+    #[test]
+    fn test_auto_diff_ndarray_f64_trait() {
+        let p = ndarray::Array1::from(vec![1.0f64, 1e-4]);
+        let f = |x: &ndarray::Array1<f64>| x[0].exp() + 1e4 * x[1].powi(2);
+        assert_eq!(p.auto_diff(&f, 1e-6), p.central_diff(&f));
+        assert_eq!(p.auto_diff(&f, 1e-2), p.forward_diff(&f));
+    }
+
+    #[test]
+    fn test_forward_diff_with_error_ndarray_f64_trait() {
+        let p = array![1.0f64, 1.0];
+        let (grad, err) = p.forward_diff_with_error(&|x: &Array1<f64>| x[0] + x[1].powi(2));
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC);
+            assert!(err[i] < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_try_forward_jacobian_vec_prod_ndarray_f64_trait() {
+        let jacobian = x2().try_forward_jacobian_vec_prod(&f2, &p1()).unwrap();
+        let res = res3();
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+        }
+
+        assert_eq!(
+            x2().try_forward_jacobian_vec_prod(&f2, &p2()),
+            Err(FiniteDiffError::DimensionMismatch {
+                expected: 6,
+                got: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_try_central_hessian_vec_prod_ndarray_f64_trait() {
+        assert_eq!(
+            x3().try_central_hessian_vec_prod(&g, &p1()),
+            Err(FiniteDiffError::DimensionMismatch {
+                expected: 4,
+                got: 6
+            })
+        );
+    }
+
+    #[test]
+    fn test_try_forward_diff_scaled_ndarray_f64_trait() {
+        let steps = array![1e-2f64, 1e-11];
+        let x = array![1e6f64, 1e-3];
+        assert!(x.try_forward_diff_scaled(&f4, &steps).is_ok());
+
+        let x = array![f64::NAN, 1e-3];
+        assert_eq!(
+            x.try_forward_diff_scaled(&f4, &steps),
+            Err(FiniteDiffError::NonFinite { index: 0 })
+        );
+
+        let x = array![1e20f64, 1e-3];
+        assert_eq!(
+            x.try_forward_diff_scaled(&f4, &steps),
+            Err(FiniteDiffError::StepUnderflow { index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_try_forward_diff_ndarray_f64_trait() {
+        let grad = x1().try_forward_diff(&f1).unwrap();
+        let res = [1.0f64, 2.0];
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let f = |x: &Array1<f64>| f64::MAX * (x[1] - 0.5).signum();
+        let x = array![1.0f64, 0.5 - 1e-10];
+        assert_eq!(
+            x.try_forward_diff(&f),
+            Err(FiniteDiffError::NonFinite { index: 1 })
+        );
+
+        // the steps are relative to the parameters and therefore not lost to rounding
+        let grad = array![1.0f64, 1e20].try_forward_diff(&f1).unwrap();
+        assert!(((grad[1] - 2e20) / 2e20).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_try_non_finite_index_ndarray_f64_trait() {
+        // `f` is finite at `x` but not if `x_1` is perturbed
+        let f = |x: &Array1<f64>| x[0] + if x[1] > 1.0 { f64::INFINITY } else { x[1] };
+        let fs = |x: &Array1<f64>| -> Array1<f64> {
+            array![if x[1] > 1.0 { f64::INFINITY } else { x[0] }, x[1]]
+        };
+        let x = array![0.0f64, 1.0];
+        let steps = array![1e-6f64, 1e-6];
+
+        assert_eq!(
+            x.try_forward_diff_scaled(&f, &steps),
+            Err(FiniteDiffError::NonFinite { index: 1 })
+        );
+        assert_eq!(
+            x.try_forward_jacobian_scaled(&fs, &steps),
+            Err(FiniteDiffError::NonFiniteJacobian { index: (1, 0) })
+        );
+    }
+
+    #[test]
+    fn test_forward_diff_mut_ndarray_f64_trait() {
+        let mut evals = 0;
+        let grad = x1().forward_diff_mut(&mut |x: &Array1<f64>| {
+            evals += 1;
+            f1(x)
+        });
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+        assert_eq!(evals, 3);
+    }
+
+    #[test]
+    fn test_forward_diff_with_ndarray_f64_trait() {
+        let f = |x: &Array1<f64>, p: &f64| p * f1(x);
+        let grad = x1().forward_diff_with(&f, &3.0);
+        let res = [3.0f64, 6.0];
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let fs = |x: &Array1<f64>, p: &f64| array![p * x[0], x[1].powi(2)];
+        let jacobian = x1().forward_jacobian_with(&fs, &3.0);
+        let res = [[3.0f64, 0.0], [0.0, 2.0]];
+        for i in 0..2 {
+            for j in 0..2 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_diff_paired_ndarray_f64_trait() {
+        // noise which only depends on the index of the pair of evaluations
+        let mut evals = 0;
+        let grad = x1().central_diff_paired(&mut |x: &Array1<f64>| {
+            let noise = 100.0 * ((evals / 2) as f64 * 1.3).sin();
+            evals += 1;
+            f1(x) + noise
+        });
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+        assert_eq!(evals, 4);
+    }
+
+    #[test]
+    fn test_central_jacobian_mut_ndarray_f64_trait() {
+        let mut evals = 0;
+        let jacobian = x2().central_jacobian_mut(&mut |x: &Array1<f64>| {
+            evals += 1;
+            f2(x)
+        });
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+        // one additional evaluation is needed to determine the dimension of the output
+        assert_eq!(evals, 13);
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_mut_ndarray_f64_trait() {
+        let mut evals = 0;
+        let hessian = x3().forward_hessian_nograd_mut(&mut |x: &Array1<f64>| {
+            evals += 1;
+            f3(x)
+        });
+        let res = res2();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+        assert_eq!(evals, 1 + 4 + 6 + 8);
+    }
+
+    #[test]
+    fn test_check_gradient_ndarray_f64_trait() {
+        assert!(x1()
+            .check_gradient(&f1, &array![1.0, 2.0], COMP_ACC)
+            .is_ok());
+
+        let report = x1()
+            .check_gradient(&f1, &array![1.0, 3.0], COMP_ACC)
+            .unwrap_err();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].index, vec![1]);
+        assert!((report.failures[0].numeric - 2.0).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_check_jacobian_ndarray_f64_trait() {
+        let mut jacobian = Array2::from_shape_fn((6, 6), |(i, j)| res1()[i][j]);
+        assert!(x2().check_jacobian(&f2, &jacobian, COMP_ACC).is_ok());
+
+        jacobian[(2, 3)] = 1.0;
+        let report = x2().check_jacobian(&f2, &jacobian, COMP_ACC).unwrap_err();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].index, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_directional_diff_ndarray_f64_trait() {
+        let p = array![1.0f64, -2.0];
+        let res = 1.0 - 4.0;
+        assert!((res - x1().forward_directional_diff(&f1, &p)).abs() < COMP_ACC);
+        assert!((res - x1().central_directional_diff(&f1, &p)).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_detect_sparsity_ndarray_f64_trait() {
+        let x = Array1::from_elem(6, 2.0f64);
+        let pert = x.detect_sparsity(&f2, 1e-3);
+        assert_eq!(pert.len(), 3);
+
+        let jacobian = x2().forward_jacobian_pert(&f2, &pert);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_jacobian_sparsity_ndarray_f64_trait() {
+        let x = Array1::from_elem(6, 2.0f64);
+        let pattern = x.jacobian_sparsity(&f2, 1e-3);
+        assert_eq!(pattern.nnz(), 16);
+        // tridiagonal
+        for (&r, &c) in pattern.row_indices().iter().zip(pattern.col_indices()) {
+            assert!(r.max(c) - r.min(c) <= 1);
+        }
+        assert_eq!(pattern.perturbation_vectors().len(), 3);
+    }
+
+    #[test]
+    fn test_forward_jacobian_pert_sparse_ndarray_f64_trait() {
+        let jacobian = x2().forward_jacobian_pert_sparse(&f2, &pert());
+        let res = res1();
+        assert_eq!(jacobian.nnz(), 16);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_banded_ndarray_f64_trait() {
+        let jacobian = x2().forward_jacobian_banded(&f2, 1, 1);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_precomputed_ndarray_f64_trait() {
+        let x = array![1.0f64, 2.0f64];
+        let grad = x.forward_diff_precomputed(&f1, f1(&x));
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_value_and_forward_diff_ndarray_f64_trait() {
+        let x = array![1.0f64, 2.0f64];
+        let f = CountingFn::new(f1);
+        let (fx, grad) = x.value_and_forward_diff(&|x| f.call(x));
+        let res = [1.0f64, 4.0];
+
+        assert_eq!(f.count(), 3);
+        assert_eq!(fx, f1(&x));
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_with_samples_ndarray_f64_trait() {
+        let x = array![1.0f64, 2.0f64];
+        let (grad, fx, samples) = x.forward_diff_with_samples(&f1);
+        assert_eq!(fx, f1(&x));
+        assert_eq!(grad, x.forward_diff(&f1));
+        assert_eq!(samples.len(), 2);
+    }
+
+    #[test]
+    fn test_forward_diff_traced_ndarray_f64_trait() {
+        let x = array![1.0f64, 2.0f64];
+        let mut trace = vec![];
+        let grad = x.forward_diff_traced(&f1, &mut |p, fp| trace.push((p.to_vec(), fp)));
+        assert_eq!(grad, x.forward_diff(&f1));
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace[0], (vec![1.0, 2.0], f1(&x)));
+    }
+
+    #[test]
+    fn test_forward_diff_subset_ndarray_f64_trait() {
+        let x = ndarray::Array1::from(vec![1.0f64, 2.0f64]);
+        let f = CountingFn::new(f1);
+        let grad = x.forward_diff_subset(&|x| f.call(x), &[1]);
+
+        assert_eq!(f.count(), 2);
+        assert_eq!(grad[0], 0.0);
+        assert_eq!(grad[1], x.forward_diff(&f1)[1]);
+    }
+
+    #[test]
+    fn test_forward_diff_masked_ndarray_f64_trait() {
+        let x = ndarray::Array1::from(vec![1.0f64, 2.0f64]);
+        let grad = x.forward_diff_masked(&f1, &[false, true]).unwrap();
+        assert_eq!(grad, x.forward_diff_subset(&f1, &[1]));
+        assert!(x.forward_diff_masked(&f1, &[true]).is_err());
+    }
+
+    #[test]
+    fn test_forward_hessian_diagonal_nograd_ndarray_f64_trait() {
+        let diag = x3().forward_hessian_diagonal_nograd(&f3);
+        let res = res2();
+        assert_eq!(diag.len(), 4);
+        for i in 0..4 {
+            assert!((res[i][i] - diag[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_grad_and_diag_ndarray_f64_trait() {
+        let (grad, diag) = x3().central_grad_and_diag(&f3);
+        assert_eq!(diag, x3().forward_hessian_diagonal_nograd(&f3));
+        let res = x3().central_diff(&f3);
+        for i in 0..4 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+}
+
+#[cfg(feature = "ndarray")]
+#[cfg(test)]
+mod tests_ndarray_f32 {
+    use super::*;
+    use ndarray::{array, Array1};
+
+    const COMP_ACC: f32 = 1e-3;
+
+    fn f1(x: &Array1<f32>) -> f32 {
+        x[0] + x[1].powi(2)
+    }
+
+    fn f2(x: &Array1<f32>) -> Array1<f32> {
+        array![
+            2.0 * (x[1].powi(3) - x[0].powi(2)),
+            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
+            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
+            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
+            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
+            3.0 * (x[5].powi(3) - x[4].powi(2)),
+        ]
+    }
+
+    fn g(x: &Array1<f32>) -> Array1<f32> {
+        array![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    }
+
+    fn x1() -> Array1<f32> {
+        array![1.0f32, 1.0f32]
+    }
+
+    fn x2() -> Array1<f32> {
+        array![1.0f32, 1.0, 1.0, 1.0, 1.0, 1.0]
+    }
+
+    fn x3() -> Array1<f32> {
+        array![1.0f32, 1.0, 1.0, 1.0]
+    }
+
+    fn res1() -> Vec<Vec<f32>> {
+        vec![
+            vec![-4.0, -6.0, 0.0, 0.0, 0.0, 0.0],
+            vec![6.0, 5.0, -6.0, 0.0, 0.0, 0.0],
+            vec![0.0, 6.0, 5.0, -6.0, 0.0, 0.0],
+            vec![0.0, 0.0, 6.0, 5.0, -6.0, 0.0],
+            vec![0.0, 0.0, 0.0, 6.0, 5.0, -6.0],
+            vec![0.0, 0.0, 0.0, 0.0, 6.0, 9.0],
+        ]
+    }
+
+    fn res2() -> Vec<Vec<f32>> {
+        vec![
+            vec![0.0, 0.0, 0.0, 0.0],
+            vec![0.0, 2.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0, 2.0],
+            vec![0.0, 0.0, 2.0, 2.0],
+        ]
+    }
+
+    #[test]
+    fn test_forward_diff_ndarray_f32_trait() {
+        let grad = x1().forward_diff(&f1);
+        let res = [1.0f32, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_backward_diff_ndarray_f32_trait() {
+        let grad = x1().backward_diff(&f1);
+        let res = [1.0f32, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_ndarray_f32_trait() {
+        let grad = x1().central_diff(&f1);
+        let res = [1.0f32, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_ndarray_f32_trait() {
+        let jacobian = x2().forward_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < 10.0 * COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_ndarray_f32_trait() {
+        let jacobian = x2().backward_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < 10.0 * COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_ndarray_f32_trait() {
+        let jacobian = x2().central_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_ndarray_f32_trait() {
+        let hessian = x3().forward_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_ndarray_f32_trait() {
+        let hessian = x3().central_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+#[cfg(test)]
+mod tests_nalgebra {
+    use super::*;
+
+    use nalgebra::{dvector, DMatrix, DVector};
+
+    const COMP_ACC: f64 = 1e-6;
+
+    fn f1(x: &DVector<f64>) -> f64 {
+        x[0] + x[1].powi(2)
+    }
+
+    fn f2(x: &DVector<f64>) -> DVector<f64> {
+        dvector![
+            2.0 * (x[1].powi(3) - x[0].powi(2)),
+            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
+            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
+            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
+            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
+            3.0 * (x[5].powi(3) - x[4].powi(2)),
+        ]
+    }
+
+    fn f3(x: &DVector<f64>) -> f64 {
+        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
+    }
+
+    fn f4(x: &DVector<f64>) -> f64 {
+        (1e-6 * x[0]).powi(2) + (1e3 * x[1]).powi(2)
+    }
+
+    fn g(x: &DVector<f64>) -> DVector<f64> {
+        dvector![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    }
+
+    fn x1() -> DVector<f64> {
+        dvector![1.0f64, 1.0f64]
+    }
+
+    fn x2() -> DVector<f64> {
+        dvector![1.0f64, 1.0, 1.0, 1.0, 1.0, 1.0]
+    }
+
+    fn x3() -> DVector<f64> {
+        dvector![1.0f64, 1.0, 1.0, 1.0]
+    }
+
+    fn res1() -> Vec<Vec<f64>> {
+        vec![
+            vec![-4.0, -6.0, 0.0, 0.0, 0.0, 0.0],
+            vec![6.0, 5.0, -6.0, 0.0, 0.0, 0.0],
+            vec![0.0, 6.0, 5.0, -6.0, 0.0, 0.0],
+            vec![0.0, 0.0, 6.0, 5.0, -6.0, 0.0],
+            vec![0.0, 0.0, 0.0, 6.0, 5.0, -6.0],
+            vec![0.0, 0.0, 0.0, 0.0, 6.0, 9.0],
+        ]
+    }
+
+    fn res2() -> Vec<Vec<f64>> {
+        vec![
+            vec![0.0, 0.0, 0.0, 0.0],
+            vec![0.0, 2.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0, 2.0],
+            vec![0.0, 0.0, 2.0, 2.0],
+        ]
+    }
+
+    fn res3() -> Vec<f64> {
+        vec![8.0, 22.0, 27.0, 32.0, 37.0, 24.0]
+    }
+
+    fn pert() -> PerturbationVectors {
+        vec![
+            PerturbationVector::new()
+                .add(0, vec![0, 1])
+                .add(3, vec![2, 3, 4]),
+            PerturbationVector::new()
+                .add(1, vec![0, 1, 2])
+                .add(4, vec![3, 4, 5]),
+            PerturbationVector::new()
+                .add(2, vec![1, 2, 3])
+                .add(5, vec![4, 5]),
+        ]
+    }
+
+    fn p1() -> DVector<f64> {
+        dvector![1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0]
+    }
+
+    fn p2() -> DVector<f64> {
+        dvector![2.0, 3.0, 4.0, 5.0]
+    }
+
+    #[test]
+    fn test_forward_diff_nalgebra_f64_trait() {
+        let grad = x1().forward_diff(&f1);
+        let res = dvector![1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let x = dvector![1.0f64, 2.0f64];
+        let grad = x.forward_diff(&f1);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_backward_diff_nalgebra_f64_trait() {
+        let grad = x1().backward_diff(&f1);
+        let res = dvector![1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let x = dvector![1.0f64, 2.0f64];
+        let grad = x.backward_diff(&f1);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_nalgebra_f64_trait() {
+        let grad = x1().central_diff(&f1);
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let x = dvector![1.0f64, 2.0f64];
+        let grad = x.central_diff(&f1);
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_nalgebra_f64_trait() {
+        let jacobian = x2().forward_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_with_progress_nalgebra_f64_trait() {
+        let mut cols = vec![];
+        let jacobian = x2().forward_jacobian_with_progress(&f2, &mut |i| {
+            cols.push(i);
+            true
+        });
+        assert_eq!(jacobian, Some(x2().forward_jacobian(&f2)));
+        assert_eq!(cols, vec![0, 1, 2, 3, 4, 5]);
+
+        let evals = core::cell::Cell::new(0);
+        let fs = |x: &DVector<f64>| {
+            evals.set(evals.get() + 1);
+            f2(x)
+        };
+        assert_eq!(
+            x2().forward_jacobian_with_progress(&fs, &mut |i| i < 2),
+            None
+        );
+        assert_eq!(evals.get(), 4);
+    }
+
+    #[test]
+    fn test_forward_jacobian_cancellable_nalgebra_f64_trait() {
+        let cancel = AtomicBool::new(false);
+        let jacobian = x2().forward_jacobian_cancellable(&f2, &cancel);
+        assert_eq!(jacobian, Some(x2().forward_jacobian(&f2)));
+
+        // cancel after two parameters
+        let evals = core::cell::Cell::new(0);
+        let fs = |x: &DVector<f64>| {
+            evals.set(evals.get() + 1);
+            if evals.get() == 3 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            f2(x)
+        };
+        assert_eq!(x2().forward_jacobian_cancellable(&fs, &cancel), None);
+        assert_eq!(evals.get(), 3);
+        assert_eq!(x2().forward_jacobian_cancellable(&fs, &cancel), None);
+        assert_eq!(evals.get(), 3);
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_cancellable_nalgebra_f64_trait() {
+        let cancel = AtomicBool::new(false);
+        let hessian = x3().forward_hessian_nograd_cancellable(&f3, &cancel);
+        assert_eq!(hessian, Some(x3().forward_hessian_nograd(&f3)));
+
+        // cancel within the second row
+        let evals = core::cell::Cell::new(0);
+        let fc = |x: &DVector<f64>| {
+            evals.set(evals.get() + 1);
+            if evals.get() == 8 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            f3(x)
+        };
+        assert_eq!(x3().forward_hessian_nograd_cancellable(&fc, &cancel), None);
+        assert_eq!(evals.get(), 10);
+    }
+
+    #[test]
+    fn test_forward_jacobian_diagonal_nalgebra_f64_trait() {
+        let diag = x2().forward_jacobian_diagonal(&|x: &DVector<f64>| x.map(|v| v * v));
+        let jacobian = x2().forward_jacobian(&|x: &DVector<f64>| x.map(|v| v * v));
+        assert_eq!(diag.len(), 6);
+        for i in 0..6 {
+            assert!((2.0 * x2()[i] - diag[i]).abs() < COMP_ACC);
+            assert_eq!(diag[i], jacobian[(i, i)]);
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_from_values_nalgebra_f64_trait() {
+        let p = x2();
+        let values: Vec<_> = p.forward_jacobian_points().iter().map(f2).collect();
+        assert_eq!(
+            p.forward_jacobian_from_values(&values),
+            p.forward_jacobian(&f2)
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_forward_jacobian_par_nalgebra_f64_trait() {
+        let jacobian = x2().forward_jacobian_par(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_backward_jacobian_nalgebra_f64_trait() {
+        let jacobian = x2().backward_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_nalgebra_f64_trait() {
+        let jacobian = x2().central_jacobian(&f2);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_into_nalgebra_f64_trait() {
+        let mut jacobian = nalgebra::DMatrix::zeros(6, 6);
+        x2().forward_jacobian_into(&f2, &mut jacobian);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_scaled_nalgebra_f64_trait() {
+        let x = dvector![1e6f64, 1e-3];
+        let steps = dvector![1e-2f64, 1e-11];
+        let grad = x.forward_diff_scaled(&f4, &steps);
+        let res = [2e-6f64, 2e3];
+
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_with_scaling_nalgebra_f64_trait() {
+        let x = nalgebra::DVector::from_vec(vec![1e6f64, 1e-3]);
+        let grad = x.forward_diff_with_scaling(&f4, &nalgebra::DVector::from_vec(vec![1e6, 1e-3]));
+        let res = [2e-6f64, 2e3];
+
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_bounded_nalgebra_f64_trait() {
+        let f = |x: &DVector<f64>| {
+            assert!(x[1] <= 1.0, "infeasible evaluation");
+            f1(x)
+        };
+        let grad = x1().forward_diff_bounded(&f, &dvector![0.0, 0.0], &dvector![2.0, 1.0]);
+        let res = [1.0f64, 2.0];
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_scaled_nalgebra_f64_trait() {
+        let x = dvector![1e6f64, 1e-3];
+        let steps = dvector![1e-2f64, 1e-11];
+        let grad = x.central_diff_scaled(&f4, &steps);
+        let res = [2e-6f64, 2e3];
+
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_forward_diff_relative_nalgebra_f64_trait() {
+        let p = dvector![1e8f64, 1.0];
+        let f = |x: &DVector<f64>| x[0].ln() + x[1].powi(2);
+        let grad = p.forward_diff_relative(&f);
+        assert_eq!(grad, p.forward_diff(&f));
+        let res = [1e-8f64, 2.0];
+        for i in 0..2 {
+            assert!(((res[i] - grad[i]) / res[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_scaled_nalgebra_f64_trait() {
+        let steps = dvector![1e-8f64, 2e-8, 1e-8, 2e-8, 1e-8, 2e-8];
+        let jacobian = x2().forward_jacobian_scaled(&f2, &steps);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_scaled_nalgebra_f64_trait() {
+        let steps = dvector![1e-8f64, 2e-8, 1e-8, 2e-8, 1e-8, 2e-8];
+        let jacobian = x2().central_jacobian_scaled(&f2, &steps);
+        let res = res1();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_nalgebra_f64_trait() {
+        let jacobian = x2().forward_jacobian_vec_prod(&f2, &p1());
+        let res = res3();
+        // println!("{:?}", jacobian);
+        // the accuracy for this is pretty bad!!
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_normalized_nalgebra_f64_trait() {
+        let jacobian = x2().forward_jacobian_vec_prod_normalized(&f2, &p1());
+        let res = res3();
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_vec_prod_nalgebra_f64_trait() {
+        let jacobian = x2().central_jacobian_vec_prod(&f2, &p1());
+        let res = res3();
+        // println!("{:?}", jacobian);
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_vjp_nalgebra_f64_trait() {
+        let vjp = x2().forward_vjp(&f2, &p1());
+        // J^T*v computed by hand
+        let res = [-16.0f64, -2.0, 3.0, 8.0, 13.0, 84.0];
+        // println!("{:?}", vjp);
+        for i in 0..6 {
+            assert!((res[i] - vjp[i]).abs() < 11.0 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_pert_nalgebra_f64_trait() {
+        let jacobian = x2().forward_jacobian_pert(&f2, &pert());
+        let res = res1();
+        // println!("jacobian:\n{:?}", jacobian);
+        // println!("res:\n{:?}", res);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_pert_nalgebra_f64_trait() {
+        let jacobian = x2().central_jacobian_pert(&f2, &pert());
+        let res = res1();
+        // println!("jacobian:\n{:?}", jacobian);
+        // println!("res:\n{:?}", res);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nalgebra_f64_trait() {
+        let hessian = x3().forward_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_forward_hessian_nalgebra_f64_trait() {
+        let hessian = x3()
+            .try_forward_hessian(&|x: &DVector<f64>| Ok::<_, ()>(g(x)))
+            .unwrap();
+        let res = res2();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+
+        // fails once the third parameter is perturbed
+        let evals = core::cell::Cell::new(0);
+        let x0 = x3();
+        let failing = |x: &DVector<f64>| {
+            evals.set(evals.get() + 1);
+            if x[2] != x0[2] {
+                Err("no convergence")
+            } else {
+                Ok(g(x))
+            }
+        };
+        assert_eq!(x3().try_forward_hessian(&failing), Err("no convergence"));
+        assert_eq!(evals.get(), 4);
+    }
+
+    #[test]
+    fn test_central_hessian_nalgebra_f64_trait() {
+        let hessian = x3().central_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_prod_nalgebra_f64_trait() {
+        let hessian = x3().forward_hessian_vec_prod(&g, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_nalgebra_f64_trait() {
+        let hessian = x3().central_hessian_vec_prod(&g, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_vec_prod_nograd_nalgebra_f64_trait() {
+        let hessian = x3().forward_hessian_vec_prod_nograd(&f3, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < 1e-3)
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_vec_prod_nograd_nalgebra_f64_trait() {
+        let hessian = x3().central_hessian_vec_prod_nograd(&f3, &p2());
+        let res = [0.0, 6.0, 10.0, 18.0];
+        for i in 0..4 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_nalgebra_f64_trait() {
+        let hessian = x3().forward_hessian_nograd(&f3);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_grad_and_hessian_nograd_nalgebra_f64_trait() {
+        let (grad, hessian) = x3().forward_grad_and_hessian_nograd(&f3);
+        let res_grad = [1.0f64, 2.0, 1.0, 2.0];
+        let res = res2();
+        for i in 0..4 {
+            assert!((res_grad[i] - grad[i]).abs() < COMP_ACC);
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_nalgebra_f64_trait() {
+        let hessian = x3().central_hessian_nograd(&f3);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_sparse_nalgebra_f64_trait() {
+        let indices = vec![[1, 1], [2, 3], [3, 3]];
+        let hessian = x3().forward_hessian_nograd_sparse(&f3, indices);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_sparse_nalgebra_f64_trait() {
+        let indices = vec![[1, 1], [2, 3], [3, 3]];
+        let hessian = x3().central_hessian_nograd_sparse(&f3, indices);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        // println!("diff:\n{:#?}", diff);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_diff_5_nalgebra_f64_trait() {
+        let p = dvector![2.0f64, -1.5f64];
+        let f_exp = |x: &DVector<f64>| x[0].exp() + x[1].exp();
+        let grad3 = p.central_diff(&f_exp);
+        let grad5 = p.central_diff_5(&f_exp);
+
+        for i in 0..2 {
+            assert!((p[i].exp() - grad5[i]).abs() < (p[i].exp() - grad3[i]).abs())
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_2_nalgebra_f64_trait() {
+        let p = dvector![2.0f64, -1.5f64];
+        let f_exp = |x: &DVector<f64>| x[0].exp() + x[1].exp();
+        let grad1 = p.forward_diff(&f_exp);
+        let grad2 = p.forward_diff_2(&f_exp);
+
+        for i in 0..2 {
+            assert!((p[i].exp() - grad2[i]).abs() < (p[i].exp() - grad1[i]).abs())
+        }
+    }
+
+    #[test]
+    fn test_central_jacobian_5_nalgebra_f64_trait() {
+        let jacobian = x2().central_jacobian_5(&f2);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_2_nalgebra_f64_trait() {
+        let jacobian = x2().forward_jacobian_2(&f2);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_richardson_diff_nalgebra_f64_trait() {
+        let p = dvector![2.0f64, 0.5];
+        let f = |x: &DVector<f64>| x[0].exp() + x[1].sin();
+        let grad = p.richardson_diff(&f, 3);
+        let res = [p[0].exp(), p[1].cos()];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < 1e-11)
+        }
+    }
+
+    #[test]
+    fn test_adaptive_diff_nalgebra_f64_trait() {
+        let p = dvector![2.0f64, 0.5];
+        let f = |x: &DVector<f64>| x[0].exp() + x[1].sin();
+        let grad = p.adaptive_diff(&f);
+        let res = [p[0].exp(), p[1].cos()];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < 1e-9)
+        }
+    }
+
+    #[test]
+    fn test_auto_diff_nalgebra_f64_trait() {
+        let p = nalgebra::DVector::from_vec(vec![1.0f64, 1e-4]);
+        let f = |x: &nalgebra::DVector<f64>| x[0].exp() + 1e4 * x[1].powi(2);
+        assert_eq!(p.auto_diff(&f, 1e-6), p.central_diff(&f));
+        assert_eq!(p.auto_diff(&f, 1e-2), p.forward_diff(&f));
+    }
+
+    #[test]
+    fn test_forward_diff_with_error_nalgebra_f64_trait() {
+        let p = dvector![1.0f64, 1.0];
+        let (grad, err) = p.forward_diff_with_error(&|x: &DVector<f64>| x[0] + x[1].powi(2));
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC);
+            assert!(err[i] < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_try_forward_jacobian_vec_prod_nalgebra_f64_trait() {
+        let jacobian = x2().try_forward_jacobian_vec_prod(&f2, &p1()).unwrap();
+        let res = res3();
+        for i in 0..6 {
+            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+        }
+
+        assert_eq!(
+            x2().try_forward_jacobian_vec_prod(&f2, &p2()),
+            Err(FiniteDiffError::DimensionMismatch {
+                expected: 6,
+                got: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_try_central_hessian_vec_prod_nalgebra_f64_trait() {
+        assert_eq!(
+            x3().try_central_hessian_vec_prod(&g, &p1()),
+            Err(FiniteDiffError::DimensionMismatch {
+                expected: 4,
+                got: 6
+            })
+        );
+    }
+
+    #[test]
+    fn test_try_forward_diff_scaled_nalgebra_f64_trait() {
+        let steps = dvector![1e-2f64, 1e-11];
+        let x = dvector![1e6f64, 1e-3];
+        assert!(x.try_forward_diff_scaled(&f4, &steps).is_ok());
+
+        let x = dvector![f64::NAN, 1e-3];
+        assert_eq!(
+            x.try_forward_diff_scaled(&f4, &steps),
+            Err(FiniteDiffError::NonFinite { index: 0 })
+        );
+
+        let x = dvector![1e20f64, 1e-3];
+        assert_eq!(
+            x.try_forward_diff_scaled(&f4, &steps),
+            Err(FiniteDiffError::StepUnderflow { index: 0 })
+        );
+    }
+
+    #[test]
+    fn test_try_forward_diff_nalgebra_f64_trait() {
+        let grad = x1().try_forward_diff(&f1).unwrap();
+        let res = [1.0f64, 2.0];
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let f = |x: &DVector<f64>| f64::MAX * (x[1] - 0.5).signum();
+        let x = dvector![1.0f64, 0.5 - 1e-10];
+        assert_eq!(
+            x.try_forward_diff(&f),
+            Err(FiniteDiffError::NonFinite { index: 1 })
+        );
+
+        // the steps are relative to the parameters and therefore not lost to rounding
+        let grad = dvector![1.0f64, 1e20].try_forward_diff(&f1).unwrap();
+        assert!(((grad[1] - 2e20) / 2e20).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_try_non_finite_index_nalgebra_f64_trait() {
+        // `f` is finite at `x` but not if `x_1` is perturbed
+        let f = |x: &DVector<f64>| x[0] + if x[1] > 1.0 { f64::INFINITY } else { x[1] };
+        let fs = |x: &DVector<f64>| -> DVector<f64> {
+            dvector![if x[1] > 1.0 { f64::INFINITY } else { x[0] }, x[1]]
+        };
+        let x = dvector![0.0f64, 1.0];
+        let steps = dvector![1e-6f64, 1e-6];
+
+        assert_eq!(
+            x.try_forward_diff_scaled(&f, &steps),
+            Err(FiniteDiffError::NonFinite { index: 1 })
+        );
+        assert_eq!(
+            x.try_forward_jacobian_scaled(&fs, &steps),
+            Err(FiniteDiffError::NonFiniteJacobian { index: (1, 0) })
+        );
+    }
+
+    #[test]
+    fn test_forward_diff_mut_nalgebra_f64_trait() {
+        let mut evals = 0;
+        let grad = x1().forward_diff_mut(&mut |x: &DVector<f64>| {
+            evals += 1;
+            f1(x)
+        });
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+        assert_eq!(evals, 3);
+    }
+
+    #[test]
+    fn test_forward_diff_with_nalgebra_f64_trait() {
+        let f = |x: &DVector<f64>, p: &f64| p * f1(x);
+        let grad = x1().forward_diff_with(&f, &3.0);
+        let res = [3.0f64, 6.0];
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+
+        let fs = |x: &DVector<f64>, p: &f64| dvector![p * x[0], x[1].powi(2)];
+        let jacobian = x1().forward_jacobian_with(&fs, &3.0);
+        let res = [[3.0f64, 0.0], [0.0, 2.0]];
+        for i in 0..2 {
+            for j in 0..2 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_diff_paired_nalgebra_f64_trait() {
+        // noise which only depends on the index of the pair of evaluations
+        let mut evals = 0;
+        let grad = x1().central_diff_paired(&mut |x: &DVector<f64>| {
+            let noise = 100.0 * ((evals / 2) as f64 * 1.3).sin();
+            evals += 1;
+            f1(x) + noise
+        });
+        let res = [1.0f64, 2.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+        assert_eq!(evals, 4);
+    }
+
+    #[test]
+    fn test_central_jacobian_mut_nalgebra_f64_trait() {
+        let mut evals = 0;
+        let jacobian = x2().central_jacobian_mut(&mut |x: &DVector<f64>| {
+            evals += 1;
+            f2(x)
+        });
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+        // one additional evaluation is needed to determine the dimension of the output
+        assert_eq!(evals, 13);
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_mut_nalgebra_f64_trait() {
+        let mut evals = 0;
+        let hessian = x3().forward_hessian_nograd_mut(&mut |x: &DVector<f64>| {
+            evals += 1;
+            f3(x)
+        });
+        let res = res2();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+        assert_eq!(evals, 1 + 4 + 6 + 8);
+    }
+
+    #[test]
+    fn test_check_gradient_nalgebra_f64_trait() {
+        assert!(x1()
+            .check_gradient(&f1, &dvector![1.0, 2.0], COMP_ACC)
+            .is_ok());
+
+        let report = x1()
+            .check_gradient(&f1, &dvector![1.0, 3.0], COMP_ACC)
+            .unwrap_err();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].index, vec![1]);
+        assert!((report.failures[0].numeric - 2.0).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_check_jacobian_nalgebra_f64_trait() {
+        let mut jacobian = DMatrix::from_fn(6, 6, |i, j| res1()[i][j]);
+        assert!(x2().check_jacobian(&f2, &jacobian, COMP_ACC).is_ok());
+
+        jacobian[(2, 3)] = 1.0;
+        let report = x2().check_jacobian(&f2, &jacobian, COMP_ACC).unwrap_err();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].index, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_directional_diff_nalgebra_f64_trait() {
+        let p = dvector![1.0f64, -2.0];
+        let res = 1.0 - 4.0;
+        assert!((res - x1().forward_directional_diff(&f1, &p)).abs() < COMP_ACC);
+        assert!((res - x1().central_directional_diff(&f1, &p)).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_detect_sparsity_nalgebra_f64_trait() {
+        let x = DVector::from_element(6, 2.0f64);
+        let pert = x.detect_sparsity(&f2, 1e-3);
+        assert_eq!(pert.len(), 3);
+
+        let jacobian = x2().forward_jacobian_pert(&f2, &pert);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_jacobian_sparsity_nalgebra_f64_trait() {
+        let x = DVector::from_element(6, 2.0f64);
+        let pattern = x.jacobian_sparsity(&f2, 1e-3);
+        assert_eq!(pattern.nnz(), 16);
+        // tridiagonal
+        for (&r, &c) in pattern.row_indices().iter().zip(pattern.col_indices()) {
+            assert!(r.max(c) - r.min(c) <= 1);
+        }
+        assert_eq!(pattern.perturbation_vectors().len(), 3);
+    }
+
+    #[test]
+    fn test_forward_jacobian_pert_sparse_nalgebra_f64_trait() {
+        let jacobian = x2().forward_jacobian_pert_sparse(&f2, &pert());
+        let res = res1();
+        assert_eq!(jacobian.nnz(), 16);
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_banded_nalgebra_f64_trait() {
+        let jacobian = x2().forward_jacobian_banded(&f2, 1, 1);
+        let res = res1();
+        for i in 0..6 {
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
     }
 
-    fn central_jacobian_pert(
-        &self,
-        fs: &dyn Fn(&Self) -> Self::OperatorOutput,
-        pert: &PerturbationVectors,
-    ) -> Self::Jacobian {
-        central_jacobian_pert_ndarray_f64(self, fs, pert)
+    #[test]
+    fn test_forward_diff_precomputed_nalgebra_f64_trait() {
+        let x = dvector![1.0f64, 2.0f64];
+        let grad = x.forward_diff_precomputed(&f1, f1(&x));
+        let res = [1.0f64, 4.0];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
     }
 
-    fn forward_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
-        forward_hessian_ndarray_f64(self, g)
+    #[test]
+    fn test_value_and_forward_diff_nalgebra_f64_trait() {
+        let x = dvector![1.0f64, 2.0f64];
+        let f = CountingFn::new(f1);
+        let (fx, grad) = x.value_and_forward_diff(&|x| f.call(x));
+        let res = [1.0f64, 4.0];
+
+        assert_eq!(f.count(), 3);
+        assert_eq!(fx, f1(&x));
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
     }
 
-    fn central_hessian(&self, g: &dyn Fn(&Self) -> Self::OperatorOutput) -> Self::Jacobian {
-        central_hessian_ndarray_f64(self, g)
+    #[test]
+    fn test_forward_diff_with_samples_nalgebra_f64_trait() {
+        let x = dvector![1.0f64, 2.0f64];
+        let (grad, fx, samples) = x.forward_diff_with_samples(&f1);
+        assert_eq!(fx, f1(&x));
+        assert_eq!(grad, x.forward_diff(&f1));
+        assert_eq!(samples.len(), 2);
     }
 
-    fn forward_hessian_vec_prod(
-        &self,
-        g: &dyn Fn(&Self) -> Self::OperatorOutput,
-        p: &Self,
-    ) -> Self {
-        forward_hessian_vec_prod_ndarray_f64(self, g, p)
+    #[test]
+    fn test_forward_diff_traced_nalgebra_f64_trait() {
+        let x = dvector![1.0f64, 2.0f64];
+        let mut trace = vec![];
+        let grad = x.forward_diff_traced(&f1, &mut |p, fp| trace.push((p.to_vec(), fp)));
+        assert_eq!(grad, x.forward_diff(&f1));
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace[0], (vec![1.0, 2.0], f1(&x)));
     }
 
-    fn central_hessian_vec_prod(
-        &self,
-        g: &dyn Fn(&Self) -> Self::OperatorOutput,
-        p: &Self,
-    ) -> Self {
-        central_hessian_vec_prod_ndarray_f64(self, g, p)
+    #[test]
+    fn test_forward_diff_subset_nalgebra_f64_trait() {
+        let x = nalgebra::DVector::from_vec(vec![1.0f64, 2.0f64]);
+        let f = CountingFn::new(f1);
+        let grad = x.forward_diff_subset(&|x| f.call(x), &[1]);
+
+        assert_eq!(f.count(), 2);
+        assert_eq!(grad[0], 0.0);
+        assert_eq!(grad[1], x.forward_diff(&f1)[1]);
     }
 
-    fn forward_hessian_nograd(&self, f: &dyn Fn(&Self) -> f64) -> Self::Hessian {
-        forward_hessian_nograd_ndarray_f64(self, f)
+    #[test]
+    fn test_forward_hessian_diagonal_nograd_nalgebra_f64_trait() {
+        let diag = x3().forward_hessian_diagonal_nograd(&f3);
+        let res = res2();
+        assert_eq!(diag.len(), 4);
+        for i in 0..4 {
+            assert!((res[i][i] - diag[i]).abs() < COMP_ACC)
+        }
     }
 
-    fn forward_hessian_nograd_sparse(
-        &self,
-        f: &dyn Fn(&Self) -> f64,
-        indices: Vec<[usize; 2]>,
-    ) -> Self::Hessian {
-        forward_hessian_nograd_sparse_ndarray_f64(self, f, indices)
+    #[test]
+    fn test_central_grad_and_diag_nalgebra_f64_trait() {
+        let (grad, diag) = x3().central_grad_and_diag(&f3);
+        assert_eq!(diag, x3().forward_hessian_diagonal_nograd(&f3));
+        let res = x3().central_diff(&f3);
+        for i in 0..4 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
     }
 }
 
+#[cfg(feature = "nalgebra")]
 #[cfg(test)]
-mod tests_vec {
+mod tests_nalgebra_f32 {
     use super::*;
+    use nalgebra::{dvector, DVector};
 
-    const COMP_ACC: f64 = 1e-6;
+    const COMP_ACC: f32 = 1e-3;
 
-    fn f1(x: &Vec<f64>) -> f64 {
+    fn f1(x: &DVector<f32>) -> f32 {
         x[0] + x[1].powi(2)
     }
 
-    fn f2(x: &Vec<f64>) -> Vec<f64> {
-        vec![
+    fn f2(x: &DVector<f32>) -> DVector<f32> {
+        dvector![
             2.0 * (x[1].powi(3) - x[0].powi(2)),
             3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
             3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
@@ -769,27 +9801,23 @@ mod tests_vec {
         ]
     }
 
-    fn f3(x: &Vec<f64>) -> f64 {
-        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
-    }
-
-    fn g(x: &Vec<f64>) -> Vec<f64> {
-        vec![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    fn g(x: &DVector<f32>) -> DVector<f32> {
+        dvector![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
     }
 
-    fn x1() -> Vec<f64> {
-        vec![1.0f64, 1.0f64]
+    fn x1() -> DVector<f32> {
+        dvector![1.0f32, 1.0f32]
     }
 
-    fn x2() -> Vec<f64> {
-        vec![1.0f64, 1.0, 1.0, 1.0, 1.0, 1.0]
+    fn x2() -> DVector<f32> {
+        dvector![1.0f32, 1.0, 1.0, 1.0, 1.0, 1.0]
     }
 
-    fn x3() -> Vec<f64> {
-        vec![1.0f64, 1.0, 1.0, 1.0]
+    fn x3() -> DVector<f32> {
+        dvector![1.0f32, 1.0, 1.0, 1.0]
     }
 
-    fn res1() -> Vec<Vec<f64>> {
+    fn res1() -> Vec<Vec<f32>> {
         vec![
             vec![-4.0, -6.0, 0.0, 0.0, 0.0, 0.0],
             vec![6.0, 5.0, -6.0, 0.0, 0.0, 0.0],
@@ -800,7 +9828,7 @@ mod tests_vec {
         ]
     }
 
-    fn res2() -> Vec<Vec<f64>> {
+    fn res2() -> Vec<Vec<f32>> {
         vec![
             vec![0.0, 0.0, 0.0, 0.0],
             vec![0.0, 2.0, 0.0, 0.0],
@@ -809,44 +9837,20 @@ mod tests_vec {
         ]
     }
 
-    fn res3() -> Vec<f64> {
-        vec![8.0, 22.0, 27.0, 32.0, 37.0, 24.0]
-    }
-
-    fn pert() -> PerturbationVectors {
-        vec![
-            PerturbationVector::new()
-                .add(0, vec![0, 1])
-                .add(3, vec![2, 3, 4]),
-            PerturbationVector::new()
-                .add(1, vec![0, 1, 2])
-                .add(4, vec![3, 4, 5]),
-            PerturbationVector::new()
-                .add(2, vec![1, 2, 3])
-                .add(5, vec![4, 5]),
-        ]
-    }
-
-    fn p1() -> Vec<f64> {
-        vec![1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0]
-    }
-
-    fn p2() -> Vec<f64> {
-        vec![2.0, 3.0, 4.0, 5.0]
-    }
-
     #[test]
-    fn test_forward_diff_vec_f64_trait() {
+    fn test_forward_diff_nalgebra_f32_trait() {
         let grad = x1().forward_diff(&f1);
-        let res = vec![1.0f64, 2.0];
+        let res = [1.0f32, 2.0];
 
         for i in 0..2 {
             assert!((res[i] - grad[i]).abs() < COMP_ACC)
         }
+    }
 
-        let p = vec![1.0f64, 2.0f64];
-        let grad = p.forward_diff(&f1);
-        let res = vec![1.0f64, 4.0];
+    #[test]
+    fn test_backward_diff_nalgebra_f32_trait() {
+        let grad = x1().backward_diff(&f1);
+        let res = [1.0f32, 2.0];
 
         for i in 0..2 {
             assert!((res[i] - grad[i]).abs() < COMP_ACC)
@@ -854,17 +9858,9 @@ mod tests_vec {
     }
 
     #[test]
-    fn test_central_diff_vec_f64_trait() {
+    fn test_central_diff_nalgebra_f32_trait() {
         let grad = x1().central_diff(&f1);
-        let res = vec![1.0f64, 2.0];
-
-        for i in 0..2 {
-            assert!((res[i] - grad[i]).abs() < COMP_ACC)
-        }
-
-        let p = vec![1.0f64, 2.0f64];
-        let grad = p.central_diff(&f1);
-        let res = vec![1.0f64, 4.0];
+        let res = [1.0f32, 2.0];
 
         for i in 0..2 {
             assert!((res[i] - grad[i]).abs() < COMP_ACC)
@@ -872,421 +9868,579 @@ mod tests_vec {
     }
 
     #[test]
-    fn test_forward_jacobian_vec_f64_trait() {
+    fn test_forward_jacobian_nalgebra_f32_trait() {
         let jacobian = x2().forward_jacobian(&f2);
         let res = res1();
         // println!("{:?}", jacobian);
         for i in 0..6 {
             for j in 0..6 {
-                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < 10.0 * COMP_ACC)
             }
         }
     }
 
     #[test]
-    fn test_central_jacobian_vec_f64_trait() {
-        let jacobian = x2().central_jacobian(&f2);
+    fn test_backward_jacobian_nalgebra_f32_trait() {
+        let jacobian = x2().backward_jacobian(&f2);
         let res = res1();
         // println!("{:?}", jacobian);
         for i in 0..6 {
             for j in 0..6 {
-                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < 10.0 * COMP_ACC)
             }
         }
     }
 
     #[test]
-    fn test_forward_jacobian_vec_prod_vec_f64_trait() {
-        let jacobian = x2().forward_jacobian_vec_prod(&f2, &p1());
-        let res = res3();
+    fn test_central_jacobian_nalgebra_f32_trait() {
+        let jacobian = x2().central_jacobian(&f2);
+        let res = res1();
         // println!("{:?}", jacobian);
-        // the accuracy for this is pretty bad!!
         for i in 0..6 {
-            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
+            for j in 0..6 {
+                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
+            }
         }
     }
 
     #[test]
-    fn test_central_jacobian_vec_prod_vec_f64_trait() {
-        let jacobian = x2().central_jacobian_vec_prod(&f2, &p1());
-        let res = res3();
-        // println!("{:?}", jacobian);
-        for i in 0..6 {
-            assert!((res[i] - jacobian[i]).abs() < COMP_ACC)
+    fn test_forward_hessian_nalgebra_f32_trait() {
+        let hessian = x3().forward_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
         }
     }
 
     #[test]
-    fn test_forward_jacobian_pert_vec_f64_trait() {
-        let jacobian = x2().forward_jacobian_pert(&f2, &pert());
-        let res = res1();
-        // println!("jacobian:\n{:?}", jacobian);
-        // println!("res:\n{:?}", res);
-        for i in 0..6 {
-            for j in 0..6 {
+    fn test_central_hessian_nalgebra_f32_trait() {
+        let hessian = x3().central_hessian(&g);
+        let res = res2();
+        // println!("hessian:\n{:#?}", hessian);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_array {
+    use super::*;
+
+    const COMP_ACC: f64 = 1e-6;
+
+    fn f(x: &[f64; 3]) -> f64 {
+        x[0] + x[1].powi(2) + x[0] * x[2].powi(2)
+    }
+
+    fn fs(x: &[f64; 3]) -> [f64; 3] {
+        [x[0] * x[1], x[1].powi(2) + x[2], x[0].sin()]
+    }
+
+    fn x() -> [f64; 3] {
+        [1.0, 2.0, 1.0]
+    }
+
+    fn res_grad() -> [f64; 3] {
+        [2.0, 4.0, 2.0]
+    }
+
+    fn res_jacobian() -> [[f64; 3]; 3] {
+        [[2.0, 0.0, 1.0f64.cos()], [1.0, 4.0, 0.0], [0.0, 1.0, 0.0]]
+    }
+
+    fn res_hessian() -> [[f64; 3]; 3] {
+        [[0.0, 0.0, 2.0], [0.0, 2.0, 0.0], [2.0, 0.0, 2.0]]
+    }
+
+    #[test]
+    fn test_forward_diff_array_trait() {
+        let grad = x().forward_diff(&f);
+        let res = res_grad();
+        for i in 0..3 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_value_and_forward_diff_array_trait() {
+        let (fx, grad) = x().value_and_forward_diff(&f);
+        let res = res_grad();
+        assert_eq!(fx, f(&x()));
+        for i in 0..3 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_subset_array_trait() {
+        let grad = x().forward_diff_subset(&f, &[0, 2]);
+        let full = x().forward_diff(&f);
+        assert_eq!(grad, [full[0], 0.0, full[2]]);
+    }
+
+    #[test]
+    fn test_central_diff_array_trait() {
+        let grad = x().central_diff(&f);
+        let res = res_grad();
+        for i in 0..3 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_central_diff_5_array_trait() {
+        let grad = x().central_diff_5(&f);
+        let res = res_grad();
+        for i in 0..3 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_diff_2_array_trait() {
+        let grad = x().forward_diff_2(&f);
+        let res = res_grad();
+        for i in 0..3 {
+            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_diff_order_array_trait() {
+        let forward = x().forward_diff_order(&f, StencilOrder::Order4);
+        let central = x().central_diff_order(&f, StencilOrder::Order6);
+        let res = res_grad();
+        for i in 0..3 {
+            assert!((res[i] - forward[i]).abs() < COMP_ACC);
+            assert!((res[i] - central[i]).abs() < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_array_trait() {
+        let jacobian = x().forward_jacobian(&fs);
+        let res = res_jacobian();
+        for i in 0..3 {
+            for j in 0..3 {
                 assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
             }
         }
     }
 
     #[test]
-    fn test_central_jacobian_pert_vec_f64_trait() {
-        let jacobian = x2().central_jacobian_pert(&f2, &pert());
-        let res = res1();
-        // println!("jacobian:\n{:?}", jacobian);
-        // println!("res:\n{:?}", res);
-        for i in 0..6 {
-            for j in 0..6 {
+    fn test_forward_jacobian_thresholded_array_trait() {
+        let fs = |x: &[f64; 3]| [x[0].powi(2), x[1].exp() * (-x[1]).exp(), x[2]];
+        let jacobian = [1.0, 0.5, 2.0].forward_jacobian_thresholded(&fs, 1e-12);
+        assert_eq!(jacobian[1], [0.0; 3]);
+        assert!((jacobian[0][0] - 2.0).abs() < COMP_ACC);
+        assert!((jacobian[2][2] - 1.0).abs() < COMP_ACC);
+    }
+
+    #[test]
+    fn test_forward_jacobian_from_values_array_trait() {
+        let values: Vec<_> = x().forward_jacobian_points().iter().map(fs).collect();
+        assert_eq!(
+            x().forward_jacobian_from_values(&values),
+            x().forward_jacobian(&fs)
+        );
+    }
+
+    #[test]
+    fn test_central_jacobian_array_trait() {
+        let jacobian = x().central_jacobian(&fs);
+        let res = res_jacobian();
+        for i in 0..3 {
+            for j in 0..3 {
                 assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
             }
         }
     }
 
     #[test]
-    fn test_forward_hessian_vec_f64_trait() {
-        let hessian = x3().forward_hessian(&g);
-        let res = res2();
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            for j in 0..4 {
-                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+    fn test_central_jacobian_into_array_trait() {
+        let mut jacobian = [[0.0; 3]; 3];
+        x().central_jacobian_into(&fs, &mut jacobian);
+        let res = res_jacobian();
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
             }
         }
     }
 
     #[test]
-    fn test_central_hessian_vec_f64_trait() {
-        let hessian = x3().central_hessian(&g);
-        let res = res2();
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            for j in 0..4 {
+    fn test_forward_jacobian_banded_array_trait() {
+        // the band covers the whole matrix
+        let jacobian = x().forward_jacobian_banded(&fs, 2, 2);
+        let res = res_jacobian();
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((res[i][j] - jacobian.get(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_2_array_trait() {
+        let jacobian = x().forward_jacobian_2(&fs);
+        let res = res_jacobian();
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((res[i][j] - jacobian[i][j]).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_array_trait() {
+        let p = [1.0, 2.0, 3.0];
+        let jvp = x().forward_jacobian_vec_prod(&fs, &p);
+        let res = [4.0, 11.0, 1.0f64.cos()];
+        for i in 0..3 {
+            assert!((res[i] - jvp[i]).abs() < 10.0 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_jacobian_vec_prod_at_array_trait() {
+        let p = [1.0, 2.0, 3.0];
+        let jvp = x().forward_jacobian_vec_prod_at(&fs, &p, &fs(&x()));
+        let res = [4.0, 11.0, 1.0f64.cos()];
+        for i in 0..3 {
+            assert!((res[i] - jvp[i]).abs() < 10.0 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_vjp_array_trait() {
+        let v = [1.0, 2.0, 3.0];
+        let vjp = x().forward_vjp(&fs, &v);
+        let res = [2.0 + 3.0 * 1.0f64.cos(), 9.0, 2.0];
+        for i in 0..3 {
+            assert!((res[i] - vjp[i]).abs() < 10.0 * COMP_ACC)
+        }
+    }
+
+    #[test]
+    fn test_forward_hessian_nograd_array_trait() {
+        let hessian = [1.0, 1.0, 1.0].forward_hessian_nograd(&f);
+        let res = res_hessian();
+        for i in 0..3 {
+            for j in 0..3 {
                 assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
             }
         }
     }
 
     #[test]
-    fn test_forward_hessian_vec_prod_vec_f64_trait() {
-        let hessian = x3().forward_hessian_vec_prod(&g, &p2());
-        let res = vec![0.0, 6.0, 10.0, 18.0];
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+    fn test_forward_hessian_nograd_packed_array_trait() {
+        let hessian = [1.0, 1.0, 1.0].forward_hessian_nograd_packed(&f);
+        let res = res_hessian();
+        assert_eq!(hessian.n, 3);
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((res[i][j] - hessian.at(i, j)).abs() < COMP_ACC)
+            }
+        }
+    }
+
+    #[test]
+    fn test_central_hessian_nograd_array_trait() {
+        let hessian = [1.0, 1.0, 1.0].central_hessian_nograd(&f);
+        let res = res_hessian();
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
+            }
         }
     }
 
     #[test]
-    fn test_central_hessian_vec_prod_vec_f64_trait() {
-        let hessian = x3().central_hessian_vec_prod(&g, &p2());
-        let res = vec![0.0, 6.0, 10.0, 18.0];
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
+    fn test_forward_hessian_vec_prod_nograd_array_trait() {
+        let hessian = [1.0, 1.0, 1.0].forward_hessian_vec_prod_nograd(&f, &[1.0, 2.0, 3.0]);
+        let res = [6.0, 4.0, 8.0];
+        for i in 0..3 {
+            assert!((res[i] - hessian[i]).abs() < 1e-3)
         }
     }
 
     #[test]
-    fn test_forward_hessian_nograd_vec_f64_trait() {
-        let hessian = x3().forward_hessian_nograd(&f3);
-        let res = res2();
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            for j in 0..4 {
-                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
-            }
+    fn test_central_hessian_vec_prod_nograd_array_trait() {
+        let hessian = [1.0, 1.0, 1.0].central_hessian_vec_prod_nograd(&f, &[1.0, 2.0, 3.0]);
+        let res = [6.0, 4.0, 8.0];
+        for i in 0..3 {
+            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
         }
     }
 
     #[test]
-    fn test_forward_hessian_nograd_sparse_vec_f64_trait() {
-        let indices = vec![[1, 1], [2, 3], [3, 3]];
-        let hessian = x3().forward_hessian_nograd_sparse(&f3, indices);
-        let res = res2();
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            for j in 0..4 {
-                assert!((res[i][j] - hessian[i][j]).abs() < COMP_ACC)
-            }
-        }
+    fn test_check_gradient_array_trait() {
+        assert!(x().check_gradient(&f, &res_grad(), 1e-6).is_ok());
+        assert!(x().check_gradient(&f, &[2.0, 4.0, 3.0], 1e-6).is_err());
     }
 }
 
 #[cfg(feature = "ndarray")]
 #[cfg(test)]
-mod tests_ndarray {
+mod tests_ndarray2 {
     use super::*;
-    use ndarray;
-    use ndarray::{array, Array1};
+    use ndarray::{array, Array2};
 
     const COMP_ACC: f64 = 1e-6;
 
-    fn f1(x: &Array1<f64>) -> f64 {
-        x[0] + x[1].powi(2)
+    fn f(x: &Array2<f64>) -> f64 {
+        x[(0, 0)] * x[(1, 1)] + x[(0, 1)].powi(2) + x[(1, 0)].exp()
     }
 
-    fn f2(x: &Array1<f64>) -> Array1<f64> {
+    fn fs(x: &Array2<f64>) -> Array2<f64> {
         array![
-            2.0 * (x[1].powi(3) - x[0].powi(2)),
-            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
-            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
-            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
-            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
-            3.0 * (x[5].powi(3) - x[4].powi(2)),
+            [x[(0, 0)] * x[(0, 1)], x[(1, 1)].powi(2)],
+            [x[(1, 0)], 0.0],
+            [1.0, x[(0, 0)]]
         ]
     }
 
-    fn f3(x: &Array1<f64>) -> f64 {
-        x[0] + x[1].powi(2) + x[2] * x[3].powi(2)
+    fn g(x: &Array2<f64>) -> Array2<f64> {
+        array![[x[(1, 1)], 2.0 * x[(0, 1)]], [x[(1, 0)].exp(), x[(0, 0)]]]
     }
 
-    fn g(x: &Array1<f64>) -> Array1<f64> {
-        array![1.0, 2.0 * x[1], x[3].powi(2), 2.0 * x[3] * x[2]]
+    fn x() -> Array2<f64> {
+        array![[1.0, 2.0], [0.5, 1.5]]
     }
 
-    fn x1() -> Array1<f64> {
-        array![1.0f64, 1.0f64]
+    fn res_grad() -> Array2<f64> {
+        g(&x())
     }
 
-    fn x2() -> Array1<f64> {
-        array![1.0f64, 1.0, 1.0, 1.0, 1.0, 1.0]
+    /// Jacobian of `fs`, rows are the flattened parameters and columns the flattened outputs
+    fn res_jacobian() -> Array2<f64> {
+        array![
+            [2.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0, 0.0, 0.0],
+            [0.0, 3.0, 0.0, 0.0, 0.0, 0.0]
+        ]
     }
 
-    fn x3() -> Array1<f64> {
-        array![1.0f64, 1.0, 1.0, 1.0]
+    fn res_hessian() -> Array2<f64> {
+        array![
+            [0.0, 0.0, 0.0, 1.0],
+            [0.0, 2.0, 0.0, 0.0],
+            [0.0, 0.0, 0.5f64.exp(), 0.0],
+            [1.0, 0.0, 0.0, 0.0]
+        ]
     }
 
-    fn res1() -> Vec<Vec<f64>> {
-        vec![
-            vec![-4.0, -6.0, 0.0, 0.0, 0.0, 0.0],
-            vec![6.0, 5.0, -6.0, 0.0, 0.0, 0.0],
-            vec![0.0, 6.0, 5.0, -6.0, 0.0, 0.0],
-            vec![0.0, 0.0, 6.0, 5.0, -6.0, 0.0],
-            vec![0.0, 0.0, 0.0, 6.0, 5.0, -6.0],
-            vec![0.0, 0.0, 0.0, 0.0, 6.0, 9.0],
-        ]
+    fn assert_close(a: &Array2<f64>, b: &Array2<f64>) {
+        assert_eq!(approx_eq_matrix_ndarray(a, b, COMP_ACC), Ok(()));
     }
 
-    fn res2() -> Vec<Vec<f64>> {
-        vec![
-            vec![0.0, 0.0, 0.0, 0.0],
-            vec![0.0, 2.0, 0.0, 0.0],
-            vec![0.0, 0.0, 0.0, 2.0],
-            vec![0.0, 0.0, 2.0, 2.0],
-        ]
+    #[test]
+    fn test_forward_diff_ndarray2_trait() {
+        assert_close(&x().forward_diff(&f), &res_grad());
     }
 
-    fn res3() -> Vec<f64> {
-        vec![8.0, 22.0, 27.0, 32.0, 37.0, 24.0]
+    #[test]
+    fn test_central_diff_ndarray2_trait() {
+        assert_close(&x().central_diff(&f), &res_grad());
     }
 
-    fn pert() -> PerturbationVectors {
-        vec![
-            PerturbationVector::new()
-                .add(0, vec![0, 1])
-                .add(3, vec![2, 3, 4]),
-            PerturbationVector::new()
-                .add(1, vec![0, 1, 2])
-                .add(4, vec![3, 4, 5]),
-            PerturbationVector::new()
-                .add(2, vec![1, 2, 3])
-                .add(5, vec![4, 5]),
-        ]
+    #[test]
+    fn test_central_diff_5_ndarray2_trait() {
+        assert_close(&x().central_diff_5(&f), &res_grad());
     }
 
-    fn p1() -> Array1<f64> {
-        array![1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0]
+    #[test]
+    fn test_forward_jacobian_ndarray2_trait() {
+        assert_close(&x().forward_jacobian(&fs), &res_jacobian());
     }
 
-    fn p2() -> Array1<f64> {
-        array![2.0, 3.0, 4.0, 5.0]
+    #[test]
+    fn test_forward_jacobian_from_values_ndarray2_trait() {
+        let points = x().forward_jacobian_points();
+        assert_eq!(points.len(), 5);
+        assert_eq!(points[0], x());
+        let values: Vec<_> = points.iter().map(fs).collect();
+        assert_eq!(
+            x().forward_jacobian_from_values(&values),
+            x().forward_jacobian(&fs)
+        );
     }
 
     #[test]
-    fn test_forward_diff_ndarray_f64_trait() {
-        let grad = x1().forward_diff(&f1);
-        let res = array![1.0f64, 2.0];
+    fn test_central_jacobian_vec_prod_ndarray2_trait() {
+        let p = array![[1.0, 2.0], [3.0, 4.0]];
+        let jvp = x().central_jacobian_vec_prod(&fs, &p);
+        // derivative of `fs` along `p`, in the shape of the function values
+        let res = array![[4.0, 12.0], [3.0, 0.0], [0.0, 1.0]];
+        assert_close(&jvp, &res);
+    }
 
-        for i in 0..2 {
-            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+    #[test]
+    fn test_forward_jacobian_vec_prod_at_ndarray2_trait() {
+        let p = array![[1.0, 2.0], [3.0, 4.0]];
+        let jvp = x().forward_jacobian_vec_prod_at(&fs, &p, &fs(&x()));
+        let res = array![[4.0, 12.0], [3.0, 0.0], [0.0, 1.0]];
+        for (a, b) in res.iter().zip(jvp.iter()) {
+            assert!((a - b).abs() < 1e-5);
         }
+        assert_eq!(jvp.dim(), (3, 2));
+    }
 
-        let x = array![1.0f64, 2.0f64];
-        let grad = x.forward_diff(&f1);
-        let res = vec![1.0f64, 4.0];
-
-        for i in 0..2 {
-            assert!((res[i] - grad[i]).abs() < COMP_ACC)
-        }
+    #[test]
+    fn test_central_hessian_ndarray2_trait() {
+        assert_close(&x().central_hessian(&g), &res_hessian());
     }
 
     #[test]
-    fn test_central_diff_ndarray_f64_trait() {
-        let grad = x1().central_diff(&f1);
-        let res = vec![1.0f64, 2.0];
+    fn test_central_hessian_vec_prod_ndarray2_trait() {
+        let p = array![[1.0, 2.0], [3.0, 4.0]];
+        let hvp = x().central_hessian_vec_prod(&g, &p);
+        let res = array![[4.0, 4.0], [3.0 * 0.5f64.exp(), 1.0]];
+        assert_close(&hvp, &res);
+    }
 
-        for i in 0..2 {
-            assert!((res[i] - grad[i]).abs() < COMP_ACC)
+    #[test]
+    fn test_central_hessian_nograd_ndarray2_trait() {
+        let hessian = x().central_hessian_nograd(&f);
+        for (a, b) in res_hessian().iter().zip(hessian.iter()) {
+            assert!((a - b).abs() < 1e-5);
         }
+    }
+}
 
-        let x = array![1.0f64, 2.0f64];
-        let grad = x.central_diff(&f1);
-        let res = vec![1.0f64, 4.0];
+#[cfg(test)]
+mod tests_nested {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
 
-        for i in 0..2 {
-            assert!((res[i] - grad[i]).abs() < COMP_ACC)
-        }
+    const COMP_ACC: f64 = 1e-6;
+
+    /// Parameters in two sublists of unequal length
+    fn x() -> Vec<Vec<f64>> {
+        vec![vec![1.0, 2.0], vec![0.5, 1.5, 3.0]]
     }
 
-    #[test]
-    fn test_forward_jacobian_ndarray_f64_trait() {
-        let jacobian = x2().forward_jacobian(&f2);
-        let res = res1();
-        // println!("{:?}", jacobian);
-        for i in 0..6 {
-            for j in 0..6 {
-                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
-            }
-        }
+    fn f(x: &Vec<Vec<f64>>) -> f64 {
+        x[0][0] * x[1][1] + x[0][1].powi(2) + x[1][0].exp() + x[1][2]
+    }
+
+    fn fs(x: &Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        vec![vec![x[0][0] * x[0][1]], vec![x[1][1].powi(2), x[1][2]]]
+    }
+
+    fn g(x: &Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        vec![
+            vec![x[1][1], 2.0 * x[0][1]],
+            vec![x[1][0].exp(), x[0][0], 1.0],
+        ]
+    }
+
+    /// Jacobian of `fs`, rows are the concatenated parameters and columns the concatenated
+    /// outputs
+    fn res_jacobian() -> Vec<Vec<f64>> {
+        vec![
+            vec![2.0, 0.0, 0.0],
+            vec![1.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0],
+            vec![0.0, 3.0, 0.0],
+            vec![0.0, 0.0, 1.0],
+        ]
+    }
+
+    fn res_hessian() -> Vec<Vec<f64>> {
+        vec![
+            vec![0.0, 0.0, 0.0, 1.0, 0.0],
+            vec![0.0, 2.0, 0.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.5f64.exp(), 0.0, 0.0],
+            vec![1.0, 0.0, 0.0, 0.0, 0.0],
+            vec![0.0; 5],
+        ]
+    }
+
+    fn assert_close(a: &Vec<Vec<f64>>, b: &Vec<Vec<f64>>) {
+        assert_eq!(approx_eq_matrix(a, b, COMP_ACC), Ok(()));
     }
 
     #[test]
-    fn test_central_jacobian_ndarray_f64_trait() {
-        let jacobian = x2().central_jacobian(&f2);
-        let res = res1();
-        // println!("{:?}", jacobian);
-        for i in 0..6 {
-            for j in 0..6 {
-                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
-            }
-        }
+    fn test_forward_diff_nested_trait() {
+        assert_close(&x().forward_diff(&f), &g(&x()));
     }
 
     #[test]
-    fn test_forward_jacobian_vec_prod_ndarray_f64_trait() {
-        let jacobian = x2().forward_jacobian_vec_prod(&f2, &p1());
-        let res = res3();
-        // println!("{:?}", jacobian);
-        // the accuracy for this is pretty bad!!
-        for i in 0..6 {
-            assert!((res[i] - jacobian[i]).abs() < 5.5 * COMP_ACC)
-        }
+    fn test_forward_diff_traced_nested_trait() {
+        let mut trace = vec![];
+        x().forward_diff_traced(&f, &mut |p, _| trace.push(p.to_vec()));
+        assert_eq!(trace.len(), 6);
+        assert_eq!(trace[0], flatten_nested(&x()));
     }
 
     #[test]
-    fn test_central_jacobian_vec_prod_ndarray_f64_trait() {
-        let jacobian = x2().central_jacobian_vec_prod(&f2, &p1());
-        let res = res3();
-        // println!("{:?}", jacobian);
-        for i in 0..6 {
-            assert!((res[i] - jacobian[i]).abs() < COMP_ACC)
-        }
+    fn test_estimate_diff_noise_nested_trait() {
+        let noise = x().estimate_diff_noise(&mut |p| f(p), 3);
+        assert_eq!(noise, vec![vec![0.0; 2], vec![0.0; 3]]);
     }
 
     #[test]
-    fn test_forward_jacobian_pert_ndarray_f64_trait() {
-        let jacobian = x2().forward_jacobian_pert(&f2, &pert());
-        let res = res1();
-        // println!("jacobian:\n{:?}", jacobian);
-        // println!("res:\n{:?}", res);
-        for i in 0..6 {
-            for j in 0..6 {
-                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
-            }
-        }
+    fn test_central_diff_nested_trait() {
+        assert_close(&x().central_diff(&f), &g(&x()));
     }
 
     #[test]
-    fn test_central_jacobian_pert_ndarray_f64_trait() {
-        let jacobian = x2().central_jacobian_pert(&f2, &pert());
-        let res = res1();
-        // println!("jacobian:\n{:?}", jacobian);
-        // println!("res:\n{:?}", res);
-        for i in 0..6 {
-            for j in 0..6 {
-                assert!((res[i][j] - jacobian[(i, j)]).abs() < COMP_ACC)
-            }
-        }
+    fn test_central_diff_5_nested_trait() {
+        assert_close(&x().central_diff_5(&f), &g(&x()));
     }
 
     #[test]
-    fn test_forward_hessian_ndarray_f64_trait() {
-        let hessian = x3().forward_hessian(&g);
-        let res = res2();
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            for j in 0..4 {
-                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
-            }
-        }
+    fn test_forward_jacobian_nested_trait() {
+        assert_close(&x().forward_jacobian(&fs), &res_jacobian());
     }
 
     #[test]
-    fn test_central_hessian_ndarray_f64_trait() {
-        let hessian = x3().central_hessian(&g);
-        let res = res2();
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            for j in 0..4 {
-                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
-            }
-        }
+    fn test_central_jacobian_vec_prod_nested_trait() {
+        let p = vec![vec![1.0, 2.0], vec![3.0, 4.0, 5.0]];
+        let jvp = x().central_jacobian_vec_prod(&fs, &p);
+        // derivative of `fs` along `p`, in the shape of the function values
+        let res = vec![vec![4.0], vec![12.0, 5.0]];
+        assert_close(&jvp, &res);
     }
 
     #[test]
-    fn test_forward_hessian_vec_prod_ndarray_f64_trait() {
-        let hessian = x3().forward_hessian_vec_prod(&g, &p2());
-        let res = vec![0.0, 6.0, 10.0, 18.0];
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
-        }
+    fn test_forward_jacobian_vec_prod_normalized_nested_trait() {
+        let p = vec![vec![1.0, 2.0], vec![3.0, 4.0, 5.0]];
+        let jvp = x().forward_jacobian_vec_prod_normalized(&fs, &p);
+        assert_eq!(
+            approx_eq_matrix(&jvp, &vec![vec![4.0], vec![12.0, 5.0]], 1e-5),
+            Ok(())
+        );
     }
 
     #[test]
-    fn test_central_hessian_vec_prod_ndarray_f64_trait() {
-        let hessian = x3().central_hessian_vec_prod(&g, &p2());
-        let res = vec![0.0, 6.0, 10.0, 18.0];
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            assert!((res[i] - hessian[i]).abs() < COMP_ACC)
-        }
+    fn test_central_hessian_nested_trait() {
+        assert_close(&x().central_hessian(&g), &res_hessian());
     }
 
     #[test]
-    fn test_forward_hessian_nograd_ndarray_f64_trait() {
-        let hessian = x3().forward_hessian_nograd(&f3);
-        let res = res2();
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            for j in 0..4 {
-                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
+    fn test_central_hessian_nograd_nested_trait() {
+        let hessian = x().central_hessian_nograd(&f);
+        for (ri, hi) in res_hessian().iter().zip(hessian.iter()) {
+            for (r, h) in ri.iter().zip(hi.iter()) {
+                assert!((r - h).abs() < 1e-4);
             }
         }
     }
 
     #[test]
-    fn test_forward_hessian_nograd_sparse_ndarray_f64_trait() {
-        let indices = vec![[1, 1], [2, 3], [3, 3]];
-        let hessian = x3().forward_hessian_nograd_sparse(&f3, indices);
-        let res = res2();
-        // println!("hessian:\n{:#?}", hessian);
-        // println!("diff:\n{:#?}", diff);
-        for i in 0..4 {
-            for j in 0..4 {
-                assert!((res[i][j] - hessian[(i, j)]).abs() < COMP_ACC)
-            }
-        }
+    fn test_forward_hessian_nograd_blocks_nested_trait() {
+        let mut rows = vec![];
+        x().forward_hessian_nograd_blocks(&f, 2, &mut |_, block| rows.extend_from_slice(block));
+        assert_eq!(rows, x().forward_hessian_nograd(&f));
     }
 }
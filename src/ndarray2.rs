@@ -0,0 +1,102 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Finite differences for matrix-valued parameters of type `ndarray::Array2<f64>`.
+//!
+//! Gradients are computed directly on the matrix and have the same shape as the parameters.
+//! All other methods of `FiniteDiff` flatten the parameters in row-major order and use the
+//! `ndarray::Array1<f64>` implementation.
+
+use crate::utils::*;
+use crate::EPS_F64;
+
+pub fn forward_diff_ndarray2(
+    x: &ndarray::Array2<f64>,
+    f: &dyn Fn(&ndarray::Array2<f64>) -> f64,
+) -> ndarray::Array2<f64> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let mut out = ndarray::Array2::zeros(x.dim());
+    for (idx, o) in out.indexed_iter_mut() {
+        let h = step_for(x[idx], EPS_F64);
+        let fx1 = mod_and_calc_ndarray2_f64(&mut xt, f, idx, h);
+        *o = (fx1 - fx) / h;
+    }
+    out
+}
+
+pub fn central_diff_ndarray2(
+    x: &ndarray::Array2<f64>,
+    f: &dyn Fn(&ndarray::Array2<f64>) -> f64,
+) -> ndarray::Array2<f64> {
+    let mut xt = x.clone();
+    let mut out = ndarray::Array2::zeros(x.dim());
+    for (idx, o) in out.indexed_iter_mut() {
+        let h = central_step(x[idx]);
+        let fx1 = mod_and_calc_ndarray2_f64(&mut xt, f, idx, h);
+        let fx2 = mod_and_calc_ndarray2_f64(&mut xt, f, idx, -h);
+        *o = (fx1 - fx2) / (2.0 * h);
+    }
+    out
+}
+
+/// Flattens a matrix in row-major order.
+#[inline(always)]
+pub fn flatten_ndarray2(x: &ndarray::Array2<f64>) -> ndarray::Array1<f64> {
+    x.iter().cloned().collect()
+}
+
+/// Reverts `flatten_ndarray2`.
+#[inline(always)]
+pub fn unflatten_ndarray2(x: &ndarray::Array1<f64>, dim: (usize, usize)) -> ndarray::Array2<f64> {
+    ndarray::Array2::from_shape_vec(dim, x.to_vec()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    const COMP_ACC: f64 = 1e-6;
+
+    fn f(x: &ndarray::Array2<f64>) -> f64 {
+        x[(0, 0)] * x[(1, 2)] + x[(0, 1)].powi(2) + x[(1, 0)].exp()
+    }
+
+    fn x() -> ndarray::Array2<f64> {
+        array![[1.0, 2.0, 3.0], [0.5, 1.5, 2.5]]
+    }
+
+    fn res() -> ndarray::Array2<f64> {
+        array![[2.5, 4.0, 0.0], [0.5f64.exp(), 0.0, 1.0]]
+    }
+
+    #[test]
+    fn test_forward_diff_ndarray2() {
+        let grad = forward_diff_ndarray2(&x(), &f);
+        assert_eq!(grad.dim(), (2, 3));
+        for (r, g) in res().iter().zip(grad.iter()) {
+            assert!((r - g).abs() < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_central_diff_ndarray2() {
+        let grad = central_diff_ndarray2(&x(), &f);
+        assert_eq!(grad.dim(), (2, 3));
+        for (r, g) in res().iter().zip(grad.iter()) {
+            assert!((r - g).abs() < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_flatten_ndarray2() {
+        let flat = flatten_ndarray2(&x());
+        assert_eq!(flat, array![1.0, 2.0, 3.0, 0.5, 1.5, 2.5]);
+        assert_eq!(unflatten_ndarray2(&flat, (2, 3)), x());
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Finite differences for parameters grouped into sublists of type `Vec<Vec<f64>>`.
+//!
+//! The sublists may differ in length. Gradients are computed directly on the nested parameters
+//! and have the same shape. All other methods of `FiniteDiff` flatten the parameters sublist by
+//! sublist and use the `Vec<f64>` implementation.
+
+use crate::utils::*;
+use crate::EPS_F64;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+pub fn forward_diff_nested(x: &Vec<Vec<f64>>, f: &dyn Fn(&Vec<Vec<f64>>) -> f64) -> Vec<Vec<f64>> {
+    let fx = (f)(x);
+    let mut xt = x.clone();
+    let mut out = x.clone();
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, o) in row.iter_mut().enumerate() {
+            let h = step_for(x[i][j], EPS_F64);
+            let fx1 = mod_and_calc_nested(&mut xt, f, (i, j), h);
+            *o = (fx1 - fx) / h;
+        }
+    }
+    out
+}
+
+pub fn central_diff_nested(x: &Vec<Vec<f64>>, f: &dyn Fn(&Vec<Vec<f64>>) -> f64) -> Vec<Vec<f64>> {
+    let mut xt = x.clone();
+    let mut out = x.clone();
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, o) in row.iter_mut().enumerate() {
+            let h = central_step(x[i][j]);
+            let fx1 = mod_and_calc_nested(&mut xt, f, (i, j), h);
+            let fx2 = mod_and_calc_nested(&mut xt, f, (i, j), -h);
+            *o = (fx1 - fx2) / (2.0 * h);
+        }
+    }
+    out
+}
+
+/// Lengths of the sublists
+#[inline(always)]
+pub fn nested_shape(x: &[Vec<f64>]) -> Vec<usize> {
+    x.iter().map(|row| row.len()).collect()
+}
+
+/// Concatenates the sublists.
+#[inline(always)]
+pub fn flatten_nested(x: &Vec<Vec<f64>>) -> Vec<f64> {
+    x.concat()
+}
+
+/// Reverts `flatten_nested`, where `shape` holds the lengths of the sublists.
+#[inline(always)]
+pub fn unflatten_nested(x: &[f64], shape: &[usize]) -> Vec<Vec<f64>> {
+    let mut rest = x;
+    shape
+        .iter()
+        .map(|&len| {
+            let (row, tail) = rest.split_at(len);
+            rest = tail;
+            row.to_vec()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    const COMP_ACC: f64 = 1e-6;
+
+    fn f(x: &Vec<Vec<f64>>) -> f64 {
+        x[0][0] * x[1][2] + x[0][1].powi(2) + x[1][0].exp()
+    }
+
+    fn x() -> Vec<Vec<f64>> {
+        vec![vec![1.0, 2.0], vec![0.5, 1.5, 2.5]]
+    }
+
+    fn res() -> Vec<Vec<f64>> {
+        vec![vec![2.5, 4.0], vec![0.5f64.exp(), 0.0, 1.0]]
+    }
+
+    #[test]
+    fn test_forward_diff_nested() {
+        let grad = forward_diff_nested(&x(), &f);
+        assert_eq!(nested_shape(&grad), vec![2, 3]);
+        for (r, g) in res().concat().iter().zip(grad.concat().iter()) {
+            assert!((r - g).abs() < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_central_diff_nested() {
+        let grad = central_diff_nested(&x(), &f);
+        assert_eq!(nested_shape(&grad), vec![2, 3]);
+        for (r, g) in res().concat().iter().zip(grad.concat().iter()) {
+            assert!((r - g).abs() < COMP_ACC);
+        }
+    }
+
+    #[test]
+    fn test_flatten_nested() {
+        let flat = flatten_nested(&x());
+        assert_eq!(flat, vec![1.0, 2.0, 0.5, 1.5, 2.5]);
+        assert_eq!(unflatten_nested(&flat, &nested_shape(&x())), x());
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+/// Symmetric `n x n` matrix of which only the upper triangle is stored.
+///
+/// The entries `(i, j)` with `i <= j` are stored row by row in `data`, i.e. in the order
+/// `(0, 0), (0, 1), ..., (0, n - 1), (1, 1), (1, 2), ..., (n - 1, n - 1)`. This requires
+/// `n * (n + 1) / 2` instead of `n^2` values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedSymmetric<F> {
+    /// Number of rows and columns
+    pub n: usize,
+    /// Upper triangle, of length `n * (n + 1) / 2`
+    pub data: Vec<F>,
+}
+
+impl<F: Copy> PackedSymmetric<F> {
+    pub(crate) fn from_upper(n: usize, data: Vec<F>) -> Self {
+        debug_assert_eq!(data.len(), n * (n + 1) / 2);
+        PackedSymmetric { n, data }
+    }
+
+    #[inline(always)]
+    fn idx(&self, i: usize, j: usize) -> usize {
+        let (i, j) = if i <= j { (i, j) } else { (j, i) };
+        i * (2 * self.n - i + 1) / 2 + j - i
+    }
+
+    /// Value at position `(i, j)`, which is the same as at `(j, i)`.
+    ///
+    /// Panics if `i` or `j` is out of bounds.
+    pub fn at(&self, i: usize, j: usize) -> F {
+        assert!(
+            i < self.n && j < self.n,
+            "index ({}, {}) out of bounds for a {}x{} matrix",
+            i,
+            j,
+            self.n,
+            self.n
+        );
+        self.data[self.idx(i, j)]
+    }
+
+    /// Converts to the dense layout used by `forward_hessian_nograd`
+    pub fn to_dense(&self) -> Vec<Vec<F>> {
+        (0..self.n)
+            .map(|i| (0..self.n).map(|j| self.at(i, j)).collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    #[test]
+    fn test_packed_symmetric_layout() {
+        let p = PackedSymmetric::from_upper(3, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            p.to_dense(),
+            vec![vec![1, 2, 3], vec![2, 4, 5], vec![3, 5, 6]]
+        );
+        assert_eq!(p.at(2, 1), 5);
+        assert_eq!(p.at(1, 2), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_packed_symmetric_out_of_bounds() {
+        // (0, 3) would otherwise silently alias (1, 1)
+        PackedSymmetric::from_upper(3, vec![1, 2, 3, 4, 5, 6]).at(0, 3);
+    }
+}
@@ -5,8 +5,19 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use crate::error::FiniteDiffError;
+use crate::jacobian::forward_jacobian_vec;
+#[cfg(feature = "nalgebra")]
+use crate::jacobian_nalgebra::forward_jacobian_nalgebra;
+#[cfg(feature = "ndarray")]
+use crate::jacobian_ndarray::forward_jacobian_ndarray;
+use crate::FloatEps;
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
 /// Perturbation Vector for the accelerated computation of the Jacobian.
 #[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PerturbationVector {
     /// x indices
     pub x_idx: Vec<usize>,
@@ -23,13 +34,560 @@ impl PerturbationVector {
         }
     }
 
+    /// Create a `PerturbationVector` with a single parameter index `x_idx` and the corresponding
+    /// function indices `r_idx`. This is the same as `PerturbationVector::new().add(x_idx, r_idx)`.
+    pub fn single(x_idx: usize, r_idx: Vec<usize>) -> Self {
+        PerturbationVector::new().add(x_idx, r_idx)
+    }
+
     /// Add an index `x_idx` and the corresponding function indices `r_idx`
     pub fn add(mut self, x_idx: usize, r_idx: Vec<usize>) -> Self {
         self.x_idx.push(x_idx);
         self.r_idx.push(r_idx);
         self
     }
+
+    /// Append all indices of `other`
+    pub fn merge(mut self, other: PerturbationVector) -> Self {
+        self.x_idx.extend(other.x_idx);
+        self.r_idx.extend(other.r_idx);
+        self
+    }
+
+    /// Remove all indices
+    pub fn clear(&mut self) {
+        self.x_idx.clear();
+        self.r_idx.clear();
+    }
+
+    /// Number of parameter indices
+    pub fn len(&self) -> usize {
+        self.x_idx.len()
+    }
+
+    /// Returns `true` if no index has been added
+    pub fn is_empty(&self) -> bool {
+        self.x_idx.is_empty()
+    }
+
+    /// Parameter indices which are perturbed together
+    pub fn indices(&self) -> &[usize] {
+        &self.x_idx
+    }
+
+    /// Checks that all parameter indices and function indices are smaller than `dim`.
+    ///
+    /// Out-of-range indices would otherwise cause a panic when the perturbation vector is used
+    /// for the computation of a Jacobian.
+    pub fn validate(&self, dim: usize) -> Result<(), FiniteDiffError> {
+        if self.x_idx.len() != self.r_idx.len() {
+            return Err(FiniteDiffError::DimensionMismatch {
+                expected: self.x_idx.len(),
+                got: self.r_idx.len(),
+            });
+        }
+        let all = self.x_idx.iter().chain(self.r_idx.iter().flatten());
+        match all.copied().find(|&index| index >= dim) {
+            Some(index) => Err(FiniteDiffError::IndexOutOfBounds { index, dim }),
+            None => Ok(()),
+        }
+    }
 }
 
 /// A collection of `PerturbationVector`s
 pub type PerturbationVectors = Vec<PerturbationVector>;
+
+/// Construction of `PerturbationVectors` from a known sparsity pattern.
+///
+/// Since `PerturbationVectors` is an alias for `Vec<PerturbationVector>`, this is provided as a
+/// trait which needs to be in scope:
+///
+/// ```
+/// use finitediff::{FromSparsityPattern, PerturbationVectors};
+///
+/// // tridiagonal pattern
+/// let n: usize = 5;
+/// let nonzeros: Vec<(usize, usize)> = (0..n)
+///     .flat_map(|r| (r.saturating_sub(1)..(r + 2).min(n)).map(move |c| (r, c)))
+///     .collect();
+/// let pert = PerturbationVectors::from_pattern(n, &nonzeros);
+/// assert_eq!(pert.len(), 3);
+/// ```
+pub trait FromSparsityPattern {
+    /// Builds perturbation groups from the `(row, col)` positions of the structurally nonzero
+    /// entries of the Jacobian, where `row` is the function index and `col` the parameter index.
+    ///
+    /// Columns which share no rows are greedily assigned to the same group.
+    fn from_pattern(n_cols: usize, nonzeros: &[(usize, usize)]) -> Self;
+}
+
+impl FromSparsityPattern for PerturbationVectors {
+    fn from_pattern(n_cols: usize, nonzeros: &[(usize, usize)]) -> Self {
+        let mut columns: Vec<Vec<usize>> = vec![vec![]; n_cols];
+        for &(r, c) in nonzeros.iter() {
+            columns[c].push(r);
+        }
+        for rows in columns.iter_mut() {
+            rows.sort_unstable();
+            rows.dedup();
+        }
+        color_columns(&columns)
+    }
+}
+
+/// Greedily groups the parameters such that the function indices within each group do not
+/// overlap.
+///
+/// `columns[i]` contains the function indices which depend on the `i`th parameter. Parameters
+/// which do not affect any function index are omitted from the result.
+pub fn color_columns(columns: &[Vec<usize>]) -> PerturbationVectors {
+    let n_rows = columns
+        .iter()
+        .flat_map(|rows| rows.iter())
+        .max()
+        .map_or(0, |&r| r + 1);
+    let mut groups: Vec<(PerturbationVector, Vec<bool>)> = vec![];
+    for (x_idx, rows) in columns.iter().enumerate() {
+        if rows.is_empty() {
+            continue;
+        }
+        let pos = groups
+            .iter()
+            .position(|(_, used)| rows.iter().all(|&r| !used[r]));
+        let pos = match pos {
+            Some(pos) => pos,
+            None => {
+                groups.push((PerturbationVector::new(), vec![false; n_rows]));
+                groups.len() - 1
+            }
+        };
+        let (pert, used) = &mut groups[pos];
+        for &r in rows.iter() {
+            used[r] = true;
+        }
+        pert.x_idx.push(x_idx);
+        pert.r_idx.push(rows.clone());
+    }
+    groups.into_iter().map(|(pert, _)| pert).collect()
+}
+
+/// Sparsity pattern of a Jacobian in coordinate (COO) format.
+///
+/// Entry `k` is nonzero at row `row_indices()[k]` (the function index) and column
+/// `col_indices()[k]` (the parameter index). The entries are sorted by row and then by column.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SparsityPattern {
+    n_rows: usize,
+    n_cols: usize,
+    rows: Vec<usize>,
+    cols: Vec<usize>,
+}
+
+impl SparsityPattern {
+    /// Builds the pattern from the function indices `columns[i]` which depend on parameter `i`
+    fn from_columns(n_rows: usize, columns: &[Vec<usize>]) -> Self {
+        let mut nonzeros: Vec<(usize, usize)> = columns
+            .iter()
+            .enumerate()
+            .flat_map(|(c, rows)| rows.iter().map(move |&r| (r, c)))
+            .collect();
+        nonzeros.sort_unstable();
+        SparsityPattern {
+            n_rows,
+            n_cols: columns.len(),
+            rows: nonzeros.iter().map(|&(r, _)| r).collect(),
+            cols: nonzeros.iter().map(|&(_, c)| c).collect(),
+        }
+    }
+
+    /// Number of function values
+    pub fn n_rows(&self) -> usize {
+        self.n_rows
+    }
+
+    /// Number of parameters
+    pub fn n_cols(&self) -> usize {
+        self.n_cols
+    }
+
+    /// Number of nonzero entries
+    pub fn nnz(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Function indices of the nonzero entries
+    pub fn row_indices(&self) -> &[usize] {
+        &self.rows
+    }
+
+    /// Parameter indices of the nonzero entries
+    pub fn col_indices(&self) -> &[usize] {
+        &self.cols
+    }
+
+    /// `PerturbationVectors` for this pattern, see `FromSparsityPattern::from_pattern`
+    pub fn perturbation_vectors(&self) -> PerturbationVectors {
+        let nonzeros: Vec<(usize, usize)> = self
+            .rows
+            .iter()
+            .copied()
+            .zip(self.cols.iter().copied())
+            .collect();
+        PerturbationVectors::from_pattern(self.n_cols, &nonzeros)
+    }
+}
+
+/// Determines the sparsity pattern of the Jacobian of `fs` at `x` and builds the corresponding
+/// `PerturbationVectors`.
+///
+/// The Jacobian is computed via forward differences and every entry whose absolute value exceeds
+/// `threshold` is considered to be nonzero. Note that entries which happen to vanish at `x` are
+/// not detected.
+pub(crate) fn detect_sparsity_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    threshold: F,
+) -> PerturbationVectors {
+    color_columns(&nonzero_columns_vec(x, fs, threshold).0)
+}
+
+pub(crate) fn jacobian_sparsity_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    threshold: F,
+) -> SparsityPattern {
+    let (columns, n_rows) = nonzero_columns_vec(x, fs, threshold);
+    SparsityPattern::from_columns(n_rows, &columns)
+}
+
+/// Probes the Jacobian and returns, for every parameter, the function indices whose derivative
+/// exceeds `threshold`, together with the number of function values.
+fn nonzero_columns_vec<F: FloatEps>(
+    x: &Vec<F>,
+    fs: &dyn Fn(&Vec<F>) -> Vec<F>,
+    threshold: F,
+) -> (Vec<Vec<usize>>, usize) {
+    let jacobian = forward_jacobian_vec(x, fs);
+    let columns = jacobian
+        .iter()
+        .map(|col| {
+            col.iter()
+                .enumerate()
+                .filter(|(_, v)| v.abs() > threshold)
+                .map(|(j, _)| j)
+                .collect()
+        })
+        .collect();
+    (columns, jacobian.first().map_or(0, |col| col.len()))
+}
+
+#[cfg(feature = "ndarray")]
+pub(crate) fn detect_sparsity_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    threshold: F,
+) -> PerturbationVectors {
+    color_columns(&nonzero_columns_ndarray(x, fs, threshold).0)
+}
+
+#[cfg(feature = "ndarray")]
+pub(crate) fn jacobian_sparsity_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    threshold: F,
+) -> SparsityPattern {
+    let (columns, n_rows) = nonzero_columns_ndarray(x, fs, threshold);
+    SparsityPattern::from_columns(n_rows, &columns)
+}
+
+/// Probes the Jacobian and returns, for every parameter, the function indices whose derivative
+/// exceeds `threshold`, together with the number of function values.
+#[cfg(feature = "ndarray")]
+fn nonzero_columns_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    fs: &dyn Fn(&ndarray::Array1<F>) -> ndarray::Array1<F>,
+    threshold: F,
+) -> (Vec<Vec<usize>>, usize) {
+    let jacobian = forward_jacobian_ndarray(x, fs);
+    let columns = jacobian
+        .outer_iter()
+        .map(|col| {
+            col.iter()
+                .enumerate()
+                .filter(|(_, v)| v.abs() > threshold)
+                .map(|(j, _)| j)
+                .collect()
+        })
+        .collect();
+    (columns, jacobian.ncols())
+}
+
+#[cfg(feature = "nalgebra")]
+pub(crate) fn detect_sparsity_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    threshold: F,
+) -> PerturbationVectors {
+    color_columns(&nonzero_columns_nalgebra(x, fs, threshold).0)
+}
+
+#[cfg(feature = "nalgebra")]
+pub(crate) fn jacobian_sparsity_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    threshold: F,
+) -> SparsityPattern {
+    let (columns, n_rows) = nonzero_columns_nalgebra(x, fs, threshold);
+    SparsityPattern::from_columns(n_rows, &columns)
+}
+
+/// Probes the Jacobian and returns, for every parameter, the function indices whose derivative
+/// exceeds `threshold`, together with the number of function values.
+#[cfg(feature = "nalgebra")]
+fn nonzero_columns_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    fs: &dyn Fn(&nalgebra::DVector<F>) -> nalgebra::DVector<F>,
+    threshold: F,
+) -> (Vec<Vec<usize>>, usize) {
+    let jacobian = forward_jacobian_nalgebra(x, fs);
+    let columns = jacobian
+        .row_iter()
+        .map(|col| {
+            col.iter()
+                .enumerate()
+                .filter(|(_, v)| v.abs() > threshold)
+                .map(|(j, _)| j)
+                .collect()
+        })
+        .collect();
+    (columns, jacobian.ncols())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f(x: &Vec<f64>) -> Vec<f64> {
+        vec![
+            2.0 * (x[1].powi(3) - x[0].powi(2)),
+            3.0 * (x[1].powi(3) - x[0].powi(2)) + 2.0 * (x[2].powi(3) - x[1].powi(2)),
+            3.0 * (x[2].powi(3) - x[1].powi(2)) + 2.0 * (x[3].powi(3) - x[2].powi(2)),
+            3.0 * (x[3].powi(3) - x[2].powi(2)) + 2.0 * (x[4].powi(3) - x[3].powi(2)),
+            3.0 * (x[4].powi(3) - x[3].powi(2)) + 2.0 * (x[5].powi(3) - x[4].powi(2)),
+            3.0 * (x[5].powi(3) - x[4].powi(2)),
+        ]
+    }
+
+    fn tridiagonal(n: usize) -> Vec<(usize, usize)> {
+        (0..n)
+            .flat_map(|r| (r.saturating_sub(1)..(r + 2).min(n)).map(move |c| (r, c)))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_pattern_tridiagonal() {
+        let pert = PerturbationVectors::from_pattern(7, &tridiagonal(7));
+        assert_eq!(pert.len(), 3);
+        assert_eq!(pert[0].x_idx, vec![0, 3, 6]);
+        assert_eq!(pert[0].r_idx, vec![vec![0, 1], vec![2, 3, 4], vec![5, 6]]);
+        assert_eq!(pert[1].x_idx, vec![1, 4]);
+        assert_eq!(pert[2].x_idx, vec![2, 5]);
+    }
+
+    #[test]
+    fn test_from_pattern_matches_manual() {
+        // pattern of the Jacobian of `f`, in arbitrary order and with duplicates
+        let mut nonzeros = tridiagonal(6);
+        nonzeros.reverse();
+        nonzeros.push((2, 2));
+        let pert = PerturbationVectors::from_pattern(6, &nonzeros);
+
+        let manual = [
+            PerturbationVector::new()
+                .add(0, vec![0, 1])
+                .add(3, vec![2, 3, 4]),
+            PerturbationVector::new()
+                .add(1, vec![0, 1, 2])
+                .add(4, vec![3, 4, 5]),
+            PerturbationVector::new()
+                .add(2, vec![1, 2, 3])
+                .add(5, vec![4, 5]),
+        ];
+        assert_eq!(pert.len(), manual.len());
+        for (a, b) in pert.iter().zip(manual.iter()) {
+            assert_eq!(a.x_idx, b.x_idx);
+            assert_eq!(a.r_idx, b.r_idx);
+        }
+    }
+
+    #[test]
+    fn test_accessors() {
+        let pert = PerturbationVector::new()
+            .add(0, vec![0, 1])
+            .add(3, vec![2, 3, 4]);
+        assert_eq!(pert.len(), 2);
+        assert!(!pert.is_empty());
+        assert_eq!(pert.indices(), &[0, 3]);
+        assert!(PerturbationVector::new().is_empty());
+    }
+
+    #[test]
+    fn test_single_merge_clear() {
+        let builder = vec![
+            PerturbationVector::new()
+                .add(0, vec![0, 1])
+                .add(3, vec![2, 3, 4]),
+            PerturbationVector::new()
+                .add(1, vec![0, 1, 2])
+                .add(4, vec![3, 4, 5]),
+            PerturbationVector::new()
+                .add(2, vec![1, 2, 3])
+                .add(5, vec![4, 5]),
+        ];
+        let rows = [
+            vec![0, 1],
+            vec![0, 1, 2],
+            vec![1, 2, 3],
+            vec![2, 3, 4],
+            vec![3, 4, 5],
+            vec![4, 5],
+        ];
+        let mut merged: PerturbationVectors = vec![];
+        for c in 0..3 {
+            merged.push(
+                PerturbationVector::single(c, rows[c].clone())
+                    .merge(PerturbationVector::single(c + 3, rows[c + 3].clone())),
+            );
+        }
+
+        let x = vec![1.0f64, 1.0, 1.0, 1.0, 1.0, 1.0];
+        assert_eq!(
+            crate::jacobian::forward_jacobian_pert_vec(&x, &f, &merged),
+            crate::jacobian::forward_jacobian_pert_vec(&x, &f, &builder)
+        );
+
+        let mut pert = PerturbationVector::single(0, vec![0, 1]);
+        assert_eq!(pert.len(), 1);
+        pert.clear();
+        assert!(pert.is_empty());
+        assert!(pert.r_idx.is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let pert = PerturbationVector::new()
+            .add(0, vec![0, 1])
+            .add(3, vec![2, 3, 4]);
+        assert_eq!(pert.validate(5), Ok(()));
+        assert_eq!(
+            pert.validate(4),
+            Err(FiniteDiffError::IndexOutOfBounds { index: 4, dim: 4 })
+        );
+        assert_eq!(
+            pert.validate(3),
+            Err(FiniteDiffError::IndexOutOfBounds { index: 3, dim: 3 })
+        );
+        let pert = PerturbationVector {
+            x_idx: vec![0, 1],
+            r_idx: vec![vec![0]],
+        };
+        assert_eq!(
+            pert.validate(3),
+            Err(FiniteDiffError::DimensionMismatch {
+                expected: 2,
+                got: 1
+            })
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let pert = vec![
+            PerturbationVector::new()
+                .add(0, vec![0, 1])
+                .add(3, vec![2, 3, 4]),
+            PerturbationVector::new()
+                .add(1, vec![0, 1, 2])
+                .add(4, vec![3, 4, 5]),
+            PerturbationVector::new()
+                .add(2, vec![1, 2, 3])
+                .add(5, vec![4, 5]),
+        ];
+        let json = serde_json::to_string(&pert).unwrap();
+        let pert2: PerturbationVectors = serde_json::from_str(&json).unwrap();
+
+        let x = vec![1.0f64, 1.0, 1.0, 1.0, 1.0, 1.0];
+        let jacobian = crate::jacobian::forward_jacobian_pert_vec(&x, &f, &pert);
+        let jacobian2 = crate::jacobian::forward_jacobian_pert_vec(&x, &f, &pert2);
+        assert_eq!(jacobian, jacobian2);
+    }
+
+    #[test]
+    fn test_color_columns() {
+        let columns = vec![vec![0, 1], vec![0, 1, 2], vec![], vec![1, 2, 3], vec![3]];
+        let pert = color_columns(&columns);
+        assert_eq!(pert.len(), 3);
+        assert_eq!(pert[0].x_idx, vec![0, 4]);
+        assert_eq!(pert[0].r_idx, vec![vec![0, 1], vec![3]]);
+        assert_eq!(pert[1].x_idx, vec![1]);
+        assert_eq!(pert[2].x_idx, vec![3]);
+    }
+
+    #[test]
+    fn test_detect_sparsity_vec_f64() {
+        let x = vec![2.0f64, 2.0, 2.0, 2.0, 2.0, 2.0];
+        let pert = detect_sparsity_vec(&x, &f, 1e-3);
+        assert_eq!(pert.len(), 3);
+        assert_eq!(pert[0].x_idx, vec![0, 3]);
+        assert_eq!(pert[0].r_idx, vec![vec![0, 1], vec![2, 3, 4]]);
+        assert_eq!(pert[1].x_idx, vec![1, 4]);
+        assert_eq!(pert[1].r_idx, vec![vec![0, 1, 2], vec![3, 4, 5]]);
+        assert_eq!(pert[2].x_idx, vec![2, 5]);
+        assert_eq!(pert[2].r_idx, vec![vec![1, 2, 3], vec![4, 5]]);
+    }
+
+    #[test]
+    fn test_jacobian_sparsity_vec_f64() {
+        let x = vec![2.0f64, 2.0, 2.0, 2.0, 2.0, 2.0];
+        let pattern = jacobian_sparsity_vec(&x, &f, 1e-3);
+        let (rows, cols): (Vec<usize>, Vec<usize>) = tridiagonal(6).into_iter().unzip();
+        assert_eq!((pattern.n_rows(), pattern.n_cols()), (6, 6));
+        assert_eq!(pattern.nnz(), 16);
+        assert_eq!(pattern.row_indices(), &rows[..]);
+        assert_eq!(pattern.col_indices(), &cols[..]);
+
+        let pert = pattern.perturbation_vectors();
+        let detected = detect_sparsity_vec(&x, &f, 1e-3);
+        assert_eq!(pert.len(), detected.len());
+        for (a, b) in pert.iter().zip(detected.iter()) {
+            assert_eq!(a.x_idx, b.x_idx);
+            assert_eq!(a.r_idx, b.r_idx);
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_detect_sparsity_ndarray_f64() {
+        let fs = |x: &ndarray::Array1<f64>| ndarray::Array1::from(f(&x.to_vec()));
+        let x = ndarray::Array1::from(vec![2.0f64, 2.0, 2.0, 2.0, 2.0, 2.0]);
+        let pert = detect_sparsity_ndarray(&x, &fs, 1e-3);
+        assert_eq!(pert.len(), 3);
+        assert_eq!(pert[2].x_idx, vec![2, 5]);
+        assert_eq!(pert[2].r_idx, vec![vec![1, 2, 3], vec![4, 5]]);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_detect_sparsity_nalgebra_f64() {
+        let fs = |x: &nalgebra::DVector<f64>| {
+            nalgebra::DVector::from_vec(f(&x.iter().cloned().collect()))
+        };
+        let x = nalgebra::DVector::from_vec(vec![2.0f64, 2.0, 2.0, 2.0, 2.0, 2.0]);
+        let pert = detect_sparsity_nalgebra(&x, &fs, 1e-3);
+        assert_eq!(pert.len(), 3);
+        assert_eq!(pert[2].x_idx, vec![2, 5]);
+        assert_eq!(pert[2].r_idx, vec![vec![1, 2, 3], vec![4, 5]]);
+    }
+}
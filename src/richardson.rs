@@ -0,0 +1,166 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::diff::*;
+#[cfg(feature = "nalgebra")]
+use crate::diff_nalgebra::*;
+#[cfg(feature = "ndarray")]
+use crate::diff_ndarray::*;
+use crate::utils::*;
+use crate::FloatEps;
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+/// Combines the central differences computed at steps `h`, `h/2`, ..., `h/2^(levels-1)` using the
+/// Romberg tableau and returns the most extrapolated row.
+///
+/// Since the error of the central difference only contains even powers of `h`, each column of
+/// the tableau removes the next even power.
+fn romberg<F: FloatEps>(mut tableau: Vec<Vec<F>>) -> Vec<F> {
+    let four = F::from(4.0).unwrap();
+    let levels = tableau.len();
+    for m in 1..levels {
+        let factor = four.powi(m as i32) - F::one();
+        for k in (m..levels).rev() {
+            tableau[k] = tableau[k]
+                .iter()
+                .zip(tableau[k - 1].iter())
+                .map(|(&a, &b)| a + (a - b) / factor)
+                .collect();
+        }
+    }
+    tableau.pop().unwrap()
+}
+
+/// Steps used for the individual levels of the extrapolation. The initial step is rather large
+/// because the truncation error is eliminated by the extrapolation anyway.
+fn richardson_steps<F: FloatEps>(levels: usize) -> Vec<F> {
+    let two = F::from(2.0).unwrap();
+    (0..levels.max(1))
+        .map(|k| eps_5::<F>() / two.powi(k as i32))
+        .collect()
+}
+
+pub fn richardson_diff_vec<F: FloatEps>(
+    x: &Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> F,
+    levels: usize,
+) -> Vec<F> {
+    let tableau = richardson_steps::<F>(levels)
+        .into_iter()
+        .map(|h| central_diff_scaled_vec(x, f, &vec![h; x.len()]))
+        .collect();
+    romberg(tableau)
+}
+
+#[cfg(feature = "ndarray")]
+pub fn richardson_diff_ndarray<F: FloatEps + ndarray::ScalarOperand>(
+    x: &ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> F,
+    levels: usize,
+) -> ndarray::Array1<F> {
+    let tableau = richardson_steps::<F>(levels)
+        .into_iter()
+        .map(|h| {
+            central_diff_scaled_ndarray(x, f, &ndarray::Array1::from_elem(x.len(), h)).to_vec()
+        })
+        .collect();
+    ndarray::Array1::from(romberg(tableau))
+}
+
+#[cfg(feature = "nalgebra")]
+pub fn richardson_diff_nalgebra<F: FloatEps + nalgebra::Scalar>(
+    x: &nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> F,
+    levels: usize,
+) -> nalgebra::DVector<F> {
+    let tableau = richardson_steps::<F>(levels)
+        .into_iter()
+        .map(|h| {
+            let steps = nalgebra::DVector::from_element(x.len(), h);
+            central_diff_scaled_nalgebra(x, f, &steps)
+                .iter()
+                .cloned()
+                .collect()
+        })
+        .collect();
+    nalgebra::DVector::from_vec(romberg(tableau))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f(x: &Vec<f64>) -> f64 {
+        x[0].exp() + x[1].sin()
+    }
+
+    fn res(x: &Vec<f64>) -> Vec<f64> {
+        vec![x[0].exp(), x[1].cos()]
+    }
+
+    #[test]
+    fn test_richardson_diff_vec_f64() {
+        let p = vec![2.0f64, 0.5];
+        let grad = richardson_diff_vec(&p, &f, 3);
+        let res = res(&p);
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < 1e-11);
+        }
+    }
+
+    #[test]
+    fn test_richardson_diff_vec_f64_beats_central() {
+        let p = vec![2.0f64, 0.5];
+        let central = central_diff_vec(&p, &f);
+        let grad = richardson_diff_vec(&p, &f, 3);
+        let res = res(&p);
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < (res[i] - central[i]).abs());
+        }
+    }
+
+    #[test]
+    fn test_richardson_diff_vec_f64_single_level() {
+        let p = vec![2.0f64, 0.5];
+        let grad = richardson_diff_vec(&p, &f, 1);
+        let steps = vec![eps_5::<f64>(); 2];
+        let central = central_diff_scaled_vec(&p, &f, &steps);
+
+        for i in 0..2 {
+            assert!((grad[i] - central[i]).abs() < f64::EPSILON);
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_richardson_diff_ndarray_f64() {
+        let f = |x: &ndarray::Array1<f64>| x[0].exp() + x[1].sin();
+        let p = ndarray::Array1::from(vec![2.0f64, 0.5]);
+        let grad = richardson_diff_ndarray(&p, &f, 3);
+        let res = [p[0].exp(), p[1].cos()];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < 1e-11);
+        }
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_richardson_diff_nalgebra_f64() {
+        let f = |x: &nalgebra::DVector<f64>| x[0].exp() + x[1].sin();
+        let p = nalgebra::DVector::from_vec(vec![2.0f64, 0.5]);
+        let grad = richardson_diff_nalgebra(&p, &f, 3);
+        let res = [p[0].exp(), p[1].cos()];
+
+        for i in 0..2 {
+            assert!((res[i] - grad[i]).abs() < 1e-11);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2018-2020 argmin developers
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use core::ops::Deref;
+
+/// Gradient returned by `FiniteDiff::forward_diff_typed`.
+///
+/// The gradient has the same type as the parameters, therefore it can accidentally be passed where
+/// a parameter vector is expected. This wrapper makes the distinction explicit. All methods of the
+/// wrapped type are available via `Deref`, and `into_inner` returns the wrapped value.
+///
+/// ```
+/// use finitediff::{FiniteDiff, Gradient};
+///
+/// fn norm(grad: &Gradient<Vec<f64>>) -> f64 {
+///     grad.iter().map(|g| g * g).sum::<f64>().sqrt()
+/// }
+///
+/// let x = vec![1.0f64, 1.0];
+/// let grad = x.forward_diff_typed(&|x| 3.0 * x[0] + 4.0 * x[1]);
+/// # assert!((norm(&grad) - 5.0).abs() < 1e-6);
+/// let grad: Vec<f64> = grad.into_inner();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient<T>(T);
+
+impl<T> Gradient<T> {
+    /// Wraps `grad`
+    pub fn new(grad: T) -> Self {
+        Gradient(grad)
+    }
+
+    /// Returns the wrapped gradient
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Gradient<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> AsRef<T> for Gradient<T> {
+    fn as_ref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Function value, gradient and Hessian returned by `FiniteDiff::derivatives`.
+///
+/// `S` is the scalar type, `G` the type of the parameters and `H` the Hessian type of the
+/// `FiniteDiff` implementation.
+///
+/// ```
+/// use finitediff::FiniteDiff;
+///
+/// let x = vec![1.0f64, 2.0];
+/// let d = x.derivatives(&|x| x[0].powi(2) * x[1]);
+/// # assert_eq!(d.value, 2.0);
+/// # assert!((d.gradient[0] - 4.0).abs() < 1e-6);
+/// # assert!((d.hessian[0][1] - 2.0).abs() < 1e-6);
+/// println!("f = {}, grad = {:?}, hessian = {:?}", d.value, d.gradient, d.hessian);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Derivatives<S, G, H> {
+    /// Function value `f(x)`
+    pub value: S,
+    /// Gradient as computed by `FiniteDiff::central_diff`
+    pub gradient: G,
+    /// Hessian as computed by `FiniteDiff::central_hessian_nograd`
+    pub hessian: H,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    #[test]
+    fn test_gradient_deref() {
+        let grad = Gradient::new(vec![1.0f64, 2.0]);
+        assert_eq!(grad.len(), 2);
+        assert_eq!(grad[1], 2.0);
+        let slice: &[f64] = &grad;
+        assert_eq!(slice, &[1.0, 2.0]);
+        assert_eq!(grad.as_ref(), &vec![1.0, 2.0]);
+        assert_eq!(grad.into_inner(), vec![1.0, 2.0]);
+    }
+}
@@ -5,40 +5,503 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use crate::FloatEps;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::ops::{Add, IndexMut};
+use num_traits::Float;
+
+/// Sets parameters of `x` back to saved values when dropped.
+///
+/// Dropping also happens while unwinding, therefore `x` is restored even if the function which is
+/// evaluated at the shifted parameters panics and the panic is caught further up.
+struct Restore<'a, X: ?Sized + IndexMut<I, Output = F>, I: Copy, F: Copy, const N: usize> {
+    x: &'a mut X,
+    saved: [(I, F); N],
+}
+
+impl<'a, X: ?Sized + IndexMut<I, Output = F>, I: Copy, F: Copy, const N: usize>
+    Restore<'a, X, I, F, N>
+{
+    #[inline(always)]
+    fn new(x: &'a mut X, saved: [(I, F); N]) -> Self {
+        Restore { x, saved }
+    }
+}
+
+impl<X: ?Sized + IndexMut<I, Output = F>, I: Copy, F: Copy, const N: usize> Drop
+    for Restore<'_, X, I, F, N>
+{
+    #[inline(always)]
+    fn drop(&mut self) {
+        // reverse order, such that the value saved first wins if an index appears twice
+        for &(idx, v) in self.saved.iter().rev() {
+            self.x[idx] = v;
+        }
+    }
+}
+
+/// Evaluates `f` at `x` with `y` added to the `idx`th parameter.
+///
+/// `x` is modified in place to avoid a copy and restored before returning: the `idx`th parameter
+/// is set back to the value it had on entry (rather than subtracting `y` again), so `x` is
+/// unchanged bit for bit. This also holds if `f` panics and the panic is caught by the caller.
+/// Together with `mod_and_calc2_vec` this is the building block of all difference schemes of this
+/// crate and can be used to implement custom stencils. `f` may be a trait object or any other
+/// function, in which case the call can be inlined.
+///
+/// ```
+/// use finitediff::mod_and_calc_vec;
+///
+/// let f = |x: &Vec<f64>| x[0].powi(3);
+/// let mut x = vec![2.0f64];
+/// let h = 1e-4;
+/// // central difference
+/// let d = (mod_and_calc_vec(&mut x, &f, 0, h) - mod_and_calc_vec(&mut x, &f, 0, -h)) / (2.0 * h);
+///
+/// assert!((d - 12.0).abs() < 1e-6);
+/// assert_eq!(x, vec![2.0]);
+/// ```
 #[inline(always)]
-pub fn mod_and_calc_vec_f64<T>(
-    x: &mut Vec<f64>,
-    f: &dyn Fn(&Vec<f64>) -> T,
+pub fn mod_and_calc_vec<F: Copy + Add<Output = F>, T, G: Fn(&Vec<F>) -> T + ?Sized>(
+    x: &mut Vec<F>,
+    f: &G,
     idx: usize,
-    y: f64,
+    y: F,
+) -> T {
+    let xtmp = x[idx];
+    x[idx] = xtmp + y;
+    let guard = Restore::new(x, [(idx, xtmp)]);
+    (f)(guard.x)
+}
+
+/// Evaluates `f` at `x` with `yi` added to the `i`th and `yj` added to the `j`th parameter.
+///
+/// This is what the stencils of mixed second derivatives need. If `i == j`, the parameter is
+/// shifted by `yi + yj`. As in `mod_and_calc_vec`, `x` is modified in place and both parameters
+/// are set back to their values on entry before returning.
+///
+/// ```
+/// use finitediff::{mod_and_calc2_vec, mod_and_calc_vec};
+///
+/// let f = |x: &Vec<f64>| x[0] * x[1].powi(2);
+/// let mut x = vec![1.0f64, 2.0];
+/// let h = 1e-4;
+/// let fx = f(&x);
+/// // forward difference approximation of d^2 f / (dx_0 dx_1)
+/// let d = (mod_and_calc2_vec(&mut x, &f, 0, 1, h, h)
+///     - mod_and_calc_vec(&mut x, &f, 0, h)
+///     - mod_and_calc_vec(&mut x, &f, 1, h)
+///     + fx)
+///     / (h * h);
+///
+/// assert!((d - 4.0).abs() < 1e-3);
+/// assert_eq!(x, vec![1.0, 2.0]);
+/// ```
+#[inline(always)]
+pub fn mod_and_calc2_vec<F: Copy + Add<Output = F>, T>(
+    x: &mut Vec<F>,
+    f: &dyn Fn(&Vec<F>) -> T,
+    i: usize,
+    j: usize,
+    yi: F,
+    yj: F,
+) -> T {
+    let xi = x[i];
+    let xj = x[j];
+    x[i] = xi + yi;
+    x[j] = x[j] + yj;
+    let guard = Restore::new(x, [(i, xi), (j, xj)]);
+    (f)(guard.x)
+}
+
+#[inline(always)]
+pub fn mod_and_calc_slice<F: Copy + Add<Output = F>, T>(
+    x: &mut [F],
+    f: &dyn Fn(&[F]) -> T,
+    idx: usize,
+    y: F,
 ) -> T {
     let xtmp = x[idx];
     x[idx] = xtmp + y;
-    let fx1 = (f)(&x);
-    x[idx] = xtmp;
-    fx1
+    let guard = Restore::new(x, [(idx, xtmp)]);
+    (f)(guard.x)
 }
 
+/// Evaluates `f` at `x` with `y` added to the `idx`th parameter. `x` is restored before
+/// returning, see `mod_and_calc_vec`.
 #[cfg(feature = "ndarray")]
 #[inline(always)]
-pub fn mod_and_calc_ndarray_f64<T>(
-    x: &mut ndarray::Array1<f64>,
-    f: &dyn Fn(&ndarray::Array1<f64>) -> T,
+pub fn mod_and_calc_ndarray<F: Copy + Add<Output = F>, T>(
+    x: &mut ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> T,
     idx: usize,
+    y: F,
+) -> T {
+    let xtmp = x[idx];
+    x[idx] = xtmp + y;
+    let guard = Restore::new(x, [(idx, xtmp)]);
+    (f)(guard.x)
+}
+
+/// Evaluates `f` at `x` with `yi` added to the `i`th and `yj` added to the `j`th parameter. `x` is
+/// restored before returning, see `mod_and_calc2_vec`.
+#[cfg(feature = "ndarray")]
+#[inline(always)]
+pub fn mod_and_calc2_ndarray<F: Copy + Add<Output = F>, T>(
+    x: &mut ndarray::Array1<F>,
+    f: &dyn Fn(&ndarray::Array1<F>) -> T,
+    i: usize,
+    j: usize,
+    yi: F,
+    yj: F,
+) -> T {
+    let xi = x[i];
+    let xj = x[j];
+    x[i] = xi + yi;
+    x[j] = x[j] + yj;
+    let guard = Restore::new(x, [(i, xi), (j, xj)]);
+    (f)(guard.x)
+}
+
+#[cfg(feature = "ndarray")]
+#[inline(always)]
+pub fn mod_and_calc_ndarray2_f64<T>(
+    x: &mut ndarray::Array2<f64>,
+    f: &dyn Fn(&ndarray::Array2<f64>) -> T,
+    idx: (usize, usize),
     y: f64,
 ) -> T {
     let xtmp = x[idx];
     x[idx] = xtmp + y;
-    let fx1 = (f)(&x);
-    x[idx] = xtmp;
-    fx1
+    let guard = Restore::new(x, [(idx, xtmp)]);
+    (f)(guard.x)
 }
 
 #[inline(always)]
-pub fn restore_symmetry_vec_f64(mut mat: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
-    for i in 0..mat.len() {
-        for j in (i + 1)..mat[i].len() {
-            let t = (mat[i][j] + mat[j][i]) / 2.0;
+pub fn mod_and_calc_array_f64<T, const N: usize>(
+    x: &mut [f64; N],
+    f: &dyn Fn(&[f64; N]) -> T,
+    idx: usize,
+    y: f64,
+) -> T {
+    let xtmp = x[idx];
+    x[idx] = xtmp + y;
+    let guard = Restore::new(x, [(idx, xtmp)]);
+    (f)(guard.x)
+}
+
+/// Sets a parameter of nested parameters back to a saved value when dropped, see `Restore`.
+struct RestoreNested<'a> {
+    x: &'a mut Vec<Vec<f64>>,
+    idx: (usize, usize),
+    saved: f64,
+}
+
+impl Drop for RestoreNested<'_> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        self.x[self.idx.0][self.idx.1] = self.saved;
+    }
+}
+
+/// Evaluates `f` at `x` with `y` added to the `j`th parameter of the `i`th sublist. `x` is
+/// restored before returning, see `mod_and_calc_vec`.
+#[inline(always)]
+pub fn mod_and_calc_nested<T>(
+    x: &mut Vec<Vec<f64>>,
+    f: &dyn Fn(&Vec<Vec<f64>>) -> T,
+    (i, j): (usize, usize),
+    y: f64,
+) -> T {
+    let saved = x[i][j];
+    x[i][j] = saved + y;
+    let guard = RestoreNested {
+        x,
+        idx: (i, j),
+        saved,
+    };
+    (f)(guard.x)
+}
+
+/// Default step `sqrt(EPS)` of forward differences and Jacobians.
+///
+/// This is the perturbation used by `forward_diff`, `backward_diff`, `forward_jacobian`,
+/// `central_jacobian` and related methods for parameters with `|x_i| <= 1`, which makes it
+/// possible to reproduce their difference quotients manually. Larger parameters are perturbed by
+/// `sqrt(EPS) * |x_i|`, see `default_step_for`. `central_diff` uses the larger step `EPS^(1/3)`
+/// instead.
+///
+/// ```
+/// use finitediff::{default_step, FiniteDiff, EPS_F64};
+///
+/// let f = |x: &Vec<f64>| x[0].powi(2);
+/// let x = vec![1.0f64];
+/// let h = default_step::<f64>();
+/// assert_eq!(h, EPS_F64.sqrt());
+/// assert_eq!(x.forward_diff(&f)[0], (f(&vec![1.0 + h]) - f(&x)) / h);
+/// ```
+#[inline(always)]
+pub fn default_step<F: FloatEps>() -> F {
+    F::EPS.sqrt()
+}
+
+/// Default step `sqrt(EPS) * max(|x_i|, 1)` of forward differences and Jacobians for the
+/// parameter `x_i`.
+///
+/// Unlike `default_step`, this includes the scaling with the magnitude of the parameter and
+/// therefore is the exact perturbation of `x_i` used by `forward_diff`, `backward_diff`,
+/// `forward_jacobian` and related methods.
+///
+/// ```
+/// use finitediff::{default_step_for, FiniteDiff, EPS_F64};
+///
+/// let f = |x: &Vec<f64>| x[0].powi(2);
+/// let x = vec![4.0f64];
+/// let h = default_step_for(x[0]);
+/// assert_eq!(h, 4.0 * EPS_F64.sqrt());
+/// assert_eq!(x.forward_diff(&f)[0], (f(&vec![4.0 + h]) - f(&x)) / h);
+/// ```
+#[inline(always)]
+pub fn default_step_for<F: FloatEps>(xi: F) -> F {
+    step_for(xi, F::EPS)
+}
+
+/// Step size for five-point stencils.
+///
+/// The truncation error of these stencils is of fourth order, therefore the step which balances
+/// truncation and rounding errors is `EPS^(1/5)` rather than `sqrt(EPS)`.
+#[inline(always)]
+pub fn eps_5<F: FloatEps>() -> F {
+    F::EPS.powf(F::from(0.2).unwrap())
+}
+
+/// Step size for second-order accurate differences with one-sided or symmetric stencils.
+///
+/// The truncation error is of second order, therefore the step which balances truncation and
+/// rounding errors is `EPS^(1/3)`.
+#[inline(always)]
+pub fn eps_3<F: FloatEps>() -> F {
+    F::EPS.cbrt()
+}
+
+/// Step size for second-order differences of function values.
+///
+/// These divide by `h^2`, therefore the step which balances truncation and rounding errors is
+/// `EPS^(1/4)`.
+#[inline(always)]
+pub fn eps_second<F: FloatEps>() -> F {
+    F::EPS.powf(F::from(0.25).unwrap())
+}
+
+/// Evaluates `f` at `x` with `y` added to the `idx`th parameter. `x` is restored before
+/// returning, see `mod_and_calc_vec`.
+#[cfg(feature = "nalgebra")]
+#[inline(always)]
+pub fn mod_and_calc_nalgebra<F: nalgebra::Scalar + Copy + Add<Output = F>, T>(
+    x: &mut nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> T,
+    idx: usize,
+    y: F,
+) -> T {
+    let xtmp = x[idx];
+    x[idx] = xtmp + y;
+    let guard = Restore::new(x, [(idx, xtmp)]);
+    (f)(guard.x)
+}
+
+/// Evaluates `f` at `x` with `yi` added to the `i`th and `yj` added to the `j`th parameter. `x` is
+/// restored before returning, see `mod_and_calc2_vec`.
+#[cfg(feature = "nalgebra")]
+#[inline(always)]
+pub fn mod_and_calc2_nalgebra<F: nalgebra::Scalar + Copy + Add<Output = F>, T>(
+    x: &mut nalgebra::DVector<F>,
+    f: &dyn Fn(&nalgebra::DVector<F>) -> T,
+    i: usize,
+    j: usize,
+    yi: F,
+    yj: F,
+) -> T {
+    let xi = x[i];
+    let xj = x[j];
+    x[i] = xi + yi;
+    x[j] = x[j] + yj;
+    let guard = Restore::new(x, [(i, xi), (j, xj)]);
+    (f)(guard.x)
+}
+
+/// Magnitude `max(|x_i|, 1)` by which relative steps are scaled.
+///
+/// The floor of 1 prevents the step from collapsing to zero for `x_i == 0` and keeps it from
+/// becoming tiny for small parameters.
+#[inline(always)]
+pub fn step_magnitude<F: Float>(xi: F) -> F {
+    xi.abs().max(F::one())
+}
+
+/// Step `sqrt(eps) * max(|x_i|, 1)` for the parameter `x_i`, where `eps` is the relative accuracy
+/// of the function values.
+///
+/// A fixed absolute step is negligible compared to large parameters, in which case the difference
+/// of the function values is dominated by rounding errors or even vanishes. All forward and central
+/// differences of gradients, Jacobians and Hessians which perturb one parameter at a time compute
+/// their steps with this function. Products with a direction `p`, such as
+/// `forward_jacobian_vec_prod` and `forward_hessian_vec_prod`, perturb all parameters at once and
+/// therefore keep the absolute step `sqrt(EPS)`.
+#[inline(always)]
+pub fn step_for<F: Float>(xi: F, eps: F) -> F {
+    eps.sqrt() * step_magnitude(xi)
+}
+
+/// Step `EPS^(1/3) * max(|x_i|, 1)` of `central_diff` for the parameter `x_i`.
+///
+/// This is larger than the step `default_step_for(x_i)` of forward differences, because the
+/// truncation error of central differences is of second order. `central_jacobian` and
+/// `central_hessian` use `default_step_for` instead, and `central_jacobian_vec_prod` and
+/// `central_hessian_vec_prod` the unscaled `default_step`.
+///
+/// ```
+/// use finitediff::{central_step, FiniteDiff, EPS_F64};
+///
+/// let f = |x: &Vec<f64>| x[0].powi(3);
+/// let x = vec![4.0f64];
+/// let h = central_step(x[0]);
+/// assert!((h - 4.0 * EPS_F64.cbrt()).abs() < 1e-15);
+/// assert_eq!(
+///     x.central_diff(&f)[0],
+///     (f(&vec![4.0 + h]) - f(&vec![4.0 - h])) / (2.0 * h)
+/// );
+/// ```
+#[inline(always)]
+pub fn central_step<F: FloatEps>(xi: F) -> F {
+    step_for(xi, eps_3::<F>().powi(2))
+}
+
+/// Indices of the `true` entries of a mask of active parameters.
+#[inline(always)]
+pub fn mask_indices(active: &[bool]) -> Vec<usize> {
+    active
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &a)| if a { Some(i) } else { None })
+        .collect()
+}
+
+/// Sum of `values` with Neumaier's variant of compensated (Kahan) summation.
+///
+/// The rounding error of every addition is accumulated separately and added at the end. Unlike a
+/// naive sum, this does not lose small values next to large values of opposite sign, for instance
+/// `[1e16, 1.0, -1e16, 1.0]` sums to `2.0` instead of `1.0`.
+#[inline(always)]
+pub fn compensated_sum<F: Float>(values: impl IntoIterator<Item = F>) -> F {
+    let mut sum = F::zero();
+    let mut c = F::zero();
+    for v in values {
+        let t = sum + v;
+        if sum.abs() >= v.abs() {
+            c = c + ((sum - t) + v);
+        } else {
+            c = c + ((v - t) + sum);
+        }
+        sum = t;
+    }
+    sum + c
+}
+
+/// Difference quotient `(a - b)/h` which returns `None` instead of a non-finite value.
+///
+/// If `a - b` overflows although both values are finite, the quotient is computed from the halved
+/// values, which is exact. This only helps for steps `h > 1`; for smaller steps an overflowing
+/// difference implies an overflowing quotient.
+#[inline(always)]
+pub fn checked_quotient<F: Float>(a: F, b: F, h: F) -> Option<F> {
+    if !a.is_finite() || !b.is_finite() {
+        return None;
+    }
+    let diff = a - b;
+    let q = if diff.is_finite() {
+        diff / h
+    } else {
+        let half = F::from(0.5).unwrap();
+        (a * half - b * half) / h * (F::one() + F::one())
+    };
+    if q.is_finite() {
+        Some(q)
+    } else {
+        None
+    }
+}
+
+/// Signed step `sqrt(EPS)` for a parameter `xi` within `[lower, upper]` such that `xi + step` stays
+/// within the bounds.
+///
+/// A negative step, i.e. a backward difference, is used if the forward step would exceed `upper`.
+/// If the interval is narrower than the default step on both sides, the larger of the feasible
+/// steps `upper - xi` and `xi - lower` is taken, which is zero if `lower == upper`.
+#[inline(always)]
+pub fn bounded_step<F: FloatEps>(xi: F, lower: F, upper: F) -> F {
+    let h = F::EPS.sqrt();
+    if xi + h <= upper {
+        h
+    } else if xi - h >= lower {
+        -h
+    } else if upper - xi >= xi - lower {
+        upper - xi
+    } else {
+        lower - xi
+    }
+}
+
+/// How `restore_symmetry_*_with` combines the entries `(i, j)` and `(j, i)` of a matrix which
+/// should be symmetric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymmetryMode {
+    /// Replace both entries by their mean
+    #[default]
+    Average,
+    /// Copy the upper triangle (`i < j`) onto the lower triangle
+    UpperToLower,
+    /// Copy the lower triangle (`i > j`) onto the upper triangle
+    LowerToUpper,
+}
+
+impl SymmetryMode {
+    /// Value of the entries `(i, j)` and `(j, i)` for `i < j`
+    #[inline(always)]
+    fn combine<F: Float>(self, upper: F, lower: F) -> F {
+        match self {
+            SymmetryMode::Average => (upper + lower) / F::from(2.0).unwrap(),
+            SymmetryMode::UpperToLower => upper,
+            SymmetryMode::LowerToUpper => lower,
+        }
+    }
+}
+
+/// Replaces the entries `(i, j)` and `(j, i)` of the square matrix `mat` by their mean.
+///
+/// Panics if `mat` is not square, including ragged rows.
+#[inline(always)]
+pub fn restore_symmetry_vec<F: Float>(mat: Vec<Vec<F>>) -> Vec<Vec<F>> {
+    restore_symmetry_vec_with(mat, SymmetryMode::Average)
+}
+
+/// Makes the square matrix `mat` symmetric by combining the entries `(i, j)` and `(j, i)`
+/// according to `mode`.
+///
+/// Panics if `mat` is not square, including ragged rows.
+pub fn restore_symmetry_vec_with<F: Float>(
+    mut mat: Vec<Vec<F>>,
+    mode: SymmetryMode,
+) -> Vec<Vec<F>> {
+    let n = mat.len();
+    assert!(
+        mat.iter().all(|row| row.len() == n),
+        "restore_symmetry_vec: matrix with {} rows is not square",
+        n
+    );
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let t = mode.combine(mat[i][j], mat[j][i]);
             mat[i][j] = t;
             mat[j][i] = t;
         }
@@ -48,14 +511,59 @@ pub fn restore_symmetry_vec_f64(mut mat: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
 
 #[cfg(feature = "ndarray")]
 #[inline(always)]
-/// Restore symmetry for an array of type `ndarray::Array2<f64>`
+/// Restore symmetry for an array of type `ndarray::Array2<F>`
 ///
 /// Unfortunately, this is *really* slow!
-pub fn restore_symmetry_ndarray_f64(mut mat: ndarray::Array2<f64>) -> ndarray::Array2<f64> {
+///
+/// Panics if `mat` is not square.
+pub fn restore_symmetry_ndarray<F: Float>(mat: ndarray::Array2<F>) -> ndarray::Array2<F> {
+    restore_symmetry_ndarray_with(mat, SymmetryMode::Average)
+}
+
+/// Same as `restore_symmetry_vec_with`, but for `ndarray::Array2<F>`.
+///
+/// Panics if `mat` is not square.
+#[cfg(feature = "ndarray")]
+pub fn restore_symmetry_ndarray_with<F: Float>(
+    mut mat: ndarray::Array2<F>,
+    mode: SymmetryMode,
+) -> ndarray::Array2<F> {
     let (nx, ny) = mat.dim();
+    assert_eq!(nx, ny, "restore_symmetry_ndarray: matrix is not square");
+    for i in 0..nx {
+        for j in (i + 1)..ny {
+            let t = mode.combine(mat[(i, j)], mat[(j, i)]);
+            mat[(i, j)] = t;
+            mat[(j, i)] = t;
+        }
+    }
+    mat
+}
+
+#[cfg(feature = "nalgebra")]
+#[inline(always)]
+/// Restore symmetry for a matrix of type `nalgebra::DMatrix<F>`
+///
+/// Panics if `mat` is not square.
+pub fn restore_symmetry_nalgebra<F: Float + nalgebra::Scalar>(
+    mat: nalgebra::DMatrix<F>,
+) -> nalgebra::DMatrix<F> {
+    restore_symmetry_nalgebra_with(mat, SymmetryMode::Average)
+}
+
+/// Same as `restore_symmetry_vec_with`, but for `nalgebra::DMatrix<F>`.
+///
+/// Panics if `mat` is not square.
+#[cfg(feature = "nalgebra")]
+pub fn restore_symmetry_nalgebra_with<F: Float + nalgebra::Scalar>(
+    mut mat: nalgebra::DMatrix<F>,
+    mode: SymmetryMode,
+) -> nalgebra::DMatrix<F> {
+    let (nx, ny) = mat.shape();
+    assert_eq!(nx, ny, "restore_symmetry_nalgebra: matrix is not square");
     for i in 0..nx {
         for j in (i + 1)..ny {
-            let t = (mat[(i, j)] + mat[(j, i)]) / 2.0;
+            let t = mode.combine(mat[(i, j)], mat[(j, i)]);
             mat[(i, j)] = t;
             mat[(j, i)] = t;
         }
@@ -63,12 +571,12 @@ pub fn restore_symmetry_ndarray_f64(mut mat: ndarray::Array2<f64>) -> ndarray::A
     mat
 }
 
-pub struct KV {
+pub struct KV<T> {
     k: Vec<usize>,
-    v: Vec<f64>,
+    v: Vec<T>,
 }
 
-impl KV {
+impl<T: Copy> KV<T> {
     pub fn new(capacity: usize) -> Self {
         KV {
             k: Vec::with_capacity(capacity),
@@ -76,13 +584,13 @@ impl KV {
         }
     }
 
-    pub fn set(&mut self, k: usize, v: f64) -> &mut Self {
+    pub fn set(&mut self, k: usize, v: T) -> &mut Self {
         self.k.push(k);
         self.v.push(v);
         self
     }
 
-    pub fn get(&self, k: usize) -> Option<f64> {
+    pub fn get(&self, k: usize) -> Option<T> {
         for (i, kk) in self.k.iter().enumerate() {
             if *kk == k {
                 return Some(self.v[i]);
@@ -94,3 +602,254 @@ impl KV {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+    #[cfg(feature = "std")]
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[cfg(feature = "std")]
+    fn panicking<X>(_x: &X) -> f64 {
+        panic!("cost function failed")
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_mod_and_calc_vec_restores_on_panic() {
+        let mut x = vec![1.0f64, 2.0, 3.0];
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            mod_and_calc_vec(&mut x, &panicking, 1, 0.5)
+        }));
+        assert!(res.is_err());
+        assert_eq!(x, vec![1.0, 2.0, 3.0]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_mod_and_calc2_vec_restores_on_panic() {
+        for &(i, j) in [(0, 2), (1, 1)].iter() {
+            let mut x = vec![1.0f64, 2.0, 3.0];
+            let res = catch_unwind(AssertUnwindSafe(|| {
+                mod_and_calc2_vec(&mut x, &panicking, i, j, 0.5, -0.25)
+            }));
+            assert!(res.is_err());
+            assert_eq!(x, vec![1.0, 2.0, 3.0]);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_mod_and_calc_array_f64_restores_on_panic() {
+        let mut x = [1.0f64, 2.0];
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            mod_and_calc_array_f64(&mut x, &panicking, 0, 0.5)
+        }));
+        assert!(res.is_err());
+        assert_eq!(x, [1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_bounded_step() {
+        let h = default_step::<f64>();
+        assert_eq!(bounded_step(0.5f64, 0.0, 1.0), h);
+        assert_eq!(bounded_step(1.0f64, 0.0, 1.0), -h);
+        assert_eq!(bounded_step(0.0f64, 0.0, h / 4.0), h / 4.0);
+        assert_eq!(bounded_step(h / 4.0, 0.0, h / 4.0), -h / 4.0);
+        assert_eq!(bounded_step(1.0f64, 1.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn test_checked_quotient() {
+        assert_eq!(checked_quotient(3.0f64, 1.0, 0.5), Some(4.0));
+        assert_eq!(
+            checked_quotient(f64::MAX, -f64::MAX, 4.0),
+            Some(f64::MAX / 2.0)
+        );
+        assert_eq!(checked_quotient(f64::MAX, -f64::MAX, 1e-8), None);
+        assert_eq!(checked_quotient(f64::MAX, 0.0, 1e-8), None);
+        assert_eq!(checked_quotient(f64::INFINITY, 0.0, 1.0), None);
+        assert_eq!(checked_quotient(f64::NAN, 0.0, 1.0), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_mod_and_calc_nested_restores_on_panic() {
+        let mut x = vec![vec![1.0f64], vec![2.0, 3.0]];
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            mod_and_calc_nested(&mut x, &panicking, (1, 0), 0.5)
+        }));
+        assert!(res.is_err());
+        assert_eq!(x, vec![vec![1.0], vec![2.0, 3.0]]);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_mod_and_calc_ndarray_restores_on_panic() {
+        let mut x = ndarray::Array1::from(vec![1.0f64, 2.0]);
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            mod_and_calc_ndarray(&mut x, &panicking, 1, 0.5)
+        }));
+        assert!(res.is_err());
+        assert_eq!(x, ndarray::Array1::from(vec![1.0, 2.0]));
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_mod_and_calc2_nalgebra_restores_on_panic() {
+        let mut x = nalgebra::DVector::from_vec(vec![1.0f64, 2.0]);
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            mod_and_calc2_nalgebra(&mut x, &panicking, 0, 1, 0.5, 0.5)
+        }));
+        assert!(res.is_err());
+        assert_eq!(x, nalgebra::DVector::from_vec(vec![1.0, 2.0]));
+    }
+
+    #[test]
+    fn test_mod_and_calc_vec_restores() {
+        let f = |x: &Vec<f64>| x[0] + 2.0 * x[1];
+        let mut x = vec![1.0f64, 2.0];
+        assert_eq!(mod_and_calc_vec(&mut x, &f, 1, 0.5), 6.0);
+        assert_eq!(mod_and_calc2_vec(&mut x, &f, 0, 0, 0.5, 0.25), 5.75);
+        assert_eq!(x, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_compensated_sum() {
+        let values = [1e16f64, 1.0, -1e16, 1.0];
+        assert_eq!(values.iter().fold(0.0, |acc, &v| acc + v), 1.0);
+        assert_eq!(compensated_sum(values.iter().cloned()), 2.0);
+        assert_eq!(compensated_sum(vec![0.1f64; 10]), 1.0);
+        assert_eq!(compensated_sum(Vec::<f64>::new()), 0.0);
+    }
+
+    #[test]
+    fn test_restore_symmetry_vec() {
+        let mat = vec![vec![1.0f64, 2.0], vec![4.0, 5.0]];
+        assert_eq!(
+            restore_symmetry_vec(mat),
+            vec![vec![1.0, 3.0], vec![3.0, 5.0]]
+        );
+    }
+
+    #[test]
+    fn test_restore_symmetry_vec_with() {
+        let mat = vec![
+            vec![1.0f64, 2.0, 3.0],
+            vec![4.0, 5.0, 6.0],
+            vec![7.0, 8.0, 9.0],
+        ];
+        assert_eq!(
+            restore_symmetry_vec_with(mat.clone(), SymmetryMode::Average),
+            vec![
+                vec![1.0, 3.0, 5.0],
+                vec![3.0, 5.0, 7.0],
+                vec![5.0, 7.0, 9.0]
+            ]
+        );
+        assert_eq!(
+            restore_symmetry_vec_with(mat.clone(), SymmetryMode::UpperToLower),
+            vec![
+                vec![1.0, 2.0, 3.0],
+                vec![2.0, 5.0, 6.0],
+                vec![3.0, 6.0, 9.0]
+            ]
+        );
+        assert_eq!(
+            restore_symmetry_vec_with(mat, SymmetryMode::LowerToUpper),
+            vec![
+                vec![1.0, 4.0, 7.0],
+                vec![4.0, 5.0, 8.0],
+                vec![7.0, 8.0, 9.0]
+            ]
+        );
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_restore_symmetry_ndarray_with() {
+        let mat = ndarray::array![[1.0f64, 2.0], [4.0, 5.0]];
+        assert_eq!(
+            restore_symmetry_ndarray_with(mat.clone(), SymmetryMode::Average),
+            ndarray::array![[1.0, 3.0], [3.0, 5.0]]
+        );
+        assert_eq!(
+            restore_symmetry_ndarray_with(mat.clone(), SymmetryMode::UpperToLower),
+            ndarray::array![[1.0, 2.0], [2.0, 5.0]]
+        );
+        assert_eq!(
+            restore_symmetry_ndarray_with(mat, SymmetryMode::LowerToUpper),
+            ndarray::array![[1.0, 4.0], [4.0, 5.0]]
+        );
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_restore_symmetry_nalgebra_with() {
+        let mat = nalgebra::DMatrix::from_row_slice(2, 2, &[1.0f64, 2.0, 4.0, 5.0]);
+        assert_eq!(
+            restore_symmetry_nalgebra_with(mat.clone(), SymmetryMode::Average),
+            nalgebra::DMatrix::from_row_slice(2, 2, &[1.0, 3.0, 3.0, 5.0])
+        );
+        assert_eq!(
+            restore_symmetry_nalgebra_with(mat.clone(), SymmetryMode::UpperToLower),
+            nalgebra::DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 2.0, 5.0])
+        );
+        assert_eq!(
+            restore_symmetry_nalgebra_with(mat, SymmetryMode::LowerToUpper),
+            nalgebra::DMatrix::from_row_slice(2, 2, &[1.0, 4.0, 4.0, 5.0])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "not square")]
+    fn test_restore_symmetry_vec_rectangular() {
+        restore_symmetry_vec(vec![vec![0.0f64; 4]; 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not square")]
+    fn test_restore_symmetry_vec_ragged() {
+        restore_symmetry_vec(vec![vec![0.0f64; 3], vec![0.0; 3], vec![0.0; 2]]);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    #[should_panic(expected = "not square")]
+    fn test_restore_symmetry_ndarray_rectangular() {
+        restore_symmetry_ndarray(ndarray::Array2::<f64>::zeros((3, 4)));
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    #[should_panic(expected = "not square")]
+    fn test_restore_symmetry_nalgebra_rectangular() {
+        restore_symmetry_nalgebra(nalgebra::DMatrix::<f64>::zeros(3, 4));
+    }
+
+    #[test]
+    fn test_mask_indices() {
+        assert_eq!(
+            mask_indices(&[true, false, false, true, true]),
+            vec![0, 3, 4]
+        );
+        assert!(mask_indices(&[false, false]).is_empty());
+        assert!(mask_indices(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_step_for() {
+        let eps = f64::EPSILON;
+        let h = eps.sqrt();
+        assert_eq!(step_for(0.0f64, eps), h);
+        assert_eq!(step_for(0.5f64, eps), h);
+        assert_eq!(step_for(1.0f64, eps), h);
+        assert_eq!(step_for(-4.0f64, eps), 4.0 * h);
+        // the step stays significant for large parameters
+        let x = 1e12f64;
+        assert!(x + step_for(x, eps) > x);
+        assert!(x - step_for(x, eps) < x);
+    }
+}